@@ -1,5 +1,7 @@
 //! Error module for the bus-mapping crate
 
+use crate::evm::{Hardfork, OpcodeId, ProgramCounter};
+use crate::operation::ContextField;
 use core::fmt::{Display, Formatter, Result as FmtResult};
 use std::error::Error as StdError;
 
@@ -12,15 +14,222 @@ pub enum Error {
     MemAddressParsing,
     /// Error while parsing an `EvmWord`.
     EvmWordParsing,
+    /// Error while parsing an `Address`.
+    AddressParsing,
+    /// Error while parsing a hex encoded byte string.
+    BytesParsing,
     /// Error while trying to convert to an incorrect `OpcodeId`.
     InvalidOpConversion,
+    /// The `OpcodeId` isn't defined at the `Hardfork` the trace is processed
+    /// with.
+    OpcodeNotInHardfork(OpcodeId, Hardfork),
+    /// No [`Opcode`](crate::evm::Opcode) implementation is registered for
+    /// the `OpcodeId` executed by a step.
+    OpcodeNotRegistered(OpcodeId),
     /// Serde de/serialization error.
     SerdeError,
+    /// The steps of the trace can't come from an execution of the EVM.
+    InvalidTrace(TraceError),
+    /// The stack and memory views of the step at the given index, missing
+    /// from the trace, can't be rebuilt by replaying it's `OpcodeId`.
+    StepNotReplayable(usize, OpcodeId),
+}
+
+/// Flaw of the steps of a trace that prevents them from coming from an
+/// execution of the EVM, found either before generating any of their
+/// operations or, for the ones that contradict the state they run against,
+/// while generating them. Each variant holds the index of the offending
+/// step.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TraceError {
+    /// The stack of the step holds more than 1024 words.
+    StackOverflow {
+        /// Index of the step.
+        step: usize,
+        /// Number of words in the stack.
+        size: usize,
+    },
+    /// A memory word of the step isn't placed at an address multiple of 32.
+    UnalignedMemory {
+        /// Index of the step.
+        step: usize,
+        /// Address of the word.
+        address: usize,
+    },
+    /// The memory of the step lacks a word below it's highest address.
+    NonContiguousMemory {
+        /// Index of the step.
+        step: usize,
+        /// Address of the first missing word.
+        address: usize,
+    },
+    /// The step runs at a pc past the end of the code of it's frame.
+    PcOutOfCode {
+        /// Index of the step.
+        step: usize,
+        /// Program counter of the step.
+        pc: ProgramCounter,
+        /// Size of the code of the frame.
+        code_size: usize,
+    },
+    /// The step doesn't continue the execution of it's frame where the
+    /// previous step left it, so the steps in between are missing.
+    MissingStep {
+        /// Index of the step.
+        step: usize,
+        /// Program counter the frame should continue at.
+        expected: ProgramCounter,
+        /// Program counter of the step.
+        found: ProgramCounter,
+    },
+    /// The step runs in a frame that no previous step opened: it's deeper
+    /// than it's previous step by more than one, or that step doesn't call
+    /// or create.
+    UnexpectedDepth {
+        /// Index of the step.
+        step: usize,
+        /// Depth of the step.
+        depth: usize,
+    },
+    /// The step runs in a frame that already halted.
+    StepAfterHalt {
+        /// Index of the step.
+        step: usize,
+    },
+    /// The step is the last one of it's frame, but it neither halts it nor
+    /// can halt it exceptionally, so the steps that follow it in the frame
+    /// are missing.
+    UnexplainedHalt {
+        /// Index of the step.
+        step: usize,
+    },
+    /// The memory of the step holds a word past the 2^64 bytes that can be
+    /// addressed, as expanding the memory that far costs more gas than any
+    /// block can hold.
+    MemoryOutOfBounds {
+        /// Index of the step.
+        step: usize,
+    },
+    /// The step enters a call frame that the call tree of the trace doesn't
+    /// hold, or runs at a depth that doesn't match it's frame.
+    CallTreeMismatch {
+        /// Index of the step.
+        step: usize,
+    },
+    /// The step pushes a field of an account whose value is known, from the
+    /// initial state or from a previous step, and doesn't match it, or moves
+    /// more value out of an account than it's balance holds (the first step
+    /// also pays for the gas and value of the transaction).
+    StateMismatch {
+        /// Index of the step.
+        step: usize,
+    },
+    /// The step reads an entry of the context of it's call that can't be
+    /// known from the transaction and the state, like the code size of an
+    /// account missing from the initial state or the gas left in a
+    /// transaction without gas limit.
+    UnknownContext {
+        /// Index of the step.
+        step: usize,
+        /// Entry of the context read.
+        field: ContextField,
+    },
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            TraceError::StackOverflow { step, size } => write!(
+                f,
+                "step {} has {} words in the stack, more than the 1024 allowed",
+                step, size
+            ),
+            TraceError::UnalignedMemory { step, address } => write!(
+                f,
+                "step {} has a memory word at 0x{:x}, which isn't 32-byte aligned",
+                step, address
+            ),
+            TraceError::NonContiguousMemory { step, address } => write!(
+                f,
+                "step {} is missing the memory word at 0x{:x}",
+                step, address
+            ),
+            TraceError::PcOutOfCode {
+                step,
+                pc,
+                code_size,
+            } => write!(
+                f,
+                "step {} runs at pc {}, past the {} bytes of code",
+                step, pc, code_size
+            ),
+            TraceError::MissingStep {
+                step,
+                expected,
+                found,
+            } => write!(
+                f,
+                "step {} runs at pc {} while it's frame continues at pc {}",
+                step, found, expected
+            ),
+            TraceError::UnexpectedDepth { step, depth } => write!(
+                f,
+                "step {} runs at depth {} in a frame that wasn't opened",
+                step, depth
+            ),
+            TraceError::StepAfterHalt { step } => {
+                write!(f, "step {} runs in a frame that already halted", step)
+            }
+            TraceError::UnexplainedHalt { step } => write!(
+                f,
+                "step {} ends it's frame without halting it",
+                step
+            ),
+            TraceError::MemoryOutOfBounds { step } => write!(
+                f,
+                "step {} has memory past the 2^64 addressable bytes",
+                step
+            ),
+            TraceError::CallTreeMismatch { step } => write!(
+                f,
+                "step {} runs in a call frame missing from the call tree",
+                step
+            ),
+            TraceError::StateMismatch { step } => write!(
+                f,
+                "step {} doesn't match the state of the accounts",
+                step
+            ),
+            TraceError::UnknownContext { step, field } => write!(
+                f,
+                "step {} reads the {:?} of it's call, which isn't known",
+                step, field
+            ),
+        }
+    }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(f, "{:?}", self)
+        match self {
+            Error::OpcodeNotInHardfork(opcode, hardfork) => write!(
+                f,
+                "opcode 0x{:02x} is not available at {:?}, it was introduced in {:?}",
+                opcode.as_u8(),
+                hardfork,
+                opcode.introduced_in()
+            ),
+            Error::OpcodeNotRegistered(opcode) => {
+                write!(f, "no Opcode is registered for {}", opcode)
+            }
+            Error::InvalidTrace(err) => write!(f, "invalid trace: {}", err),
+            Error::StepNotReplayable(step, opcode) => write!(
+                f,
+                "step {} can't be replayed to rebuild it's stack and memory, it runs {}",
+                step, opcode
+            ),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
 
@@ -1,21 +1,57 @@
 //! Evm types needed for parsing instruction sets as well
 
+pub mod gas;
+pub mod hardfork;
 pub(crate) mod instruction;
 pub(crate) mod opcodes;
+pub mod precompiles;
 
 use crate::error::Error;
-use core::{convert::TryInto, str::FromStr};
+use core::{convert::TryInto, fmt, str::FromStr};
 use lazy_static::lazy_static;
-use num::{BigUint, Num, Zero};
+use num::{BigUint, Num, ToPrimitive, Zero};
 use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
 pub use {
+    gas::{Gas, GasCost, GasMismatch},
+    hardfork::Hardfork,
     instruction::Instruction,
-    opcodes::{ids::OpcodeId, Opcode},
+    opcodes::{
+        create::{create2_address, create_address},
+        gen_stack_reads,
+        ids::OpcodeId,
+        Opcode, OpcodeRegistry,
+    },
 };
 
 lazy_static! {
     /// Ref to zero addr for Memory.
     pub(crate) static ref MEM_ADDR_ZERO: MemoryAddress = MemoryAddress(BigUint::zero());
+    /// Keccak256 hash of the empty byte string, which is the code hash of every
+    /// account without code.
+    pub(crate) static ref EMPTY_CODE_HASH: EvmWord = EvmWord::from_be_bytes(&keccak256(&[]));
+}
+
+/// Computes the Keccak256 hash of the provided bytes.
+pub(crate) fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&Keccak256::digest(bytes));
+    hash
+}
+
+/// Decodes the hex encoded byte string `s`, with or without a `0x` prefix.
+pub(crate) fn bytes_from_hex(s: &str) -> Result<Vec<u8>, Error> {
+    let s = s.trim_start_matches("0x");
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return Err(Error::BytesParsing);
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|idx| {
+            u8::from_str_radix(&s[idx..idx + 2], 16)
+                .map_err(|_| Error::BytesParsing)
+        })
+        .collect()
 }
 
 /// Wrapper type over `usize` which represents the program counter of the Evm.
@@ -24,6 +60,12 @@ lazy_static! {
 )]
 pub struct ProgramCounter(pub(crate) usize);
 
+impl fmt::Display for ProgramCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl From<ProgramCounter> for usize {
     fn from(addr: ProgramCounter) -> usize {
         addr.0
@@ -44,6 +86,12 @@ impl From<usize> for ProgramCounter {
 #[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
 pub struct GlobalCounter(pub(crate) usize);
 
+impl fmt::Display for GlobalCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl From<GlobalCounter> for usize {
     fn from(addr: GlobalCounter) -> usize {
         addr.0
@@ -82,6 +130,19 @@ impl From<MemoryAddress> for BigUint {
     }
 }
 
+impl From<usize> for MemoryAddress {
+    fn from(addr: usize) -> MemoryAddress {
+        MemoryAddress(BigUint::from(addr))
+    }
+}
+
+impl fmt::Display for MemoryAddress {
+    /// Formats the address in hex, as the memory keys of a trace are.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:x}", self.0)
+    }
+}
+
 impl FromStr for MemoryAddress {
     type Err = Error;
 
@@ -117,6 +178,12 @@ impl From<usize> for StackAddress {
     }
 }
 
+impl fmt::Display for StackAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl FromStr for StackAddress {
     type Err = Error;
 
@@ -140,6 +207,14 @@ impl FromStr for StackAddress {
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 pub struct EvmWord(pub(crate) BigUint);
 
+impl fmt::Display for EvmWord {
+    /// Formats the word in hex without leading zeros, as the stack words of a
+    /// trace are.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:x}", self.0)
+    }
+}
+
 impl FromStr for EvmWord {
     type Err = Error;
 
@@ -173,8 +248,96 @@ impl EvmWord {
         array
     }
 
+    /// Return the big-endian byte representation of the word as a 32-byte
+    /// array.
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let bytes = self.0.to_bytes_be();
+        let mut array = [0u8; 32];
+        array[32 - bytes.len()..].copy_from_slice(&bytes);
+
+        array
+    }
+
+    /// Generates an `EvmWord` from it's big-endian byte representation.
+    pub fn from_be_bytes(bytes: &[u8]) -> EvmWord {
+        EvmWord(BigUint::from_bytes_be(bytes))
+    }
+
+    /// Returns the value of the word as a `u64` if it fits in it.
+    pub fn to_u64(&self) -> Option<u64> {
+        self.0.to_u64()
+    }
+
+    /// Returns the value of the word as a `usize` if it fits in it.
+    pub fn to_usize(&self) -> Option<usize> {
+        self.0.to_usize()
+    }
+
+    /// Returns true if the word is zero.
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
     /// Returns the underlying representation of the `EvmWord` as a [`BigUint`].
     pub fn as_big_uint(&self) -> &BigUint {
         &self.0
     }
 }
+
+/// Represents the `Address` of an account of the EVM, which is a 20-byte
+/// value.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct Address(pub(crate) [u8; 20]);
+
+impl Address {
+    /// Returns the zero address.
+    pub const fn zero() -> Address {
+        Address([0u8; 20])
+    }
+
+    /// Returns the big-endian byte representation of the address.
+    pub const fn as_bytes(&self) -> &[u8; 20] {
+        &self.0
+    }
+
+    /// Returns the address as an [`EvmWord`] so that it can be placed in
+    /// the stack.
+    pub fn to_word(&self) -> EvmWord {
+        EvmWord::from_be_bytes(&self.0)
+    }
+
+    /// Generates an `Address` from the 20 least significant bytes of an
+    /// [`EvmWord`], as the EVM does when it pops an address from the stack.
+    pub fn from_word(word: &EvmWord) -> Address {
+        let mut array = [0u8; 20];
+        array.copy_from_slice(&word.to_be_bytes()[12..]);
+        Address(array)
+    }
+}
+
+impl From<[u8; 20]> for Address {
+    fn from(bytes: [u8; 20]) -> Self {
+        Address(bytes)
+    }
+}
+
+impl fmt::Display for Address {
+    /// Formats the 20 bytes of the address in hex.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("0x")?;
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let word = EvmWord::from_str(s.trim_start_matches("0x"))
+            .map_err(|_| Error::AddressParsing)?;
+        if word.0.bits() > 160 {
+            return Err(Error::AddressParsing);
+        }
+        Ok(Address::from_word(&word))
+    }
+}
@@ -0,0 +1,926 @@
+//! Gas accounting of the EVM: the cost of each [`OpcodeId`] (static part,
+//! memory expansion, [EIP-2929](https://eips.ethereum.org/EIPS/eip-2929)
+//! warm/cold accesses, `SSTORE` rules and gas forwarded to calls) at each
+//! [`Hardfork`] and the verification of the gas transitions found in an
+//! execution trace.
+
+use super::{
+    Address, EvmWord, GlobalCounter, Hardfork, OpcodeId, ProgramCounter,
+};
+use crate::exec_trace::{ExecutionStep, StepSnapshots, TraceState, TxContext};
+use core::fmt;
+use num::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+/// Wrapper type over `u64` which represents the gas left at some point of the
+/// execution.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    PartialOrd,
+    Ord,
+)]
+pub struct Gas(pub(crate) u64);
+
+impl From<Gas> for u64 {
+    fn from(gas: Gas) -> u64 {
+        gas.0
+    }
+}
+
+impl From<u64> for Gas {
+    fn from(gas: u64) -> Self {
+        Gas(gas)
+    }
+}
+
+impl fmt::Display for Gas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Wrapper type over `u64` which represents the amount of gas consumed by
+/// the execution of an [`OpcodeId`].
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    PartialOrd,
+    Ord,
+)]
+pub struct GasCost(pub(crate) u64);
+
+impl GasCost {
+    /// Constant cost for free step
+    pub const ZERO: Self = Self(0);
+    /// Constant cost for `JUMPDEST`
+    pub const ONE: Self = Self(1);
+    /// Constant cost for quick step
+    pub const QUICK: Self = Self(2);
+    /// Constant cost for fastest step
+    pub const FASTEST: Self = Self(3);
+    /// Constant cost for fast step
+    pub const FAST: Self = Self(5);
+    /// Constant cost for mid step
+    pub const MID: Self = Self(8);
+    /// Constant cost for slow step
+    pub const SLOW: Self = Self(10);
+    /// Constant cost for ext step
+    pub const EXT: Self = Self(20);
+    /// Constant cost for `SHA3`
+    pub const SHA3: Self = Self(30);
+    /// Cost per word hashed by `SHA3` and `CREATE2`
+    pub const SHA3_WORD: Self = Self(6);
+    /// Cost per word copied by the copy opcodes
+    pub const COPY_WORD: Self = Self(3);
+    /// Cost per byte of the exponent of `EXP`
+    pub const EXP_BYTE: Self = Self(50);
+    /// Linear cost per word of memory
+    pub const MEMORY: Self = Self(3);
+    /// Cost of accessing a warm account or storage slot
+    pub const WARM_ACCESS: Self = Self(100);
+    /// Cost of a cold `SLOAD`
+    pub const COLD_SLOAD: Self = Self(2100);
+    /// Cost of accessing a cold account
+    pub const COLD_ACCOUNT_ACCESS: Self = Self(2600);
+    /// Cost of an `SSTORE` that sets a zero slot to a non-zero value
+    pub const SSTORE_SET: Self = Self(20000);
+    /// Cost of an `SSTORE` that changes the committed value of a slot
+    pub const SSTORE_RESET: Self = Self(2900);
+    /// Extra cost of a call that transfers value
+    pub const CALL_VALUE: Self = Self(9000);
+    /// Gas given for free to the callee of a call that transfers value
+    pub const CALL_STIPEND: Self = Self(2300);
+    /// Extra cost of a call that brings an empty account into existence
+    pub const NEW_ACCOUNT: Self = Self(25000);
+    /// Constant cost for `CREATE` and `CREATE2`
+    pub const CREATE: Self = Self(32000);
+    /// Constant cost for `SELFDESTRUCT`
+    pub const SELFDESTRUCT: Self = Self(5000);
+    /// Constant cost for the `LOG` opcodes
+    pub const LOG: Self = Self(375);
+    /// Cost per topic of the `LOG` opcodes
+    pub const LOG_TOPIC: Self = Self(375);
+    /// Cost per byte of data of the `LOG` opcodes
+    pub const LOG_DATA: Self = Self(8);
+    /// Intrinsic cost of a transaction
+    pub const TX: Self = Self(21000);
+    /// Intrinsic cost per zero byte of the input of a transaction
+    pub const TX_DATA_ZERO: Self = Self(4);
+    /// Intrinsic cost per non-zero byte of the input of a transaction
+    pub const TX_DATA_NON_ZERO: Self = Self(16);
+    /// Intrinsic cost per account of the access list of a transaction
+    pub const TX_ACCESS_LIST_ADDRESS: Self = Self(2400);
+    /// Intrinsic cost per storage key of the access list of a transaction
+    pub const TX_ACCESS_LIST_STORAGE_KEY: Self = Self(1900);
+
+    /// Returns the `GasCost` as a `u64`.
+    #[inline]
+    pub const fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<GasCost> for u64 {
+    fn from(cost: GasCost) -> u64 {
+        cost.0
+    }
+}
+
+impl From<u64> for GasCost {
+    fn from(cost: u64) -> Self {
+        GasCost(cost)
+    }
+}
+
+impl fmt::Display for GasCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Step of an [`ExecutionTrace`](crate::exec_trace::ExecutionTrace) whose gas
+/// transition doesn't match the cost computed for it's [`OpcodeId`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GasMismatch {
+    gc: GlobalCounter,
+    pc: ProgramCounter,
+    opcode: OpcodeId,
+    expected: GasCost,
+    found: GasCost,
+}
+
+impl GasMismatch {
+    /// Generate a new `GasMismatch` instance from it's fields.
+    pub const fn new(
+        gc: GlobalCounter,
+        pc: ProgramCounter,
+        opcode: OpcodeId,
+        expected: GasCost,
+        found: GasCost,
+    ) -> GasMismatch {
+        GasMismatch {
+            gc,
+            pc,
+            opcode,
+            expected,
+            found,
+        }
+    }
+
+    #[inline]
+    /// Return the [`GlobalCounter`] of the mismatching step.
+    pub const fn gc(&self) -> GlobalCounter {
+        self.gc
+    }
+
+    #[inline]
+    /// Return the [`ProgramCounter`] of the mismatching step.
+    pub const fn pc(&self) -> ProgramCounter {
+        self.pc
+    }
+
+    #[inline]
+    /// Return the [`OpcodeId`] executed by the mismatching step.
+    pub const fn opcode(&self) -> OpcodeId {
+        self.opcode
+    }
+
+    #[inline]
+    /// Return the gas cost computed for the step.
+    pub const fn expected(&self) -> GasCost {
+        self.expected
+    }
+
+    #[inline]
+    /// Return the gas cost found in the trace for the step.
+    pub const fn found(&self) -> GasCost {
+        self.found
+    }
+}
+
+/// Returns the number of 32-byte words needed to hold `byte_size` bytes.
+pub const fn memory_word_size(byte_size: u64) -> u64 {
+    byte_size / 32 + (byte_size % 32 != 0) as u64
+}
+
+/// Returns the cost of expanding the memory from `curr_words` to
+/// `next_words` words (zero if it doesn't grow).
+pub fn memory_expansion_cost(curr_words: u64, next_words: u64) -> GasCost {
+    let cost = |words: u64| {
+        words
+            .saturating_mul(GasCost::MEMORY.0)
+            .saturating_add(words.saturating_mul(words) / 512)
+    };
+    if next_words <= curr_words {
+        GasCost::ZERO
+    } else {
+        GasCost(cost(next_words) - cost(curr_words))
+    }
+}
+
+/// Gas assumed to be left at the steps of traces that don't report it, which
+/// is the gas limit of an Ethereum mainnet block.
+pub const DEFAULT_GAS_LIMIT: u64 = 30_000_000;
+
+/// Returns true if a step with `gas` left (or the [`DEFAULT_GAS_LIMIT`] if
+/// the trace doesn't report it) can pay for expanding the memory of it's
+/// frame from `curr_words` words up to the byte `end`, which bounds the memory
+/// it can access.
+pub fn can_expand_memory(gas: Option<Gas>, curr_words: u64, end: u64) -> bool {
+    let gas = gas.map_or(DEFAULT_GAS_LIMIT, u64::from);
+    memory_expansion_cost(curr_words, memory_word_size(end)).0 <= gas
+}
+
+/// Returns the size in words of the largest memory whose expansion from an
+/// empty one can be paid for with `gas`, which bounds the memory that a step
+/// with that much gas left can access.
+pub fn max_memory_words(gas: u64) -> u64 {
+    // The expansion to `low` words is affordable and the one to `high` words
+    // isn't.
+    let (mut low, mut high) = (0u64, gas / GasCost::MEMORY.0 + 1);
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if memory_expansion_cost(0, mid).0 <= gas {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// Returns the gas left once `gas` has been reduced by `1/64` as
+/// [EIP-150](https://eips.ethereum.org/EIPS/eip-150) mandates for the gas
+/// that a call frame can forward.
+pub const fn all_but_one_64th(gas: u64) -> u64 {
+    gas - gas / 64
+}
+
+/// Returns the intrinsic gas of the transaction described by `tx_ctx` at
+/// `hardfork`: the gas charged before it's first opcode runs.
+pub fn intrinsic_gas(tx_ctx: &TxContext, hardfork: Hardfork) -> u64 {
+    // Non-zero bytes were repriced by EIP-2028.
+    let non_zero_byte_cost = if hardfork >= Hardfork::Istanbul {
+        GasCost::TX_DATA_NON_ZERO.0
+    } else {
+        68
+    };
+    let input_cost: u64 = tx_ctx
+        .input()
+        .iter()
+        .map(|byte| {
+            if *byte == 0 {
+                GasCost::TX_DATA_ZERO.0
+            } else {
+                non_zero_byte_cost
+            }
+        })
+        .sum();
+    // Contract creations are charged as a `CREATE` since Homestead.
+    let create_cost = if tx_ctx.is_create() && hardfork >= Hardfork::Homestead {
+        GasCost::CREATE.0
+    } else {
+        0
+    };
+    // Access lists were introduced by EIP-2930.
+    let access_list_cost: u64 = if hardfork >= Hardfork::Berlin {
+        tx_ctx
+            .access_list()
+            .iter()
+            .map(|(_, keys)| {
+                GasCost::TX_ACCESS_LIST_ADDRESS.0
+                    + GasCost::TX_ACCESS_LIST_STORAGE_KEY.0 * keys.len() as u64
+            })
+            .sum()
+    } else {
+        0
+    };
+    GasCost::TX.0 + create_cost + input_cost + access_list_cost
+}
+
+/// Returns the part of the `refund` counter accumulated by a transaction that
+/// used `gas_used` gas which is given back to the sender at `hardfork`,
+/// capped at a half of the gas used (a fifth since
+/// [EIP-3529](https://eips.ethereum.org/EIPS/eip-3529)).
+pub const fn capped_refund(
+    gas_used: u64,
+    refund: u64,
+    hardfork: Hardfork,
+) -> u64 {
+    let quotient = match hardfork {
+        Hardfork::London => 5,
+        _ => 2,
+    };
+    let cap = gas_used / quotient;
+    if refund < cap {
+        refund
+    } else {
+        cap
+    }
+}
+
+/// Computes the cost of the [`OpcodeId`] executed by `exec_step` reading it's
+/// operands from the stack and memory [before](StepSnapshots::before) it runs
+/// and marking the accounts and storage slots it accesses as warm in `state`.
+/// The memory expansion is charged from the
+/// [sizes](ExecutionStep::memory_size_before) tracked for the step.
+///
+/// For the call opcodes the cost includes the gas forwarded to the callee,
+/// which requires the gas left before the step to be known.
+///
+/// The cost follows the pricing of the [`Hardfork`] of `state`.
+pub fn gen_gas_cost(
+    state: &mut TraceState,
+    snapshots: StepSnapshots<'_>,
+    exec_step: &ExecutionStep,
+) -> GasCost {
+    let before = snapshots.before(exec_step);
+    let opcode = exec_step.instruction().opcode_id();
+    let hardfork = state.hardfork();
+    // Accounts and storage slots are only priced as warm or cold since
+    // Berlin.
+    let access_list = hardfork >= Hardfork::Berlin;
+    let constant_cost = constant_gas_cost(hardfork, opcode);
+    // Operand `idx` counting from the top of the stack before the step.
+    let arg = |idx: usize| -> EvmWord {
+        before
+            .stack()
+            .iter()
+            .rev()
+            .nth(idx)
+            .cloned()
+            .unwrap_or_else(|| EvmWord::from(0u8))
+    };
+    let arg_u64 = |idx: usize| arg(idx).to_u64().unwrap_or(u64::MAX);
+    // The memory expansion is charged on top of the dynamic cost of every
+    // opcode, using the sizes tracked for the step.
+    let memory_cost = memory_expansion_cost(
+        exec_step.memory_size_before(),
+        exec_step.memory_size_after(),
+    );
+    let address_access_cost = |state: &mut TraceState, address: Address| {
+        if access_list && state.access_address(address) {
+            GasCost::COLD_ACCOUNT_ACCESS.0 - GasCost::WARM_ACCESS.0
+        } else {
+            0
+        }
+    };
+
+    let dynamic_cost = match opcode {
+        OpcodeId::EXP => {
+            let exponent_bytes = (arg(1).as_big_uint().bits() + 7) / 8;
+            // Repriced by EIP-160.
+            let byte_cost = if hardfork >= Hardfork::SpuriousDragon {
+                GasCost::EXP_BYTE.0
+            } else {
+                10
+            };
+            byte_cost * exponent_bytes
+        }
+        OpcodeId::SHA3 => GasCost::SHA3_WORD.0 * memory_word_size(arg_u64(1)),
+        OpcodeId::CALLDATACOPY
+        | OpcodeId::CODECOPY
+        | OpcodeId::RETURNDATACOPY => {
+            GasCost::COPY_WORD.0 * memory_word_size(arg_u64(2))
+        }
+        OpcodeId::EXTCODECOPY => {
+            address_access_cost(state, Address::from_word(&arg(0)))
+                + GasCost::COPY_WORD.0 * memory_word_size(arg_u64(3))
+        }
+        OpcodeId::BALANCE | OpcodeId::EXTCODESIZE | OpcodeId::EXTCODEHASH => {
+            address_access_cost(state, Address::from_word(&arg(0)))
+        }
+        OpcodeId::SLOAD => {
+            let address = *state.call().address();
+            if access_list && state.access_storage(address, arg(0)) {
+                GasCost::COLD_SLOAD.0 - GasCost::WARM_ACCESS.0
+            } else {
+                0
+            }
+        }
+        OpcodeId::SSTORE => sstore_cost(state, arg(0), arg(1)).0,
+        OpcodeId::LOG0
+        | OpcodeId::LOG1
+        | OpcodeId::LOG2
+        | OpcodeId::LOG3
+        | OpcodeId::LOG4 => {
+            let topics = (opcode.as_u8() - OpcodeId::LOG0.as_u8()) as u64;
+            (GasCost::LOG_TOPIC.0 * topics)
+                .saturating_add(GasCost::LOG_DATA.0.saturating_mul(arg_u64(1)))
+        }
+        OpcodeId::CREATE2 => {
+            GasCost::SHA3_WORD.0 * memory_word_size(arg_u64(2))
+        }
+        OpcodeId::CALL
+        | OpcodeId::CALLCODE
+        | OpcodeId::DELEGATECALL
+        | OpcodeId::STATICCALL => {
+            // `CALL` and `CALLCODE` hold the value sent as 3rd operand.
+            let value = if matches!(opcode, OpcodeId::CALL | OpcodeId::CALLCODE)
+            {
+                arg(2)
+            } else {
+                EvmWord::from(0u8)
+            };
+            let callee = Address::from_word(&arg(1));
+
+            let mut cost = address_access_cost(state, callee);
+            if !value.is_zero() {
+                cost += GasCost::CALL_VALUE.0;
+            }
+            // Since EIP-161 only the calls that transfer value bring new
+            // accounts into existence.
+            if opcode == OpcodeId::CALL
+                && (!value.is_zero() || hardfork < Hardfork::SpuriousDragon)
+                && state.state_db().account(&callee).is_empty()
+            {
+                cost += GasCost::NEW_ACCOUNT.0;
+            }
+
+            // The gas forwarded to the callee is also charged to the caller,
+            // which can't forward more than all but one 64th of the gas left
+            // in it's frame since EIP-150. Calls that transfer value give the
+            // callee a stipend on top of it.
+            let forwarded = if hardfork < Hardfork::TangerineWhistle {
+                arg_u64(0)
+            } else {
+                let gas = state
+                    .gas_left()
+                    .or_else(|| exec_step.gas().map(u64::from))
+                    .unwrap_or(0);
+                let available = gas.saturating_sub(
+                    constant_cost
+                        .0
+                        .saturating_add(memory_cost.0)
+                        .saturating_add(cost),
+                );
+                arg_u64(0).min(all_but_one_64th(available))
+            };
+            let stipend = if value.is_zero() {
+                0
+            } else {
+                GasCost::CALL_STIPEND.0
+            };
+            state.set_call_gas(
+                state.gas_left().map(|_| forwarded.saturating_add(stipend)),
+            );
+            cost.saturating_add(forwarded)
+        }
+        OpcodeId::SELFDESTRUCT => {
+            let beneficiary = Address::from_word(&arg(0));
+            let address = *state.call().address();
+            // Since EIP-150 sending the balance to an empty account brings it
+            // into existence, which since EIP-161 is only charged when the
+            // balance isn't zero.
+            let new_account_cost = if hardfork >= Hardfork::TangerineWhistle
+                && state.state_db().account(&beneficiary).is_empty()
+                && (hardfork < Hardfork::SpuriousDragon
+                    || !state.state_db().account(&address).balance().is_zero())
+            {
+                GasCost::NEW_ACCOUNT.0
+            } else {
+                0
+            };
+            // The constant cost doesn't include a warm access, so the whole
+            // cold access is charged on top of it.
+            let access_cost =
+                if access_list && state.access_address(beneficiary) {
+                    GasCost::COLD_ACCOUNT_ACCESS.0
+                } else {
+                    0
+                };
+            access_cost + new_account_cost
+        }
+        _ => 0,
+    };
+
+    // Operands too large for the gas of the step saturate the cost, so that
+    // they're reported as an out of gas halt rather than overflowing.
+    GasCost(
+        constant_cost
+            .0
+            .saturating_add(memory_cost.0)
+            .saturating_add(dynamic_cost),
+    )
+}
+
+/// Returns the part of the gas cost of `opcode` at `hardfork` that doesn't
+/// depend on it's operands nor on the state.
+///
+/// It only differs from [`OpcodeId::constant_gas_cost`], which holds the
+/// costs since Berlin, for the opcodes that access the state.
+fn constant_gas_cost(hardfork: Hardfork, opcode: OpcodeId) -> GasCost {
+    if hardfork >= Hardfork::Berlin {
+        return opcode.constant_gas_cost();
+    }
+    // Repricings of EIP-150 and EIP-1884.
+    let tangerine_whistle = hardfork >= Hardfork::TangerineWhistle;
+    let istanbul = hardfork >= Hardfork::Istanbul;
+    GasCost(match opcode {
+        OpcodeId::SLOAD if istanbul => 800,
+        OpcodeId::SLOAD if tangerine_whistle => 200,
+        OpcodeId::SLOAD => 50,
+        OpcodeId::BALANCE if istanbul => 700,
+        OpcodeId::BALANCE if tangerine_whistle => 400,
+        OpcodeId::BALANCE => 20,
+        OpcodeId::EXTCODEHASH if istanbul => 700,
+        OpcodeId::EXTCODEHASH => 400,
+        OpcodeId::EXTCODESIZE
+        | OpcodeId::EXTCODECOPY
+        | OpcodeId::CALL
+        | OpcodeId::CALLCODE
+        | OpcodeId::DELEGATECALL
+        | OpcodeId::STATICCALL
+            if tangerine_whistle =>
+        {
+            700
+        }
+        OpcodeId::EXTCODESIZE | OpcodeId::EXTCODECOPY => 20,
+        OpcodeId::CALL
+        | OpcodeId::CALLCODE
+        | OpcodeId::DELEGATECALL
+        | OpcodeId::STATICCALL => 40,
+        OpcodeId::SELFDESTRUCT if !tangerine_whistle => 0,
+        _ => opcode.constant_gas_cost().0,
+    })
+}
+
+/// Computes the cost of an `SSTORE` of `value` under `key` following
+/// [EIP-2200](https://eips.ethereum.org/EIPS/eip-2200) and
+/// [EIP-2929](https://eips.ethereum.org/EIPS/eip-2929) (or the flat pricing
+/// used before Istanbul).
+fn sstore_cost(
+    state: &mut TraceState,
+    key: EvmWord,
+    value: EvmWord,
+) -> GasCost {
+    let hardfork = state.hardfork();
+    let address = *state.call().address();
+    let current = state
+        .state_db()
+        .storage(&address, &key)
+        .cloned()
+        .unwrap_or_else(|| EvmWord::from(0u8));
+    if hardfork < Hardfork::Istanbul {
+        return if current.is_zero() && !value.is_zero() {
+            GasCost::SSTORE_SET
+        } else {
+            GasCost(5000)
+        };
+    }
+
+    let committed = state.committed_storage(&address, &key);
+    let (cold_cost, noop_cost, reset_cost) = if hardfork >= Hardfork::Berlin {
+        let cold_cost = if state.access_storage(address, key) {
+            GasCost::COLD_SLOAD.0
+        } else {
+            0
+        };
+        (cold_cost, GasCost::WARM_ACCESS.0, GasCost::SSTORE_RESET.0)
+    } else {
+        (0, 800, 5000)
+    };
+
+    let cost = if current == value || committed != current {
+        noop_cost
+    } else if committed.is_zero() {
+        GasCost::SSTORE_SET.0
+    } else {
+        reset_cost
+    };
+    GasCost(cold_cost + cost)
+}
+
+/// Returns the end of the memory region accessed by a call which reads it's
+/// input from `args` and writes it's output to `ret` (both expressed as
+/// `(offset, size)`).
+fn call_memory_end(args: (u64, u64), ret: (u64, u64)) -> u64 {
+    let end = |(offset, size): (u64, u64)| {
+        if size == 0 {
+            0
+        } else {
+            offset.saturating_add(size)
+        }
+    };
+    end(args).max(end(ret))
+}
+
+/// Returns the size in 32-byte words of the memory view of `step`.
+pub(crate) fn memory_words(step: &ExecutionStep) -> u64 {
+    step.memory()
+        .keys()
+        .last()
+        .map(|addr| addr.0.to_u64().map_or(u64::MAX, |addr| addr / 32 + 1))
+        .unwrap_or(0)
+}
+
+/// Returns the memory region accessed by `opcode` as `(offset, size)` given
+/// it's operand `arg(idx)` counting from the top of the stack before it runs,
+/// or `None` if it doesn't access any byte of memory.
+///
+/// The region of the calls starts at address zero and ends at the end of the
+/// furthest of their input and output regions.
+pub fn memory_access(
+    opcode: OpcodeId,
+    arg: impl Fn(usize) -> u64,
+) -> Option<(u64, u64)> {
+    let (offset, size) = match opcode {
+        OpcodeId::SHA3
+        | OpcodeId::LOG0
+        | OpcodeId::LOG1
+        | OpcodeId::LOG2
+        | OpcodeId::LOG3
+        | OpcodeId::LOG4
+        | OpcodeId::RETURN
+        | OpcodeId::REVERT => (arg(0), arg(1)),
+        OpcodeId::CALLDATACOPY
+        | OpcodeId::CODECOPY
+        | OpcodeId::RETURNDATACOPY => (arg(0), arg(2)),
+        OpcodeId::EXTCODECOPY => (arg(1), arg(3)),
+        OpcodeId::MLOAD | OpcodeId::MSTORE => (arg(0), 32),
+        OpcodeId::MSTORE8 => (arg(0), 1),
+        OpcodeId::CREATE | OpcodeId::CREATE2 => (arg(1), arg(2)),
+        OpcodeId::CALL | OpcodeId::CALLCODE => {
+            (0, call_memory_end((arg(3), arg(4)), (arg(5), arg(6))))
+        }
+        OpcodeId::DELEGATECALL | OpcodeId::STATICCALL => {
+            (0, call_memory_end((arg(2), arg(3)), (arg(4), arg(5))))
+        }
+        _ => return None,
+    };
+    Some((offset, size)).filter(|(_, size)| *size != 0)
+}
+
+/// Compares the gas transition of each one of the `steps` against the
+/// `computed` cost of it's [`OpcodeId`], returning the steps that mismatch.
+///
+/// The cost found for a step is the difference between it's gas and the gas
+/// of the next step when both run in the same call frame. For the steps that
+/// end a frame and for the calls (whose next step also accounts for the gas
+/// returned by the callee) the `gasCost` reported by the trace is used.
+pub(crate) fn check_gas(
+    steps: &[ExecutionStep],
+    computed: &[GasCost],
+) -> Vec<GasMismatch> {
+    steps
+        .iter()
+        .zip(computed.iter())
+        .enumerate()
+        .filter_map(|(idx, (step, expected))| {
+            let opcode = step.instruction().opcode_id();
+            if opcode == OpcodeId::INVALID {
+                // Consumes all of the gas left in the frame.
+                return None;
+            }
+            let found = match steps.get(idx + 1) {
+                Some(next)
+                    if next.depth() == step.depth()
+                        && !opcode.is_call_or_create() =>
+                {
+                    step.gas().zip(next.gas()).map_or(
+                        step.gas_cost(),
+                        |(gas, next_gas)| {
+                            GasCost(gas.0.wrapping_sub(next_gas.0))
+                        },
+                    )
+                }
+                _ => step.gas_cost(),
+            };
+            (found != *expected).then(|| {
+                GasMismatch::new(step.gc(), step.pc(), opcode, *expected, found)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod gas_tests {
+    use super::*;
+    use crate::exec_trace::{ExecutionTrace, TxContext};
+    use crate::test_util;
+
+    const GAS_TRACE: &str = r#"
+    [
+        { "memory": {}, "stack": ["2a"], "opcode": "PUSH1 2a", "pc": 0, "gas": 100000, "gasCost": 3 },
+        { "memory": {}, "stack": ["2a", "0"], "opcode": "PUSH1 00", "pc": 2, "gas": 99997, "gasCost": 3 },
+        { "memory": {}, "stack": [], "opcode": "SSTORE", "pc": 4, "gas": 99994, "gasCost": 22100 },
+        { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 5, "gas": 77894, "gasCost": 3 },
+        { "memory": {}, "stack": ["2a"], "opcode": "SLOAD", "pc": 7, "gas": 77891, "gasCost": 100 },
+        { "memory": {}, "stack": ["2a", "20"], "opcode": "PUSH1 20", "pc": 8, "gas": 77791, "gasCost": 3 },
+        { "memory": {}, "stack": ["2a", "20", "0"], "opcode": "PUSH1 00", "pc": 10, "gas": 77788, "gasCost": 3 },
+        { "memory": {}, "stack": ["2a"], "opcode": "RETURN", "pc": 12, "gas": 77785, "gasCost": 3 }
+    ]
+    "#;
+
+    fn trace_from(input_trace: &str) -> ExecutionTrace<pasta_curves::Fp> {
+        trace_at(input_trace, Hardfork::default())
+    }
+
+    fn trace_at(
+        input_trace: &str,
+        hardfork: Hardfork,
+    ) -> ExecutionTrace<pasta_curves::Fp> {
+        let block_ctants = test_util::block_ctants();
+        let tx_ctx = test_util::tx_ctx(Some(test_util::address("aa")), vec![]);
+        ExecutionTrace::from_trace_bytes_with_hardfork(
+            input_trace.as_bytes(),
+            block_ctants,
+            tx_ctx,
+            hardfork,
+        )
+        .expect("Error on trace generation")
+    }
+
+    #[test]
+    fn gas_transitions_match() {
+        assert_eq!(trace_from(GAS_TRACE).gas_mismatches(), &[]);
+    }
+
+    #[test]
+    fn gas_transition_mismatches_are_reported() {
+        // The SSTORE is reported as if it was charged 100 gas less.
+        let trace = trace_from(
+            &GAS_TRACE.replace(r#""gas": 77894"#, r#""gas": 77994"#),
+        );
+        let mismatches: Vec<(OpcodeId, GasCost, GasCost)> = trace
+            .gas_mismatches()
+            .iter()
+            .map(|mismatch| {
+                (mismatch.opcode(), mismatch.expected(), mismatch.found())
+            })
+            .collect();
+        assert_eq!(
+            mismatches,
+            vec![
+                (OpcodeId::SSTORE, GasCost(22100), GasCost(22000)),
+                (OpcodeId::PUSH1, GasCost(3), GasCost(103)),
+            ]
+        );
+    }
+
+    #[test]
+    fn gas_depends_on_hardfork() {
+        // Same trace as `GAS_TRACE`, priced at Istanbul: no cold accesses
+        // and `SLOAD` costs 800.
+        let istanbul_trace = r#"
+        [
+            { "memory": {}, "stack": ["2a"], "opcode": "PUSH1 2a", "pc": 0, "gas": 100000, "gasCost": 3 },
+            { "memory": {}, "stack": ["2a", "0"], "opcode": "PUSH1 00", "pc": 2, "gas": 99997, "gasCost": 3 },
+            { "memory": {}, "stack": [], "opcode": "SSTORE", "pc": 4, "gas": 99994, "gasCost": 20000 },
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 5, "gas": 79994, "gasCost": 3 },
+            { "memory": {}, "stack": ["2a"], "opcode": "SLOAD", "pc": 7, "gas": 79991, "gasCost": 800 },
+            { "memory": {}, "stack": ["2a", "20"], "opcode": "PUSH1 20", "pc": 8, "gas": 79191, "gasCost": 3 },
+            { "memory": {}, "stack": ["2a", "20", "0"], "opcode": "PUSH1 00", "pc": 10, "gas": 79188, "gasCost": 3 },
+            { "memory": {}, "stack": ["2a"], "opcode": "RETURN", "pc": 12, "gas": 79185, "gasCost": 3 }
+        ]
+        "#;
+        assert_eq!(
+            trace_at(istanbul_trace, Hardfork::Istanbul).gas_mismatches(),
+            &[]
+        );
+        // At Berlin the storage slot accesses are priced as cold.
+        let mismatches: Vec<(OpcodeId, GasCost)> =
+            trace_at(istanbul_trace, Hardfork::Berlin)
+                .gas_mismatches()
+                .iter()
+                .map(|mismatch| (mismatch.opcode(), mismatch.expected()))
+                .collect();
+        assert_eq!(
+            mismatches,
+            vec![
+                (OpcodeId::SSTORE, GasCost(22100)),
+                (OpcodeId::SLOAD, GasCost(100)),
+            ]
+        );
+    }
+
+    #[test]
+    fn huge_operands_saturate_the_cost() {
+        // A `LOG4` of 2^64 - 1 bytes can't be paid for, and it's cost
+        // saturates instead of overflowing.
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["0", "0", "0", "0", "ffffffffffffffff", "0"], "opcode": "PUSH1 00", "pc": 0, "gas": 100000, "gasCost": 3 },
+            { "memory": {}, "stack": [], "opcode": "LOG4", "pc": 2, "gas": 99997, "gasCost": 99997 }
+        ]
+        "#;
+        let mismatches: Vec<(OpcodeId, GasCost)> = trace_from(input_trace)
+            .gas_mismatches()
+            .iter()
+            .map(|mismatch| (mismatch.opcode(), mismatch.expected()))
+            .collect();
+        assert_eq!(mismatches, vec![(OpcodeId::LOG4, GasCost(u64::MAX))]);
+    }
+
+    #[test]
+    fn selfdestruct_cost() {
+        let trace_with_beneficiary = |beneficiary: &str, gas_cost: u64| {
+            trace_from(&format!(
+                r#"
+                [
+                    {{ "memory": {{}}, "stack": ["{0}"], "opcode": "PUSH1 {0}", "pc": 0, "gas": 100000, "gasCost": 3 }},
+                    {{ "memory": {{}}, "stack": [], "opcode": "SELFDESTRUCT", "pc": 2, "gas": 99997, "gasCost": {1} }}
+                ]
+                "#,
+                beneficiary, gas_cost
+            ))
+        };
+        // The contract itself is warm, while a cold beneficiary costs the
+        // whole cold access on top of the constant cost.
+        assert_eq!(trace_with_beneficiary("aa", 5000).gas_mismatches(), &[]);
+        assert_eq!(trace_with_beneficiary("bb", 7600).gas_mismatches(), &[]);
+    }
+
+    #[test]
+    fn intrinsic_gas_mismatches_are_reported() {
+        // The transaction of `GAS_TRACE` has a gas limit of 100000, which is
+        // all left at it's first step.
+        let trace = trace_from(GAS_TRACE);
+        assert_eq!(trace.intrinsic_gas(), GasCost(21000));
+        assert_eq!(trace.intrinsic_gas_mismatch(), Some(GasCost(0)));
+
+        let tx_ctx = TxContext::new(
+            Address::zero(),
+            Some(test_util::address("aa")),
+            0,
+            EvmWord::from(0u8),
+            EvmWord::from(0u8),
+            121_000,
+            vec![],
+        );
+        let trace =
+            ExecutionTrace::<pasta_curves::Fp>::from_trace_bytes_with_tx(
+                GAS_TRACE.as_bytes(),
+                test_util::block_ctants(),
+                tx_ctx,
+            )
+            .expect("Error on trace generation");
+        assert_eq!(trace.intrinsic_gas_mismatch(), None);
+    }
+
+    #[test]
+    fn intrinsic_gas_of_transactions() {
+        let tx_ctx = |to: Option<Address>| {
+            TxContext::new(
+                Address::zero(),
+                to,
+                0,
+                EvmWord::from(0u8),
+                EvmWord::from(0u8),
+                100_000,
+                vec![0x00, 0x01],
+            )
+        };
+        let call = tx_ctx(Some(Address::zero()));
+        assert_eq!(intrinsic_gas(&call, Hardfork::London), 21000 + 4 + 16);
+        assert_eq!(intrinsic_gas(&call, Hardfork::Petersburg), 21000 + 4 + 68);
+        let create = tx_ctx(None);
+        assert_eq!(
+            intrinsic_gas(&create, Hardfork::London),
+            21000 + 32000 + 4 + 16
+        );
+        assert_eq!(intrinsic_gas(&create, Hardfork::Frontier), 21000 + 4 + 68);
+        let access_list = call.with_access_list(vec![
+            (
+                Address::zero(),
+                vec![EvmWord::from(0u8), EvmWord::from(1u8)],
+            ),
+            (Address::zero(), vec![]),
+        ]);
+        assert_eq!(
+            intrinsic_gas(&access_list, Hardfork::Berlin),
+            21000 + 4 + 16 + 2 * 2400 + 2 * 1900
+        );
+
+        assert_eq!(capped_refund(21000, 15000, Hardfork::Berlin), 10500);
+        assert_eq!(capped_refund(21000, 15000, Hardfork::London), 4200);
+    }
+
+    #[test]
+    fn memory_expansion() {
+        assert_eq!(memory_word_size(0), 0);
+        assert_eq!(memory_word_size(1), 1);
+        assert_eq!(memory_word_size(33), 2);
+        assert_eq!(memory_expansion_cost(0, 1), GasCost(3));
+        assert_eq!(memory_expansion_cost(1, 1), GasCost::ZERO);
+        // 3 * 1024 + 1024^2 / 512
+        assert_eq!(memory_expansion_cost(0, 1024), GasCost(5120));
+        assert_eq!(memory_expansion_cost(1, 1024), GasCost(5117));
+        assert_eq!(max_memory_words(2), 0);
+        assert_eq!(max_memory_words(5120), 1024);
+        assert_eq!(max_memory_words(5122), 1024);
+    }
+}
@@ -0,0 +1,173 @@
+//! Hardforks of the EVM, which define the set of opcodes and precompiled
+//! contracts available and the gas they cost.
+
+use super::{precompiles::Precompile, OpcodeId};
+use serde::{Deserialize, Serialize};
+
+/// Hardforks of Ethereum mainnet that changed the behaviour of the EVM,
+/// ordered by activation.
+///
+/// Hardforks that only delayed the difficulty bomb (Muir Glacier, Arrow
+/// Glacier...) are not listed, since they are equivalent to the previous
+/// one as far as the EVM is concerned.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    PartialEq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub enum Hardfork {
+    /// Launch of the network.
+    Frontier,
+    /// Adds `DELEGATECALL`.
+    Homestead,
+    /// Reprices the IO-heavy opcodes
+    /// ([EIP-150](https://eips.ethereum.org/EIPS/eip-150)).
+    TangerineWhistle,
+    /// Reprices `EXP` and removes empty accounts
+    /// ([EIP-160](https://eips.ethereum.org/EIPS/eip-160),
+    /// [EIP-161](https://eips.ethereum.org/EIPS/eip-161)).
+    SpuriousDragon,
+    /// Adds `REVERT`, `RETURNDATASIZE`, `RETURNDATACOPY`, `STATICCALL` and
+    /// the precompiles `0x05` to `0x08`.
+    Byzantium,
+    /// Adds `SHL`, `SHR`, `SAR`, `EXTCODEHASH` and `CREATE2`.
+    Constantinople,
+    /// Removes the net gas metering of `SSTORE` introduced by
+    /// Constantinople, which mainnet never activated.
+    Petersburg,
+    /// Adds `CHAINID`, `SELFBALANCE` and the `0x09` precompile and reprices
+    /// `SLOAD`, `SSTORE` and `BALANCE`
+    /// ([EIP-1884](https://eips.ethereum.org/EIPS/eip-1884),
+    /// [EIP-2200](https://eips.ethereum.org/EIPS/eip-2200)).
+    Istanbul,
+    /// Prices state accesses depending on whether they are warm or cold
+    /// ([EIP-2929](https://eips.ethereum.org/EIPS/eip-2929)).
+    Berlin,
+    /// Adds `BASEFEE`.
+    London,
+}
+
+impl Default for Hardfork {
+    fn default() -> Self {
+        Hardfork::London
+    }
+}
+
+impl Hardfork {
+    /// Returns the `Hardfork` active on Ethereum mainnet at the block
+    /// `block_number`.
+    pub const fn mainnet_at(block_number: u64) -> Hardfork {
+        match block_number {
+            0..=1_149_999 => Hardfork::Frontier,
+            1_150_000..=2_462_999 => Hardfork::Homestead,
+            2_463_000..=2_674_999 => Hardfork::TangerineWhistle,
+            2_675_000..=4_369_999 => Hardfork::SpuriousDragon,
+            4_370_000..=7_279_999 => Hardfork::Byzantium,
+            // Constantinople and Petersburg were activated at the same block.
+            7_280_000..=9_068_999 => Hardfork::Petersburg,
+            9_069_000..=12_243_999 => Hardfork::Istanbul,
+            12_244_000..=12_964_999 => Hardfork::Berlin,
+            _ => Hardfork::London,
+        }
+    }
+
+    /// Returns true if `opcode` is defined at this `Hardfork`.
+    pub fn has_opcode(&self, opcode: OpcodeId) -> bool {
+        *self >= opcode.introduced_in()
+    }
+
+    /// Returns true if `precompile` is deployed at this `Hardfork`.
+    pub fn has_precompile(&self, precompile: Precompile) -> bool {
+        let introduced_in = match precompile {
+            Precompile::EcRecover
+            | Precompile::Sha256
+            | Precompile::Ripemd160
+            | Precompile::Identity => Hardfork::Frontier,
+            Precompile::ModExp
+            | Precompile::Bn256Add
+            | Precompile::Bn256ScalarMul
+            | Precompile::Bn256Pairing => Hardfork::Byzantium,
+            Precompile::Blake2F => Hardfork::Istanbul,
+        };
+        *self >= introduced_in
+    }
+}
+
+#[cfg(test)]
+mod hardfork_tests {
+    use super::*;
+    use crate::{
+        exec_trace::{ExecutionTrace, TxContext},
+        test_util, Error,
+    };
+    use core::str::FromStr;
+
+    #[test]
+    fn mainnet_schedule() {
+        assert_eq!(Hardfork::mainnet_at(0), Hardfork::Frontier);
+        assert_eq!(Hardfork::mainnet_at(4_370_000), Hardfork::Byzantium);
+        assert_eq!(Hardfork::mainnet_at(12_243_999), Hardfork::Istanbul);
+        assert_eq!(Hardfork::mainnet_at(12_244_000), Hardfork::Berlin);
+        assert_eq!(Hardfork::mainnet_at(15_000_000), Hardfork::London);
+    }
+
+    #[test]
+    fn opcodes_are_gated() {
+        assert!(Hardfork::London.has_opcode(OpcodeId::BASEFEE));
+        assert!(!Hardfork::Berlin.has_opcode(OpcodeId::BASEFEE));
+        assert!(Hardfork::Istanbul.has_opcode(OpcodeId::CHAINID));
+        assert!(!Hardfork::Petersburg.has_opcode(OpcodeId::CHAINID));
+        assert!(Hardfork::Frontier.has_opcode(OpcodeId::CALL));
+
+        assert!(OpcodeId::from_str_with_hardfork(
+            "CHAINID",
+            Hardfork::Istanbul
+        )
+        .is_ok());
+        assert!(
+            OpcodeId::from_str_with_hardfork("BASEFEE", Hardfork::Berlin)
+                .is_err()
+        );
+        // Parsing without a `Hardfork` accepts every known opcode.
+        assert_eq!(OpcodeId::from_str("BASEFEE").unwrap(), OpcodeId::BASEFEE);
+    }
+
+    #[test]
+    fn traces_are_gated() {
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["7"], "opcode": "BASEFEE", "pc": 0 }
+        ]
+        "#;
+        let block_ctants = test_util::block_ctants();
+
+        let result = ExecutionTrace::from_trace_bytes_with_hardfork(
+            input_trace.as_bytes(),
+            block_ctants,
+            TxContext::default(),
+            Hardfork::Berlin,
+        );
+        assert!(matches!(
+            result,
+            Err(Error::OpcodeNotInHardfork(
+                OpcodeId::BASEFEE,
+                Hardfork::Berlin
+            ))
+        ));
+    }
+
+    #[test]
+    fn precompiles_are_gated() {
+        assert!(Hardfork::Frontier.has_precompile(Precompile::Identity));
+        assert!(!Hardfork::SpuriousDragon.has_precompile(Precompile::ModExp));
+        assert!(!Hardfork::Petersburg.has_precompile(Precompile::Blake2F));
+        assert!(Hardfork::Istanbul.has_precompile(Precompile::Blake2F));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::error::Error;
 
 use super::{opcodes::ids::OpcodeId, EvmWord};
-use core::str::FromStr;
+use core::{fmt, str::FromStr};
 
 /// Represents the union between an [`OpcodeId`] and the actual associated value
 /// (if it has any).
@@ -36,6 +36,18 @@ impl Instruction {
     }
 }
 
+impl fmt::Display for Instruction {
+    /// Formats the instruction as the trace does, followed by it's associated
+    /// value if it has any (i.e. `PUSH1 0x40`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.opcode)?;
+        match &self.assoc_value {
+            Some(value) => write!(f, " {}", value),
+            None => Ok(()),
+        }
+    }
+}
+
 impl FromStr for Instruction {
     type Err = crate::error::Error;
 
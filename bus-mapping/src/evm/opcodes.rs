@@ -1,45 +1,415 @@
 //! Definition of each opcode of the EVM.
+mod account;
+mod call;
+mod copy;
+pub(crate) mod create;
+mod environment;
+mod halt;
 pub mod ids;
+mod log;
+mod memory;
 mod push;
-use self::push::Push1;
+mod stack;
+mod storage;
+use self::account::{Balance, ExtCodeHash, SelfBalance};
+use self::call::{Call, CallCode, DelegateCall, StaticCall};
+use self::copy::{CalldataCopy, CodeCopy, ExtCodeCopy, ReturnDataCopy};
+use self::create::{Create, Create2};
+use self::environment::{
+    CallValue, CalldataSize, Caller, CodeSize, ContractAddress, GasLeft,
+    GasPrice, Msize, Origin, ReturnDataSize,
+};
+use self::halt::{Invalid, Return, Revert, SelfDestruct, Stop};
+use self::log::Log;
+use self::memory::{Mload, Mstore, Mstore8};
+use self::push::Push;
+use self::stack::{Dup, StackOnly, Swap};
+use self::storage::{Sload, Sstore};
 use crate::{
-    exec_trace::ExecutionStep, operation::container::OperationContainer,
+    evm::{gas, EvmWord, GasCost, MemoryAddress, StackAddress},
+    exec_trace::{ExecutionStep, StepSnapshots, TraceState},
+    operation::{container::OperationContainer, MemoryOp, StackOp, RW},
 };
+use core::convert::TryFrom;
 use core::fmt::Debug;
 use ids::OpcodeId;
+use std::collections::HashMap;
 
 /// Generic opcode trait which defines the logic of the
 /// [`Operation`](crate::operation::Operation) that should be generated for an
 /// [`ExecutionStep`](crate::exec_trace::ExecutionStep) depending of the
 /// [`OpcodeId`] it contains.
+///
+/// The implementation used for each [`OpcodeId`] is looked up in an
+/// [`OpcodeRegistry`].
 pub trait Opcode: Debug {
     /// Generate the associated [`MemoryOp`](crate::operation::MemoryOp)s,
     /// [`StackOp`](crate::operation::StackOp)s, and
     /// [`StorageOp`](crate::operation::StorageOp)s associated to the Opcode
     /// is implemented for.
+    ///
+    /// The operands consumed by the instruction are read from the view of
+    /// the stack and memory [before](StepSnapshots::before) it runs, and it's
+    /// results from the view [after](StepSnapshots::after) it. Which of the
+    /// steps around `exec_step` holds each view is given by the
+    /// [`SnapshotConvention`](crate::exec_trace::SnapshotConvention) of the
+    /// trace, so implementations must not assume that `exec_step` holds any
+    /// of them. The [`TraceState`] provides the call frame and the accounts
+    /// state the step runs against.
     fn gen_associated_ops(
         &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
         exec_step: &mut ExecutionStep,
         container: &mut OperationContainer,
     ) -> usize;
-}
 
-// This is implemented for OpcodeId so that we can downcast the responsabilities
-// to the specific Opcode structure implementations since OpcodeId is a single
-// structure with all the OPCODES stated as associated constants.
-// Easier to solve with a macro. But leaving here for now until we refactor in a
-// future PR.
-impl Opcode for OpcodeId {
-    fn gen_associated_ops(
+    /// Computes the gas cost of `exec_step`, reading it's operands from
+    /// `snapshots` in the same way as
+    /// [`gen_associated_ops`](Opcode::gen_associated_ops) does.
+    ///
+    /// Defaults to the cost the EVM charges, computed by
+    /// [`gas::gen_gas_cost`].
+    fn gen_gas_cost(
         &self,
-        exec_step: &mut ExecutionStep,
-        container: &mut OperationContainer,
-    ) -> usize {
-        match *self {
-            OpcodeId::PUSH1 => {
-                Push1 {}.gen_associated_ops(exec_step, container)
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &ExecutionStep,
+    ) -> GasCost {
+        gas::gen_gas_cost(state, snapshots, exec_step)
+    }
+}
+
+/// Registry that maps each [`OpcodeId`] to the [`Opcode`] implementation
+/// that generates the [`Operation`](crate::operation::Operation)s and computes
+/// the gas cost of the steps that execute it.
+///
+/// [`OpcodeRegistry::default`] holds the generators of all of the EVM
+/// opcodes. Integrators can register their own ones to
+/// support custom opcodes or to override the behaviour of the default ones.
+#[derive(Debug)]
+pub struct OpcodeRegistry {
+    opcodes: HashMap<OpcodeId, Box<dyn Opcode>>,
+}
+
+impl Default for OpcodeRegistry {
+    fn default() -> Self {
+        let mut registry = OpcodeRegistry::new();
+        // Every known opcode reads it's operands from the stack and pushes
+        // it's result, while the ones with other effects are replaced below.
+        for byte in 0..=u8::MAX {
+            let id = OpcodeId(byte);
+            if id.name().is_some() {
+                registry.register(id, Box::new(StackOnly));
             }
-            _ => unimplemented!(),
+        }
+        registry.register(OpcodeId::STOP, Box::new(Stop));
+        registry.register(OpcodeId::ADDRESS, Box::new(ContractAddress));
+        registry.register(OpcodeId::BALANCE, Box::new(Balance));
+        registry.register(OpcodeId::ORIGIN, Box::new(Origin));
+        registry.register(OpcodeId::CALLER, Box::new(Caller));
+        registry.register(OpcodeId::CALLVALUE, Box::new(CallValue));
+        registry.register(OpcodeId::CALLDATASIZE, Box::new(CalldataSize));
+        registry.register(OpcodeId::CALLDATACOPY, Box::new(CalldataCopy));
+        registry.register(OpcodeId::CODESIZE, Box::new(CodeSize));
+        registry.register(OpcodeId::CODECOPY, Box::new(CodeCopy));
+        registry.register(OpcodeId::GASPRICE, Box::new(GasPrice));
+        registry.register(OpcodeId::EXTCODECOPY, Box::new(ExtCodeCopy));
+        registry.register(OpcodeId::RETURNDATASIZE, Box::new(ReturnDataSize));
+        registry.register(OpcodeId::RETURNDATACOPY, Box::new(ReturnDataCopy));
+        registry.register(OpcodeId::EXTCODEHASH, Box::new(ExtCodeHash));
+        registry.register(OpcodeId::SELFBALANCE, Box::new(SelfBalance));
+        registry.register(OpcodeId::MLOAD, Box::new(Mload));
+        registry.register(OpcodeId::MSTORE, Box::new(Mstore));
+        registry.register(OpcodeId::MSTORE8, Box::new(Mstore8));
+        registry.register(OpcodeId::SLOAD, Box::new(Sload));
+        registry.register(OpcodeId::SSTORE, Box::new(Sstore));
+        registry.register(OpcodeId::MSIZE, Box::new(Msize));
+        registry.register(OpcodeId::GAS, Box::new(GasLeft));
+        for byte in OpcodeId::PUSH1.as_u8()..=OpcodeId::PUSH32.as_u8() {
+            registry.register(OpcodeId(byte), Box::new(Push));
+        }
+        for byte in OpcodeId::DUP1.as_u8()..=OpcodeId::DUP16.as_u8() {
+            registry.register(OpcodeId(byte), Box::new(Dup));
+        }
+        for byte in OpcodeId::SWAP1.as_u8()..=OpcodeId::SWAP16.as_u8() {
+            registry.register(OpcodeId(byte), Box::new(Swap));
+        }
+        registry.register(OpcodeId::LOG0, Box::new(Log));
+        registry.register(OpcodeId::LOG1, Box::new(Log));
+        registry.register(OpcodeId::LOG2, Box::new(Log));
+        registry.register(OpcodeId::LOG3, Box::new(Log));
+        registry.register(OpcodeId::LOG4, Box::new(Log));
+        registry.register(OpcodeId::CREATE, Box::new(Create));
+        registry.register(OpcodeId::CALL, Box::new(Call));
+        registry.register(OpcodeId::CALLCODE, Box::new(CallCode));
+        registry.register(OpcodeId::RETURN, Box::new(Return));
+        registry.register(OpcodeId::DELEGATECALL, Box::new(DelegateCall));
+        registry.register(OpcodeId::CREATE2, Box::new(Create2));
+        registry.register(OpcodeId::STATICCALL, Box::new(StaticCall));
+        registry.register(OpcodeId::REVERT, Box::new(Revert));
+        registry.register(OpcodeId::INVALID, Box::new(Invalid));
+        registry.register(OpcodeId::SELFDESTRUCT, Box::new(SelfDestruct));
+        registry
+    }
+}
+
+impl OpcodeRegistry {
+    /// Generates a new empty `OpcodeRegistry`.
+    pub fn new() -> OpcodeRegistry {
+        OpcodeRegistry {
+            opcodes: HashMap::new(),
+        }
+    }
+
+    /// Registers `opcode` as the implementation of `id`, returning the one it
+    /// replaces (if any).
+    pub fn register(
+        &mut self,
+        id: OpcodeId,
+        opcode: Box<dyn Opcode>,
+    ) -> Option<Box<dyn Opcode>> {
+        self.opcodes.insert(id, opcode)
+    }
+
+    /// Returns the [`Opcode`] implementation registered for `id`.
+    pub fn get(&self, id: &OpcodeId) -> Option<&dyn Opcode> {
+        self.opcodes.get(id).map(|opcode| opcode.as_ref())
+    }
+
+    /// Returns true if there's an [`Opcode`] implementation registered for
+    /// `id`.
+    pub fn contains(&self, id: &OpcodeId) -> bool {
+        self.opcodes.contains_key(id)
+    }
+}
+
+/// Generates the [`StackOp`] reads of the `n` words placed at the top of the
+/// stack before the instruction of `exec_step` runs, which are the operands
+/// it consumes, and adds them to the bus-mapping instance of `exec_step`.
+///
+/// Returns the words read, starting from the top of the stack.
+pub fn gen_stack_reads(
+    state: &TraceState,
+    snapshots: StepSnapshots<'_>,
+    n: usize,
+    exec_step: &mut ExecutionStep,
+    container: &mut OperationContainer,
+) -> Vec<EvmWord> {
+    let before = snapshots.before(exec_step);
+    let top = usize::from(before.stack_addr());
+    let values: Vec<EvmWord> =
+        before.stack().iter().rev().take(n).cloned().collect();
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(idx, value)| {
+            exec_step.add_op(
+                container,
+                StackOp::new(
+                    RW::READ,
+                    exec_step.next_op_gc(),
+                    state.call().call_id(),
+                    (top + idx).into(),
+                    value.clone(),
+                ),
+            );
+            value
+        })
+        .collect()
+}
+
+/// Returns the [`StackAddress`] and the value of the word placed at the top
+/// of the stack once the instruction of `exec_step` has been executed, which
+/// is the result it pushes.
+///
+/// Returns `None` if `snapshots` holds no view of the stack after the step,
+/// like for the last step of a frame under the
+/// [`PreState`](crate::exec_trace::SnapshotConvention::PreState) convention
+/// (which includes the steps that halt exceptionally), in which case the
+/// result isn't written.
+pub(crate) fn stack_result(
+    snapshots: StepSnapshots<'_>,
+    exec_step: &ExecutionStep,
+) -> Option<(StackAddress, EvmWord)> {
+    let after = snapshots.after(exec_step)?;
+    Some((after.stack_addr(), after.stack().last()?.clone()))
+}
+
+/// Returns the byte offset and size of the memory region of `size` bytes
+/// starting at `offset` that the instruction of `exec_step` accesses, or
+/// `None` if the step can't pay for the expansion of the memory up to the end
+/// of the region, in which case it halts exceptionally without accessing it.
+/// Empty regions are accessible whatever their offset.
+///
+/// The gas left before the step bounds the expansion, or the
+/// [`DEFAULT_GAS_LIMIT`](gas::DEFAULT_GAS_LIMIT) if the trace doesn't report
+/// it.
+pub(crate) fn memory_region(
+    exec_step: &ExecutionStep,
+    offset: &EvmWord,
+    size: &EvmWord,
+) -> Option<(usize, usize)> {
+    if size.is_zero() {
+        return Some((0, 0));
+    }
+    let (offset, size) = (offset.to_u64()?, size.to_u64()?);
+    let end = offset.checked_add(size)?;
+    if !gas::can_expand_memory(
+        exec_step.gas(),
+        exec_step.memory_size_before(),
+        end,
+    ) {
+        return None;
+    }
+    Some((usize::try_from(offset).ok()?, usize::try_from(size).ok()?))
+}
+
+/// Returns the `size` bytes of memory starting at `offset` before the
+/// instruction of `exec_step` runs, a region that [`memory_region`] found
+/// `exec_step` can access.
+pub(crate) fn memory_bytes_before(
+    snapshots: StepSnapshots<'_>,
+    exec_step: &ExecutionStep,
+    offset: usize,
+    size: usize,
+) -> Vec<u8> {
+    snapshots.before(exec_step).read_memory(offset, size)
+}
+
+/// Returns the `size` bytes of memory starting at `offset` once the
+/// instruction of `exec_step` has been executed, a region that
+/// [`memory_region`] found `exec_step` can access, or `None` if `snapshots`
+/// holds no view of the memory after the step (see [`stack_result`]).
+pub(crate) fn memory_bytes_after(
+    snapshots: StepSnapshots<'_>,
+    exec_step: &ExecutionStep,
+    offset: usize,
+    size: usize,
+) -> Option<Vec<u8>> {
+    Some(snapshots.after(exec_step)?.read_memory(offset, size))
+}
+
+/// Generates the [`MemoryOp`] that reads or writes `byte` at the byte address
+/// `address` of the memory of the call `call_id`, and adds it to the
+/// bus-mapping instance of `exec_step`.
+pub(crate) fn gen_memory_op(
+    exec_step: &mut ExecutionStep,
+    container: &mut OperationContainer,
+    rw: RW,
+    call_id: usize,
+    address: usize,
+    byte: u8,
+) {
+    exec_step.add_op(
+        container,
+        MemoryOp::new(
+            rw,
+            exec_step.next_op_gc(),
+            call_id,
+            MemoryAddress::from(address),
+            EvmWord::from(byte),
+        ),
+    );
+}
+
+#[cfg(test)]
+mod opcodes_tests {
+    use super::*;
+    use crate::{
+        evm::Hardfork,
+        exec_trace::{ExecutionTrace, TxContext},
+        test_util, Error,
+    };
+
+    /// `JUMPDEST` replacement which costs 7 gas.
+    #[derive(Debug, Copy, Clone)]
+    struct CustomJumpDest;
+
+    impl Opcode for CustomJumpDest {
+        fn gen_associated_ops(
+            &self,
+            _state: &mut TraceState,
+            _snapshots: StepSnapshots<'_>,
+            exec_step: &mut ExecutionStep,
+            _container: &mut OperationContainer,
+        ) -> usize {
+            exec_step.bus_mapping_instance().len()
+        }
+
+        fn gen_gas_cost(
+            &self,
+            _state: &mut TraceState,
+            _snapshots: StepSnapshots<'_>,
+            _exec_step: &ExecutionStep,
+        ) -> GasCost {
+            GasCost(7)
+        }
+    }
+
+    #[test]
+    fn custom_opcode_registration() {
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0, "gas": 100, "gasCost": 3 },
+            { "memory": {}, "stack": ["1"], "opcode": "JUMPDEST", "pc": 2, "gas": 97, "gasCost": 7 },
+            { "memory": {}, "stack": ["1"], "opcode": "STOP", "pc": 3, "gas": 90, "gasCost": 0 }
+        ]
+        "#;
+
+        let mut registry = OpcodeRegistry::default();
+        // Registering an already supported opcode overrides it.
+        assert!(registry.contains(&OpcodeId::JUMPDEST));
+        assert!(registry
+            .register(OpcodeId::JUMPDEST, Box::new(CustomJumpDest))
+            .is_some());
+
+        let block_ctants = test_util::block_ctants();
+        let trace = ExecutionTrace::from_trace_bytes_with_registry(
+            input_trace.as_bytes(),
+            block_ctants,
+            TxContext::default(),
+            Hardfork::default(),
+            &registry,
+        )
+        .expect("Error on trace generation");
+
+        assert!(trace[1].bus_mapping_instance().is_empty());
+        assert_eq!(trace.gas_mismatches(), &[]);
+    }
+
+    #[test]
+    fn unregistered_opcodes_fail() {
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0 },
+            { "memory": {}, "stack": ["1"], "opcode": "STOP", "pc": 2 }
+        ]
+        "#;
+
+        let mut registry = OpcodeRegistry::new();
+        registry.register(OpcodeId::STOP, Box::new(Stop));
+        let block_ctants = test_util::block_ctants();
+        let result = ExecutionTrace::from_trace_bytes_with_registry(
+            input_trace.as_bytes(),
+            block_ctants,
+            TxContext::default(),
+            Hardfork::default(),
+            &registry,
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::OpcodeNotRegistered(OpcodeId::PUSH1))
+        ));
+    }
+
+    #[test]
+    fn every_opcode_is_registered() {
+        let registry = OpcodeRegistry::default();
+        for byte in 0..=u8::MAX {
+            let id = OpcodeId(byte);
+            assert_eq!(registry.contains(&id), id.name().is_some());
         }
     }
 }
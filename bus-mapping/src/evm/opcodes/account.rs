@@ -0,0 +1,350 @@
+//! Opcodes that read the fields of an account.
+use super::{gen_stack_reads, stack_result, Opcode};
+use crate::{
+    evm::{Address, EvmWord, StackAddress},
+    exec_trace::{ExecutionStep, StepSnapshots, TraceState},
+    operation::{container::OperationContainer, AccountField, StackOp, RW},
+    TraceError,
+};
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `BALANCE` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Balance;
+
+impl Opcode for Balance {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let address = Address::from_word(
+            &gen_stack_reads(state, snapshots, 1, exec_step, container)[0],
+        );
+        gen_balance_ops(state, snapshots, address, exec_step, container)
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `SELFBALANCE` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct SelfBalance;
+
+impl Opcode for SelfBalance {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let address = *state.call().address();
+        gen_balance_ops(state, snapshots, address, exec_step, container)
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `EXTCODEHASH` [`Instruction`](crate::evm::instruction::Instruction).
+///
+/// The code hash of an empty account is read as usual, while the word pushed
+/// into the stack is zero
+/// ([EIP-1052](https://eips.ethereum.org/EIPS/eip-1052)).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ExtCodeHash;
+
+impl Opcode for ExtCodeHash {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let address = Address::from_word(
+            &gen_stack_reads(state, snapshots, 1, exec_step, container)[0],
+        );
+
+        // The stack after this step holds the code hash, which is checked
+        // against the state or seeds it when it isn't known yet. Zero is
+        // pushed for empty accounts, which can only be checked when all of
+        // their fields are known. Without it the code hash can only be read
+        // if it's known, and nothing is pushed.
+        let result = stack_result(snapshots, exec_step);
+        let known_non_empty = [
+            AccountField::Nonce,
+            AccountField::Balance,
+            AccountField::CodeHash,
+        ]
+        .iter()
+        .all(|field| state.is_known(&address, *field))
+            && !state.state_db().account(&address).is_empty();
+        match &result {
+            Some((_, value)) if !value.is_zero() => {
+                state.check_account_field(
+                    address,
+                    AccountField::CodeHash,
+                    value,
+                );
+            }
+            Some(_) if known_non_empty => {
+                let step = state.step_idx();
+                state.invalidate(TraceError::StateMismatch { step });
+            }
+            None if !state.is_known(&address, AccountField::CodeHash) => {
+                return exec_step.bus_mapping_instance().len();
+            }
+            _ => (),
+        }
+
+        state.gen_account_read(
+            exec_step,
+            container,
+            address,
+            AccountField::CodeHash,
+        );
+        gen_stack_write(state, result, exec_step, container)
+    }
+}
+
+/// Reads the balance of the account located at `address` and writes it into
+/// the stack.
+fn gen_balance_ops(
+    state: &mut TraceState,
+    snapshots: StepSnapshots<'_>,
+    address: Address,
+    exec_step: &mut ExecutionStep,
+    container: &mut OperationContainer,
+) -> usize {
+    // The stack after this step holds the balance, which is checked against
+    // the state or seeds it when it isn't known yet. Without it the balance
+    // can only be read if it's known, and nothing is pushed.
+    let result = stack_result(snapshots, exec_step);
+    match &result {
+        Some((_, value)) => {
+            state.check_account_field(address, AccountField::Balance, value)
+        }
+        None if !state.is_known(&address, AccountField::Balance) => {
+            return exec_step.bus_mapping_instance().len();
+        }
+        None => (),
+    }
+
+    let balance = state.gen_account_read(
+        exec_step,
+        container,
+        address,
+        AccountField::Balance,
+    );
+    gen_stack_write(
+        state,
+        result.map(|(stack_addr, _)| (stack_addr, balance)),
+        exec_step,
+        container,
+    )
+}
+
+/// Writes the `result` of the step into the stack, if the stack after the
+/// step is known.
+fn gen_stack_write(
+    state: &TraceState,
+    result: Option<(StackAddress, EvmWord)>,
+    exec_step: &mut ExecutionStep,
+    container: &mut OperationContainer,
+) -> usize {
+    if let Some((stack_addr, value)) = result {
+        exec_step.add_op(
+            container,
+            StackOp::new(
+                RW::WRITE,
+                exec_step.next_op_gc(),
+                state.call().call_id(),
+                stack_addr,
+                value,
+            ),
+        );
+    }
+
+    exec_step.bus_mapping_instance().len()
+}
+
+#[cfg(test)]
+mod account_tests {
+    use crate::{
+        evm::{Address, EvmWord},
+        exec_trace::{ExecutionTrace, ExecutionTraceBuilder, TxContext},
+        operation::{AccountField, RW},
+        state_db::StateDB,
+        test_util, Error, TraceError,
+    };
+    use core::str::FromStr;
+
+    #[test]
+    fn account_reads_and_value_transfers() {
+        // The root call, which receives 0x0a wei, reads it's balance twice
+        // and the code hash of 0xbb, sends 3 wei to 0xcc and self-destructs
+        // in favour of 0xbb.
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["aa"], "opcode": "PUSH1 aa", "pc": 0 },
+            { "memory": {}, "stack": ["a"], "opcode": "BALANCE", "pc": 2 },
+            { "memory": {}, "stack": ["a", "a"], "opcode": "SELFBALANCE", "pc": 3 },
+            { "memory": {}, "stack": ["a", "a", "bb"], "opcode": "PUSH1 bb", "pc": 4 },
+            { "memory": {}, "stack": ["a", "a", "1234"], "opcode": "EXTCODEHASH", "pc": 6 },
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 7 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 9 },
+            { "memory": {}, "stack": ["0", "0", "0"], "opcode": "PUSH1 00", "pc": 11 },
+            { "memory": {}, "stack": ["0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 13 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "3"], "opcode": "PUSH1 03", "pc": 15 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "3", "cc"], "opcode": "PUSH1 cc", "pc": 17 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "3", "cc", "ff"], "opcode": "PUSH1 ff", "pc": 19 },
+            { "memory": {}, "stack": ["1"], "opcode": "CALL", "pc": 21 },
+            { "memory": {}, "stack": ["1", "bb"], "opcode": "PUSH1 bb", "pc": 22 },
+            { "memory": {}, "stack": ["1"], "opcode": "SELFDESTRUCT", "pc": 24 }
+        ]
+        "#;
+
+        let block_ctants = test_util::block_ctants();
+        let address =
+            |hex: &str| Address::from_str(&format!("{:0>40}", hex)).unwrap();
+        let (contract, beneficiary, receiver) =
+            (address("aa"), address("bb"), address("cc"));
+        let tx_ctx = TxContext::new(
+            Address::zero(),
+            Some(contract),
+            0,
+            EvmWord::from(0x0au8),
+            EvmWord::from(0u8),
+            100_000,
+            vec![],
+        );
+
+        let trace = ExecutionTrace::from_trace_bytes_with_tx(
+            input_trace.as_bytes(),
+            block_ctants,
+            tx_ctx,
+        )
+        .expect("Error on trace generation");
+
+        // The value of the transaction is transferred before the first step.
+        let first_writes: Vec<(Address, EvmWord, EvmWord)> = trace
+            .sorted_account_ops()
+            .filter(|op| {
+                op.rw() == RW::WRITE
+                    && op.field() == AccountField::Balance
+                    && op.gc() < trace[1].gc()
+            })
+            .map(|op| {
+                (*op.address(), op.value().clone(), op.value_prev().clone())
+            })
+            .collect();
+        assert_eq!(
+            first_writes,
+            vec![
+                (Address::zero(), 0u8.into(), 0x0au8.into()),
+                (contract, 0x0au8.into(), 0u8.into()),
+            ]
+        );
+
+        let reads: Vec<(Address, AccountField, EvmWord)> = trace
+            .sorted_account_ops()
+            .filter(|op| op.rw() == RW::READ)
+            .map(|op| (*op.address(), op.field(), op.value().clone()))
+            .collect();
+        assert_eq!(
+            reads,
+            vec![
+                (contract, AccountField::Balance, EvmWord::from(0x0au8)),
+                (contract, AccountField::Balance, EvmWord::from(0x0au8)),
+                (
+                    beneficiary,
+                    AccountField::CodeHash,
+                    EvmWord::from(0x1234u64)
+                ),
+            ]
+        );
+
+        let state_db = trace.state_db();
+        assert_eq!(state_db.account(&contract).balance(), &0u8.into());
+        assert_eq!(state_db.account(&receiver).balance(), &3u8.into());
+        assert_eq!(state_db.account(&beneficiary).balance(), &7u8.into());
+        assert_eq!(
+            state_db.account(&beneficiary).code_hash(),
+            &0x1234u64.into()
+        );
+    }
+
+    #[test]
+    fn known_balances_are_checked() {
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["aa"], "opcode": "PUSH1 aa", "pc": 0 },
+            { "memory": {}, "stack": ["a"], "opcode": "BALANCE", "pc": 2 },
+            { "memory": {}, "stack": ["a"], "opcode": "STOP", "pc": 3 }
+        ]
+        "#;
+
+        let block_ctants = test_util::block_ctants();
+        let contract = test_util::address("aa");
+        let tx_ctx = TxContext::new(
+            Address::zero(),
+            Some(contract),
+            0,
+            EvmWord::from(0x0au8),
+            EvmWord::from(0u8),
+            100_000,
+            vec![],
+        );
+        // The contract already holds 5 wei, so it's balance is 0x0f once the
+        // value of the transaction is received.
+        let mut prestate = StateDB::new();
+        prestate.set_balance(&contract, EvmWord::from(5u8));
+        prestate.set_code(&contract, vec![0x60, 0xaa, 0x31, 0x00]);
+
+        let mut builder = ExecutionTraceBuilder::new(block_ctants)
+            .with_tx(tx_ctx)
+            .with_prestate(prestate);
+        builder
+            .push_trace_bytes(input_trace.as_bytes())
+            .expect("Error on parsing");
+        assert!(matches!(
+            builder.finish(),
+            Err(Error::InvalidTrace(TraceError::StateMismatch { step: 1 }))
+        ));
+    }
+
+    #[test]
+    fn unaffordable_transactions_fail() {
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": [], "opcode": "STOP", "pc": 0 }
+        ]
+        "#;
+
+        // The sender holds 4 wei, and the transaction sends 5.
+        let sender = test_util::address("11");
+        let tx_ctx = TxContext::new(
+            sender,
+            Some(test_util::address("aa")),
+            0,
+            EvmWord::from(5u8),
+            EvmWord::from(0u8),
+            100_000,
+            vec![],
+        );
+        let mut prestate = StateDB::new();
+        prestate.set_balance(&sender, EvmWord::from(4u8));
+
+        let mut builder = ExecutionTraceBuilder::new(test_util::block_ctants())
+            .with_tx(tx_ctx)
+            .with_prestate(prestate);
+        builder
+            .push_trace_bytes(input_trace.as_bytes())
+            .expect("Error on parsing");
+        assert!(matches!(
+            builder.finish(),
+            Err(Error::InvalidTrace(TraceError::StateMismatch { step: 0 }))
+        ));
+    }
+}
@@ -0,0 +1,456 @@
+use super::{
+    gen_memory_op, gen_stack_reads, memory_bytes_before, memory_region,
+    stack_result, Opcode,
+};
+use crate::{
+    evm::{
+        precompiles::{Precompile, PrecompileCall},
+        Address, EvmWord,
+    },
+    exec_trace::{CallKind, ExecutionStep, StepSnapshots, TraceState},
+    operation::{container::OperationContainer, StackOp, RW},
+};
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `CALL` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Call;
+
+impl Opcode for Call {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        gen_call_ops(CallKind::Call, state, snapshots, exec_step, container)
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `CALLCODE` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct CallCode;
+
+impl Opcode for CallCode {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        gen_call_ops(CallKind::CallCode, state, snapshots, exec_step, container)
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `DELEGATECALL` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct DelegateCall;
+
+impl Opcode for DelegateCall {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        gen_call_ops(
+            CallKind::DelegateCall,
+            state,
+            snapshots,
+            exec_step,
+            container,
+        )
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `STATICCALL` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct StaticCall;
+
+impl Opcode for StaticCall {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        gen_call_ops(
+            CallKind::StaticCall,
+            state,
+            snapshots,
+            exec_step,
+            container,
+        )
+    }
+}
+
+/// Generates the operations of a call:
+/// 1. Reads `gas, address, value, args_offset, args_size, ret_offset,
+/// ret_size` from the stack (`DELEGATECALL` and `STATICCALL` have no
+/// `value`).
+/// 2. Opens the call frame of the callee and, for `CALL`, transfers the
+/// value to it when the caller can afford it. Calls that fail without
+/// running any step open no frame, and the ones that can't pay for the
+/// memory they access halt exceptionally without generating more
+/// operations.
+/// 3. If the callee is a [`Precompile`], which has no steps in the trace,
+/// runs it natively (once, also telling whether it succeeds when the stack
+/// after the step is unknown): the input is read from the memory of the caller,
+/// the output is written into the memory of the precompile frame (which holds
+/// the return data of the call) and copied into the memory of the caller,
+/// and a [`PrecompileCall`] witness is recorded.
+/// 4. Writes the success flag of the call into the stack, when the stack
+/// after the step is known.
+fn gen_call_ops(
+    kind: CallKind,
+    state: &mut TraceState,
+    snapshots: StepSnapshots<'_>,
+    exec_step: &mut ExecutionStep,
+    container: &mut OperationContainer,
+) -> usize {
+    let has_value = matches!(kind, CallKind::Call | CallKind::CallCode);
+    let operands = gen_stack_reads(
+        state,
+        snapshots,
+        if has_value { 7 } else { 6 },
+        exec_step,
+        container,
+    );
+    let callee = Address::from_word(&operands[1]);
+    let (value, memory_operands) = match kind {
+        CallKind::DelegateCall => {
+            (state.call().value().clone(), &operands[2..])
+        }
+        CallKind::StaticCall => (EvmWord::from(0u8), &operands[2..]),
+        _ => (operands[2].clone(), &operands[3..]),
+    };
+    // A step that can't pay for expanding the memory over the input or the
+    // output of the call halts exceptionally without calling.
+    let regions =
+        memory_region(exec_step, &memory_operands[0], &memory_operands[1]).zip(
+            memory_region(exec_step, &memory_operands[2], &memory_operands[3]),
+        );
+    let ((args_offset, args_size), (ret_offset, ret_size)) = match regions {
+        Some(regions) => regions,
+        None => return exec_step.bus_mapping_instance().len(),
+    };
+    let calldata =
+        memory_bytes_before(snapshots, exec_step, args_offset, args_size);
+
+    let caller_call_id = state.call().call_id();
+    let caller = *state.call().address();
+    // The stack after this step holds the success flag of the call.
+    let result = stack_result(snapshots, exec_step);
+
+    // `CALLCODE` and `DELEGATECALL` run the code of the callee in the context
+    // of the current account.
+    let address = match kind {
+        CallKind::CallCode | CallKind::DelegateCall => *state.call().address(),
+        _ => callee,
+    };
+    let code_hash = state.state_db().account(&callee).code_hash().clone();
+    let code = state
+        .state_db()
+        .code(&code_hash)
+        .map(|code| code.to_vec())
+        .unwrap_or_default();
+    let hardfork = state.hardfork();
+    let precompile = Precompile::from_address(&callee)
+        .filter(|precompile| hardfork.has_precompile(*precompile));
+    let execution = precompile.map(|precompile| precompile.execute(&calldata));
+    // Without the stack after the step, a precompile succeeds if it accepts
+    // it's input and the gas forwarded to it pays for it's cost, and any
+    // other call if it opens a frame or the caller can afford the value and
+    // isn't too deep.
+    let success = match (&result, precompile) {
+        (Some((_, success)), _) => !success.is_zero(),
+        (None, Some(precompile)) => {
+            let gas_cost = precompile.gas_cost(&calldata, hardfork);
+            matches!(execution, Some(Ok(_)))
+                && !matches!(state.call_gas(), Some(gas) if gas_cost.0 > gas)
+        }
+        (None, None) => {
+            state.enters_frame()
+                || (state.call().depth() <= 1024
+                    && (!has_value || state.can_transfer(&caller, &value)))
+        }
+    };
+    // A call that fails without running any step, because the caller can't
+    // afford the value or is too deep, opens no frame and leaves no return
+    // data.
+    if precompile.is_none() && !success && !state.enters_frame() {
+        state.clear_return_data();
+        state.return_call_gas();
+    } else {
+        // Only `CALL` moves value between accounts: `CALLCODE` transfers it
+        // from the current account to itself, and the other kinds don't
+        // transfer any. The transfer happens inside of the callee frame, so
+        // that it's undone if the call reverts.
+        let transfer =
+            kind == CallKind::Call && state.can_transfer(&caller, &value);
+        let call_id = state.push_call(
+            kind,
+            address,
+            value.clone(),
+            calldata.clone(),
+            args_offset,
+            code,
+        );
+        if transfer {
+            state.transfer(exec_step, container, caller, callee, &value);
+        }
+
+        if let Some(precompile) = precompile {
+            calldata.iter().enumerate().for_each(|(idx, byte)| {
+                gen_memory_op(
+                    exec_step,
+                    container,
+                    RW::READ,
+                    caller_call_id,
+                    args_offset + idx,
+                    *byte,
+                )
+            });
+
+            let output = match execution {
+                Some(Ok(output)) if success => output,
+                _ => vec![],
+            };
+            output.iter().enumerate().for_each(|(idx, byte)| {
+                gen_memory_op(
+                    exec_step,
+                    container,
+                    RW::WRITE,
+                    call_id,
+                    idx,
+                    *byte,
+                )
+            });
+            output
+                .iter()
+                .take(ret_size)
+                .enumerate()
+                .for_each(|(idx, byte)| {
+                    gen_memory_op(
+                        exec_step,
+                        container,
+                        RW::WRITE,
+                        caller_call_id,
+                        ret_offset + idx,
+                        *byte,
+                    )
+                });
+
+            state.set_return_data(output.clone(), 0);
+            let gas_cost = precompile.gas_cost(&calldata, hardfork);
+            state.add_precompile_call(PrecompileCall::new(
+                call_id, precompile, calldata, output, gas_cost, success,
+            ));
+            if !success {
+                state.revert_call(exec_step, container);
+            } else {
+                state.charge_gas(gas_cost);
+                state.halt_call();
+            }
+        } else if !state.enters_frame() {
+            // A callee without code runs an implicit `STOP`, so it returns
+            // all of it's gas to the caller.
+            state.halt_call();
+        }
+    }
+
+    if let Some((stack_addr, pushed)) = result {
+        exec_step.add_op(
+            container,
+            StackOp::new(
+                RW::WRITE,
+                exec_step.next_op_gc(),
+                caller_call_id,
+                stack_addr,
+                pushed,
+            ),
+        );
+    }
+
+    exec_step.bus_mapping_instance().len()
+}
+
+#[cfg(test)]
+mod call_tests {
+    use crate::{
+        evm::{precompiles::Precompile, EvmWord, MemoryAddress},
+        exec_trace::{
+            CallKind, ExecutionTrace, ExecutionTraceBuilder, SnapshotConvention,
+        },
+        operation::RW,
+        test_util,
+    };
+
+    #[test]
+    fn identity_precompile_call() {
+        // Calls the identity precompile with the 4 bytes at memory offset 0
+        // as input, copying the output into memory offset 0x20.
+        let input_trace = r#"
+        [
+            { "memory": { "0": "deadbeef00000000000000000000000000000000000000000000000000000000" }, "stack": ["4"], "opcode": "PUSH1 04", "pc": 0 },
+            { "memory": { "0": "deadbeef00000000000000000000000000000000000000000000000000000000" }, "stack": ["4", "20"], "opcode": "PUSH1 20", "pc": 2 },
+            { "memory": { "0": "deadbeef00000000000000000000000000000000000000000000000000000000" }, "stack": ["4", "20", "4"], "opcode": "PUSH1 04", "pc": 4 },
+            { "memory": { "0": "deadbeef00000000000000000000000000000000000000000000000000000000" }, "stack": ["4", "20", "4", "0"], "opcode": "PUSH1 00", "pc": 6 },
+            { "memory": { "0": "deadbeef00000000000000000000000000000000000000000000000000000000" }, "stack": ["4", "20", "4", "0", "0"], "opcode": "PUSH1 00", "pc": 8 },
+            { "memory": { "0": "deadbeef00000000000000000000000000000000000000000000000000000000" }, "stack": ["4", "20", "4", "0", "0", "4"], "opcode": "PUSH1 04", "pc": 10 },
+            { "memory": { "0": "deadbeef00000000000000000000000000000000000000000000000000000000" }, "stack": ["4", "20", "4", "0", "0", "4", "ffff"], "opcode": "PUSH1 ff", "pc": 12 },
+            { "memory": {
+                "0": "deadbeef00000000000000000000000000000000000000000000000000000000",
+                "20": "deadbeef00000000000000000000000000000000000000000000000000000000"
+              }, "stack": ["1"], "opcode": "CALL", "pc": 14 },
+            { "memory": {
+                "0": "deadbeef00000000000000000000000000000000000000000000000000000000",
+                "20": "deadbeef00000000000000000000000000000000000000000000000000000000"
+              }, "stack": ["1"], "opcode": "STOP", "pc": 15 }
+        ]
+        "#;
+
+        let block_ctants = test_util::block_ctants();
+        let tx_ctx = test_util::tx_ctx(Some(test_util::address("aa")), vec![]);
+
+        let trace = ExecutionTrace::from_trace_bytes_with_tx(
+            input_trace.as_bytes(),
+            block_ctants,
+            tx_ctx,
+        )
+        .expect("Error on trace generation");
+        let input = vec![0xde, 0xad, 0xbe, 0xef];
+
+        // The precompile runs in it's own frame, which has no steps.
+        assert_eq!(trace.calls().len(), 2);
+        assert_eq!(trace.calls()[1].kind(), CallKind::Call);
+        assert_eq!(trace.calls()[1].address(), &Precompile::Identity.address());
+        assert!(!trace.calls()[1].is_reverted());
+
+        let precompile_call = &trace.precompile_calls()[0];
+        assert_eq!(precompile_call.call_id(), 2);
+        assert_eq!(precompile_call.precompile(), Precompile::Identity);
+        assert_eq!(precompile_call.input(), input.as_slice());
+        assert_eq!(precompile_call.output(), input.as_slice());
+        assert!(precompile_call.success());
+
+        let memory_ops: Vec<(RW, usize, MemoryAddress, EvmWord)> = trace
+            .sorted_memory_ops()
+            .map(|op| {
+                (
+                    op.rw(),
+                    op.call_id(),
+                    op.address().clone(),
+                    op.value().clone(),
+                )
+            })
+            .collect();
+        let byte_ops = |rw, call_id, offset: usize| {
+            input.iter().enumerate().map(move |(idx, byte)| {
+                (
+                    rw,
+                    call_id,
+                    MemoryAddress::from(offset + idx),
+                    EvmWord::from(*byte),
+                )
+            })
+        };
+        // Input read from the caller, output copied into the caller and
+        // output returned by the precompile frame.
+        let expected: Vec<_> = byte_ops(RW::READ, 1, 0)
+            .chain(byte_ops(RW::WRITE, 1, 0x20))
+            .chain(byte_ops(RW::WRITE, 2, 0))
+            .collect();
+        assert_eq!(memory_ops, expected);
+
+        // The success flag is written into the stack of the caller.
+        let stack_write = trace.sorted_stack_ops().find(|op| {
+            op.rw() == RW::WRITE && op.value() == &EvmWord::from(1u8)
+        });
+        assert!(stack_write.is_some());
+    }
+
+    #[test]
+    fn failed_calls_open_no_frame() {
+        // The first call sends a value the contract doesn't have, so it fails
+        // without running any step. The second one calls the identity
+        // precompile with an output region of size zero at an offset past
+        // the addressable memory.
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"], "opcode": "PUSH32 ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "pc": 2 },
+            { "memory": {}, "stack": ["0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "0"], "opcode": "PUSH1 00", "pc": 35 },
+            { "memory": {}, "stack": ["0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "0", "0"], "opcode": "PUSH1 00", "pc": 37 },
+            { "memory": {}, "stack": ["0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "0", "0", "1"], "opcode": "PUSH1 01", "pc": 39 },
+            { "memory": {}, "stack": ["0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "0", "0", "1", "bb"], "opcode": "PUSH1 bb", "pc": 41 },
+            { "memory": {}, "stack": ["0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "0", "0", "1", "bb", "ffff"], "opcode": "PUSH2 ffff", "pc": 43 },
+            { "memory": {}, "stack": ["0"], "opcode": "CALL", "pc": 46 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 47 },
+            { "memory": {}, "stack": ["0", "0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"], "opcode": "PUSH32 ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "pc": 49 },
+            { "memory": {}, "stack": ["0", "0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "0"], "opcode": "PUSH1 00", "pc": 82 },
+            { "memory": {}, "stack": ["0", "0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "0", "0"], "opcode": "PUSH1 00", "pc": 84 },
+            { "memory": {}, "stack": ["0", "0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 86 },
+            { "memory": {}, "stack": ["0", "0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "0", "0", "0", "4"], "opcode": "PUSH1 04", "pc": 88 },
+            { "memory": {}, "stack": ["0", "0", "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff", "0", "0", "0", "4", "ffff"], "opcode": "PUSH2 ffff", "pc": 90 },
+            { "memory": {}, "stack": ["0", "1"], "opcode": "CALL", "pc": 93 },
+            { "memory": {}, "stack": ["0", "1"], "opcode": "STOP", "pc": 94 }
+        ]
+        "#;
+
+        let block_ctants = test_util::block_ctants();
+        let tx_ctx = test_util::tx_ctx(Some(test_util::address("aa")), vec![]);
+
+        let trace = ExecutionTrace::from_trace_bytes_with_tx(
+            input_trace.as_bytes(),
+            block_ctants,
+            tx_ctx,
+        )
+        .expect("Error on trace generation");
+
+        // Only the precompile call opens a frame.
+        assert_eq!(trace.calls().len(), 2);
+        assert_eq!(trace.calls()[1].address(), &Precompile::Identity.address());
+        assert_eq!(trace.precompile_calls().len(), 1);
+        assert!(trace.precompile_calls()[0].success());
+        assert_eq!(trace.sorted_memory_ops().count(), 0);
+    }
+
+    #[test]
+    fn precompiles_fail_without_the_gas_for_their_cost() {
+        // The identity precompile is given 15 gas for 4 bytes of input,
+        // which cost 18. The trace ends at the call, so it's success flag
+        // is unknown.
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": [], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 04", "pc": 4 },
+            { "memory": {}, "stack": ["0", "0", "4"], "opcode": "PUSH1 00", "pc": 6 },
+            { "memory": {}, "stack": ["0", "0", "4", "0"], "opcode": "PUSH1 00", "pc": 8 },
+            { "memory": {}, "stack": ["0", "0", "4", "0", "0"], "opcode": "PUSH1 04", "pc": 10 },
+            { "memory": {}, "stack": ["0", "0", "4", "0", "0", "4"], "opcode": "PUSH1 0f", "pc": 12 },
+            { "memory": {}, "stack": ["0", "0", "4", "0", "0", "4", "f"], "opcode": "CALL", "pc": 14 }
+        ]
+        "#;
+
+        let mut builder = ExecutionTraceBuilder::new(test_util::block_ctants())
+            .with_tx(test_util::tx_ctx(Some(test_util::address("aa")), vec![]))
+            .with_convention(SnapshotConvention::PreState);
+        builder
+            .push_trace_bytes(input_trace.as_bytes())
+            .expect("Error on parsing");
+        let trace = builder.finish().expect("Error on trace generation");
+
+        assert_eq!(trace.precompile_calls().len(), 1);
+        assert!(!trace.precompile_calls()[0].success());
+        assert!(trace.calls()[1].is_reverted());
+    }
+}
@@ -0,0 +1,424 @@
+//! Opcodes that copy a range of bytes of the calldata, of some code or of the
+//! return data into memory.
+use super::{
+    gen_memory_op, gen_stack_reads, memory_bytes_after, memory_region, Opcode,
+};
+use crate::{
+    evm::{keccak256, Address, EvmWord, EMPTY_CODE_HASH},
+    exec_trace::{
+        CopyByte, CopyEvent, CopySource, ExecutionStep, StepSnapshots,
+        TraceState,
+    },
+    operation::{container::OperationContainer, RW},
+};
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `CALLDATACOPY` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct CalldataCopy;
+
+impl Opcode for CalldataCopy {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let operands =
+            gen_stack_reads(state, snapshots, 3, exec_step, container);
+        let calldata = state.call().calldata().to_vec();
+        let memory = state.calldata_source();
+        gen_copy_ops(
+            state,
+            CopyFrom {
+                src: CopySource::Calldata,
+                bytes: Some(&calldata),
+                memory,
+            },
+            &operands,
+            snapshots,
+            exec_step,
+            container,
+        )
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `CODECOPY` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct CodeCopy;
+
+impl Opcode for CodeCopy {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let operands =
+            gen_stack_reads(state, snapshots, 3, exec_step, container);
+
+        // A call that runs code can't have empty code, so empty code means
+        // that it isn't known.
+        let code = state.call().code().to_vec();
+        let (code_hash, code) = if code.is_empty() {
+            let address = state.call().address();
+            (state.state_db().account(address).code_hash().clone(), None)
+        } else {
+            (EvmWord::from_be_bytes(&keccak256(&code)), Some(code))
+        };
+        gen_copy_ops(
+            state,
+            CopyFrom {
+                src: CopySource::Code(code_hash),
+                bytes: code.as_deref(),
+                memory: None,
+            },
+            &operands,
+            snapshots,
+            exec_step,
+            container,
+        )
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `EXTCODECOPY` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ExtCodeCopy;
+
+impl Opcode for ExtCodeCopy {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let operands =
+            gen_stack_reads(state, snapshots, 4, exec_step, container);
+
+        let address = Address::from_word(&operands[0]);
+        let code_hash = state.state_db().account(&address).code_hash().clone();
+        let code = if code_hash == *EMPTY_CODE_HASH {
+            Some(vec![])
+        } else {
+            state.state_db().code(&code_hash).map(|code| code.to_vec())
+        };
+        gen_copy_ops(
+            state,
+            CopyFrom {
+                src: CopySource::Code(code_hash),
+                bytes: code.as_deref(),
+                memory: None,
+            },
+            &operands[1..],
+            snapshots,
+            exec_step,
+            container,
+        )
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `RETURNDATACOPY` [`Instruction`](crate::evm::instruction::Instruction).
+///
+/// Reading past the end of the return data is an exceptional halt, in which
+/// case nothing is copied.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ReturnDataCopy;
+
+impl Opcode for ReturnDataCopy {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let operands =
+            gen_stack_reads(state, snapshots, 3, exec_step, container);
+
+        let return_data = state.return_data().to_vec();
+        let end = operands[1]
+            .to_usize()
+            .zip(operands[2].to_usize())
+            .and_then(|(offset, size)| offset.checked_add(size));
+        if !matches!(end, Some(end) if end <= return_data.len()) {
+            return exec_step.bus_mapping_instance().len();
+        }
+        let memory = Some(state.return_data_source());
+        gen_copy_ops(
+            state,
+            CopyFrom {
+                src: CopySource::ReturnData,
+                bytes: Some(&return_data),
+                memory,
+            },
+            &operands,
+            snapshots,
+            exec_step,
+            container,
+        )
+    }
+}
+
+/// Source of the bytes copied by a step.
+struct CopyFrom<'a> {
+    src: CopySource,
+    // Bytes of the source, if they are known.
+    bytes: Option<&'a [u8]>,
+    // `call_id` and address of the memory holding the source, when it's
+    // the memory of a call (the calldata of a sub-call is read from the
+    // memory of it's caller, and the return data from the memory of the
+    // callee).
+    memory: Option<(usize, usize)>,
+}
+
+/// Copies the bytes of the source `from` selected by the `dest_offset,
+/// offset, size` `operands` into memory, generating a [`MemoryOp`] write for
+/// each one of them, and records the [`CopyEvent`] of the step.
+///
+/// Bytes past the end of the source are zeros used as padding. The other
+/// ones are read from the memory holding the source through a [`MemoryOp`]
+/// read before being written, when the source is held in memory. When the
+/// source isn't known (it's bytes are `None`), the copied bytes are taken
+/// from the memory after the step instead.
+///
+/// A step that can't pay for expanding the memory over the destination
+/// halts exceptionally without copying anything.
+///
+/// [`MemoryOp`]: crate::operation::MemoryOp
+fn gen_copy_ops(
+    state: &mut TraceState,
+    from: CopyFrom<'_>,
+    operands: &[EvmWord],
+    snapshots: StepSnapshots<'_>,
+    exec_step: &mut ExecutionStep,
+    container: &mut OperationContainer,
+) -> usize {
+    if operands[2].is_zero() {
+        return exec_step.bus_mapping_instance().len();
+    }
+    let (dst_offset, size) =
+        match memory_region(exec_step, &operands[0], &operands[2]) {
+            Some(region) => region,
+            None => return exec_step.bus_mapping_instance().len(),
+        };
+    // Offsets that don't fit in memory only read padding.
+    let src_offset = operands[1].to_usize().unwrap_or(usize::MAX);
+
+    let copied = match from.bytes {
+        Some(_) => vec![],
+        None => {
+            match memory_bytes_after(snapshots, exec_step, dst_offset, size) {
+                Some(copied) => copied,
+                None => return exec_step.bus_mapping_instance().len(),
+            }
+        }
+    };
+    let call_id = state.call().call_id();
+    let bytes = (0..size)
+        .map(|idx| {
+            let (value, is_pad) = match from.bytes {
+                Some(src_bytes) => {
+                    match src_bytes.get(src_offset.saturating_add(idx)) {
+                        Some(byte) => (*byte, false),
+                        None => (0, true),
+                    }
+                }
+                None => (copied[idx], false),
+            };
+            if let Some((src_call_id, src_address)) =
+                from.memory.filter(|_| !is_pad)
+            {
+                gen_memory_op(
+                    exec_step,
+                    container,
+                    RW::READ,
+                    src_call_id,
+                    src_address + src_offset + idx,
+                    value,
+                );
+            }
+            let gc = exec_step.next_op_gc();
+            gen_memory_op(
+                exec_step,
+                container,
+                RW::WRITE,
+                call_id,
+                dst_offset + idx,
+                value,
+            );
+            CopyByte::new(value, is_pad, gc)
+        })
+        .collect();
+
+    state.add_copy_event(CopyEvent::new(
+        exec_step.gc(),
+        call_id,
+        from.src,
+        src_offset,
+        dst_offset,
+        bytes,
+    ));
+    exec_step.bus_mapping_instance().len()
+}
+
+#[cfg(test)]
+mod copy_tests {
+    use crate::{
+        evm::{EvmWord, MemoryAddress},
+        exec_trace::{CopySource, ExecutionTrace},
+        operation::RW,
+        test_util,
+    };
+    use pasta_curves::Fp;
+
+    fn trace(input_trace: &str, input: Vec<u8>) -> ExecutionTrace<Fp> {
+        let block_ctants = test_util::block_ctants();
+        let tx_ctx = test_util::tx_ctx(Some(test_util::address("aa")), input);
+
+        ExecutionTrace::from_trace_bytes_with_tx(
+            input_trace.as_bytes(),
+            block_ctants,
+            tx_ctx,
+        )
+        .expect("Error on trace generation")
+    }
+
+    #[test]
+    fn calldata_copy_with_padding() {
+        // Copies 4 bytes of the calldata starting at offset 1, the last two
+        // of which are past it's end.
+        let trace = trace(
+            r#"
+        [
+            { "memory": {}, "stack": ["4"], "opcode": "PUSH1 04", "pc": 0 },
+            { "memory": {}, "stack": ["4", "1"], "opcode": "PUSH1 01", "pc": 2 },
+            { "memory": {}, "stack": ["4", "1", "0"], "opcode": "PUSH1 00", "pc": 4 },
+            { "memory": { "0": "bbcc000000000000000000000000000000000000000000000000000000000000" }, "stack": [], "opcode": "CALLDATACOPY", "pc": 6 }
+        ]
+        "#,
+            vec![0xaa, 0xbb, 0xcc],
+        );
+
+        let event = &trace.copy_events()[0];
+        assert_eq!(event.step_gc(), trace[3].gc());
+        assert_eq!(event.src(), &CopySource::Calldata);
+        assert_eq!((event.src_offset(), event.dst_offset()), (1, 0));
+        let bytes: Vec<(u8, bool)> = event
+            .bytes()
+            .iter()
+            .map(|byte| (byte.value(), byte.is_pad()))
+            .collect();
+        assert_eq!(
+            bytes,
+            vec![(0xbb, false), (0xcc, false), (0, true), (0, true)]
+        );
+
+        // Each byte is written into memory by the op the event points to.
+        let memory_writes: Vec<_> = trace
+            .sorted_memory_ops()
+            .map(|op| {
+                assert_eq!(op.rw(), RW::WRITE);
+                (op.gc(), op.address().clone(), op.value().clone())
+            })
+            .collect();
+        let expected: Vec<_> = event
+            .bytes()
+            .iter()
+            .enumerate()
+            .map(|(idx, byte)| {
+                (
+                    byte.gc(),
+                    MemoryAddress::from(idx),
+                    EvmWord::from(byte.value()),
+                )
+            })
+            .collect();
+        assert_eq!(memory_writes, expected);
+    }
+
+    #[test]
+    fn return_data_copy() {
+        // The root call calls 0xbb, which returns the 3 bytes 0x112233, and
+        // copies the last two of them into memory. A second copy reads past
+        // the end of the return data, which halts without copying anything.
+        let trace = trace(
+            r#"
+        [
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": ["0", "0", "0"], "opcode": "PUSH1 00", "pc": 4 },
+            { "memory": {}, "stack": ["0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 6 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 8 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0", "bb"], "opcode": "PUSH1 bb", "pc": 10 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0", "bb", "ff"], "opcode": "PUSH1 ff", "pc": 12 },
+            { "memory": {}, "stack": ["1"], "opcode": "CALL", "pc": 14 },
+            { "memory": { "0": "1122330000000000000000000000000000000000000000000000000000000000" }, "stack": ["3"], "opcode": "PUSH1 03", "pc": 0, "depth": 2 },
+            { "memory": { "0": "1122330000000000000000000000000000000000000000000000000000000000" }, "stack": ["3", "0"], "opcode": "PUSH1 00", "pc": 2, "depth": 2 },
+            { "memory": { "0": "1122330000000000000000000000000000000000000000000000000000000000" }, "stack": [], "opcode": "RETURN", "pc": 4, "depth": 2 },
+            { "memory": {}, "stack": ["1", "2"], "opcode": "PUSH1 02", "pc": 15 },
+            { "memory": {}, "stack": ["1", "2", "1"], "opcode": "PUSH1 01", "pc": 17 },
+            { "memory": {}, "stack": ["1", "2", "1", "0"], "opcode": "PUSH1 00", "pc": 19 },
+            { "memory": { "0": "2233000000000000000000000000000000000000000000000000000000000000" }, "stack": ["1"], "opcode": "RETURNDATACOPY", "pc": 21 },
+            { "memory": { "0": "2233000000000000000000000000000000000000000000000000000000000000" }, "stack": ["1", "4"], "opcode": "PUSH1 04", "pc": 22 },
+            { "memory": { "0": "2233000000000000000000000000000000000000000000000000000000000000" }, "stack": ["1", "4", "0"], "opcode": "PUSH1 00", "pc": 24 },
+            { "memory": { "0": "2233000000000000000000000000000000000000000000000000000000000000" }, "stack": ["1", "4", "0", "0"], "opcode": "PUSH1 00", "pc": 26 },
+            { "memory": { "0": "2233000000000000000000000000000000000000000000000000000000000000" }, "stack": ["1"], "opcode": "RETURNDATACOPY", "pc": 28 }
+        ]
+        "#,
+            vec![],
+        );
+
+        assert_eq!(trace.copy_events().len(), 1);
+        let event = &trace.copy_events()[0];
+        assert_eq!(event.call_id(), 1);
+        assert_eq!(event.src(), &CopySource::ReturnData);
+        let bytes: Vec<u8> =
+            event.bytes().iter().map(|byte| byte.value()).collect();
+        assert_eq!(bytes, vec![0x22, 0x33]);
+
+        // The bytes are read from the memory of the callee, where they were
+        // returned from.
+        let reads: Vec<_> = trace
+            .sorted_memory_ops()
+            .filter(|op| op.rw() == RW::READ)
+            .map(|op| (op.call_id(), op.address().clone(), op.value().clone()))
+            .collect();
+        assert_eq!(
+            reads,
+            vec![
+                (2, MemoryAddress::from(1), EvmWord::from(0x22u8)),
+                (2, MemoryAddress::from(2), EvmWord::from(0x33u8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_and_unaffordable_copies() {
+        // An empty copy to an offset past the memory that can be paid for
+        // copies nothing, and so does a copy too large to be paid for.
+        let trace = trace(
+            r#"
+        [
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": ["0", "0", "ffffffffffffffffffffffffffffffffff"], "opcode": "PUSH17 ffffffffffffffffffffffffffffffffff", "pc": 4 },
+            { "memory": {}, "stack": [], "opcode": "CALLDATACOPY", "pc": 22 },
+            { "memory": {}, "stack": ["ffffffffffff"], "opcode": "PUSH6 ffffffffffff", "pc": 23 },
+            { "memory": {}, "stack": ["ffffffffffff", "0"], "opcode": "PUSH1 00", "pc": 30 },
+            { "memory": {}, "stack": ["ffffffffffff", "0", "0"], "opcode": "PUSH1 00", "pc": 32 },
+            { "memory": {}, "stack": [], "opcode": "CALLDATACOPY", "pc": 34 }
+        ]
+        "#,
+            vec![0xaa],
+        );
+
+        assert!(trace.copy_events().is_empty());
+        assert_eq!(trace.sorted_memory_ops().count(), 0);
+    }
+}
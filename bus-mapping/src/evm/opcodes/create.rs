@@ -0,0 +1,436 @@
+use super::{
+    gen_stack_reads, memory_bytes_before, memory_region, stack_result, Opcode,
+};
+use crate::{
+    evm::{keccak256, Address, EvmWord},
+    exec_trace::{CallKind, ExecutionStep, StepSnapshots, TraceState},
+    operation::{container::OperationContainer, AccountField, StackOp, RW},
+};
+
+/// Returns the address of the contract created via `CREATE` (or via a
+/// contract creation transaction) by `sender` when it's nonce is `nonce`:
+/// `keccak256(rlp([sender, nonce]))[12..]`.
+pub fn create_address(sender: &Address, nonce: u64) -> Address {
+    // RLP encoding of the nonce as a scalar.
+    let nonce_bytes: Vec<u8> = nonce
+        .to_be_bytes()
+        .iter()
+        .copied()
+        .skip_while(|byte| *byte == 0)
+        .collect();
+    let mut nonce_rlp = vec![];
+    match nonce_bytes.as_slice() {
+        [byte] if *byte < 0x80 => nonce_rlp.push(*byte),
+        bytes => {
+            nonce_rlp.push(0x80 + bytes.len() as u8);
+            nonce_rlp.extend_from_slice(bytes);
+        }
+    }
+
+    // RLP encoding of the list `[sender, nonce]`, whose payload is always
+    // shorter than 56 bytes.
+    let mut stream = vec![0xc0 + (21 + nonce_rlp.len()) as u8, 0x80 + 20];
+    stream.extend_from_slice(sender.as_bytes());
+    stream.extend_from_slice(&nonce_rlp);
+
+    address_from_hash(&keccak256(&stream))
+}
+
+/// Returns the address of the contract created via `CREATE2` by `sender`
+/// with the provided `salt` and `init_code`:
+/// `keccak256(0xff ++ sender ++ salt ++ keccak256(init_code))[12..]`.
+pub fn create2_address(
+    sender: &Address,
+    salt: &EvmWord,
+    init_code: &[u8],
+) -> Address {
+    let mut stream = vec![0xff];
+    stream.extend_from_slice(sender.as_bytes());
+    stream.extend_from_slice(&salt.to_be_bytes());
+    stream.extend_from_slice(&keccak256(init_code));
+
+    address_from_hash(&keccak256(&stream))
+}
+
+fn address_from_hash(hash: &[u8; 32]) -> Address {
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    Address::from(address)
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `CREATE` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Create;
+
+impl Opcode for Create {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        gen_create_ops(CallKind::Create, state, snapshots, exec_step, container)
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `CREATE2` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Create2;
+
+impl Opcode for Create2 {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        gen_create_ops(
+            CallKind::Create2,
+            state,
+            snapshots,
+            exec_step,
+            container,
+        )
+    }
+}
+
+/// Generates the operations of a contract creation:
+/// 1. Reads `value, offset, size` (plus `salt` for `CREATE2`) from the stack.
+/// 2. Bumps the nonce of the creator.
+/// 3. Opens the call frame in which the init code runs and sets the nonce of
+/// the new account to 1 ([EIP-161](https://eips.ethereum.org/EIPS/eip-161))
+/// and transfers the value to it (when the creator can afford it) inside of
+/// it, so that both are undone if the creation reverts.
+/// 4. Writes the address of the new contract into the stack.
+///
+/// The `RETURN` that ends the init code frame deploys the runtime code.
+///
+/// A creation that fails before running the init code opens no frame: the
+/// creator is too deep, can't afford the value or it's nonce can't be bumped
+/// ([EIP-2681](https://eips.ethereum.org/EIPS/eip-2681)), all of which leave
+/// it's nonce untouched, or the address is already in use, which is the case
+/// when the step pushes zero and the following step doesn't run the init
+/// code. A step that can't pay for reading the init code halts exceptionally
+/// after reading the stack.
+fn gen_create_ops(
+    kind: CallKind,
+    state: &mut TraceState,
+    snapshots: StepSnapshots<'_>,
+    exec_step: &mut ExecutionStep,
+    container: &mut OperationContainer,
+) -> usize {
+    let operands = gen_stack_reads(
+        state,
+        snapshots,
+        if kind == CallKind::Create2 { 4 } else { 3 },
+        exec_step,
+        container,
+    );
+    let (value, offset, size) = (&operands[0], &operands[1], &operands[2]);
+    let init_code =
+        match memory_region(exec_step, offset, size).map(|(offset, size)| {
+            memory_bytes_before(snapshots, exec_step, offset, size)
+        }) {
+            Some(init_code) => init_code,
+            None => return exec_step.bus_mapping_instance().len(),
+        };
+
+    let creator = *state.call().address();
+    let creator_call_id = state.call().call_id();
+    let creator_nonce = state.state_db().account(&creator).nonce();
+    let address = match kind {
+        CallKind::Create2 => {
+            create2_address(&creator, &operands[3], &init_code)
+        }
+        _ => create_address(&creator, creator_nonce),
+    };
+    // The stack after this step holds the address of the new contract (or
+    // zero if the creation failed). Without it, init code that runs no step
+    // is taken to be empty, which always succeeds.
+    let result = stack_result(snapshots, exec_step);
+    let created = match &result {
+        Some((_, created)) => !created.is_zero(),
+        None => init_code.is_empty(),
+    };
+
+    let nonce = creator_nonce.checked_add(1).filter(|_| {
+        state.call().depth() <= 1024 && state.can_transfer(&creator, value)
+    });
+    if let Some(nonce) = nonce {
+        state.gen_account_write(
+            exec_step,
+            container,
+            creator,
+            AccountField::Nonce,
+            EvmWord::from(nonce),
+        );
+        if state.enters_frame() || created {
+            state.push_call(kind, address, value.clone(), vec![], 0, init_code);
+            state.gen_account_write(
+                exec_step,
+                container,
+                address,
+                AccountField::Nonce,
+                EvmWord::from(1u8),
+            );
+            state.transfer(exec_step, container, creator, address, value);
+        }
+    }
+
+    if let Some((stack_addr, pushed)) = result {
+        exec_step.add_op(
+            container,
+            StackOp::new(
+                RW::WRITE,
+                exec_step.next_op_gc(),
+                creator_call_id,
+                stack_addr,
+                pushed,
+            ),
+        );
+    }
+
+    exec_step.bus_mapping_instance().len()
+}
+
+#[cfg(test)]
+mod create_tests {
+    use super::*;
+    use crate::{
+        evm::EMPTY_CODE_HASH,
+        exec_trace::{ExecutionTrace, ExecutionTraceBuilder},
+        operation::AccountOp,
+        state_db::StateDB,
+        test_util,
+    };
+    use core::str::FromStr;
+
+    #[test]
+    fn contract_addresses() {
+        let sender =
+            Address::from_str("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0")
+                .unwrap();
+        assert_eq!(
+            create_address(&sender, 0),
+            Address::from_str("cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d")
+                .unwrap()
+        );
+        assert_eq!(
+            create_address(&sender, 1),
+            Address::from_str("343c43a37d37dff08ae8c4a11544c718abb4fcf8")
+                .unwrap()
+        );
+
+        // Example 5 of EIP-1014.
+        let sender =
+            Address::from_str("00000000000000000000000000000000deadbeef")
+                .unwrap();
+        assert_eq!(
+            create2_address(
+                &sender,
+                &EvmWord::from_str("cafebabe").unwrap(),
+                &[0xde, 0xad, 0xbe, 0xef]
+            ),
+            Address::from_str("60f3f640a8508fC6a86d45DF051962668E1e8AC7")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn create_deploys_returned_code() {
+        // Init code: PUSH1 01 PUSH1 00 RETURN, which deploys `0x00`.
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["5"], "opcode": "PUSH1 05", "pc": 0 },
+            { "memory": {}, "stack": ["5", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            {
+                "memory": {
+                    "0": "60016000f3000000000000000000000000000000000000000000000000000000"
+                },
+                "stack": ["5", "0", "0"],
+                "opcode": "PUSH1 00",
+                "pc": 4
+            },
+            {
+                "memory": {
+                    "0": "60016000f3000000000000000000000000000000000000000000000000000000"
+                },
+                "stack": ["45eb6484d76cfe3f45708b91f5af8ce495134fac"],
+                "opcode": "CREATE",
+                "pc": 6
+            },
+            { "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0, "depth": 2 },
+            { "memory": {}, "stack": ["1", "0"], "opcode": "PUSH1 00", "pc": 2, "depth": 2 },
+            { "memory": {}, "stack": [], "opcode": "RETURN", "pc": 4, "depth": 2 }
+        ]
+        "#;
+
+        let block_ctants = test_util::block_ctants();
+        let contract = test_util::address("aa");
+        let tx_ctx = test_util::tx_ctx(Some(contract), vec![]);
+
+        let trace = ExecutionTrace::from_trace_bytes_with_tx(
+            input_trace.as_bytes(),
+            block_ctants,
+            tx_ctx,
+        )
+        .expect("Error on trace generation");
+
+        let created = create_address(&contract, 0);
+        assert_eq!(
+            created,
+            Address::from_str("45eb6484d76cfe3f45708b91f5af8ce495134fac")
+                .unwrap()
+        );
+
+        // The init code runs in a fresh call frame.
+        let create_call = &trace.calls()[1];
+        assert_eq!(create_call.kind(), CallKind::Create);
+        assert_eq!(create_call.address(), &created);
+        assert_eq!(create_call.code(), &[0x60, 0x01, 0x60, 0x00, 0xf3]);
+        assert!(trace
+            .sorted_stack_ops()
+            .any(|op| op.call_id() == 2 && op.rw() == RW::WRITE));
+
+        // The created account is accessed for the first time.
+        let accesses: Vec<(bool, bool)> = trace
+            .sorted_access_list_ops()
+            .filter(|op| op.address() == &created)
+            .map(|op| (op.value(), op.value_prev()))
+            .collect();
+        assert_eq!(accesses, vec![(true, false)]);
+
+        // Nonces are bumped and the returned code is deployed.
+        let code_hash = EvmWord::from_be_bytes(&keccak256(&[0x00]));
+        assert_eq!(trace.state_db().account(&contract).nonce(), 1);
+        assert_eq!(trace.state_db().account(&created).nonce(), 1);
+        assert_eq!(trace.state_db().account(&created).code_hash(), &code_hash);
+        assert_eq!(trace.state_db().code(&code_hash), Some(&[0x00][..]));
+        assert_eq!(
+            trace.sorted_account_ops().cloned().collect::<Vec<_>>(),
+            vec![
+                // Nonce increment of the sender of the transaction.
+                AccountOp::new(
+                    RW::WRITE,
+                    1.into(),
+                    Address::zero(),
+                    AccountField::Nonce,
+                    EvmWord::from(1u8),
+                    EvmWord::from(0u8),
+                ),
+                AccountOp::new(
+                    RW::WRITE,
+                    22.into(),
+                    contract,
+                    AccountField::Nonce,
+                    EvmWord::from(1u8),
+                    EvmWord::from(0u8),
+                ),
+                AccountOp::new(
+                    RW::WRITE,
+                    23.into(),
+                    created,
+                    AccountField::Nonce,
+                    EvmWord::from(1u8),
+                    EvmWord::from(0u8),
+                ),
+                AccountOp::new(
+                    RW::WRITE,
+                    33.into(),
+                    created,
+                    AccountField::CodeHash,
+                    code_hash,
+                    EMPTY_CODE_HASH.clone(),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_creations_open_no_frame() {
+        let contract = test_util::address("aa");
+        // CREATE with the given `size` of init code at an offset past any
+        // memory, which pushes zero and is followed by a STOP of the root
+        // call.
+        let trace_with_size = |size: &str| {
+            let input_trace = format!(
+                r#"
+                [
+                    {{ "memory": {{}}, "stack": ["{}", "{}", "0"], "opcode": "PUSH1 00", "pc": 0 }},
+                    {{ "memory": {{}}, "stack": ["0"], "opcode": "CREATE", "pc": 2 }},
+                    {{ "memory": {{}}, "stack": ["0"], "opcode": "STOP", "pc": 3 }}
+                ]
+                "#,
+                size,
+                "f".repeat(64)
+            );
+            let block_ctants = test_util::block_ctants();
+            let tx_ctx = test_util::tx_ctx(Some(contract), vec![]);
+            ExecutionTrace::from_trace_bytes_with_tx(
+                input_trace.as_bytes(),
+                block_ctants,
+                tx_ctx,
+            )
+            .expect("Error on trace generation")
+        };
+        let creator_nonce = |trace: &ExecutionTrace<pasta_curves::Fp>| {
+            trace.state_db().account(&contract).nonce()
+        };
+
+        // Empty init code can be read wherever it's placed, but the address
+        // is in use, so the creator nonce is bumped without opening a frame.
+        let trace = trace_with_size("0");
+        assert_eq!(trace.calls().len(), 1);
+        assert_eq!(creator_nonce(&trace), 1);
+        let stack_writes = |trace: &ExecutionTrace<pasta_curves::Fp>| {
+            trace
+                .sorted_stack_ops()
+                .filter(|op| op.rw() == RW::WRITE)
+                .count()
+        };
+        assert_eq!(stack_writes(&trace), 2);
+
+        // The init code can't be read, so the step halts exceptionally.
+        let trace = trace_with_size("20");
+        assert_eq!(trace.calls().len(), 1);
+        assert_eq!(creator_nonce(&trace), 0);
+        assert_eq!(stack_writes(&trace), 1);
+    }
+
+    #[test]
+    fn creators_with_the_max_nonce_fail() {
+        // PUSH1 00 DUP1 DUP1 CREATE STOP
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "DUP1", "pc": 2 },
+            { "memory": {}, "stack": ["0", "0", "0"], "opcode": "DUP1", "pc": 3 },
+            { "memory": {}, "stack": ["0"], "opcode": "CREATE", "pc": 4 },
+            { "memory": {}, "stack": ["0"], "opcode": "STOP", "pc": 5 }
+        ]
+        "#;
+        let contract = test_util::address("aa");
+        let mut prestate = StateDB::new();
+        prestate.set_code(&contract, vec![0x60, 0x00, 0x80, 0x80, 0xf0, 0x00]);
+        prestate.set_nonce(&contract, u64::MAX);
+
+        let mut builder = ExecutionTraceBuilder::new(test_util::block_ctants())
+            .with_tx(test_util::tx_ctx(Some(contract), vec![]))
+            .with_prestate(prestate);
+        builder
+            .push_trace_bytes(input_trace.as_bytes())
+            .expect("Error on parsing");
+        let trace = builder.finish().expect("Error on trace generation");
+
+        // The nonce can't be bumped, so the creation opens no frame.
+        assert_eq!(trace.calls().len(), 1);
+        assert_eq!(trace.state_db().account(&contract).nonce(), u64::MAX);
+        assert!(!trace
+            .sorted_account_ops()
+            .any(|op| op.address() == &contract));
+    }
+}
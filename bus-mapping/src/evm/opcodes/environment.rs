@@ -0,0 +1,316 @@
+//! Opcodes that read the context of the transaction or of the current call.
+use super::{stack_result, Opcode};
+use crate::{
+    exec_trace::{ExecutionStep, StepSnapshots, TraceState},
+    operation::{container::OperationContainer, ContextField, StackOp, RW},
+};
+
+/// Defines the structure of an opcode that reads the entry `$field` of the
+/// context and pushes it into the stack.
+macro_rules! impl_context_opcode {
+    ($($name:ident, $opcode:literal, $field:ident;)*) => {
+        $(
+            #[doc = concat!(
+                "Structure used to implement [`Opcode`] trait over it ",
+                "corresponding to the `", $opcode, "` ",
+                "[`Instruction`](crate::evm::instruction::Instruction)."
+            )]
+            #[derive(Debug, Copy, Clone)]
+            pub(crate) struct $name;
+
+            impl Opcode for $name {
+                fn gen_associated_ops(
+                    &self,
+                    state: &mut TraceState,
+                    snapshots: StepSnapshots<'_>,
+                    exec_step: &mut ExecutionStep,
+                    container: &mut OperationContainer,
+                ) -> usize {
+                    gen_context_ops(
+                        ContextField::$field,
+                        state,
+                        snapshots,
+                        exec_step,
+                        container,
+                    )
+                }
+            }
+        )*
+    };
+}
+
+impl_context_opcode!(
+    ContractAddress, "ADDRESS", Address;
+    Origin, "ORIGIN", Origin;
+    Caller, "CALLER", Caller;
+    CallValue, "CALLVALUE", Value;
+    CalldataSize, "CALLDATASIZE", CalldataSize;
+    CodeSize, "CODESIZE", CodeSize;
+    GasPrice, "GASPRICE", GasPrice;
+    ReturnDataSize, "RETURNDATASIZE", ReturnDataSize;
+    Msize, "MSIZE", MemorySize;
+    GasLeft, "GAS", Gas;
+);
+
+/// Reads the entry `field` of the context through a
+/// [`ContextOp`](crate::operation::ContextOp) and writes it into the stack.
+fn gen_context_ops(
+    field: ContextField,
+    state: &mut TraceState,
+    snapshots: StepSnapshots<'_>,
+    exec_step: &mut ExecutionStep,
+    container: &mut OperationContainer,
+) -> usize {
+    let value = state.gen_context_read(exec_step, container, field);
+    if let Some((address, _)) = stack_result(snapshots, exec_step) {
+        exec_step.add_op(
+            container,
+            StackOp::new(
+                RW::WRITE,
+                exec_step.next_op_gc(),
+                state.call().call_id(),
+                address,
+                value,
+            ),
+        );
+    }
+
+    exec_step.bus_mapping_instance().len()
+}
+
+#[cfg(test)]
+mod environment_tests {
+    use crate::{
+        evm::EvmWord,
+        exec_trace::{ExecutionTrace, ExecutionTraceBuilder, TxContext},
+        operation::{ContextField, RW},
+        state_db::StateDB,
+        test_util, Error, TraceError,
+    };
+    use core::str::FromStr;
+    use pasta_curves::Fp;
+
+    fn trace(
+        input_trace: &str,
+        prestate: StateDB,
+    ) -> Result<ExecutionTrace<Fp>, Error> {
+        let block_ctants = test_util::block_ctants();
+        let tx_ctx = TxContext::new(
+            test_util::address("11"),
+            Some(test_util::address("aa")),
+            0,
+            EvmWord::from(5u8),
+            EvmWord::from(7u8),
+            100_000,
+            vec![1, 2, 3],
+        );
+
+        let mut builder = ExecutionTraceBuilder::new(block_ctants)
+            .with_tx(tx_ctx)
+            .with_prestate(prestate);
+        builder
+            .push_trace_bytes(input_trace.as_bytes())
+            .expect("Error on parsing");
+        builder.finish()
+    }
+
+    fn context_reads(
+        trace: &ExecutionTrace<Fp>,
+    ) -> Vec<(usize, ContextField, EvmWord)> {
+        trace
+            .sorted_context_ops()
+            .map(|op| (op.call_id(), op.field(), op.value().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn context_reads_and_stack_writes() {
+        // The 0x20 bytes of code of 0xaa are known, and the root call gets
+        // 100000 - 21048 gas, the intrinsic gas of the transaction.
+        let mut code =
+            vec![0x30, 0x32, 0x33, 0x34, 0x36, 0x38, 0x3a, 0x59, 0x5a];
+        code.resize(0x20, 0);
+        let mut prestate = StateDB::new();
+        prestate.set_balance(
+            &test_util::address("11"),
+            EvmWord::from(1_000_000u32),
+        );
+        prestate.set_code(&test_util::address("aa"), code);
+        let trace = trace(
+            r#"
+        [
+            { "memory": {}, "stack": ["aa"], "opcode": "ADDRESS", "pc": 0 },
+            { "memory": {}, "stack": ["aa", "11"], "opcode": "ORIGIN", "pc": 1 },
+            { "memory": {}, "stack": ["aa", "11", "11"], "opcode": "CALLER", "pc": 2 },
+            { "memory": {}, "stack": ["aa", "11", "11", "5"], "opcode": "CALLVALUE", "pc": 3 },
+            { "memory": {}, "stack": ["aa", "11", "11", "5", "3"], "opcode": "CALLDATASIZE", "pc": 4 },
+            { "memory": {}, "stack": ["aa", "11", "11", "5", "3", "20"], "opcode": "CODESIZE", "pc": 5 },
+            { "memory": {}, "stack": ["aa", "11", "11", "5", "3", "20", "7"], "opcode": "GASPRICE", "pc": 6 },
+            { "memory": {}, "stack": ["aa", "11", "11", "5", "3", "20", "7", "0"], "opcode": "MSIZE", "pc": 7 },
+            { "memory": {}, "stack": ["aa", "11", "11", "5", "3", "20", "7", "0", "13456"], "opcode": "GAS", "pc": 8 }
+        ]
+        "#,
+            prestate,
+        )
+        .expect("Error on trace generation");
+
+        let word = |hex: &str| EvmWord::from_str(hex).unwrap();
+        assert_eq!(
+            context_reads(&trace),
+            vec![
+                (1, ContextField::Origin, word("11")),
+                (1, ContextField::GasPrice, word("7")),
+                (1, ContextField::Address, word("aa")),
+                (1, ContextField::Caller, word("11")),
+                (1, ContextField::Value, word("5")),
+                (1, ContextField::CalldataSize, word("3")),
+                (1, ContextField::CodeSize, word("20")),
+                (1, ContextField::MemorySize, word("0")),
+                (1, ContextField::Gas, word("13456")),
+            ]
+        );
+
+        // Each read is followed by the stack write of the value read.
+        let last_step = &trace[trace.steps().len() - 1];
+        assert!(trace.sorted_stack_ops().any(|op| {
+            op.rw() == RW::WRITE
+                && op.address() == &last_step.stack_addr()
+                && op.value() == &word("13456")
+        }));
+    }
+
+    #[test]
+    fn unknown_context_entries_fail() {
+        // The code of 0xaa is missing from the state.
+        let result = trace(
+            r#"
+        [
+            { "memory": {}, "stack": ["20"], "opcode": "CODESIZE", "pc": 0 }
+        ]
+        "#,
+            StateDB::new(),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::InvalidTrace(TraceError::UnknownContext {
+                step: 0,
+                field: ContextField::CodeSize
+            }))
+        ));
+    }
+
+    #[test]
+    fn sub_call_context() {
+        // The root call calls 0xbb, which reads it's context and returns 3
+        // bytes.
+        let trace = trace(
+            r#"
+        [
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": ["0", "0", "0"], "opcode": "PUSH1 00", "pc": 4 },
+            { "memory": {}, "stack": ["0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 6 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 8 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0", "bb"], "opcode": "PUSH1 bb", "pc": 10 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0", "bb", "ff"], "opcode": "PUSH1 ff", "pc": 12 },
+            { "memory": {}, "stack": ["1"], "opcode": "CALL", "pc": 14 },
+            { "memory": {}, "stack": ["aa"], "opcode": "CALLER", "pc": 0, "depth": 2 },
+            { "memory": {}, "stack": ["aa", "bb"], "opcode": "ADDRESS", "pc": 1, "depth": 2 },
+            { "memory": {}, "stack": ["aa", "bb", "3"], "opcode": "PUSH1 03", "pc": 2, "depth": 2 },
+            { "memory": {}, "stack": ["aa", "bb", "3", "0"], "opcode": "PUSH1 00", "pc": 4, "depth": 2 },
+            { "memory": {}, "stack": ["aa", "bb"], "opcode": "RETURN", "pc": 6, "depth": 2 },
+            { "memory": {}, "stack": ["1", "3"], "opcode": "RETURNDATASIZE", "pc": 15 },
+            { "memory": {}, "stack": ["1", "3"], "opcode": "STOP", "pc": 16 }
+        ]
+        "#,
+            StateDB::new(),
+        )
+        .expect("Error on trace generation");
+
+        assert_eq!(
+            context_reads(&trace),
+            vec![
+                (1, ContextField::ReturnDataSize, EvmWord::from(3u8)),
+                (2, ContextField::Address, test_util::address("bb").to_word()),
+                (2, ContextField::Caller, test_util::address("aa").to_word()),
+            ]
+        );
+        assert_eq!(
+            trace.context_table().get(2, ContextField::CalldataSize),
+            Some(&EvmWord::from(0u8))
+        );
+    }
+
+    #[test]
+    fn gas_left_is_tracked_across_calls() {
+        // The root call forwards 100 gas to 0xbb (cold), which reads the gas
+        // it has left and returns the rest of it to the root call.
+        let trace = trace(
+            r#"
+        [
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": ["0", "0", "0"], "opcode": "PUSH1 00", "pc": 4 },
+            { "memory": {}, "stack": ["0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 6 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 8 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0", "bb"], "opcode": "PUSH1 bb", "pc": 10 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0", "bb", "64"], "opcode": "PUSH1 64", "pc": 12 },
+            { "memory": {}, "stack": ["62"], "opcode": "CALL", "pc": 14 },
+            { "memory": {}, "stack": ["62"], "opcode": "GAS", "pc": 0, "depth": 2 },
+            { "memory": {}, "stack": ["62"], "opcode": "STOP", "pc": 1, "depth": 2 },
+            { "memory": {}, "stack": ["1", "12a27"], "opcode": "GAS", "pc": 15 }
+        ]
+        "#,
+            StateDB::new(),
+        )
+        .expect("Error on trace generation");
+
+        // 78952 gas, minus 21 for the pushes and 2600 + 100 for the call,
+        // plus the 98 returned.
+        assert_eq!(
+            context_reads(&trace),
+            vec![
+                (1, ContextField::Gas, EvmWord::from(0x12a27u32)),
+                (2, ContextField::Gas, EvmWord::from(0x62u8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn gas_left_after_a_value_call_to_an_account_without_code() {
+        // The root call sends 1 wei and 100 gas to 0xbb, which has no code
+        // and gives the gas back along with the stipend of the transfer.
+        let trace = trace(
+            r#"
+        [
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": ["0", "0", "0"], "opcode": "PUSH1 00", "pc": 4 },
+            { "memory": {}, "stack": ["0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 6 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "1"], "opcode": "PUSH1 01", "pc": 8 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "1", "bb"], "opcode": "PUSH1 bb", "pc": 10 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "1", "bb", "64"], "opcode": "PUSH1 64", "pc": 12 },
+            { "memory": {}, "stack": ["1"], "opcode": "CALL", "pc": 14 },
+            { "memory": {}, "stack": ["1", "ae55"], "opcode": "GAS", "pc": 15 },
+            { "memory": {}, "stack": ["1", "ae55"], "opcode": "STOP", "pc": 16 }
+        ]
+        "#,
+            StateDB::new(),
+        )
+        .expect("Error on trace generation");
+
+        // 78952 gas, minus 21 for the pushes and 36600 + 100 for the call
+        // (including the creation of 0xbb), plus the 2400 returned.
+        assert_eq!(
+            context_reads(&trace),
+            vec![(1, ContextField::Gas, EvmWord::from(0xae55u32))]
+        );
+        assert_eq!(
+            trace
+                .state_db()
+                .account(&test_util::address("bb"))
+                .balance(),
+            &EvmWord::from(1u8)
+        );
+    }
+}
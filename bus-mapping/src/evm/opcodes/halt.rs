@@ -0,0 +1,276 @@
+//! Opcodes that halt the execution of the current call frame.
+use super::{gen_stack_reads, memory_bytes_before, memory_region, Opcode};
+use crate::{
+    evm::{Address, EvmWord},
+    exec_trace::{ExecutionStep, StepSnapshots, TraceState},
+    operation::{container::OperationContainer, AccountField},
+};
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `STOP` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Stop;
+
+impl Opcode for Stop {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        _snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        _container: &mut OperationContainer,
+    ) -> usize {
+        state.halt_call();
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `RETURN` [`Instruction`](crate::evm::instruction::Instruction).
+///
+/// When the frame being returned from runs init code, the returned bytes are
+/// the runtime code of the created contract, which gets deployed.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Return;
+
+impl Opcode for Return {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let operands =
+            gen_stack_reads(state, snapshots, 2, exec_step, container);
+
+        // A step that can't pay for reading the returned data halts
+        // exceptionally, so it's frame is reverted once exited.
+        let (offset, data) =
+            match memory_region(exec_step, &operands[0], &operands[1]).map(
+                |(offset, size)| {
+                    (
+                        offset,
+                        memory_bytes_before(snapshots, exec_step, offset, size),
+                    )
+                },
+            ) {
+                Some(returned) => returned,
+                None => return exec_step.bus_mapping_instance().len(),
+            };
+        // The code returned by init code isn't returned to the creator.
+        if !state.call().kind().is_create() {
+            state.set_return_data(data, offset);
+        } else {
+            let address = *state.call().address();
+            let code_hash = state.state_db_mut().insert_code(data);
+            state.gen_account_write(
+                exec_step,
+                container,
+                address,
+                AccountField::CodeHash,
+                code_hash,
+            );
+        }
+
+        state.halt_call();
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `REVERT` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Revert;
+
+impl Opcode for Revert {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let operands =
+            gen_stack_reads(state, snapshots, 2, exec_step, container);
+
+        // A step that can't pay for reading the returned data halts
+        // exceptionally, returning no data and consuming all of it's gas.
+        let returned = memory_region(exec_step, &operands[0], &operands[1])
+            .map(|(offset, size)| {
+                (
+                    offset,
+                    memory_bytes_before(snapshots, exec_step, offset, size),
+                )
+            });
+        state.revert_call(exec_step, container);
+        match returned {
+            Some((offset, data)) => {
+                state.set_return_data(data, offset);
+                state.return_gas_left();
+            }
+            None => state.set_return_data(vec![], 0),
+        }
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `INVALID` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Invalid;
+
+impl Opcode for Invalid {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        _snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        state.revert_call(exec_step, container);
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `SELFDESTRUCT` [`Instruction`](crate::evm::instruction::Instruction).
+///
+/// The whole balance of the destroyed account is moved to the beneficiary.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct SelfDestruct;
+
+impl Opcode for SelfDestruct {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let beneficiary = Address::from_word(
+            &gen_stack_reads(state, snapshots, 1, exec_step, container)[0],
+        );
+
+        // The balance is credited to the beneficiary before the one of the
+        // destroyed account is cleared, so that it's burnt when both are the
+        // same account.
+        let address = *state.call().address();
+        let balance = state.state_db().account(&address).balance().clone();
+        if !balance.is_zero() {
+            let beneficiary_balance =
+                &state.state_db().account(&beneficiary).balance().0
+                    + &balance.0;
+            state.gen_account_write(
+                exec_step,
+                container,
+                beneficiary,
+                AccountField::Balance,
+                EvmWord(beneficiary_balance),
+            );
+            state.gen_account_write(
+                exec_step,
+                container,
+                address,
+                AccountField::Balance,
+                EvmWord::from(0u8),
+            );
+        }
+
+        state.halt_call();
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+#[cfg(test)]
+mod halt_tests {
+    use crate::{
+        evm::{create_address, Address, EvmWord},
+        exec_trace::ExecutionTrace,
+        operation::RW,
+        test_util,
+    };
+
+    #[test]
+    fn revert_undoes_state_writes() {
+        // The root call stores 0x2a into slot 0, which is in the access list
+        // of the transaction, and creates a contract whose init code stores
+        // 0x07 into it's own slot 0 and then reverts.
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["2a"], "opcode": "PUSH1 2a", "pc": 0 },
+            { "memory": {}, "stack": ["2a", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": [], "opcode": "SSTORE", "pc": 4 },
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 5 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 7 },
+            { "memory": {}, "stack": ["0", "0", "0"], "opcode": "PUSH1 00", "pc": 9 },
+            { "memory": {}, "stack": ["0"], "opcode": "CREATE", "pc": 11 },
+            { "memory": {}, "stack": ["7"], "opcode": "PUSH1 07", "pc": 0, "depth": 2 },
+            { "memory": {}, "stack": ["7", "0"], "opcode": "PUSH1 00", "pc": 2, "depth": 2 },
+            { "memory": {}, "stack": [], "opcode": "SSTORE", "pc": 4, "depth": 2 },
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 5, "depth": 2 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 7, "depth": 2 },
+            { "memory": {}, "stack": [], "opcode": "REVERT", "pc": 9, "depth": 2 },
+            { "memory": {}, "stack": ["0"], "opcode": "STOP", "pc": 12 }
+        ]
+        "#;
+
+        let block_ctants = test_util::block_ctants();
+        let contract = test_util::address("aa");
+        let tx_ctx = test_util::tx_ctx(Some(contract), vec![])
+            .with_access_list(vec![(contract, vec![EvmWord::from(0u8)])]);
+
+        let trace = ExecutionTrace::from_trace_bytes_with_tx(
+            input_trace.as_bytes(),
+            block_ctants,
+            tx_ctx,
+        )
+        .expect("Error on trace generation");
+        let created = create_address(&contract, 0);
+        let slot = EvmWord::from(0u8);
+
+        assert!(!trace.calls()[0].is_reverted());
+        assert!(trace.calls()[1].is_reverted());
+
+        // Only the writes done outside of the reverted frame persist.
+        let state_db = trace.state_db();
+        assert_eq!(state_db.storage(&contract, &slot), Some(&0x2au8.into()));
+        assert_eq!(state_db.storage(&created, &slot), Some(&0u8.into()));
+        assert_eq!(state_db.account(&contract).nonce(), 1);
+        assert_eq!(state_db.account(&created).nonce(), 0);
+
+        // The reverted write is undone by a compensating one.
+        let created_ops: Vec<(RW, EvmWord, EvmWord)> = trace
+            .sorted_storage_ops()
+            .filter(|op| op.address() == &created)
+            .map(|op| (op.rw(), op.value().clone(), op.value_prev().clone()))
+            .collect();
+        assert_eq!(
+            created_ops,
+            vec![
+                (RW::WRITE, 7u8.into(), 0u8.into()),
+                (RW::WRITE, 0u8.into(), 7u8.into()),
+            ]
+        );
+
+        // The access list makes the first access to slot 0 of the contract
+        // warm, while the cold access of the reverted frame is undone.
+        let accesses = |address: Address, key: Option<&EvmWord>| {
+            trace
+                .sorted_access_list_ops()
+                .filter(|op| op.address() == &address && op.key() == key)
+                .map(|op| (op.value(), op.value_prev()))
+                .collect::<Vec<(bool, bool)>>()
+        };
+        assert_eq!(
+            accesses(contract, Some(&slot)),
+            vec![(true, false), (true, true)]
+        );
+        assert_eq!(
+            accesses(created, Some(&slot)),
+            vec![(true, false), (false, true)]
+        );
+        // The created account is accessed by the frame that creates it, so it
+        // stays warm.
+        assert_eq!(accesses(created, None), vec![(true, false)]);
+    }
+}
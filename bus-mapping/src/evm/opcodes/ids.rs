@@ -1,10 +1,11 @@
 use crate::error::Error;
-use core::fmt::Debug;
+use crate::evm::{GasCost, Hardfork};
+use core::fmt::{self, Debug};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 /// Opcode enum. One-to-one corresponding to an `u8` value.
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct OpcodeId(pub u8);
 
 // Core opcodes.
@@ -328,6 +329,415 @@ impl OpcodeId {
     pub const fn as_usize(&self) -> usize {
         self.0 as usize
     }
+
+    /// Returns the [`Hardfork`] that introduced the `OpcodeId`.
+    pub const fn introduced_in(&self) -> Hardfork {
+        match *self {
+            OpcodeId::DELEGATECALL => Hardfork::Homestead,
+            OpcodeId::REVERT
+            | OpcodeId::RETURNDATASIZE
+            | OpcodeId::RETURNDATACOPY
+            | OpcodeId::STATICCALL => Hardfork::Byzantium,
+            OpcodeId::SHL
+            | OpcodeId::SHR
+            | OpcodeId::SAR
+            | OpcodeId::EXTCODEHASH
+            | OpcodeId::CREATE2 => Hardfork::Constantinople,
+            OpcodeId::CHAINID | OpcodeId::SELFBALANCE => Hardfork::Istanbul,
+            OpcodeId::BASEFEE => Hardfork::London,
+            _ => Hardfork::Frontier,
+        }
+    }
+
+    /// Parses an `OpcodeId` as [`OpcodeId::from_str`] does, failing with
+    /// [`Error::OpcodeNotInHardfork`] if it isn't defined at `hardfork`.
+    pub fn from_str_with_hardfork(
+        s: &str,
+        hardfork: Hardfork,
+    ) -> Result<OpcodeId, Error> {
+        let opcode = OpcodeId::from_str(s)?;
+        if hardfork.has_opcode(opcode) {
+            Ok(opcode)
+        } else {
+            Err(Error::OpcodeNotInHardfork(opcode, hardfork))
+        }
+    }
+
+    /// Returns true if the `OpcodeId` opens a new call frame.
+    pub const fn is_call_or_create(&self) -> bool {
+        matches!(
+            *self,
+            OpcodeId::CALL
+                | OpcodeId::CALLCODE
+                | OpcodeId::DELEGATECALL
+                | OpcodeId::STATICCALL
+                | OpcodeId::CREATE
+                | OpcodeId::CREATE2
+        )
+    }
+
+    /// Returns true if the `OpcodeId` ends the execution of the current call
+    /// frame.
+    pub const fn is_halt(&self) -> bool {
+        matches!(
+            *self,
+            OpcodeId::STOP
+                | OpcodeId::RETURN
+                | OpcodeId::REVERT
+                | OpcodeId::INVALID
+                | OpcodeId::SELFDESTRUCT
+        )
+    }
+
+    /// Returns the number of bytes of the immediate value that follows the
+    /// `OpcodeId` in the code, which is only non-zero for the `PUSH`
+    /// opcodes.
+    pub const fn push_size(&self) -> usize {
+        match self.0 {
+            0x60..=0x7f => (self.0 - 0x5f) as usize,
+            _ => 0,
+        }
+    }
+
+    /// Returns the number of words the `OpcodeId` takes from the top of the
+    /// stack, which is zero for the opcodes that aren't known.
+    pub const fn stack_inputs(&self) -> usize {
+        match *self {
+            OpcodeId::ISZERO
+            | OpcodeId::NOT
+            | OpcodeId::BALANCE
+            | OpcodeId::CALLDATALOAD
+            | OpcodeId::EXTCODESIZE
+            | OpcodeId::EXTCODEHASH
+            | OpcodeId::BLOCKHASH
+            | OpcodeId::POP
+            | OpcodeId::MLOAD
+            | OpcodeId::SLOAD
+            | OpcodeId::JUMP
+            | OpcodeId::SELFDESTRUCT => 1,
+            OpcodeId::ADD
+            | OpcodeId::MUL
+            | OpcodeId::SUB
+            | OpcodeId::DIV
+            | OpcodeId::SDIV
+            | OpcodeId::MOD
+            | OpcodeId::SMOD
+            | OpcodeId::EXP
+            | OpcodeId::SIGNEXTEND
+            | OpcodeId::LT
+            | OpcodeId::GT
+            | OpcodeId::SLT
+            | OpcodeId::SGT
+            | OpcodeId::EQ
+            | OpcodeId::AND
+            | OpcodeId::OR
+            | OpcodeId::XOR
+            | OpcodeId::BYTE
+            | OpcodeId::SHL
+            | OpcodeId::SHR
+            | OpcodeId::SAR
+            | OpcodeId::SHA3
+            | OpcodeId::MSTORE
+            | OpcodeId::MSTORE8
+            | OpcodeId::SSTORE
+            | OpcodeId::JUMPI
+            | OpcodeId::RETURN
+            | OpcodeId::REVERT => 2,
+            OpcodeId::ADDMOD
+            | OpcodeId::MULMOD
+            | OpcodeId::CALLDATACOPY
+            | OpcodeId::CODECOPY
+            | OpcodeId::RETURNDATACOPY
+            | OpcodeId::CREATE => 3,
+            OpcodeId::EXTCODECOPY | OpcodeId::CREATE2 => 4,
+            OpcodeId::DELEGATECALL | OpcodeId::STATICCALL => 6,
+            OpcodeId::CALL | OpcodeId::CALLCODE => 7,
+            // DUP1..DUP16, SWAP1..SWAP16 and LOG0..LOG4.
+            OpcodeId(byte @ 0x80..=0x8f) => (byte - 0x7f) as usize,
+            OpcodeId(byte @ 0x90..=0x9f) => (byte - 0x8e) as usize,
+            OpcodeId(byte @ 0xa0..=0xa4) => (byte - 0x9e) as usize,
+            _ => 0,
+        }
+    }
+
+    /// Returns the number of words the `OpcodeId` pushes to the stack once
+    /// it's inputs have been taken, which is zero for the opcodes that aren't
+    /// known.
+    pub const fn stack_outputs(&self) -> usize {
+        match *self {
+            OpcodeId::STOP
+            | OpcodeId::CALLDATACOPY
+            | OpcodeId::CODECOPY
+            | OpcodeId::EXTCODECOPY
+            | OpcodeId::RETURNDATACOPY
+            | OpcodeId::POP
+            | OpcodeId::MSTORE
+            | OpcodeId::MSTORE8
+            | OpcodeId::SSTORE
+            | OpcodeId::JUMP
+            | OpcodeId::JUMPI
+            | OpcodeId::JUMPDEST
+            | OpcodeId(0xa0..=0xa4)
+            | OpcodeId::RETURN
+            | OpcodeId::REVERT
+            | OpcodeId::INVALID
+            | OpcodeId::SELFDESTRUCT => 0,
+            // DUP1..DUP16 and SWAP1..SWAP16 push back the words they take.
+            OpcodeId(byte @ 0x80..=0x8f) => (byte - 0x7e) as usize,
+            OpcodeId(byte @ 0x90..=0x9f) => (byte - 0x8e) as usize,
+            _ if self.name().is_some() => 1,
+            _ => 0,
+        }
+    }
+
+    /// Returns the part of the gas cost of the `OpcodeId` that doesn't depend
+    /// on it's operands nor on the state.
+    pub const fn constant_gas_cost(&self) -> GasCost {
+        match *self {
+            OpcodeId::STOP
+            | OpcodeId::RETURN
+            | OpcodeId::REVERT
+            | OpcodeId::INVALID
+            | OpcodeId::SSTORE => GasCost::ZERO,
+            OpcodeId::JUMPDEST => GasCost::ONE,
+            OpcodeId::ADDRESS
+            | OpcodeId::ORIGIN
+            | OpcodeId::CALLER
+            | OpcodeId::CALLVALUE
+            | OpcodeId::CALLDATASIZE
+            | OpcodeId::CODESIZE
+            | OpcodeId::GASPRICE
+            | OpcodeId::RETURNDATASIZE
+            | OpcodeId::COINBASE
+            | OpcodeId::TIMESTAMP
+            | OpcodeId::NUMBER
+            | OpcodeId::DIFFICULTY
+            | OpcodeId::GASLIMIT
+            | OpcodeId::CHAINID
+            | OpcodeId::BASEFEE
+            | OpcodeId::POP
+            | OpcodeId::PC
+            | OpcodeId::MSIZE
+            | OpcodeId::GAS => GasCost::QUICK,
+            OpcodeId::ADD
+            | OpcodeId::SUB
+            | OpcodeId::LT
+            | OpcodeId::GT
+            | OpcodeId::SLT
+            | OpcodeId::SGT
+            | OpcodeId::EQ
+            | OpcodeId::ISZERO
+            | OpcodeId::AND
+            | OpcodeId::OR
+            | OpcodeId::XOR
+            | OpcodeId::NOT
+            | OpcodeId::BYTE
+            | OpcodeId::SHL
+            | OpcodeId::SHR
+            | OpcodeId::SAR
+            | OpcodeId::CALLDATALOAD
+            | OpcodeId::CALLDATACOPY
+            | OpcodeId::CODECOPY
+            | OpcodeId::RETURNDATACOPY
+            | OpcodeId::MLOAD
+            | OpcodeId::MSTORE
+            | OpcodeId::MSTORE8
+            // PUSHn, DUPn and SWAPn
+            | OpcodeId(0x60..=0x9f) => GasCost::FASTEST,
+            OpcodeId::MUL
+            | OpcodeId::DIV
+            | OpcodeId::SDIV
+            | OpcodeId::MOD
+            | OpcodeId::SMOD
+            | OpcodeId::SIGNEXTEND
+            | OpcodeId::SELFBALANCE => GasCost::FAST,
+            OpcodeId::ADDMOD | OpcodeId::MULMOD | OpcodeId::JUMP => {
+                GasCost::MID
+            }
+            OpcodeId::EXP | OpcodeId::JUMPI => GasCost::SLOW,
+            OpcodeId::BLOCKHASH => GasCost::EXT,
+            OpcodeId::SHA3 => GasCost::SHA3,
+            OpcodeId::BALANCE
+            | OpcodeId::EXTCODESIZE
+            | OpcodeId::EXTCODECOPY
+            | OpcodeId::EXTCODEHASH
+            | OpcodeId::SLOAD
+            | OpcodeId::CALL
+            | OpcodeId::CALLCODE
+            | OpcodeId::DELEGATECALL
+            | OpcodeId::STATICCALL => GasCost::WARM_ACCESS,
+            OpcodeId::LOG0
+            | OpcodeId::LOG1
+            | OpcodeId::LOG2
+            | OpcodeId::LOG3
+            | OpcodeId::LOG4 => GasCost::LOG,
+            OpcodeId::CREATE | OpcodeId::CREATE2 => GasCost::CREATE,
+            OpcodeId::SELFDESTRUCT => GasCost::SELFDESTRUCT,
+            _ => GasCost::ZERO,
+        }
+    }
+
+    /// Returns the mnemonic of the `OpcodeId` (i.e. `"PUSH1"`), or `None` if
+    /// it's not a known opcode.
+    pub const fn name(&self) -> Option<&'static str> {
+        Some(match *self {
+            OpcodeId::STOP => "STOP",
+            OpcodeId::ADD => "ADD",
+            OpcodeId::MUL => "MUL",
+            OpcodeId::SUB => "SUB",
+            OpcodeId::DIV => "DIV",
+            OpcodeId::SDIV => "SDIV",
+            OpcodeId::MOD => "MOD",
+            OpcodeId::SMOD => "SMOD",
+            OpcodeId::ADDMOD => "ADDMOD",
+            OpcodeId::MULMOD => "MULMOD",
+            OpcodeId::EXP => "EXP",
+            OpcodeId::SIGNEXTEND => "SIGNEXTEND",
+            OpcodeId::LT => "LT",
+            OpcodeId::GT => "GT",
+            OpcodeId::SLT => "SLT",
+            OpcodeId::SGT => "SGT",
+            OpcodeId::EQ => "EQ",
+            OpcodeId::ISZERO => "ISZERO",
+            OpcodeId::AND => "AND",
+            OpcodeId::OR => "OR",
+            OpcodeId::XOR => "XOR",
+            OpcodeId::NOT => "NOT",
+            OpcodeId::BYTE => "BYTE",
+            OpcodeId::CALLDATALOAD => "CALLDATALOAD",
+            OpcodeId::CALLDATASIZE => "CALLDATASIZE",
+            OpcodeId::CALLDATACOPY => "CALLDATACOPY",
+            OpcodeId::CODESIZE => "CODESIZE",
+            OpcodeId::CODECOPY => "CODECOPY",
+            OpcodeId::SHL => "SHL",
+            OpcodeId::SHR => "SHR",
+            OpcodeId::SAR => "SAR",
+            OpcodeId::POP => "POP",
+            OpcodeId::MLOAD => "MLOAD",
+            OpcodeId::MSTORE => "MSTORE",
+            OpcodeId::MSTORE8 => "MSTORE8",
+            OpcodeId::JUMP => "JUMP",
+            OpcodeId::JUMPI => "JUMPI",
+            OpcodeId::PC => "PC",
+            OpcodeId::MSIZE => "MSIZE",
+            OpcodeId::JUMPDEST => "JUMPDEST",
+            OpcodeId::PUSH1 => "PUSH1",
+            OpcodeId::PUSH2 => "PUSH2",
+            OpcodeId::PUSH3 => "PUSH3",
+            OpcodeId::PUSH4 => "PUSH4",
+            OpcodeId::PUSH5 => "PUSH5",
+            OpcodeId::PUSH6 => "PUSH6",
+            OpcodeId::PUSH7 => "PUSH7",
+            OpcodeId::PUSH8 => "PUSH8",
+            OpcodeId::PUSH9 => "PUSH9",
+            OpcodeId::PUSH10 => "PUSH10",
+            OpcodeId::PUSH11 => "PUSH11",
+            OpcodeId::PUSH12 => "PUSH12",
+            OpcodeId::PUSH13 => "PUSH13",
+            OpcodeId::PUSH14 => "PUSH14",
+            OpcodeId::PUSH15 => "PUSH15",
+            OpcodeId::PUSH16 => "PUSH16",
+            OpcodeId::PUSH17 => "PUSH17",
+            OpcodeId::PUSH18 => "PUSH18",
+            OpcodeId::PUSH19 => "PUSH19",
+            OpcodeId::PUSH20 => "PUSH20",
+            OpcodeId::PUSH21 => "PUSH21",
+            OpcodeId::PUSH22 => "PUSH22",
+            OpcodeId::PUSH23 => "PUSH23",
+            OpcodeId::PUSH24 => "PUSH24",
+            OpcodeId::PUSH25 => "PUSH25",
+            OpcodeId::PUSH26 => "PUSH26",
+            OpcodeId::PUSH27 => "PUSH27",
+            OpcodeId::PUSH28 => "PUSH28",
+            OpcodeId::PUSH29 => "PUSH29",
+            OpcodeId::PUSH30 => "PUSH30",
+            OpcodeId::PUSH31 => "PUSH31",
+            OpcodeId::PUSH32 => "PUSH32",
+            OpcodeId::DUP1 => "DUP1",
+            OpcodeId::DUP2 => "DUP2",
+            OpcodeId::DUP3 => "DUP3",
+            OpcodeId::DUP4 => "DUP4",
+            OpcodeId::DUP5 => "DUP5",
+            OpcodeId::DUP6 => "DUP6",
+            OpcodeId::DUP7 => "DUP7",
+            OpcodeId::DUP8 => "DUP8",
+            OpcodeId::DUP9 => "DUP9",
+            OpcodeId::DUP10 => "DUP10",
+            OpcodeId::DUP11 => "DUP11",
+            OpcodeId::DUP12 => "DUP12",
+            OpcodeId::DUP13 => "DUP13",
+            OpcodeId::DUP14 => "DUP14",
+            OpcodeId::DUP15 => "DUP15",
+            OpcodeId::DUP16 => "DUP16",
+            OpcodeId::SWAP1 => "SWAP1",
+            OpcodeId::SWAP2 => "SWAP2",
+            OpcodeId::SWAP3 => "SWAP3",
+            OpcodeId::SWAP4 => "SWAP4",
+            OpcodeId::SWAP5 => "SWAP5",
+            OpcodeId::SWAP6 => "SWAP6",
+            OpcodeId::SWAP7 => "SWAP7",
+            OpcodeId::SWAP8 => "SWAP8",
+            OpcodeId::SWAP9 => "SWAP9",
+            OpcodeId::SWAP10 => "SWAP10",
+            OpcodeId::SWAP11 => "SWAP11",
+            OpcodeId::SWAP12 => "SWAP12",
+            OpcodeId::SWAP13 => "SWAP13",
+            OpcodeId::SWAP14 => "SWAP14",
+            OpcodeId::SWAP15 => "SWAP15",
+            OpcodeId::SWAP16 => "SWAP16",
+            OpcodeId::RETURN => "RETURN",
+            OpcodeId::REVERT => "REVERT",
+            OpcodeId::INVALID => "INVALID",
+            OpcodeId::SHA3 => "SHA3",
+            OpcodeId::ADDRESS => "ADDRESS",
+            OpcodeId::BALANCE => "BALANCE",
+            OpcodeId::SELFBALANCE => "SELFBALANCE",
+            OpcodeId::ORIGIN => "ORIGIN",
+            OpcodeId::CALLER => "CALLER",
+            OpcodeId::CALLVALUE => "CALLVALUE",
+            OpcodeId::GASPRICE => "GASPRICE",
+            OpcodeId::EXTCODESIZE => "EXTCODESIZE",
+            OpcodeId::EXTCODECOPY => "EXTCODECOPY",
+            OpcodeId::EXTCODEHASH => "EXTCODEHASH",
+            OpcodeId::RETURNDATASIZE => "RETURNDATASIZE",
+            OpcodeId::RETURNDATACOPY => "RETURNDATACOPY",
+            OpcodeId::BLOCKHASH => "BLOCKHASH",
+            OpcodeId::COINBASE => "COINBASE",
+            OpcodeId::TIMESTAMP => "TIMESTAMP",
+            OpcodeId::NUMBER => "NUMBER",
+            OpcodeId::DIFFICULTY => "DIFFICULTY",
+            OpcodeId::GASLIMIT => "GASLIMIT",
+            OpcodeId::SLOAD => "SLOAD",
+            OpcodeId::SSTORE => "SSTORE",
+            OpcodeId::GAS => "GAS",
+            OpcodeId::LOG0 => "LOG0",
+            OpcodeId::LOG1 => "LOG1",
+            OpcodeId::LOG2 => "LOG2",
+            OpcodeId::LOG3 => "LOG3",
+            OpcodeId::LOG4 => "LOG4",
+            OpcodeId::CREATE => "CREATE",
+            OpcodeId::CREATE2 => "CREATE2",
+            OpcodeId::CALL => "CALL",
+            OpcodeId::CALLCODE => "CALLCODE",
+            OpcodeId::DELEGATECALL => "DELEGATECALL",
+            OpcodeId::STATICCALL => "STATICCALL",
+            OpcodeId::SELFDESTRUCT => "SELFDESTRUCT",
+            OpcodeId::CHAINID => "CHAINID",
+            OpcodeId::BASEFEE => "BASEFEE",
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for OpcodeId {
+    /// Formats the mnemonic of the opcode, or it's hex value if it's not a
+    /// known one.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "0x{:02x}", self.0),
+        }
+    }
 }
 
 impl FromStr for OpcodeId {
@@ -477,6 +887,7 @@ impl FromStr for OpcodeId {
             "STATICCALL" => OpcodeId::STATICCALL,
             "SELFDESTRUCT" => OpcodeId::SELFDESTRUCT,
             "CHAINID" => OpcodeId::CHAINID,
+            "BASEFEE" => OpcodeId::BASEFEE,
             _ => return Err(Error::OpcodeParsing),
         })
     }
@@ -0,0 +1,133 @@
+use super::{
+    gen_memory_op, gen_stack_reads, memory_bytes_before, memory_region, Opcode,
+};
+use crate::{
+    evm::OpcodeId,
+    exec_trace::{ExecutionStep, StepSnapshots, TraceState},
+    operation::{container::OperationContainer, RW},
+};
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `LOG0` to `LOG4` [`Instruction`](crate::evm::instruction::Instruction)s,
+/// which differ in the number of topics of the log they emit.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Log;
+
+impl Opcode for Log {
+    /// Reads `offset, size` and the topics from the stack and the data of the
+    /// log from the memory, and emits the log through
+    /// [`TraceState::gen_log`]. A step that can't pay for reading the data
+    /// halts exceptionally without emitting it.
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let topics = (exec_step.instruction().opcode_id().as_u8()
+            - OpcodeId::LOG0.as_u8()) as usize;
+        let operands =
+            gen_stack_reads(state, snapshots, 2 + topics, exec_step, container);
+        let (offset, data) =
+            match memory_region(exec_step, &operands[0], &operands[1]).map(
+                |(offset, size)| {
+                    (
+                        offset,
+                        memory_bytes_before(snapshots, exec_step, offset, size),
+                    )
+                },
+            ) {
+                Some(region) => region,
+                None => return exec_step.bus_mapping_instance().len(),
+            };
+
+        let call_id = state.call().call_id();
+        data.iter().enumerate().for_each(|(idx, byte)| {
+            gen_memory_op(
+                exec_step,
+                container,
+                RW::READ,
+                call_id,
+                offset + idx,
+                *byte,
+            )
+        });
+        state.gen_log(exec_step, container, &operands[2..], &data);
+
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+#[cfg(test)]
+mod log_tests {
+    use crate::{
+        evm::{create_address, EvmWord},
+        exec_trace::ExecutionTrace,
+        operation::LogField,
+        test_util,
+    };
+
+    #[test]
+    fn reverted_logs_are_removed() {
+        // The root call emits a log with topic 0xaa and creates a contract
+        // whose init code emits a log and reverts, so the log emitted by the
+        // root call afterwards takes the id of the reverted one.
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["aa"], "opcode": "PUSH1 aa", "pc": 0 },
+            { "memory": {}, "stack": ["aa", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": ["aa", "0", "0"], "opcode": "PUSH1 00", "pc": 4 },
+            { "memory": {}, "stack": [], "opcode": "LOG1", "pc": 6 },
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 7 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 9 },
+            { "memory": {}, "stack": ["0", "0", "0"], "opcode": "PUSH1 00", "pc": 11 },
+            { "memory": {}, "stack": ["0"], "opcode": "CREATE", "pc": 13 },
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0, "depth": 2 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 2, "depth": 2 },
+            { "memory": {}, "stack": [], "opcode": "LOG0", "pc": 4, "depth": 2 },
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 5, "depth": 2 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 7, "depth": 2 },
+            { "memory": {}, "stack": [], "opcode": "REVERT", "pc": 9, "depth": 2 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 14 },
+            { "memory": {}, "stack": [], "opcode": "LOG0", "pc": 16 },
+            { "memory": {}, "stack": [], "opcode": "STOP", "pc": 17 }
+        ]
+        "#;
+
+        let block_ctants = test_util::block_ctants();
+        let contract = test_util::address("aa");
+        let tx_ctx = test_util::tx_ctx(Some(contract), vec![]);
+
+        let trace = ExecutionTrace::from_trace_bytes_with_tx(
+            input_trace.as_bytes(),
+            block_ctants,
+            tx_ctx,
+        )
+        .expect("Error on trace generation");
+        let created = create_address(&contract, 0);
+
+        let log_ops: Vec<(usize, LogField, EvmWord, EvmWord)> = trace
+            .sorted_log_ops()
+            .map(|op| {
+                (
+                    op.log_id(),
+                    op.field(),
+                    op.value().clone(),
+                    op.value_prev().clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            log_ops,
+            vec![
+                (1, LogField::Address, contract.to_word(), 0u8.into()),
+                (1, LogField::Topic, 0xaau8.into(), 0u8.into()),
+                // Emitted by the init code and removed once it reverts.
+                (2, LogField::Address, created.to_word(), 0u8.into()),
+                (2, LogField::Address, 0u8.into(), created.to_word()),
+                (2, LogField::Address, contract.to_word(), 0u8.into()),
+            ]
+        );
+    }
+}
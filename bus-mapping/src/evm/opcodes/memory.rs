@@ -0,0 +1,248 @@
+//! Opcodes that load words from memory or store them into it.
+use super::{
+    gen_memory_op, gen_stack_reads, memory_bytes_before, memory_region,
+    stack_result, Opcode,
+};
+use crate::{
+    evm::EvmWord,
+    exec_trace::{ExecutionStep, StepSnapshots, TraceState},
+    operation::{container::OperationContainer, StackOp, RW},
+};
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `MLOAD` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Mload;
+
+impl Opcode for Mload {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let offset = gen_stack_reads(state, snapshots, 1, exec_step, container)
+            .remove(0);
+
+        // A step that can't pay for expanding the memory up to the loaded
+        // word halts exceptionally without reading it.
+        let (offset, bytes) =
+            match memory_region(exec_step, &offset, &EvmWord::from(32u8)).map(
+                |(offset, size)| {
+                    let bytes =
+                        memory_bytes_before(snapshots, exec_step, offset, size);
+                    (offset, bytes)
+                },
+            ) {
+                Some(region) => region,
+                None => return exec_step.bus_mapping_instance().len(),
+            };
+        let call_id = state.call().call_id();
+        for (idx, byte) in bytes.iter().enumerate() {
+            gen_memory_op(
+                exec_step,
+                container,
+                RW::READ,
+                call_id,
+                offset + idx,
+                *byte,
+            );
+        }
+
+        if let Some((address, value)) = stack_result(snapshots, exec_step) {
+            exec_step.add_op(
+                container,
+                StackOp::new(
+                    RW::WRITE,
+                    exec_step.next_op_gc(),
+                    call_id,
+                    address,
+                    value,
+                ),
+            );
+        }
+
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `MSTORE` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Mstore;
+
+impl Opcode for Mstore {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let operands =
+            gen_stack_reads(state, snapshots, 2, exec_step, container);
+        gen_store_ops(
+            state,
+            &operands[0],
+            &operands[1].to_be_bytes(),
+            exec_step,
+            container,
+        )
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `MSTORE8` [`Instruction`](crate::evm::instruction::Instruction), which
+/// only stores the least significant byte of it's value.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Mstore8;
+
+impl Opcode for Mstore8 {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let operands =
+            gen_stack_reads(state, snapshots, 2, exec_step, container);
+        gen_store_ops(
+            state,
+            &operands[0],
+            &operands[1].to_be_bytes()[31..],
+            exec_step,
+            container,
+        )
+    }
+}
+
+/// Generates the [`MemoryOp`](crate::operation::MemoryOp) writes of the
+/// `bytes` stored at `offset` by `exec_step`, unless it can't pay for
+/// expanding the memory up to them, in which case it halts exceptionally
+/// without storing them.
+fn gen_store_ops(
+    state: &TraceState,
+    offset: &EvmWord,
+    bytes: &[u8],
+    exec_step: &mut ExecutionStep,
+    container: &mut OperationContainer,
+) -> usize {
+    if let Some((offset, _)) =
+        memory_region(exec_step, offset, &EvmWord::from(bytes.len()))
+    {
+        let call_id = state.call().call_id();
+        for (idx, byte) in bytes.iter().enumerate() {
+            gen_memory_op(
+                exec_step,
+                container,
+                RW::WRITE,
+                call_id,
+                offset + idx,
+                *byte,
+            );
+        }
+    }
+
+    exec_step.bus_mapping_instance().len()
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use crate::{
+        evm::{EvmWord, MemoryAddress, OpcodeId, StackAddress},
+        exec_trace::ExecutionTrace,
+        operation::RW,
+        test_util,
+    };
+
+    #[test]
+    fn stores_and_loads_are_byte_ops() {
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["1234"], "opcode": "PUSH2 1234", "pc": 0 },
+            { "memory": {}, "stack": ["1234", "20"], "opcode": "PUSH1 20", "pc": 3 },
+            { "memory": { "0": "0000000000000000000000000000000000000000000000000000000000000000", "20": "0000000000000000000000000000000000000000000000000000000000001234" }, "stack": [], "opcode": "MSTORE", "pc": 5 },
+            { "memory": { "0": "0000000000000000000000000000000000000000000000000000000000000000", "20": "0000000000000000000000000000000000000000000000000000000000001234" }, "stack": ["ff"], "opcode": "PUSH1 ff", "pc": 6 },
+            { "memory": { "0": "0000000000000000000000000000000000000000000000000000000000000000", "20": "0000000000000000000000000000000000000000000000000000000000001234" }, "stack": ["ff", "0"], "opcode": "PUSH1 00", "pc": 8 },
+            { "memory": { "0": "ff00000000000000000000000000000000000000000000000000000000000000", "20": "0000000000000000000000000000000000000000000000000000000000001234" }, "stack": [], "opcode": "MSTORE8", "pc": 10 },
+            { "memory": { "0": "ff00000000000000000000000000000000000000000000000000000000000000", "20": "0000000000000000000000000000000000000000000000000000000000001234" }, "stack": ["20"], "opcode": "PUSH1 20", "pc": 11 },
+            { "memory": { "0": "ff00000000000000000000000000000000000000000000000000000000000000", "20": "0000000000000000000000000000000000000000000000000000000000001234" }, "stack": ["1234"], "opcode": "MLOAD", "pc": 13 },
+            { "memory": { "0": "ff00000000000000000000000000000000000000000000000000000000000000", "20": "0000000000000000000000000000000000000000000000000000000000001234" }, "stack": ["1234", "1234"], "opcode": "DUP1", "pc": 14 },
+            { "memory": { "0": "ff00000000000000000000000000000000000000000000000000000000000000", "20": "0000000000000000000000000000000000000000000000000000000000001234" }, "stack": ["2468"], "opcode": "ADD", "pc": 15 },
+            { "memory": { "0": "ff00000000000000000000000000000000000000000000000000000000000000", "20": "0000000000000000000000000000000000000000000000000000000000001234" }, "stack": ["2468"], "opcode": "STOP", "pc": 16 }
+        ]
+        "#;
+        let block_ctants = test_util::block_ctants();
+        let trace = ExecutionTrace::from_trace_bytes(
+            input_trace.as_bytes(),
+            block_ctants,
+        )
+        .expect("Error on trace generation");
+        assert_eq!(trace.state_violations(), vec![]);
+
+        // `MSTORE` writes the 32 bytes of it's value, `MSTORE8` a single
+        // byte and `MLOAD` reads back the word stored by `MSTORE`.
+        let memory_ops = |step: usize, rw: RW| {
+            trace
+                .sorted_memory_ops()
+                .filter(|op| {
+                    op.rw() == rw
+                        && trace.origin_of(*op).map(|origin| origin.step())
+                            == Some(step)
+                })
+                .map(|op| (op.address().clone(), op.value().clone()))
+                .collect::<Vec<(MemoryAddress, EvmWord)>>()
+        };
+        let mstore = memory_ops(2, RW::WRITE);
+        assert_eq!(mstore.len(), 32);
+        assert_eq!(mstore[30], (MemoryAddress::from(62), 0x12u8.into()));
+        assert_eq!(mstore[31], (MemoryAddress::from(63), 0x34u8.into()));
+        assert_eq!(
+            memory_ops(5, RW::WRITE),
+            vec![(MemoryAddress::from(0), 0xffu8.into())]
+        );
+        assert_eq!(memory_ops(7, RW::READ), mstore);
+
+        // `DUP1` only reads the word it duplicates.
+        let stack_ops: Vec<(OpcodeId, RW, StackAddress)> = trace
+            .sorted_stack_ops()
+            .filter(|op| op.gc() > trace[8].gc())
+            .map(|op| {
+                let origin = trace.origin_of(op).expect("op has an origin");
+                (origin.opcode(), op.rw(), *op.address())
+            })
+            .collect();
+        assert_eq!(
+            stack_ops,
+            vec![
+                (OpcodeId::DUP1, RW::WRITE, StackAddress::from(1022)),
+                (OpcodeId::ADD, RW::READ, StackAddress::from(1022)),
+                (OpcodeId::DUP1, RW::READ, StackAddress::from(1023)),
+                (OpcodeId::ADD, RW::READ, StackAddress::from(1023)),
+                (OpcodeId::ADD, RW::WRITE, StackAddress::from(1023)),
+            ]
+        );
+    }
+
+    #[test]
+    fn steps_without_gas_left_access_no_memory() {
+        // The `MSTORE` has no gas left to expand the memory, so it halts
+        // exceptionally without storing it's value.
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0, "gas": 6, "gasCost": 3 },
+            { "memory": {}, "stack": ["1", "0"], "opcode": "PUSH1 00", "pc": 2, "gas": 3, "gasCost": 3 },
+            { "memory": {}, "stack": ["1", "0"], "opcode": "MSTORE", "pc": 4, "gas": 0, "gasCost": 6 }
+        ]
+        "#;
+        let block_ctants = test_util::block_ctants();
+        let trace = ExecutionTrace::from_trace_bytes(
+            input_trace.as_bytes(),
+            block_ctants,
+        )
+        .expect("Error on trace generation");
+        assert_eq!(trace.sorted_memory_ops().count(), 0);
+    }
+}
@@ -1,39 +1,33 @@
-// Port this to a macro if possible to avoid defining all the PushN
-use super::Opcode;
+use super::{stack_result, Opcode};
 use crate::{
-    evm::GlobalCounter,
-    exec_trace::ExecutionStep,
+    exec_trace::{ExecutionStep, StepSnapshots, TraceState},
     operation::{container::OperationContainer, StackOp, RW},
 };
 
-/// Number of ops that PUSH1 adds to the container & busmapping
-const PUSH1_OP_NUM: usize = 1;
-
 /// Structure used to implement [`Opcode`] trait over it corresponding to the
-/// `PUSH1 X` [`Instruction`](crate::evm::instruction::Instruction).
+/// `PUSH1 X..PUSH32 X` [`Instruction`](crate::evm::instruction::Instruction)s.
 #[derive(Debug, Copy, Clone)]
-pub(crate) struct Push1;
+pub(crate) struct Push;
 
-impl Opcode for Push1 {
+impl Opcode for Push {
     fn gen_associated_ops(
         &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
         exec_step: &mut ExecutionStep,
         container: &mut OperationContainer,
     ) -> usize {
-        let op = StackOp::new(
-            RW::WRITE,
-            GlobalCounter::from(exec_step.gc().0 + 1),
-            exec_step.stack_addr(),
-            // TODO: This could be more robust.
-            // We should have a way to collect this addr (the parsed one which
-            // is not tied to 1024)
-            exec_step.stack()[exec_step.stack().len() - 1].clone(),
-        );
-
-        exec_step
-            .bus_mapping_instance_mut()
-            .push(container.insert(op));
+        if let Some((address, value)) = stack_result(snapshots, exec_step) {
+            let op = StackOp::new(
+                RW::WRITE,
+                exec_step.next_op_gc(),
+                state.call().call_id(),
+                address,
+                value,
+            );
+            exec_step.add_op(container, op);
+        }
 
-        PUSH1_OP_NUM
+        exec_step.bus_mapping_instance().len()
     }
 }
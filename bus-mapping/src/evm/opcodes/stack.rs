@@ -0,0 +1,192 @@
+//! Opcodes whose only effects are over the stack.
+use super::{gen_stack_reads, stack_result, Opcode};
+use crate::{
+    evm::{EvmWord, StackAddress},
+    exec_trace::{ExecutionStep, StepSnapshots, TraceState},
+    operation::{container::OperationContainer, StackOp, RW},
+};
+
+/// Structure used to implement [`Opcode`] trait over the
+/// [`Instruction`](crate::evm::instruction::Instruction)s that only read
+/// their operands from the stack and push their result to it (if any), like
+/// the arithmetic, comparison and bitwise ones.
+///
+/// The number of operands and results of each instruction are given by
+/// [`OpcodeId::stack_inputs`](super::OpcodeId::stack_inputs) and
+/// [`OpcodeId::stack_outputs`](super::OpcodeId::stack_outputs).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct StackOnly;
+
+impl Opcode for StackOnly {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let opcode = exec_step.instruction().opcode_id();
+        gen_stack_reads(
+            state,
+            snapshots,
+            opcode.stack_inputs(),
+            exec_step,
+            container,
+        );
+
+        if let Some((address, value)) = stack_result(snapshots, exec_step)
+            .filter(|_| opcode.stack_outputs() > 0)
+        {
+            gen_stack_op(
+                state,
+                exec_step,
+                container,
+                RW::WRITE,
+                address,
+                value,
+            );
+        }
+
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `DUP1..DUP16` [`Instruction`](crate::evm::instruction::Instruction)s.
+///
+/// Only the duplicated word is read, and it's copy is written on top of the
+/// stack.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Dup;
+
+impl Opcode for Dup {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        // `DUPn` duplicates the n-th word counting from the top.
+        let n = exec_step.instruction().opcode_id().stack_inputs();
+        let top = stack_top(snapshots, exec_step);
+        // A step that underflows the stack halts exceptionally.
+        let value = match stack_word(snapshots, exec_step, n - 1) {
+            Some(value) => value,
+            None => return exec_step.bus_mapping_instance().len(),
+        };
+        gen_stack_op(
+            state,
+            exec_step,
+            container,
+            RW::READ,
+            (top + n - 1).into(),
+            value.clone(),
+        );
+        // Neither can it push the copy to a full stack.
+        if let Some(address) = top.checked_sub(1) {
+            gen_stack_op(
+                state,
+                exec_step,
+                container,
+                RW::WRITE,
+                address.into(),
+                value,
+            );
+        }
+
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `SWAP1..SWAP16` [`Instruction`](crate::evm::instruction::Instruction)s.
+///
+/// The top of the stack and the word it's swapped with are read, and then
+/// written into each other's position.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Swap;
+
+impl Opcode for Swap {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        // `SWAPn` swaps the top with the (n+1)-th word counting from the top.
+        let n = exec_step.instruction().opcode_id().stack_inputs() - 1;
+        let top = stack_top(snapshots, exec_step);
+        // A step that underflows the stack halts exceptionally.
+        let (a, b) = match (
+            stack_word(snapshots, exec_step, 0),
+            stack_word(snapshots, exec_step, n),
+        ) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return exec_step.bus_mapping_instance().len(),
+        };
+        for (rw, idx, value) in [
+            (RW::READ, 0, &a),
+            (RW::READ, n, &b),
+            (RW::WRITE, 0, &b),
+            (RW::WRITE, n, &a),
+        ] {
+            gen_stack_op(
+                state,
+                exec_step,
+                container,
+                rw,
+                (top + idx).into(),
+                value.clone(),
+            );
+        }
+
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+/// Returns the [`StackAddress`] of the top of the stack before the
+/// instruction of `exec_step` runs, as a `usize`.
+fn stack_top(snapshots: StepSnapshots<'_>, exec_step: &ExecutionStep) -> usize {
+    usize::from(snapshots.before(exec_step).stack_addr())
+}
+
+/// Returns the word placed `idx` words below the top of the stack before the
+/// instruction of `exec_step` runs, or `None` if the stack holds less words.
+fn stack_word(
+    snapshots: StepSnapshots<'_>,
+    exec_step: &ExecutionStep,
+    idx: usize,
+) -> Option<EvmWord> {
+    snapshots
+        .before(exec_step)
+        .stack()
+        .iter()
+        .rev()
+        .nth(idx)
+        .cloned()
+}
+
+/// Generates the [`StackOp`] that reads or writes `value` at `address` of the
+/// stack of the current call, and adds it to the bus-mapping instance of
+/// `exec_step`.
+fn gen_stack_op(
+    state: &TraceState,
+    exec_step: &mut ExecutionStep,
+    container: &mut OperationContainer,
+    rw: RW,
+    address: StackAddress,
+    value: EvmWord,
+) {
+    exec_step.add_op(
+        container,
+        StackOp::new(
+            rw,
+            exec_step.next_op_gc(),
+            state.call().call_id(),
+            address,
+            value,
+        ),
+    );
+}
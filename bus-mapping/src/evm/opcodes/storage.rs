@@ -0,0 +1,92 @@
+use super::{gen_stack_reads, stack_result, Opcode};
+use crate::{
+    exec_trace::{ExecutionStep, StepSnapshots, TraceState},
+    operation::{container::OperationContainer, StackOp, StorageOp, RW},
+};
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `SLOAD` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Sload;
+
+impl Opcode for Sload {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let key = gen_stack_reads(state, snapshots, 1, exec_step, container)
+            .remove(0);
+
+        // The stack after this step holds the loaded value, which seeds the
+        // state when the slot wasn't known yet. Without it the slot can only
+        // be read if it's known, and the value isn't pushed.
+        let address = *state.call().address();
+        let result = stack_result(snapshots, exec_step);
+        let value = match (state.state_db().storage(&address, &key), &result) {
+            (Some(value), _) => value.clone(),
+            (None, Some((_, value))) => {
+                state.state_db_mut().set_storage(
+                    &address,
+                    key.clone(),
+                    value.clone(),
+                );
+                value.clone()
+            }
+            (None, None) => return exec_step.bus_mapping_instance().len(),
+        };
+
+        exec_step.add_op(
+            container,
+            StorageOp::new(
+                RW::READ,
+                exec_step.next_op_gc(),
+                address,
+                key,
+                value.clone(),
+                value.clone(),
+            ),
+        );
+        if let Some((stack_addr, _)) = result {
+            exec_step.add_op(
+                container,
+                StackOp::new(
+                    RW::WRITE,
+                    exec_step.next_op_gc(),
+                    state.call().call_id(),
+                    stack_addr,
+                    value,
+                ),
+            );
+        }
+
+        exec_step.bus_mapping_instance().len()
+    }
+}
+
+/// Structure used to implement [`Opcode`] trait over it corresponding to the
+/// `SSTORE` [`Instruction`](crate::evm::instruction::Instruction).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Sstore;
+
+impl Opcode for Sstore {
+    fn gen_associated_ops(
+        &self,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) -> usize {
+        let mut operands =
+            gen_stack_reads(state, snapshots, 2, exec_step, container);
+        let value = operands.remove(1);
+        let key = operands.remove(0);
+
+        let address = *state.call().address();
+        state.gen_storage_write(exec_step, container, address, key, value);
+
+        exec_step.bus_mapping_instance().len()
+    }
+}
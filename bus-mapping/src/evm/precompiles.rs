@@ -0,0 +1,674 @@
+//! Native implementation of the precompiled contracts of the EVM, which are
+//! called as any other account but don't run bytecode, so execution traces
+//! don't hold any step for them.
+
+use super::{gas::DEFAULT_GAS_LIMIT, keccak256, Address, GasCost, Hardfork};
+use bn::{arith::U256, AffineG1, AffineG2, Fq, Fq2, Fr, Group, Gt, G1, G2};
+use core::convert::TryFrom;
+use num::{BigUint, One, Zero};
+use ripemd160::Ripemd160;
+use sha2::{Digest, Sha256};
+
+/// Precompiled contracts of the EVM, located at the addresses `0x01` to
+/// `0x09`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Precompile {
+    /// `0x01`: Recovery of the signer address of an ECDSA signature.
+    EcRecover,
+    /// `0x02`: SHA2-256 hash function.
+    Sha256,
+    /// `0x03`: RIPEMD-160 hash function.
+    Ripemd160,
+    /// `0x04`: Identity function.
+    Identity,
+    /// `0x05`: Modular exponentiation
+    /// ([EIP-198](https://eips.ethereum.org/EIPS/eip-198)).
+    ModExp,
+    /// `0x06`: Point addition on the alt_bn128 curve
+    /// ([EIP-196](https://eips.ethereum.org/EIPS/eip-196)).
+    Bn256Add,
+    /// `0x07`: Scalar multiplication on the alt_bn128 curve
+    /// ([EIP-196](https://eips.ethereum.org/EIPS/eip-196)).
+    Bn256ScalarMul,
+    /// `0x08`: Pairing check on the alt_bn128 curve
+    /// ([EIP-197](https://eips.ethereum.org/EIPS/eip-197)).
+    Bn256Pairing,
+    /// `0x09`: Compression function `F` of BLAKE2b
+    /// ([EIP-152](https://eips.ethereum.org/EIPS/eip-152)).
+    Blake2F,
+}
+
+/// Error returned when the input of a [`Precompile`] is invalid, which makes
+/// the call fail consuming all of the gas it was given.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PrecompileError;
+
+impl Precompile {
+    /// Returns the `Precompile` located at `address` (if any).
+    pub fn from_address(address: &Address) -> Option<Precompile> {
+        let bytes = address.as_bytes();
+        if bytes[..19].iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        Some(match bytes[19] {
+            0x01 => Precompile::EcRecover,
+            0x02 => Precompile::Sha256,
+            0x03 => Precompile::Ripemd160,
+            0x04 => Precompile::Identity,
+            0x05 => Precompile::ModExp,
+            0x06 => Precompile::Bn256Add,
+            0x07 => Precompile::Bn256ScalarMul,
+            0x08 => Precompile::Bn256Pairing,
+            0x09 => Precompile::Blake2F,
+            _ => return None,
+        })
+    }
+
+    /// Returns the [`Address`] the `Precompile` is located at.
+    pub fn address(&self) -> Address {
+        let mut address = [0u8; 20];
+        address[19] = *self as u8 + 1;
+        Address::from(address)
+    }
+
+    /// Returns the gas consumed by a call to the `Precompile` with `input`
+    /// at `hardfork`.
+    pub fn gas_cost(&self, input: &[u8], hardfork: Hardfork) -> GasCost {
+        // The alt_bn128 precompiles were repriced by EIP-1108.
+        let istanbul = hardfork >= Hardfork::Istanbul;
+        let words = (input.len() as u64 + 31) / 32;
+        GasCost(match self {
+            Precompile::EcRecover => 3000,
+            Precompile::Sha256 => 60 + 12 * words,
+            Precompile::Ripemd160 => 600 + 120 * words,
+            Precompile::Identity => 15 + 3 * words,
+            Precompile::ModExp => modexp_gas_cost(input, hardfork),
+            Precompile::Bn256Add if istanbul => 150,
+            Precompile::Bn256Add => 500,
+            Precompile::Bn256ScalarMul if istanbul => 6000,
+            Precompile::Bn256ScalarMul => 40000,
+            Precompile::Bn256Pairing if istanbul => {
+                45000 + 34000 * (input.len() as u64 / 192)
+            }
+            Precompile::Bn256Pairing => {
+                100000 + 80000 * (input.len() as u64 / 192)
+            }
+            Precompile::Blake2F => {
+                if input.len() < 4 {
+                    0
+                } else {
+                    u32::from_be_bytes([input[0], input[1], input[2], input[3]])
+                        as u64
+                }
+            }
+        })
+    }
+
+    /// Executes the `Precompile` with `input`, returning it's output.
+    pub fn execute(&self, input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+        match self {
+            Precompile::EcRecover => Ok(ecrecover(input)),
+            Precompile::Sha256 => Ok(Sha256::digest(input).to_vec()),
+            Precompile::Ripemd160 => {
+                let mut output = vec![0u8; 12];
+                output.extend_from_slice(&Ripemd160::digest(input));
+                Ok(output)
+            }
+            Precompile::Identity => Ok(input.to_vec()),
+            Precompile::ModExp => modexp(input),
+            Precompile::Bn256Add => bn256_add(input),
+            Precompile::Bn256ScalarMul => bn256_scalar_mul(input),
+            Precompile::Bn256Pairing => bn256_pairing(input),
+            Precompile::Blake2F => blake2f(input),
+        }
+    }
+}
+
+/// Witness of a call to a [`Precompile`]: it's input and output together with
+/// the gas it consumed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PrecompileCall {
+    call_id: usize,
+    precompile: Precompile,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    gas_cost: GasCost,
+    success: bool,
+}
+
+impl PrecompileCall {
+    /// Generate a new `PrecompileCall` instance from it's fields.
+    pub fn new(
+        call_id: usize,
+        precompile: Precompile,
+        input: Vec<u8>,
+        output: Vec<u8>,
+        gas_cost: GasCost,
+        success: bool,
+    ) -> PrecompileCall {
+        PrecompileCall {
+            call_id,
+            precompile,
+            input,
+            output,
+            gas_cost,
+            success,
+        }
+    }
+
+    #[inline]
+    /// Return the `call_id` of the call frame opened for the precompile.
+    pub const fn call_id(&self) -> usize {
+        self.call_id
+    }
+
+    #[inline]
+    /// Return the [`Precompile`] called.
+    pub const fn precompile(&self) -> Precompile {
+        self.precompile
+    }
+
+    #[inline]
+    /// Return the input the precompile was called with.
+    pub fn input(&self) -> &[u8] {
+        &self.input
+    }
+
+    #[inline]
+    /// Return the output of the precompile (empty if the call failed).
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    #[inline]
+    /// Return the gas consumed by the precompile.
+    pub const fn gas_cost(&self) -> GasCost {
+        self.gas_cost
+    }
+
+    #[inline]
+    /// Return true if the call succeeded.
+    pub const fn success(&self) -> bool {
+        self.success
+    }
+}
+
+/// Returns `len` bytes of `input` starting at `offset`, padded with zeros
+/// past it's end.
+fn padded(input: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    if offset < input.len() {
+        let end = input.len().min(offset.saturating_add(len));
+        bytes[..end - offset].copy_from_slice(&input[offset..end]);
+    }
+    bytes
+}
+
+fn ecrecover(input: &[u8]) -> Vec<u8> {
+    let input = padded(input, 0, 128);
+    // `v` must be 27 or 28, with all of the upper bytes set to zero.
+    if input[32..63].iter().any(|byte| *byte != 0)
+        || !(input[63] == 27 || input[63] == 28)
+    {
+        return vec![];
+    }
+    let recover = || -> Result<Vec<u8>, libsecp256k1::Error> {
+        let message = libsecp256k1::Message::parse_slice(&input[..32])?;
+        let signature =
+            libsecp256k1::Signature::parse_standard_slice(&input[64..128])?;
+        let recovery_id = libsecp256k1::RecoveryId::parse(input[63] - 27)?;
+        let public_key =
+            libsecp256k1::recover(&message, &signature, &recovery_id)?;
+        let mut output = vec![0u8; 12];
+        output
+            .extend_from_slice(&keccak256(&public_key.serialize()[1..])[12..]);
+        Ok(output)
+    };
+    recover().unwrap_or_default()
+}
+
+/// Returns the lengths of the base, exponent and modulus of a `ModExp`
+/// input, saturated to `u64::MAX`.
+fn modexp_lengths(input: &[u8]) -> (u64, u64, u64) {
+    let length = |idx: usize| {
+        let bytes = padded(input, idx * 32, 32);
+        if bytes[..24].iter().any(|byte| *byte != 0) {
+            u64::MAX
+        } else {
+            let mut low = [0u8; 8];
+            low.copy_from_slice(&bytes[24..]);
+            u64::from_be_bytes(low)
+        }
+    };
+    (length(0), length(1), length(2))
+}
+
+/// Gas cost of `ModExp` at `hardfork`, as defined by
+/// [EIP-198](https://eips.ethereum.org/EIPS/eip-198) and repriced by
+/// [EIP-2565](https://eips.ethereum.org/EIPS/eip-2565) at Berlin.
+fn modexp_gas_cost(input: &[u8], hardfork: Hardfork) -> u64 {
+    let (base_len, exp_len, mod_len) = modexp_lengths(input);
+    let max_len = base_len.max(mod_len);
+
+    // Only the first 32 bytes of the exponent count for the bit length.
+    let exp_head = BigUint::from_bytes_be(&padded(
+        input,
+        96usize.saturating_add(base_len.min(usize::MAX as u64) as usize),
+        exp_len.min(32) as usize,
+    ));
+    let exp_head_bits = exp_head.bits().saturating_sub(1);
+    let iteration_count = if exp_len <= 32 {
+        exp_head_bits
+    } else {
+        (exp_len - 32)
+            .saturating_mul(8)
+            .saturating_add(exp_head_bits)
+    }
+    .max(1);
+
+    if hardfork >= Hardfork::Berlin {
+        let words = max_len.saturating_add(7) / 8;
+        let multiplication_complexity = words.saturating_mul(words);
+        (multiplication_complexity.saturating_mul(iteration_count) / 3).max(200)
+    } else {
+        let square = max_len.saturating_mul(max_len);
+        let multiplication_complexity = if max_len <= 64 {
+            square
+        } else if max_len <= 1024 {
+            (square / 4)
+                .saturating_add(96 * max_len)
+                .saturating_sub(3072)
+        } else {
+            (square / 16)
+                .saturating_add(max_len.saturating_mul(480))
+                .saturating_sub(199680)
+        };
+        multiplication_complexity.saturating_mul(iteration_count) / 20
+    }
+}
+
+/// Computes `base^exponent mod modulus` for the operands of a `ModExp` input.
+///
+/// Inputs that cost more than the gas of a whole block (see
+/// [`DEFAULT_GAS_LIMIT`]) at the cheapest pricing can't be paid for by any
+/// call, so they fail without allocating their operands.
+fn modexp(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    if modexp_gas_cost(input, Hardfork::Berlin) > DEFAULT_GAS_LIMIT {
+        return Err(PrecompileError);
+    }
+    let (base_len, exp_len, mod_len) = modexp_lengths(input);
+    let length = |len: u64| usize::try_from(len).map_err(|_| PrecompileError);
+    let (base_len, exp_len, mod_len) =
+        (length(base_len)?, length(exp_len)?, length(mod_len)?);
+    let exp_offset = 96usize.checked_add(base_len).ok_or(PrecompileError)?;
+    let mod_offset = exp_offset.checked_add(exp_len).ok_or(PrecompileError)?;
+    let base = BigUint::from_bytes_be(&padded(input, 96, base_len));
+    let exponent = BigUint::from_bytes_be(&padded(input, exp_offset, exp_len));
+    let modulus = BigUint::from_bytes_be(&padded(input, mod_offset, mod_len));
+
+    let result = if modulus.is_zero() || modulus.is_one() {
+        BigUint::zero()
+    } else {
+        base.modpow(&exponent, &modulus)
+    };
+    let result_bytes = result.to_bytes_be();
+    let mut output = vec![0u8; mod_len];
+    if !result.is_zero() {
+        output[mod_len - result_bytes.len()..].copy_from_slice(&result_bytes);
+    }
+    Ok(output)
+}
+
+fn read_fq(bytes: &[u8]) -> Result<Fq, PrecompileError> {
+    Fq::from_slice(bytes).map_err(|_| PrecompileError)
+}
+
+fn read_g1(bytes: &[u8]) -> Result<G1, PrecompileError> {
+    let (x, y) = (read_fq(&bytes[..32])?, read_fq(&bytes[32..64])?);
+    if x.is_zero() && y.is_zero() {
+        Ok(G1::zero())
+    } else {
+        AffineG1::new(x, y)
+            .map(G1::from)
+            .map_err(|_| PrecompileError)
+    }
+}
+
+fn read_g2(bytes: &[u8]) -> Result<G2, PrecompileError> {
+    // Coordinates are encoded as `(imaginary, real)`.
+    let x = Fq2::new(read_fq(&bytes[32..64])?, read_fq(&bytes[..32])?);
+    let y = Fq2::new(read_fq(&bytes[96..128])?, read_fq(&bytes[64..96])?);
+    if x.is_zero() && y.is_zero() {
+        Ok(G2::zero())
+    } else {
+        AffineG2::new(x, y)
+            .map(G2::from)
+            .map_err(|_| PrecompileError)
+    }
+}
+
+fn write_g1(point: G1) -> Vec<u8> {
+    let mut output = vec![0u8; 64];
+    if let Some(point) = AffineG1::from_jacobian(point) {
+        point
+            .x()
+            .to_big_endian(&mut output[..32])
+            .expect("slice has 32 bytes");
+        point
+            .y()
+            .to_big_endian(&mut output[32..])
+            .expect("slice has 32 bytes");
+    }
+    output
+}
+
+fn bn256_add(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    let input = padded(input, 0, 128);
+    Ok(write_g1(read_g1(&input[..64])? + read_g1(&input[64..])?))
+}
+
+fn bn256_scalar_mul(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    let input = padded(input, 0, 96);
+    let scalar = Fr::new_mul_factor(
+        U256::from_slice(&input[64..]).map_err(|_| PrecompileError)?,
+    );
+    Ok(write_g1(read_g1(&input[..64])? * scalar))
+}
+
+fn bn256_pairing(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    if input.len() % 192 != 0 {
+        return Err(PrecompileError);
+    }
+    let pairs = input
+        .chunks(192)
+        .map(|pair| Ok((read_g1(&pair[..64])?, read_g2(&pair[64..])?)))
+        .collect::<Result<Vec<(G1, G2)>, PrecompileError>>()?;
+
+    let mut output = vec![0u8; 32];
+    if bn::pairing_batch(&pairs) == Gt::one() {
+        output[31] = 1;
+    }
+    Ok(output)
+}
+
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const BLAKE2B_SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// Compression function `F` of BLAKE2b running `rounds` rounds over the
+/// state `h` with the message block `m`, the offset counters `t` and the
+/// final block flag `f`.
+fn blake2b_compress(
+    rounds: u32,
+    h: &mut [u64; 8],
+    m: &[u64; 16],
+    t: [u64; 2],
+    f: bool,
+) {
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&BLAKE2B_IV);
+    v[12] ^= t[0];
+    v[13] ^= t[1];
+    if f {
+        v[14] = !v[14];
+    }
+
+    let mix = |v: &mut [u64; 16],
+               a: usize,
+               b: usize,
+               c: usize,
+               d: usize,
+               x: u64,
+               y: u64| {
+        v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+        v[d] = (v[d] ^ v[a]).rotate_right(32);
+        v[c] = v[c].wrapping_add(v[d]);
+        v[b] = (v[b] ^ v[c]).rotate_right(24);
+        v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+        v[d] = (v[d] ^ v[a]).rotate_right(16);
+        v[c] = v[c].wrapping_add(v[d]);
+        v[b] = (v[b] ^ v[c]).rotate_right(63);
+    };
+    for round in 0..rounds as usize {
+        let s = &BLAKE2B_SIGMA[round % 10];
+        mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+
+    for idx in 0..8 {
+        h[idx] ^= v[idx] ^ v[idx + 8];
+    }
+}
+
+fn blake2f(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    if input.len() != 213 || input[212] > 1 {
+        return Err(PrecompileError);
+    }
+    let read_u64 = |offset: usize| {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&input[offset..offset + 8]);
+        u64::from_le_bytes(bytes)
+    };
+
+    let rounds = u32::from_be_bytes([input[0], input[1], input[2], input[3]]);
+    let mut h = [0u64; 8];
+    h.iter_mut()
+        .enumerate()
+        .for_each(|(idx, word)| *word = read_u64(4 + idx * 8));
+    let mut m = [0u64; 16];
+    m.iter_mut()
+        .enumerate()
+        .for_each(|(idx, word)| *word = read_u64(68 + idx * 8));
+    let t = [read_u64(196), read_u64(204)];
+
+    blake2b_compress(rounds, &mut h, &m, t, input[212] == 1);
+    Ok(h.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+#[cfg(test)]
+mod precompiles_tests {
+    use super::*;
+    use crate::test_util;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|idx| u8::from_str_radix(&s[idx..idx + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn precompile_addresses() {
+        for byte in 1..=9u8 {
+            let mut address = [0u8; 20];
+            address[19] = byte;
+            let precompile = Precompile::from_address(&Address::from(address))
+                .expect("precompile address");
+            assert_eq!(precompile.address(), Address::from(address));
+        }
+        assert_eq!(Precompile::from_address(&Address::zero()), None);
+        assert_eq!(Precompile::from_address(&test_util::address("0a")), None);
+    }
+
+    #[test]
+    fn hash_precompiles() {
+        assert_eq!(
+            Precompile::Sha256.execute(b"abc").unwrap(),
+            hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(
+            Precompile::Ripemd160.execute(b"abc").unwrap(),
+            hex("0000000000000000000000008eb208f7e05d987a9b044a8e98c6b087f15a0bfc")
+        );
+        assert_eq!(Precompile::Identity.execute(b"abc").unwrap(), b"abc");
+        assert_eq!(
+            Precompile::Sha256.gas_cost(b"abc", Hardfork::default()),
+            GasCost(72)
+        );
+        assert_eq!(
+            Precompile::Ripemd160.gas_cost(&[0; 33], Hardfork::default()),
+            GasCost(840)
+        );
+        assert_eq!(
+            Precompile::Identity.gas_cost(&[], Hardfork::default()),
+            GasCost(15)
+        );
+    }
+
+    #[test]
+    fn ecrecover_precompile() {
+        let input = hex(
+            "18c547e4f7b0f325ad1e56f57e26c745b09a3e503d86e00e5255ff7f715d3d1c\
+             000000000000000000000000000000000000000000000000000000000000001c\
+             73b1693892219d736caba55bdb67216e485557ea6b6af75f37096c9aa6a5a75f\
+             eeb940b1d03b21e36b0e47e79769f095fe2ab855bd91e3a38756b7d75a9c4549",
+        );
+        assert_eq!(
+            Precompile::EcRecover.execute(&input).unwrap(),
+            hex("000000000000000000000000a94f5374fce5edbc8e2a8697c15331677e6ebf0b")
+        );
+        // Invalid `v` values don't fail but return no output.
+        let mut invalid_input = input;
+        invalid_input[63] = 29;
+        assert_eq!(
+            Precompile::EcRecover.execute(&invalid_input).unwrap(),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn modexp_precompile() {
+        // 3^5 mod 7
+        let input = hex(
+            "0000000000000000000000000000000000000000000000000000000000000001\
+             0000000000000000000000000000000000000000000000000000000000000001\
+             0000000000000000000000000000000000000000000000000000000000000001\
+             030507",
+        );
+        assert_eq!(Precompile::ModExp.execute(&input).unwrap(), vec![5]);
+        assert_eq!(
+            Precompile::ModExp.gas_cost(&input, Hardfork::Berlin),
+            GasCost(200)
+        );
+        // EIP-198 pricing has no minimum: 1^2 * (bits(5) - 1) / 20.
+        assert_eq!(
+            Precompile::ModExp.gas_cost(&input, Hardfork::Istanbul),
+            GasCost(0)
+        );
+
+        // A 2^64 - 1 bytes modulus can't be paid for, and fails without
+        // being allocated.
+        let huge_input = hex(
+            "0000000000000000000000000000000000000000000000000000000000000001\
+             0000000000000000000000000000000000000000000000000000000000000001\
+             000000000000000000000000000000000000000000000000ffffffffffffffff\
+             0305",
+        );
+        assert_eq!(
+            Precompile::ModExp.gas_cost(&huge_input, Hardfork::Berlin),
+            GasCost(u64::MAX / 3)
+        );
+        assert_eq!(
+            Precompile::ModExp.execute(&huge_input),
+            Err(PrecompileError)
+        );
+    }
+
+    #[test]
+    fn bn256_precompiles() {
+        // Generator of G1 doubled by addition and by scalar multiplication.
+        let generator = hex(
+            "0000000000000000000000000000000000000000000000000000000000000001\
+             0000000000000000000000000000000000000000000000000000000000000002",
+        );
+        let doubled = Precompile::Bn256Add
+            .execute(&[generator.clone(), generator.clone()].concat())
+            .unwrap();
+        let mut two = vec![0u8; 32];
+        two[31] = 2;
+        assert_eq!(
+            Precompile::Bn256ScalarMul
+                .execute(&[generator.clone(), two].concat())
+                .unwrap(),
+            doubled
+        );
+        assert_ne!(doubled, generator);
+
+        // Points out of the curve are rejected.
+        let mut invalid_point = generator;
+        invalid_point[63] = 3;
+        assert_eq!(
+            Precompile::Bn256Add.execute(&invalid_point),
+            Err(PrecompileError)
+        );
+
+        // An empty pairing check succeeds.
+        assert_eq!(
+            Precompile::Bn256Pairing.execute(&[]).unwrap(),
+            hex("0000000000000000000000000000000000000000000000000000000000000001")
+        );
+        assert_eq!(
+            Precompile::Bn256Pairing.execute(&[0; 191]),
+            Err(PrecompileError)
+        );
+    }
+
+    #[test]
+    fn blake2f_precompile() {
+        // BLAKE2b-512("abc") computed as a single compression with 12 rounds.
+        let mut input = 12u32.to_be_bytes().to_vec();
+        let mut h = BLAKE2B_IV;
+        h[0] ^= 0x01010040;
+        h.iter()
+            .for_each(|word| input.extend_from_slice(&word.to_le_bytes()));
+        let mut m = [0u8; 128];
+        m[..3].copy_from_slice(b"abc");
+        input.extend_from_slice(&m);
+        input.extend_from_slice(&3u64.to_le_bytes());
+        input.extend_from_slice(&0u64.to_le_bytes());
+        input.push(1);
+
+        assert_eq!(
+            Precompile::Blake2F.execute(&input).unwrap(),
+            hex(
+                "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+                 7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+            )
+        );
+        assert_eq!(
+            Precompile::Blake2F.gas_cost(&input, Hardfork::default()),
+            GasCost(12)
+        );
+        assert_eq!(
+            Precompile::Blake2F.execute(&input[..212]),
+            Err(PrecompileError)
+        );
+    }
+}
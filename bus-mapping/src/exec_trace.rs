@@ -1,16 +1,80 @@
 //! This module contains the logic for parsing and interacting with EVM
 //! execution traces.
+pub(crate) mod builder;
+pub(crate) mod call_tree;
+pub(crate) mod consistency;
+pub(crate) mod context;
+pub(crate) mod copy_event;
 pub(crate) mod exec_step;
-use crate::evm::EvmWord;
+pub(crate) mod export;
+pub(crate) mod memory;
+pub(crate) mod origin;
+pub(crate) mod replay;
+pub(crate) mod rw_table;
+pub(crate) mod snapshot;
+pub(crate) mod state;
+pub(crate) mod validation;
+use crate::evm::{
+    precompiles::PrecompileCall, Address, EvmWord, GasCost, GasMismatch,
+    GlobalCounter, Hardfork, OpcodeId, OpcodeRegistry,
+};
 use crate::operation::{container::OperationContainer, Operation};
-use crate::operation::{MemoryOp, StackOp, StorageOp, Target};
+use crate::operation::{
+    AccessListOp, AccountOp, ContextOp, LogOp, MemoryOp, OpKind, SortedOps,
+    StackOp, StorageOp, Target,
+};
+use crate::state_db::StateDB;
 use crate::Error;
+pub use builder::{ExecutionTraceBuilder, Tracer};
+pub use call_tree::{CallFrame, CallTree};
+pub use consistency::{StateAddress, StateViolation, ViolationKind};
+pub use context::{CallContext, CallKind, ContextTable, TxContext};
+pub use copy_event::{CopyByte, CopyEvent, CopySource};
+use core::fmt;
+use core::marker::PhantomData;
 use core::ops::{Index, IndexMut};
 pub use exec_step::ExecutionStep;
+pub use export::ExportFormat;
+use ff::PrimeField;
+pub use memory::MemoryExpansion;
+use num::BigUint;
+pub use origin::OpOrigin;
 use pasta_curves::arithmetic::FieldExt;
+pub use rw_table::{RwRow, RwTarget};
+pub use snapshot::{SnapshotConvention, StepSnapshots};
+pub use state::TraceState;
 use std::convert::TryFrom;
 
 use self::exec_step::ParsedExecutionStep;
+use self::export::Table;
+
+/// Parses the steps of a trace in JSON format, as taken by the
+/// `from_trace_bytes` constructors of [`ExecutionTrace`].
+pub(crate) fn parse_trace_bytes(
+    bytes: &[u8],
+) -> Result<Vec<ExecutionStep>, Error> {
+    serde_json::from_slice::<Vec<ParsedExecutionStep>>(bytes)
+        .map_err(|_| Error::SerdeError)?
+        .iter()
+        .map(ExecutionStep::try_from)
+        .collect()
+}
+
+/// Returns the code run by the root call of `tx_ctx` when it's known before
+/// the trace is built: the init code of contract creations, and the code of
+/// the called account when it's part of the `prestate`.
+fn root_code<'a>(
+    tx_ctx: &'a TxContext,
+    prestate: &'a StateDB,
+) -> Option<&'a [u8]> {
+    match tx_ctx.to() {
+        None => Some(tx_ctx.input()),
+        Some(to) if prestate.contains_account(to) => {
+            prestate.code(prestate.account(to).code_hash())
+        }
+        Some(_) => None,
+    }
+}
 
 /// Definition of all of the constants related to an Ethereum block and
 /// therefore, related with an [`ExecutionTrace`].
@@ -97,6 +161,21 @@ impl<F: FieldExt> BlockConstants<F> {
     pub fn base_fee(&self) -> &F {
         &self.base_fee
     }
+
+    /// Returns the coinbase of the block as an [`Address`].
+    pub fn coinbase_address(&self) -> Address {
+        Address::from_word(&field_to_word(&self.coinbase))
+    }
+
+    /// Returns the base fee of the block as an [`EvmWord`].
+    pub fn base_fee_word(&self) -> EvmWord {
+        field_to_word(&self.base_fee)
+    }
+}
+
+/// Returns the [`EvmWord`] with the same value as the field element `value`.
+fn field_to_word<F: FieldExt>(value: &F) -> EvmWord {
+    EvmWord(BigUint::from_bytes_le(PrimeField::to_repr(value).as_ref()))
 }
 
 /// Result of the parsing of an EVM execution trace.
@@ -113,8 +192,9 @@ impl<F: FieldExt> BlockConstants<F> {
 ///
 /// 3. Generate and provide and ordered list of all of the
 /// [`StackOp`](crate::operation::StackOp)s,
-/// [`MemoryOp`](crate::operation::MemoryOp)s and
-/// [`StorageOp`](crate::operation::StorageOp)s that each
+/// [`MemoryOp`](crate::operation::MemoryOp)s,
+/// [`StorageOp`](crate::operation::StorageOp)s and
+/// [`AccountOp`](crate::operation::AccountOp)s that each
 /// [`Instruction`](crate::evm::Instruction) that derive from the trace so that
 /// the State Proof witnesses are already obtained on a structured manner and
 /// ready to be added into the State circuit.
@@ -122,7 +202,19 @@ impl<F: FieldExt> BlockConstants<F> {
 pub struct ExecutionTrace<F: FieldExt> {
     steps: Vec<ExecutionStep>,
     block_ctants: BlockConstants<F>,
+    tx_ctx: TxContext,
+    hardfork: Hardfork,
+    convention: SnapshotConvention,
+    calls: Vec<CallContext>,
+    context_table: ContextTable,
+    state_db: StateDB,
     container: OperationContainer,
+    gas_mismatches: Vec<GasMismatch>,
+    intrinsic_gas: GasCost,
+    intrinsic_gas_mismatch: Option<GasCost>,
+    precompile_calls: Vec<PrecompileCall>,
+    copy_events: Vec<CopyEvent>,
+    memory_expansions: Vec<MemoryExpansion>,
 }
 
 impl<F: FieldExt> Index<usize> for ExecutionTrace<F> {
@@ -138,91 +230,536 @@ impl<F: FieldExt> IndexMut<usize> for ExecutionTrace<F> {
     }
 }
 
+impl<F: FieldExt> fmt::Display for ExecutionTrace<F> {
+    /// Formats the steps of the trace as an aligned text table, as done by
+    /// [`ExecutionTrace::export_steps`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.export_steps(ExportFormat::Text))
+    }
+}
+
 impl<F: FieldExt> ExecutionTrace<F> {
     /// Given an EVM trace in JSON format according to the specs and format
     /// shown in [zkevm-test-vectors crate](https://github.com/appliedzkp/zkevm-testing-vectors), generate an `ExecutionTrace`
     /// and generate all of the [`Operation`]s associated to each one of it's
     /// [`ExecutionStep`]s filling them bus-mapping instances.
+    ///
+    /// The trace is assumed to be generated by the default [`TxContext`].
     pub fn from_trace_bytes<T: AsRef<[u8]>>(
         bytes: T,
         block_ctants: BlockConstants<F>,
     ) -> Result<ExecutionTrace<F>, Error> {
-        let trace_loaded =
-            serde_json::from_slice::<Vec<ParsedExecutionStep>>(bytes.as_ref())
-                .map_err(|_| Error::SerdeError)?
-                .iter()
-                .map(ExecutionStep::try_from)
-                .collect::<Result<Vec<ExecutionStep>, Error>>()?;
+        Self::from_trace_bytes_with_tx(
+            bytes,
+            block_ctants,
+            TxContext::default(),
+        )
+    }
 
-        Ok(ExecutionTrace::<F>::new(trace_loaded, block_ctants))
+    /// Same as [`ExecutionTrace::from_trace_bytes`] but for a trace generated
+    /// by the transaction described by `tx_ctx`.
+    ///
+    /// The trace is assumed to be executed at the latest supported
+    /// [`Hardfork`].
+    pub fn from_trace_bytes_with_tx<T: AsRef<[u8]>>(
+        bytes: T,
+        block_ctants: BlockConstants<F>,
+        tx_ctx: TxContext,
+    ) -> Result<ExecutionTrace<F>, Error> {
+        Self::from_trace_bytes_with_hardfork(
+            bytes,
+            block_ctants,
+            tx_ctx,
+            Hardfork::default(),
+        )
+    }
+
+    /// Same as [`ExecutionTrace::from_trace_bytes_with_tx`] but for a trace
+    /// executed at `hardfork`, which defines the opcodes the trace can hold
+    /// and the gas they cost.
+    pub fn from_trace_bytes_with_hardfork<T: AsRef<[u8]>>(
+        bytes: T,
+        block_ctants: BlockConstants<F>,
+        tx_ctx: TxContext,
+        hardfork: Hardfork,
+    ) -> Result<ExecutionTrace<F>, Error> {
+        Self::from_trace_bytes_with_registry(
+            bytes,
+            block_ctants,
+            tx_ctx,
+            hardfork,
+            &OpcodeRegistry::default(),
+        )
+    }
+
+    /// Same as [`ExecutionTrace::from_trace_bytes_with_hardfork`] but
+    /// generating the [`Operation`]s and gas costs of each step with the
+    /// [`Opcode`](crate::evm::Opcode)s registered in `registry`.
+    pub fn from_trace_bytes_with_registry<T: AsRef<[u8]>>(
+        bytes: T,
+        block_ctants: BlockConstants<F>,
+        tx_ctx: TxContext,
+        hardfork: Hardfork,
+        registry: &OpcodeRegistry,
+    ) -> Result<ExecutionTrace<F>, Error> {
+        Self::from_trace_bytes_with_convention(
+            bytes,
+            block_ctants,
+            tx_ctx,
+            hardfork,
+            registry,
+            SnapshotConvention::default(),
+        )
+    }
+
+    /// Same as [`ExecutionTrace::from_trace_bytes_with_registry`] but for a
+    /// trace whose stack and memory views follow `convention`, like the
+    /// [`PreState`](SnapshotConvention::PreState) struct logs returned by
+    /// geth.
+    pub fn from_trace_bytes_with_convention<T: AsRef<[u8]>>(
+        bytes: T,
+        block_ctants: BlockConstants<F>,
+        tx_ctx: TxContext,
+        hardfork: Hardfork,
+        registry: &OpcodeRegistry,
+        convention: SnapshotConvention,
+    ) -> Result<ExecutionTrace<F>, Error> {
+        ExecutionTrace::<F>::new(
+            parse_trace_bytes(bytes.as_ref())?,
+            block_ctants,
+            tx_ctx,
+            hardfork,
+            registry,
+            convention,
+            StateDB::new(),
+        )
     }
 
-    /// Given a vector of [`ExecutionStep`]s and a [`BlockConstants`] instance,
-    /// generate an [`ExecutionTrace`] by:
+    /// Given a vector of [`ExecutionStep`]s, a [`BlockConstants`], a
+    /// [`TxContext`], a [`Hardfork`], an [`OpcodeRegistry`] instance, the
+    /// [`SnapshotConvention`] of the steps and the [`StateDB`] before the
+    /// transaction, generate an [`ExecutionTrace`] by:
     ///
-    /// 1) Setting the correct [`GlobalCounter`](crate::evm::GlobalCounter) to
-    /// each [`ExecutionStep`].
+    /// 1) Setting the correct [`GlobalCounter`](crate::evm::GlobalCounter) and
+    /// memory sizes to each [`ExecutionStep`].
     /// 2) Generating the corresponding [`Operation`]s, registering them in the
     /// container and storing the [`OperationRef`]s to each one of the
     /// generated ops into the bus-mapping instances of each [`ExecutionStep`].
+    ///
+    /// Fails with [`Error::OpcodeNotInHardfork`] if any step executes an
+    /// [`OpcodeId`](crate::evm::OpcodeId) that isn't defined at `hardfork`,
+    /// with [`Error::InvalidTrace`] if the steps are structurally unsound
+    /// (see [`TraceError`](crate::TraceError)), and with
+    /// [`Error::OpcodeNotRegistered`] if `registry` can't generate the
+    /// operations of any of them.
     pub(crate) fn new(
-        steps: Vec<ExecutionStep>,
+        mut steps: Vec<ExecutionStep>,
         block_ctants: BlockConstants<F>,
-    ) -> Self {
+        tx_ctx: TxContext,
+        hardfork: Hardfork,
+        registry: &OpcodeRegistry,
+        convention: SnapshotConvention,
+        prestate: StateDB,
+    ) -> Result<Self, Error> {
+        if let Some(opcode) = steps
+            .iter()
+            .map(|step| step.instruction().opcode_id())
+            .find(|opcode| !hardfork.has_opcode(*opcode))
+        {
+            return Err(Error::OpcodeNotInHardfork(opcode, hardfork));
+        }
+        validation::validate_steps(
+            &steps,
+            root_code(&tx_ctx, &prestate),
+            convention,
+        )
+        .map_err(Error::InvalidTrace)?;
+        let memory_expansions = memory::track_memory(&mut steps, convention);
+
         ExecutionTrace {
             steps,
             block_ctants,
-            /// Dummy empty container to enable build.
+            tx_ctx,
+            hardfork,
+            convention,
+            // Dummy empty calls and container to enable build, which starts
+            // from the prestate.
+            calls: vec![],
+            context_table: ContextTable::default(),
+            state_db: prestate,
             container: OperationContainer::new(),
+            gas_mismatches: vec![],
+            intrinsic_gas: GasCost::ZERO,
+            intrinsic_gas_mismatch: None,
+            precompile_calls: vec![],
+            copy_events: vec![],
+            memory_expansions,
         }
-        .build()
+        .build(registry)
+    }
+
+    /// Returns the [`TxContext`] of the transaction that generated the trace.
+    pub fn tx_ctx(&self) -> &TxContext {
+        &self.tx_ctx
+    }
+
+    /// Returns the [`Hardfork`] the trace was executed at.
+    pub fn hardfork(&self) -> Hardfork {
+        self.hardfork
+    }
+
+    /// Returns the [`SnapshotConvention`] followed by the stack and memory
+    /// views of the steps of the trace.
+    pub fn snapshot_convention(&self) -> SnapshotConvention {
+        self.convention
+    }
+
+    /// Returns all of the [`CallContext`]s opened during the trace, ordered
+    /// by `call_id`.
+    pub fn calls(&self) -> &[CallContext] {
+        &self.calls
+    }
+
+    /// Returns the [`ContextTable`] with the entries of the transaction and of
+    /// the calls opened during the trace, which the [`ContextOp`]s read.
+    pub fn context_table(&self) -> &ContextTable {
+        &self.context_table
+    }
+
+    /// Returns the [`StateDB`] with the state of the accounts once all of the
+    /// steps of the trace have been executed.
+    pub fn state_db(&self) -> &StateDB {
+        &self.state_db
+    }
+
+    /// Returns the steps whose gas transition doesn't match the gas cost
+    /// computed for them.
+    ///
+    /// It's always empty for traces that don't report the gas of their
+    /// steps.
+    pub fn gas_mismatches(&self) -> &[GasMismatch] {
+        &self.gas_mismatches
+    }
+
+    /// Returns the intrinsic gas of the transaction: the gas it's charged
+    /// before it's first step runs (see
+    /// [`intrinsic_gas`](crate::evm::gas::intrinsic_gas)).
+    pub fn intrinsic_gas(&self) -> GasCost {
+        self.intrinsic_gas
+    }
+
+    /// Returns the gas charged to the transaction before it's first step runs
+    /// according to the trace, which is the gas limit of the transaction
+    /// minus the gas left at that step, if it doesn't match it's
+    /// [`intrinsic_gas`](ExecutionTrace::intrinsic_gas).
+    ///
+    /// It's always `None` for traces that don't report the gas of their
+    /// steps or whose transaction has no gas limit.
+    pub fn intrinsic_gas_mismatch(&self) -> Option<GasCost> {
+        self.intrinsic_gas_mismatch
+    }
+
+    /// Returns the witnesses of the calls to precompiled contracts done during
+    /// the trace, in the order they were executed.
+    pub fn precompile_calls(&self) -> &[PrecompileCall] {
+        &self.precompile_calls
+    }
+
+    /// Returns the [`OperationContainer`] holding all of the [`Operation`]s of
+    /// the trace, which can be indexed with the [`OperationRef`]s of the
+    /// bus-mapping instances of it's steps.
+    pub fn container(&self) -> &OperationContainer {
+        &self.container
+    }
+
+    /// Returns the [`CopyEvent`]s of the steps that copy bytes into memory,
+    /// in the order they were executed.
+    pub fn copy_events(&self) -> &[CopyEvent] {
+        &self.copy_events
+    }
+
+    /// Returns the [`MemoryExpansion`]s performed by the steps, in the order
+    /// of the steps.
+    pub fn memory_expansions(&self) -> &[MemoryExpansion] {
+        &self.memory_expansions
     }
 
-    /// Returns an ordered `Vec` containing all the [`StackOp`]s of the actual
-    /// `ExecutionTrace` so that they can be directly included in the State
-    /// proof.
-    pub fn sorted_stack_ops(&self) -> Vec<StackOp> {
+    /// Returns an iterator over all the [`StackOp`]s of the actual
+    /// `ExecutionTrace` in sorted order so that they can be directly included
+    /// in the State proof.
+    pub fn sorted_stack_ops(&self) -> SortedOps<'_, StackOp> {
         self.container.sorted_stack()
     }
 
-    /// Returns an ordered `Vec` containing all the [`MemoryOp`]s of the actual
-    /// `ExecutionTrace` so that they can be directly included in the State
-    /// proof.
-    pub fn sorted_memory_ops(&self) -> Vec<MemoryOp> {
+    /// Returns an iterator over all the [`MemoryOp`]s of the actual
+    /// `ExecutionTrace` in sorted order so that they can be directly included
+    /// in the State proof.
+    pub fn sorted_memory_ops(&self) -> SortedOps<'_, MemoryOp> {
         self.container.sorted_memory()
     }
 
-    /// Returns an ordered `Vec` containing all the [`StorageOp`]s of the actual
-    /// `ExecutionTrace` so that they can be directly included in the State
-    /// proof.
-    pub fn sorted_storage_ops(&self) -> Vec<StorageOp> {
+    /// Returns an iterator over all the [`StorageOp`]s of the actual
+    /// `ExecutionTrace` in sorted order so that they can be directly included
+    /// in the State proof.
+    pub fn sorted_storage_ops(&self) -> SortedOps<'_, StorageOp> {
         self.container.sorted_storage()
     }
 
+    /// Returns an iterator over all the [`AccountOp`]s of the actual
+    /// `ExecutionTrace` in sorted order so that they can be directly included
+    /// in the State proof.
+    pub fn sorted_account_ops(&self) -> SortedOps<'_, AccountOp> {
+        self.container.sorted_account()
+    }
+
+    /// Returns an iterator over all the [`ContextOp`]s of the actual
+    /// `ExecutionTrace` in sorted order so that they can be directly included
+    /// in the State proof.
+    pub fn sorted_context_ops(&self) -> SortedOps<'_, ContextOp> {
+        self.container.sorted_context()
+    }
+
+    /// Returns an iterator over all the [`AccessListOp`]s of the actual
+    /// `ExecutionTrace` in sorted order so that they can be directly included
+    /// in the State proof.
+    pub fn sorted_access_list_ops(&self) -> SortedOps<'_, AccessListOp> {
+        self.container.sorted_access_list()
+    }
+
+    /// Returns an iterator over all the [`LogOp`]s of the actual
+    /// `ExecutionTrace` in sorted order so that they can be directly included
+    /// in the State proof.
+    pub fn sorted_log_ops(&self) -> SortedOps<'_, LogOp> {
+        self.container.sorted_log()
+    }
+
+    /// Returns the read/write table of the trace: the operations of every
+    /// target normalised into [`RwRow`]s and sorted by target, call id,
+    /// address, key and [`GlobalCounter`], ready to be assigned by the
+    /// circuits or hashed into a commitment.
+    pub fn rw_table(&self) -> Vec<RwRow> {
+        rw_table::build_rw_table(&self.container)
+    }
+
+    /// Returns the [`OpOrigin`] of the operation with the [`GlobalCounter`]
+    /// `gc`, which names the step,
+    /// [`ProgramCounter`](crate::evm::ProgramCounter) and [`OpcodeId`] that
+    /// generated it, or `None` if no operation of the trace has that counter.
+    pub fn op_origin(&self, gc: GlobalCounter) -> Option<OpOrigin> {
+        origin::find_origin(&self.steps, gc)
+    }
+
+    /// Returns the [`OpOrigin`] of `op`, which can be any of the operations
+    /// yielded by the sorted iterators of the trace or it's container.
+    pub fn origin_of<T: OpKind>(&self, op: &T) -> Option<OpOrigin> {
+        self.op_origin(op.gc())
+    }
+
+    /// Renders the sorted [`MemoryOp`]s of the trace as a table in the given
+    /// [`ExportFormat`]. When `annotate` is set, each row is followed by the
+    /// `pc` and opcode of the step that generated the operation.
+    pub fn export_memory_ops(
+        &self,
+        format: ExportFormat,
+        annotate: bool,
+    ) -> String {
+        Table::from_ops(self.sorted_memory_ops(), annotate, |op| {
+            self.origin_of(op)
+        })
+        .render(format)
+    }
+
+    /// Renders the sorted [`StackOp`]s of the trace as a table in the given
+    /// [`ExportFormat`]. When `annotate` is set, each row is followed by the
+    /// `pc` and opcode of the step that generated the operation.
+    pub fn export_stack_ops(
+        &self,
+        format: ExportFormat,
+        annotate: bool,
+    ) -> String {
+        Table::from_ops(self.sorted_stack_ops(), annotate, |op| {
+            self.origin_of(op)
+        })
+        .render(format)
+    }
+
+    /// Renders the sorted [`StorageOp`]s of the trace as a table in the given
+    /// [`ExportFormat`]. When `annotate` is set, each row is followed by the
+    /// `pc` and opcode of the step that generated the operation.
+    pub fn export_storage_ops(
+        &self,
+        format: ExportFormat,
+        annotate: bool,
+    ) -> String {
+        Table::from_ops(self.sorted_storage_ops(), annotate, |op| {
+            self.origin_of(op)
+        })
+        .render(format)
+    }
+
+    /// Renders the [`ExecutionStep`]s of the trace as a table in the given
+    /// [`ExportFormat`], with one row per step.
+    pub fn export_steps(&self, format: ExportFormat) -> String {
+        Table::from_steps(&self.steps).render(format)
+    }
+
+    /// Checks the sorted [`MemoryOp`]s, [`StackOp`]s and [`StorageOp`]s of the
+    /// trace against the rules the State circuit enforces over them, returning
+    /// the [`StateViolation`]s found (none for a well-formed witness).
+    pub fn state_violations(&self) -> Vec<StateViolation> {
+        consistency::check_state_consistency(&self.container, &self.steps)
+    }
+
     /// Traverses the trace step by step, and for each [`ExecutionStep`]:
-    /// 1. Sets the correct [`GlobalCounter`](crate::evm::GlobalCounter).
-    /// 2. Generates the corresponding [`Operation`]s and stores them inside the
+    /// 1. Sets the correct [`GlobalCounter`](crate::evm::GlobalCounter). The
+    /// first step also gets the operations of the effects of the transaction
+    /// that take place before it runs (see [`TraceState::begin_tx`]).
+    /// 2. Exits the call frames the step no longer runs in, reverting the ones
+    /// that ended on an exceptional halt.
+    /// 3. Computes the gas cost of the step, generating the
+    /// [`AccessListOp`]s of the accounts and storage slots it accesses.
+    /// 4. Generates the corresponding [`Operation`]s and stores them inside the
     /// [`OperationContainer`] instance stored inside of the trace + adds the
     /// [`OperationRef`]s obtained from the container addition into each
     /// [`ExecutionStep`] bus-mapping instances.
-    fn build(mut self) -> Self {
+    ///
+    /// Once done, the operations of the effects of the transaction that take
+    /// place after it's last step (see [`TraceState::end_tx`]) are added to
+    /// the last step, and the computed gas costs and the intrinsic gas of the
+    /// transaction are checked against the gas reported by the trace.
+    ///
+    /// Fails with [`Error::OpcodeNotRegistered`] at the first step whose
+    /// [`OpcodeId`] has no [`Opcode`](crate::evm::Opcode) registered in
+    /// `registry`.
+    fn build(mut self, registry: &OpcodeRegistry) -> Result<Self, Error> {
         // Set a counter to add the correct global counters.
         let mut gc = 0usize;
         let mut new_container = OperationContainer::new();
-        self.steps_mut().iter_mut().for_each(|exec_step| {
+        let mut state = TraceState::new(
+            self.tx_ctx.clone(),
+            core::mem::take(&mut self.state_db),
+            self.hardfork,
+        );
+        let mut gas_costs: Vec<GasCost> = Vec::with_capacity(self.steps.len());
+        let convention = self.convention;
+        for idx in 0..self.steps.len() {
+            let next_idx = snapshot::next_in_frame(&self.steps, idx);
+            let next_depth = self.steps.get(idx + 1).map(|step| step.depth());
+            let (prev_steps, steps) = self.steps_mut().split_at_mut(idx);
+            let (exec_step, next_steps) = steps
+                .split_first_mut()
+                .expect("idx is in bounds of the steps");
             // Set correct global counter
             exec_step.set_gc(gc);
+            // Exit the frames that ended before this step, which may revert
+            // them, and get the previous and next steps executed in the same
+            // frame, which hold the views around the step.
+            let prev_step = state
+                .enter_step(idx, exec_step, next_depth, &mut new_container)
+                .map(|prev_idx| &prev_steps[prev_idx]);
+            let next_step =
+                next_idx.map(|next_idx| &next_steps[next_idx - idx - 1]);
+            let snapshots =
+                StepSnapshots::new(convention, prev_step, next_step);
+            if idx == 0 {
+                state.begin_tx(exec_step, &mut new_container);
+            }
+            let gas_cost =
+                exec_step.gen_gas_cost(registry, &mut state, snapshots)?;
+            state.charge_gas(gas_cost);
+            gas_costs.push(gas_cost);
+            state.gen_access_ops(exec_step, &mut new_container);
             // Add the `OpcodeId` associated ops and increment the gc counting
-            // all of them.
-            gc += exec_step.gen_associated_ops::<F>(&mut new_container);
+            // all of them. A step that underflows the stack halts before
+            // reading any of it's operands.
+            let opcode = exec_step.instruction().opcode_id();
+            if snapshots.before(exec_step).stack().len()
+                >= opcode.stack_inputs()
+            {
+                exec_step.gen_associated_ops::<F>(
+                    registry,
+                    &mut state,
+                    snapshots,
+                    &mut new_container,
+                )?;
+            }
+            if let Some(error) = state.take_error() {
+                return Err(Error::InvalidTrace(error));
+            }
+            state.expand_memory(snapshots, exec_step);
+            state.gen_access_ops(exec_step, &mut new_container);
+            gc += exec_step.bus_mapping_instance().len();
             // Sum 1 to counter so that we set the next exec_step GC to the
             // correct index
             gc += 1;
-        });
+        }
+
+        if let Some(last_step) = self.steps.len().checked_sub(1) {
+            // The gas left once the root call halts. Exceptional halts
+            // consume all of it.
+            let root_halt = self
+                .steps
+                .iter()
+                .zip(gas_costs.iter())
+                .rev()
+                .find(|(step, _)| step.depth() <= 1)
+                .filter(|(step, _)| {
+                    matches!(
+                        step.instruction().opcode_id(),
+                        OpcodeId::STOP
+                            | OpcodeId::RETURN
+                            | OpcodeId::REVERT
+                            | OpcodeId::SELFDESTRUCT
+                    )
+                });
+            let (gas_left, refund) = root_halt
+                .map(|(step, cost)| {
+                    let gas = step.gas().map_or(0, u64::from);
+                    (gas.saturating_sub(cost.0), step.refund().0)
+                })
+                .unwrap_or((0, 0));
+            let coinbase = self.block_ctants.coinbase_address();
+            let base_fee = self.block_ctants.base_fee_word();
+            state.end_tx(
+                &mut self.steps[last_step],
+                &mut new_container,
+                gas_left,
+                refund,
+                coinbase,
+                &base_fee,
+            );
+        }
 
-        // Replace the empty original container with the new one we just filled.
+        // Replace the empty original container, calls and state with the ones
+        // we just filled.
+        let (calls, context_table, state_db, precompile_calls, copy_events) =
+            state.finish();
+        self.calls = calls;
+        self.context_table = context_table;
+        self.state_db = state_db;
+        self.precompile_calls = precompile_calls;
+        self.copy_events = copy_events;
+        new_container.sort();
         self.container = new_container;
-        self
+        if self.steps.iter().any(|step| step.gas().is_some()) {
+            self.gas_mismatches =
+                crate::evm::gas::check_gas(&self.steps, &gas_costs);
+        }
+        // The gas left at the first step is the gas limit of the transaction
+        // once it's intrinsic gas has been charged.
+        self.intrinsic_gas = GasCost(crate::evm::gas::intrinsic_gas(
+            &self.tx_ctx,
+            self.hardfork,
+        ));
+        let tx_gas = self.tx_ctx.gas();
+        self.intrinsic_gas_mismatch = self
+            .steps
+            .first()
+            .and_then(|step| step.gas())
+            .filter(|_| tx_gas != 0)
+            .map(|gas| GasCost(tx_gas.saturating_sub(gas.0)))
+            .filter(|found| *found != self.intrinsic_gas);
+        Ok(self)
     }
 
     /// Registers an [`Operation`] into the [`OperationContainer`] and then adds
@@ -259,30 +796,83 @@ impl<F: FieldExt> ExecutionTrace<F> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// The target and index of an `Operation` in the context of an
 /// `ExecutionTrace`.
-pub struct OperationRef(Target, usize);
+///
+/// References are typed by the kind of operation they point to, so that
+/// indexing an [`OperationContainer`] with an `OperationRef<StackOp>` returns
+/// a [`StackOp`]. The bus-mapping instances of the [`ExecutionStep`]s hold
+/// untyped `OperationRef<Operation>`s (the default), which resolve into an
+/// [`Operation`] and can be turned into typed ones with
+/// [`OperationRef::typed`].
+pub struct OperationRef<T = Operation> {
+    target: Target,
+    idx: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for OperationRef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for OperationRef<T> {}
+
+impl<T> PartialEq for OperationRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.target, self.idx) == (other.target, other.idx)
+    }
+}
+
+impl<T> Eq for OperationRef<T> {}
+
+impl<T> fmt::Debug for OperationRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OperationRef")
+            .field(&self.target)
+            .field(&self.idx)
+            .finish()
+    }
+}
 
 impl From<(Target, usize)> for OperationRef {
     fn from(op_ref_data: (Target, usize)) -> Self {
-        match op_ref_data.0 {
-            Target::Memory => Self(Target::Memory, op_ref_data.1),
-            Target::Stack => Self(Target::Stack, op_ref_data.1),
-            Target::Storage => Self(Target::Storage, op_ref_data.1),
-        }
+        OperationRef::new(op_ref_data.0, op_ref_data.1)
     }
 }
 
-impl OperationRef {
+impl<T> OperationRef<T> {
+    pub(crate) const fn new(target: Target, idx: usize) -> Self {
+        OperationRef {
+            target,
+            idx,
+            _marker: PhantomData,
+        }
+    }
+
     /// Return the `OperationRef` as a `usize`.
     pub const fn as_usize(&self) -> usize {
-        self.1
+        self.idx
     }
 
     /// Return the [`Target`] op type of the `OperationRef`.
     pub const fn target(&self) -> Target {
-        self.0
+        self.target
+    }
+
+    /// Return the untyped version of the `OperationRef`.
+    pub const fn untyped(&self) -> OperationRef {
+        OperationRef::new(self.target, self.idx)
+    }
+}
+
+impl OperationRef {
+    /// Return the `OperationRef` typed by the kind of operation `U` it points
+    /// to, or `None` if it points to another kind of operation.
+    pub fn typed<U: OpKind>(&self) -> Option<OperationRef<U>> {
+        U::has_target(self.target)
+            .then(|| OperationRef::new(self.target, self.idx))
     }
 }
 
@@ -291,14 +881,14 @@ mod trace_tests {
     use super::*;
     use crate::{
         evm::{
-            opcodes::ids::OpcodeId, GlobalCounter, Instruction, MemoryAddress,
-            ProgramCounter, StackAddress,
+            opcodes::ids::OpcodeId, Gas, GlobalCounter, Instruction,
+            MemoryAddress, ProgramCounter, StackAddress,
         },
         exec_trace::ExecutionStep,
-        operation::{StackOp, RW},
+        operation::{AccountField, StackOp, RW},
+        test_util,
     };
     use alloc::collections::BTreeMap;
-    use num::BigUint;
 
     #[test]
     fn exec_trace_parsing() {
@@ -327,21 +917,12 @@ mod trace_tests {
                     "80"
                 ],
                 "opcode": "PUSH1 80",
-                "pc": 1
+                "pc": 2
             }
         ]
         "#;
 
-        let block_ctants = BlockConstants::new(
-            EvmWord::from(0u8),
-            pasta_curves::Fp::zero(),
-            pasta_curves::Fp::zero(),
-            pasta_curves::Fp::zero(),
-            pasta_curves::Fp::zero(),
-            pasta_curves::Fp::zero(),
-            pasta_curves::Fp::zero(),
-            pasta_curves::Fp::zero(),
-        );
+        let block_ctants = test_util::block_ctants();
 
         // Generate the expected ExecutionTrace corresponding to the JSON
         // provided above.
@@ -365,43 +946,105 @@ mod trace_tests {
             vec![EvmWord::from(0x40u8)],
             Instruction::new(OpcodeId::PUSH1, Some(EvmWord::from(0x40u8))),
             ProgramCounter::from(0),
+            1,
+            None,
+            GasCost::default(),
+            Gas::default(),
             GlobalCounter::from(0),
         );
+        // The memory of the view is taken as already there before the step.
+        step_1.set_memory_size(3, 3);
+
+        // The first step starts with the nonce increment of the sender of the
+        // transaction.
+        let nonce_op = container.insert(AccountOp::new(
+            RW::WRITE,
+            GlobalCounter(1usize),
+            Address::zero(),
+            AccountField::Nonce,
+            EvmWord::from(1u8),
+            EvmWord::from(0u8),
+        ));
+        step_1.bus_mapping_instance_mut().push(nonce_op.untyped());
+
+        // Followed by the accesses of the sender, the receiver (which is the
+        // sender too) and the precompiled contracts.
+        let precompiles = (1..=9u8).map(|precompile| {
+            let mut address = [0u8; 20];
+            address[19] = precompile;
+            (Address::from(address), false)
+        });
+        let accesses = vec![(Address::zero(), false), (Address::zero(), true)]
+            .into_iter()
+            .chain(precompiles);
+        for (idx, (address, was_warm)) in accesses.enumerate() {
+            let access_op = container.insert(AccessListOp::new(
+                RW::WRITE,
+                GlobalCounter(idx + 2),
+                address,
+                None,
+                true,
+                was_warm,
+            ));
+            step_1.bus_mapping_instance_mut().push(access_op.untyped());
+        }
 
         // Add StackOp associated to this opcode to the container &
         // step.bus_mapping
-        step_1
-            .bus_mapping_instance_mut()
-            .push(container.insert(StackOp::new(
-                RW::WRITE,
-                GlobalCounter(1usize),
-                StackAddress::from(1023),
-                EvmWord::from(0x40u8),
-            )));
+        let stack_op = container.insert(StackOp::new(
+            RW::WRITE,
+            GlobalCounter(13usize),
+            1,
+            StackAddress::from(1023),
+            EvmWord::from(0x40u8),
+        ));
+        step_1.bus_mapping_instance_mut().push(stack_op.untyped());
 
         // Generate Step2 corresponding to PUSH1 80
         let mut step_2 = ExecutionStep::new(
             mem_map,
             vec![EvmWord::from(0x40u8), EvmWord::from(0x80u8)],
             Instruction::new(OpcodeId::PUSH1, Some(EvmWord::from(0x80u8))),
-            ProgramCounter::from(1),
-            GlobalCounter::from(2),
+            ProgramCounter::from(2),
+            1,
+            None,
+            GasCost::default(),
+            Gas::default(),
+            GlobalCounter::from(14),
         );
+        step_2.set_memory_size(3, 3);
 
         // Add StackOp associated to this opcode to the container &
         // step.bus_mapping
-        step_2
-            .bus_mapping_instance_mut()
-            .push(container.insert(StackOp::new(
-                RW::WRITE,
-                GlobalCounter(3usize),
-                StackAddress::from(1022),
-                EvmWord::from(0x80u8),
-            )));
+        let stack_op = container.insert(StackOp::new(
+            RW::WRITE,
+            GlobalCounter(15usize),
+            1,
+            StackAddress::from(1022),
+            EvmWord::from(0x80u8),
+        ));
+        step_2.bus_mapping_instance_mut().push(stack_op.untyped());
+        let mut state_db = StateDB::new();
+        state_db.set_nonce(&Address::zero(), 1);
+        let root_call = TxContext::default().root_call(1);
+        let mut context_table = ContextTable::new(&TxContext::default());
+        context_table.insert_call(&root_call);
         let expected_exec_trace = ExecutionTrace {
             steps: vec![step_1, step_2],
             block_ctants: block_ctants.clone(),
+            tx_ctx: TxContext::default(),
+            hardfork: Hardfork::London,
+            convention: SnapshotConvention::PostState,
+            calls: vec![root_call],
+            context_table,
+            state_db,
             container,
+            gas_mismatches: vec![],
+            intrinsic_gas: GasCost(21000),
+            intrinsic_gas_mismatch: None,
+            precompile_calls: vec![],
+            copy_events: vec![],
+            memory_expansions: vec![],
         };
 
         // Obtained trace computation
@@ -411,6 +1054,101 @@ mod trace_tests {
         )
         .expect("Error on trace generation");
 
-        assert_eq!(obtained_exec_trace, expected_exec_trace)
+        assert_eq!(obtained_exec_trace, expected_exec_trace);
+
+        // The bus-mapping instance of each step resolves into it's ops.
+        let step_2_ref = obtained_exec_trace[1].bus_mapping_instance()[0];
+        let stack_op = step_2_ref
+            .typed::<StackOp>()
+            .map(|op_ref| &obtained_exec_trace.container()[op_ref])
+            .expect("PUSH1 writes into the stack");
+        assert_eq!(stack_op.gc(), GlobalCounter(15usize));
+        assert!(step_2_ref.typed::<AccountOp>().is_none());
+    }
+
+    #[test]
+    fn tx_level_ops() {
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0, "gas": 29000, "gasCost": 3 },
+            { "memory": {}, "stack": ["1"], "opcode": "STOP", "pc": 2, "gas": 28997, "gasCost": 0 }
+        ]
+        "#;
+        let coinbase = Address::from_word(&EvmWord::from(0xccu8));
+        let block_ctants = BlockConstants::new(
+            EvmWord::from(0u8),
+            pasta_curves::Fp::from(0xccu64),
+            pasta_curves::Fp::zero(),
+            pasta_curves::Fp::zero(),
+            pasta_curves::Fp::zero(),
+            pasta_curves::Fp::zero(),
+            pasta_curves::Fp::zero(),
+            pasta_curves::Fp::one(),
+        );
+        let sender = Address::from([0xaa; 20]);
+        let receiver = Address::from([0xbb; 20]);
+        // Sends 5 wei paying a gas price of 2 over a base fee of 1.
+        let tx_ctx = TxContext::new(
+            sender,
+            Some(receiver),
+            3,
+            EvmWord::from(5u8),
+            EvmWord::from(2u8),
+            50_000,
+            vec![],
+        );
+
+        let trace = ExecutionTrace::from_trace_bytes_with_tx(
+            input_trace.as_bytes(),
+            block_ctants,
+            tx_ctx,
+        )
+        .expect("Error on trace generation");
+        assert_eq!(trace.gas_mismatches(), &[]);
+
+        let account_ops: Vec<(usize, Address, AccountField, EvmWord)> = trace
+            .sorted_account_ops()
+            .map(|op| {
+                (op.gc().0, *op.address(), op.field(), op.value().clone())
+            })
+            .collect();
+        assert_eq!(
+            account_ops,
+            vec![
+                // The coinbase gets the priority fee of the 21003 gas used.
+                (19, coinbase, AccountField::Balance, 21003u16.into()),
+                // The sender buys 50000 gas, bumps it's nonce, sends the value
+                // and gets back the 28997 gas left.
+                (2, sender, AccountField::Nonce, 4u8.into()),
+                (1, sender, AccountField::Balance, 5u8.into()),
+                (3, sender, AccountField::Balance, 0u8.into()),
+                (18, sender, AccountField::Balance, 57994u16.into()),
+                (4, receiver, AccountField::Balance, 5u8.into()),
+            ]
+        );
+
+        // The effects of the transaction belong to it's first and last steps,
+        // while the counters of the steps themselves aren't operations.
+        let push = OpOrigin::new(0, ProgramCounter::from(0), OpcodeId::PUSH1);
+        let stop = OpOrigin::new(1, ProgramCounter::from(2), OpcodeId::STOP);
+        let origins: Vec<Option<OpOrigin>> = trace
+            .sorted_account_ops()
+            .map(|op| trace.origin_of(op))
+            .collect();
+        assert_eq!(
+            origins,
+            vec![
+                Some(stop),
+                Some(push),
+                Some(push),
+                Some(push),
+                Some(stop),
+                Some(push)
+            ]
+        );
+        let stack_op = trace.sorted_stack_ops().next().unwrap();
+        assert_eq!(trace.origin_of(stack_op), Some(push));
+        assert_eq!(trace.op_origin(trace[1].gc()), None);
+        assert_eq!(trace.op_origin(GlobalCounter::from(100)), None);
     }
 }
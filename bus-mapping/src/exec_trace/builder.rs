@@ -0,0 +1,383 @@
+//! Incremental construction of an [`ExecutionTrace`] from the steps that an
+//! EVM reports while it executes a transaction, instead of from a trace in
+//! JSON format.
+
+use super::exec_step::{MissingViews, ParsedExecutionStep};
+use super::{
+    parse_trace_bytes, replay, BlockConstants, CallKind, ExecutionStep,
+    ExecutionTrace, SnapshotConvention, TxContext,
+};
+use crate::error::{Error, TraceError};
+use crate::evm::{Address, EvmWord, Hardfork, OpcodeRegistry};
+use crate::state_db::StateDB;
+use pasta_curves::arithmetic::FieldExt;
+
+/// Hooks that an EVM calls while it executes a transaction, which let the
+/// implementor follow the execution step by step.
+///
+/// All of the hooks but [`Tracer::step`] do nothing by default.
+pub trait Tracer {
+    /// Called before the first step of the transaction described by `tx_ctx`
+    /// runs.
+    fn tx_start(&mut self, _tx_ctx: &TxContext) {}
+
+    /// Called with each one of the steps of the transaction, in the order
+    /// they run.
+    fn step(&mut self, step: &ExecutionStep);
+
+    /// Called when a step opens the frame of a call or creation of the given
+    /// `kind`, done by `caller` to `address` with `value` and `input` (the
+    /// calldata or the init code), before any step of the frame runs.
+    fn call_enter(
+        &mut self,
+        _kind: CallKind,
+        _caller: &Address,
+        _address: &Address,
+        _value: &EvmWord,
+        _input: &[u8],
+    ) {
+    }
+
+    /// Called when the current frame halts returning `output`, where
+    /// `success` is false if it reverted.
+    fn call_exit(&mut self, _output: &[u8], _success: bool) {}
+
+    /// Called once the root call of the transaction halts returning `output`,
+    /// where `success` is false if it reverted.
+    fn tx_end(&mut self, _output: &[u8], _success: bool) {}
+}
+
+/// Builder of an [`ExecutionTrace`] whose [`ExecutionStep`]s are pushed one
+/// by one, either directly with [`ExecutionTraceBuilder::push_step`] or by
+/// an EVM through it's [`Tracer`] hooks. Once all of them are in,
+/// [`ExecutionTraceBuilder::finish`] generates the trace like the
+/// `from_trace_bytes` constructors of [`ExecutionTrace`] do.
+///
+/// Unless set otherwise, the trace is generated by the default
+/// [`TxContext`] at the latest supported [`Hardfork`], with the default
+/// [`OpcodeRegistry`] and [`SnapshotConvention`] and from empty accounts.
+/// EVMs that report each step before running it must set the
+/// [`PreState`](SnapshotConvention::PreState) convention.
+#[derive(Debug)]
+pub struct ExecutionTraceBuilder<F: FieldExt> {
+    steps: Vec<ExecutionStep>,
+    block_ctants: BlockConstants<F>,
+    tx_ctx: TxContext,
+    hardfork: Hardfork,
+    registry: OpcodeRegistry,
+    convention: SnapshotConvention,
+    prestate: StateDB,
+    // Views that the trace of each step lacks, which are rebuilt once all of
+    // the steps are in.
+    missing_views: Vec<MissingViews>,
+    // Depth of the frame the hooks of the tracer are in, and the first step
+    // that was traced at another depth.
+    depth: usize,
+    depth_mismatch: Option<TraceError>,
+}
+
+impl<F: FieldExt> ExecutionTraceBuilder<F> {
+    /// Generates a new `ExecutionTraceBuilder` without steps for a block
+    /// with the given [`BlockConstants`].
+    pub fn new(block_ctants: BlockConstants<F>) -> ExecutionTraceBuilder<F> {
+        ExecutionTraceBuilder {
+            steps: vec![],
+            block_ctants,
+            tx_ctx: TxContext::default(),
+            hardfork: Hardfork::default(),
+            registry: OpcodeRegistry::default(),
+            convention: SnapshotConvention::default(),
+            prestate: StateDB::new(),
+            missing_views: vec![],
+            depth: 1,
+            depth_mismatch: None,
+        }
+    }
+
+    /// Returns the `ExecutionTraceBuilder` for a trace generated by the
+    /// transaction described by `tx_ctx`.
+    pub fn with_tx(mut self, tx_ctx: TxContext) -> ExecutionTraceBuilder<F> {
+        self.tx_ctx = tx_ctx;
+        self
+    }
+
+    /// Returns the `ExecutionTraceBuilder` for a trace executed at
+    /// `hardfork`.
+    pub fn with_hardfork(
+        mut self,
+        hardfork: Hardfork,
+    ) -> ExecutionTraceBuilder<F> {
+        self.hardfork = hardfork;
+        self
+    }
+
+    /// Returns the `ExecutionTraceBuilder` for a trace whose operations and
+    /// gas costs are generated with the [`Opcode`](crate::evm::Opcode)s
+    /// registered in `registry`.
+    pub fn with_registry(
+        mut self,
+        registry: OpcodeRegistry,
+    ) -> ExecutionTraceBuilder<F> {
+        self.registry = registry;
+        self
+    }
+
+    /// Returns the `ExecutionTraceBuilder` for a trace whose stack and memory
+    /// views follow `convention`.
+    pub fn with_convention(
+        mut self,
+        convention: SnapshotConvention,
+    ) -> ExecutionTraceBuilder<F> {
+        self.convention = convention;
+        self
+    }
+
+    /// Returns the `ExecutionTraceBuilder` for a trace that starts from the
+    /// state of the accounts in `prestate`, like the one parsed from geth's
+    /// `prestateTracer` with [`StateDB::from_prestate_bytes`], instead of
+    /// from empty accounts.
+    ///
+    /// The first operation over each account field and storage slot then
+    /// takes it's previous value from `prestate`, and the code of the
+    /// account called by the transaction bounds the steps of the root call.
+    pub fn with_prestate(
+        mut self,
+        prestate: StateDB,
+    ) -> ExecutionTraceBuilder<F> {
+        self.prestate = prestate;
+        self
+    }
+
+    /// Appends `step` to the steps of the trace.
+    pub fn push_step(&mut self, step: ExecutionStep) {
+        self.steps.push(step);
+        self.missing_views.push(MissingViews::NONE);
+    }
+
+    /// Appends the steps of a trace in JSON format, like the ones taken by
+    /// the `from_trace_bytes` constructors of [`ExecutionTrace`], to the
+    /// steps of the trace.
+    pub fn push_trace_bytes<T: AsRef<[u8]>>(
+        &mut self,
+        bytes: T,
+    ) -> Result<(), Error> {
+        parse_trace_bytes(bytes.as_ref())?
+            .into_iter()
+            .for_each(|step| self.push_step(step));
+        Ok(())
+    }
+
+    /// Same as [`ExecutionTraceBuilder::push_trace_bytes`] but for a trace
+    /// generated with the `disableMemory` and `disableStack` options of the
+    /// tracer, whose steps may lack their memory and stack.
+    ///
+    /// The views missing are rebuilt by [`ExecutionTraceBuilder::finish`],
+    /// which replays the instructions of the steps over the code run by the
+    /// transaction, starting from an empty stack and memory and taking the
+    /// views that the steps hold as they are. The code is the init code of
+    /// creations and the one that the called account holds in the prestate
+    /// otherwise. Only the steps of the root call can be replayed, and their
+    /// instructions must not depend on state other than the transaction,
+    /// the block, the balances of the prestate and the code and storage of
+    /// the account (see [`Error::StepNotReplayable`]).
+    pub fn push_slim_trace_bytes<T: AsRef<[u8]>>(
+        &mut self,
+        bytes: T,
+    ) -> Result<(), Error> {
+        let steps =
+            serde_json::from_slice::<Vec<ParsedExecutionStep>>(bytes.as_ref())
+                .map_err(|_| Error::SerdeError)?
+                .iter()
+                .map(ParsedExecutionStep::slim_step)
+                .collect::<Result<Vec<(ExecutionStep, MissingViews)>, Error>>(
+                )?;
+        for (step, missing) in steps {
+            self.steps.push(step);
+            self.missing_views.push(missing);
+        }
+        Ok(())
+    }
+
+    /// Returns the steps pushed so far.
+    pub fn steps(&self) -> &[ExecutionStep] {
+        &self.steps
+    }
+
+    /// Generates the [`ExecutionTrace`] of the steps pushed, with all of the
+    /// [`Operation`](crate::operation::Operation)s of each one of them.
+    ///
+    /// Fails like the `from_trace_bytes` constructors of [`ExecutionTrace`]
+    /// do, with [`Error::StepNotReplayable`] if the views that a step lacks
+    /// can't be rebuilt, and with [`TraceError::CallTreeMismatch`] if a step
+    /// was traced at a depth other than the one of the frame entered through
+    /// the hooks of the [`Tracer`].
+    pub fn finish(mut self) -> Result<ExecutionTrace<F>, Error> {
+        if let Some(err) = self.depth_mismatch {
+            return Err(Error::InvalidTrace(err));
+        }
+        if self
+            .missing_views
+            .iter()
+            .any(|missing| *missing != MissingViews::NONE)
+        {
+            replay::replay_steps(
+                &mut self.steps,
+                &self.missing_views,
+                &self.tx_ctx,
+                &self.block_ctants,
+                &self.prestate,
+                self.convention,
+            )?;
+        }
+        ExecutionTrace::new(
+            self.steps,
+            self.block_ctants,
+            self.tx_ctx,
+            self.hardfork,
+            &self.registry,
+            self.convention,
+            self.prestate,
+        )
+    }
+}
+
+impl<F: FieldExt> Tracer for ExecutionTraceBuilder<F> {
+    /// Sets the [`TxContext`] of the trace.
+    fn tx_start(&mut self, tx_ctx: &TxContext) {
+        self.tx_ctx = tx_ctx.clone();
+        self.depth = 1;
+    }
+
+    /// Pushes a copy of `step`, which must run at the depth of the frame
+    /// entered through the hooks.
+    fn step(&mut self, step: &ExecutionStep) {
+        if step.depth() != self.depth && self.depth_mismatch.is_none() {
+            self.depth_mismatch = Some(TraceError::CallTreeMismatch {
+                step: self.steps.len(),
+            });
+        }
+        self.push_step(step.clone());
+    }
+
+    fn call_enter(
+        &mut self,
+        _kind: CallKind,
+        _caller: &Address,
+        _address: &Address,
+        _value: &EvmWord,
+        _input: &[u8],
+    ) {
+        self.depth += 1;
+    }
+
+    fn call_exit(&mut self, _output: &[u8], _success: bool) {
+        self.depth = self.depth.saturating_sub(1).max(1);
+    }
+}
+
+#[cfg(test)]
+mod builder_tests {
+    use super::*;
+    use crate::exec_trace::exec_step::ParsedExecutionStep;
+    use crate::test_util;
+    use core::convert::TryFrom;
+
+    #[test]
+    fn traces_built_step_by_step() {
+        // The root call calls 0xbb, which returns the address of it's caller.
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": ["0", "0", "0"], "opcode": "PUSH1 00", "pc": 4 },
+            { "memory": {}, "stack": ["0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 6 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 8 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0", "bb"], "opcode": "PUSH1 bb", "pc": 10 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0", "bb", "ff"], "opcode": "PUSH1 ff", "pc": 12 },
+            { "memory": {}, "stack": ["1"], "opcode": "CALL", "pc": 14 },
+            { "memory": {}, "stack": ["aa"], "opcode": "CALLER", "pc": 0, "depth": 2 },
+            { "memory": {}, "stack": ["aa", "20"], "opcode": "PUSH1 20", "pc": 1, "depth": 2 },
+            { "memory": {}, "stack": ["aa", "20", "0"], "opcode": "PUSH1 00", "pc": 3, "depth": 2 },
+            { "memory": {}, "stack": ["aa"], "opcode": "RETURN", "pc": 5, "depth": 2 },
+            { "memory": {}, "stack": ["1", "20"], "opcode": "RETURNDATASIZE", "pc": 15 },
+            { "memory": {}, "stack": ["1", "20"], "opcode": "STOP", "pc": 16 }
+        ]
+        "#;
+        let steps: Vec<ExecutionStep> =
+            serde_json::from_str::<Vec<ParsedExecutionStep>>(input_trace)
+                .expect("Error on parsing")
+                .iter()
+                .map(|step| ExecutionStep::try_from(step).unwrap())
+                .collect();
+        let tx_ctx = TxContext::new(
+            test_util::address("11"),
+            Some(test_util::address("aa")),
+            0,
+            EvmWord::from(0u8),
+            EvmWord::from(0u8),
+            100_000,
+            vec![],
+        );
+        let block_ctants = test_util::block_ctants();
+        let expected = ExecutionTrace::from_trace_bytes_with_tx(
+            input_trace.as_bytes(),
+            block_ctants.clone(),
+            tx_ctx.clone(),
+        )
+        .expect("Error on trace generation");
+
+        // Steps pushed directly.
+        let mut builder = ExecutionTraceBuilder::new(block_ctants.clone())
+            .with_tx(tx_ctx.clone());
+        steps
+            .iter()
+            .cloned()
+            .for_each(|step| builder.push_step(step));
+        assert_eq!(builder.steps(), &steps[..]);
+        assert_eq!(builder.finish().unwrap(), expected);
+
+        // Steps parsed from the trace.
+        let mut builder = ExecutionTraceBuilder::new(block_ctants.clone())
+            .with_tx(tx_ctx.clone());
+        builder.push_trace_bytes(input_trace).unwrap();
+        assert_eq!(builder.steps(), &steps[..]);
+        assert!(matches!(
+            builder.push_trace_bytes("[{}]"),
+            Err(Error::SerdeError)
+        ));
+
+        // Steps traced by an EVM, which enters and exits the sub-call.
+        let trace_with = |steps: &[ExecutionStep]| {
+            let mut builder = ExecutionTraceBuilder::new(block_ctants.clone());
+            builder.tx_start(&tx_ctx);
+            for (idx, step) in steps.iter().enumerate() {
+                if idx == 8 {
+                    builder.call_enter(
+                        CallKind::Call,
+                        &test_util::address("aa"),
+                        &test_util::address("bb"),
+                        &EvmWord::from(0u8),
+                        &[],
+                    );
+                }
+                builder.step(step);
+                if idx == 11 {
+                    builder.call_exit(&[0xaa], true);
+                }
+            }
+            builder.tx_end(&[], true);
+            builder.finish()
+        };
+        assert_eq!(trace_with(&steps).unwrap(), expected);
+
+        // Steps traced outside of the frame the EVM is in.
+        let mut misplaced = steps.clone();
+        misplaced.swap(7, 8);
+        assert!(matches!(
+            trace_with(&misplaced),
+            Err(Error::InvalidTrace(TraceError::CallTreeMismatch {
+                step: 7
+            }))
+        ));
+    }
+}
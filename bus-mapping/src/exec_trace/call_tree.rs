@@ -0,0 +1,409 @@
+//! Call tree of a transaction as reported by geth's `callTracer`, and it's
+//! correlation with the [`ExecutionStep`]s of the struct logs of the same
+//! transaction.
+
+use super::{CallKind, ExecutionStep};
+use crate::error::{Error, TraceError};
+use crate::evm::{bytes_from_hex, Address, EvmWord, OpcodeId};
+use core::ops::Index;
+use core::str::FromStr;
+use num::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+/// Call frame of a [`CallTree`], holding the data that the `callTracer`
+/// reports for it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CallFrame {
+    kind: CallKind,
+    depth: usize,
+    parent: Option<usize>,
+    from: Address,
+    to: Option<Address>,
+    address: Option<Address>,
+    value: EvmWord,
+    gas: u64,
+    gas_used: u64,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    error: Option<String>,
+    calls: Vec<usize>,
+}
+
+impl CallFrame {
+    #[inline]
+    /// Return the [`CallKind`] of the frame.
+    pub const fn kind(&self) -> CallKind {
+        self.kind
+    }
+
+    #[inline]
+    /// Return the depth of the frame (the root call has depth 1).
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+
+    #[inline]
+    /// Return the index of the frame that opened this one inside of the
+    /// [`CallTree`], which is `None` for the root call.
+    pub const fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    #[inline]
+    /// Return the address of the account that opened the frame.
+    pub const fn from(&self) -> &Address {
+        &self.from
+    }
+
+    #[inline]
+    /// Return the address of the account called, or of the contract created
+    /// (`None` if the creation failed before it got one).
+    pub const fn to(&self) -> Option<&Address> {
+        self.to.as_ref()
+    }
+
+    #[inline]
+    /// Return the address of the account whose context the frame runs in,
+    /// which is the one of it's caller for `CALLCODE` and `DELEGATECALL`.
+    pub const fn address(&self) -> Option<&Address> {
+        self.address.as_ref()
+    }
+
+    #[inline]
+    /// Return the value sent along with the call.
+    pub const fn value(&self) -> &EvmWord {
+        &self.value
+    }
+
+    #[inline]
+    /// Return the gas made available to the frame.
+    pub const fn gas(&self) -> u64 {
+        self.gas
+    }
+
+    #[inline]
+    /// Return the gas used by the frame.
+    pub const fn gas_used(&self) -> u64 {
+        self.gas_used
+    }
+
+    #[inline]
+    /// Return the calldata of the call, or the init code of the creation.
+    pub fn input(&self) -> &[u8] {
+        &self.input
+    }
+
+    #[inline]
+    /// Return the data returned by the frame.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    #[inline]
+    /// Return the error the frame failed with, if any.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    #[inline]
+    /// Return the indexes of the frames opened by this one inside of the
+    /// [`CallTree`], in the order they were opened.
+    pub fn calls(&self) -> &[usize] {
+        &self.calls
+    }
+}
+
+/// Tree of the call frames opened by a transaction, as reported by geth's
+/// `callTracer`.
+///
+/// The frames are stored in the order they were opened, so the root call is
+/// the first one and each frame links to it's parent and to the frames it
+/// opened through their indexes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CallTree {
+    frames: Vec<CallFrame>,
+}
+
+impl Index<usize> for CallTree {
+    type Output = CallFrame;
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.frames[index]
+    }
+}
+
+impl CallTree {
+    /// Parses the output of geth's `callTracer`: the root call, with the
+    /// `type`, `from`, `to`, `value`, `gas`, `gasUsed`, `input`, `output`,
+    /// `error` and the nested `calls` of each frame.
+    ///
+    /// The `SELFDESTRUCT` entries reported by the tracer are left out, as
+    /// they don't open a frame.
+    pub fn from_tracer_bytes<T: AsRef<[u8]>>(
+        bytes: T,
+    ) -> Result<CallTree, Error> {
+        let parsed = serde_json::from_slice::<ParsedCallFrame>(bytes.as_ref())
+            .map_err(|_| Error::SerdeError)?;
+        let mut frames = vec![];
+        parsed.flatten(None, &mut frames)?;
+        if frames.is_empty() {
+            return Err(Error::SerdeError);
+        }
+        Ok(CallTree { frames })
+    }
+
+    /// Returns the [`CallFrame`] of the root call.
+    pub fn root(&self) -> &CallFrame {
+        &self.frames[0]
+    }
+
+    /// Returns all of the [`CallFrame`]s, in the order they were opened.
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    /// Returns the index of the [`CallFrame`] each one of the `steps` runs
+    /// in, following the depth transitions of the steps: a step one level
+    /// deeper than the previous one enters the next frame opened by the
+    /// frame of the previous step.
+    ///
+    /// Calls that don't run any step (like the ones to precompiles or to
+    /// accounts without code) skip their frame as long as it's of the kind
+    /// of the opcode of the call.
+    ///
+    /// Fails with [`TraceError::CallTreeMismatch`] if a step enters a frame
+    /// that the tree doesn't hold.
+    pub fn attribute_steps(
+        &self,
+        steps: &[ExecutionStep],
+    ) -> Result<Vec<usize>, TraceError> {
+        // Frames entered by the steps, indexed by `depth - 1`.
+        let mut active = vec![0];
+        // Number of the frames opened by each frame that have been entered.
+        let mut entered = vec![0; self.frames.len()];
+        let mut attributed = Vec::with_capacity(steps.len());
+        for (idx, step) in steps.iter().enumerate() {
+            let depth = step.depth().max(1);
+            if depth == active.len() + 1 {
+                let parent = active[active.len() - 1];
+                let frame = *self.frames[parent]
+                    .calls
+                    .get(entered[parent])
+                    .ok_or(TraceError::CallTreeMismatch { step: idx })?;
+                entered[parent] += 1;
+                active.push(frame);
+            } else if depth <= active.len() {
+                active.truncate(depth);
+            } else {
+                return Err(TraceError::CallTreeMismatch { step: idx });
+            }
+            let frame = active[active.len() - 1];
+            attributed.push(frame);
+
+            // A call that doesn't run any step is followed by a step of it's
+            // own frame.
+            let kind = CallKind::from_opcode(step.instruction().opcode_id());
+            let enters_frame = matches!(steps.get(idx + 1), Some(next) if next.depth() > depth);
+            if let (Some(kind), false) = (kind, enters_frame) {
+                let next_call = self.frames[frame].calls.get(entered[frame]);
+                if next_call.map(|call| self.frames[*call].kind) == Some(kind) {
+                    entered[frame] += 1;
+                }
+            }
+        }
+        Ok(attributed)
+    }
+}
+
+/// Helper structure whose only purpose is to serve as a De/Serialization
+/// derivation guide for the serde Derive macro.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[doc(hidden)]
+struct ParsedCallFrame<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    from: &'a str,
+    #[serde(default, borrow)]
+    to: Option<&'a str>,
+    #[serde(default, borrow)]
+    value: Option<&'a str>,
+    gas: &'a str,
+    #[serde(rename = "gasUsed")]
+    gas_used: &'a str,
+    input: &'a str,
+    #[serde(default, borrow)]
+    output: Option<&'a str>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default, borrow)]
+    calls: Vec<ParsedCallFrame<'a>>,
+}
+
+impl<'a> ParsedCallFrame<'a> {
+    /// Appends the frame and the frames it opened to `frames`, returning the
+    /// index of the frame, or `None` if it's a `SELFDESTRUCT` entry.
+    fn flatten(
+        &self,
+        parent: Option<usize>,
+        frames: &mut Vec<CallFrame>,
+    ) -> Result<Option<usize>, Error> {
+        let kind = match CallKind::from_opcode(OpcodeId::from_str(self.kind)?) {
+            Some(kind) => kind,
+            None => return Ok(None),
+        };
+        let word = |hex: &str| EvmWord::from_str(hex.trim_start_matches("0x"));
+        let gas =
+            |hex: &str| word(hex)?.0.to_u64().ok_or(Error::EvmWordParsing);
+        let to = self.to.map(Address::from_str).transpose()?;
+        let address = match kind {
+            CallKind::CallCode | CallKind::DelegateCall => {
+                parent.and_then(|parent| frames[parent].address)
+            }
+            _ => to,
+        };
+
+        let idx = frames.len();
+        frames.push(CallFrame {
+            kind,
+            depth: parent.map_or(1, |parent| frames[parent].depth + 1),
+            parent,
+            from: Address::from_str(self.from)?,
+            to,
+            address,
+            value: self
+                .value
+                .map(word)
+                .transpose()?
+                .unwrap_or_else(|| EvmWord::from(0u8)),
+            gas: gas(self.gas)?,
+            gas_used: gas(self.gas_used)?,
+            input: bytes_from_hex(self.input)?,
+            output: self
+                .output
+                .map(bytes_from_hex)
+                .transpose()?
+                .unwrap_or_default(),
+            error: self.error.clone(),
+            calls: vec![],
+        });
+        for call in self.calls.iter() {
+            if let Some(call_idx) = call.flatten(Some(idx), frames)? {
+                frames[idx].calls.push(call_idx);
+            }
+        }
+        Ok(Some(idx))
+    }
+}
+
+#[cfg(test)]
+mod call_tree_tests {
+    use super::*;
+    use crate::evm::{
+        Gas, GasCost, GlobalCounter, Instruction, ProgramCounter,
+    };
+    use alloc::collections::BTreeMap;
+
+    #[test]
+    fn call_tree_parsing_and_attribution() {
+        let call_tree = r#"
+        {
+            "type": "CALL",
+            "from": "0x00000000000000000000000000000000000000ff",
+            "to": "0x00000000000000000000000000000000000000aa",
+            "value": "0x5",
+            "gas": "0x186a0",
+            "gasUsed": "0x5208",
+            "input": "0x0102",
+            "output": "0x",
+            "calls": [
+                {
+                    "type": "STATICCALL",
+                    "from": "0x00000000000000000000000000000000000000aa",
+                    "to": "0x0000000000000000000000000000000000000004",
+                    "gas": "0x100",
+                    "gasUsed": "0xf",
+                    "input": "0xab",
+                    "output": "0xab"
+                },
+                {
+                    "type": "DELEGATECALL",
+                    "from": "0x00000000000000000000000000000000000000aa",
+                    "to": "0x00000000000000000000000000000000000000bb",
+                    "gas": "0x200",
+                    "gasUsed": "0x200",
+                    "input": "0x",
+                    "error": "execution reverted",
+                    "calls": [
+                        {
+                            "type": "SELFDESTRUCT",
+                            "from": "0x00000000000000000000000000000000000000aa",
+                            "to": "0x00000000000000000000000000000000000000ff",
+                            "gas": "0x0",
+                            "gasUsed": "0x0",
+                            "input": "0x"
+                        }
+                    ]
+                }
+            ]
+        }
+        "#;
+        let tree = CallTree::from_tracer_bytes(call_tree)
+            .expect("Error on call tree parsing");
+        let address = |byte: u8| {
+            let mut address = [0u8; 20];
+            address[19] = byte;
+            Address::from(address)
+        };
+
+        assert_eq!(tree.frames().len(), 3);
+        assert_eq!(tree.root().kind(), CallKind::Call);
+        assert_eq!(tree.root().value(), &EvmWord::from(5u8));
+        assert_eq!(tree.root().gas(), 100_000);
+        assert_eq!(tree.root().gas_used(), 21_000);
+        assert_eq!(tree.root().input(), &[1, 2]);
+        assert_eq!(tree.root().calls(), &[1, 2]);
+        assert_eq!(tree[1].output(), &[0xab]);
+        assert_eq!(tree[1].depth(), 2);
+        // Delegated calls run in the context of their caller.
+        assert_eq!(tree[2].to(), Some(&address(0xbb)));
+        assert_eq!(tree[2].address(), Some(&address(0xaa)));
+        assert_eq!(tree[2].parent(), Some(0));
+        assert_eq!(tree[2].error(), Some("execution reverted"));
+        assert!(tree[2].calls().is_empty());
+
+        let step = |opcode: OpcodeId, depth: usize| {
+            ExecutionStep::new(
+                BTreeMap::new(),
+                vec![],
+                Instruction::new(opcode, None),
+                ProgramCounter::from(0),
+                depth,
+                None,
+                GasCost::default(),
+                Gas::default(),
+                GlobalCounter::from(0),
+            )
+        };
+        // The call to the precompile doesn't run any step.
+        let steps = vec![
+            step(OpcodeId::STATICCALL, 1),
+            step(OpcodeId::DELEGATECALL, 1),
+            step(OpcodeId::REVERT, 2),
+            step(OpcodeId::STOP, 1),
+        ];
+        assert_eq!(tree.attribute_steps(&steps), Ok(vec![0, 0, 2, 0]));
+
+        // Frames missing from the tree can't be entered.
+        let steps = vec![
+            step(OpcodeId::STATICCALL, 1),
+            step(OpcodeId::STOP, 2),
+            step(OpcodeId::DELEGATECALL, 1),
+            step(OpcodeId::STOP, 2),
+            step(OpcodeId::CALL, 1),
+            step(OpcodeId::STOP, 2),
+        ];
+        assert_eq!(
+            tree.attribute_steps(&steps),
+            Err(TraceError::CallTreeMismatch { step: 5 })
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Native checks of the rules the State circuit enforces over the sorted
+//! [`MemoryOp`]s, [`StackOp`]s and [`StorageOp`]s of an
+//! [`ExecutionTrace`](super::ExecutionTrace), so that witness bugs show up
+//! without having to run the prover.
+
+use super::{origin::step_of, ExecutionStep};
+use crate::evm::{
+    Address, EvmWord, GlobalCounter, MemoryAddress, StackAddress,
+};
+use crate::operation::{
+    container::OperationContainer, MemoryOp, Operation, StackOp, StorageOp, RW,
+};
+
+/// Location of the state accessed by the operation of a [`StateViolation`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StateAddress {
+    /// Byte of the memory of the call `call_id`.
+    Memory(usize, MemoryAddress),
+    /// Slot of the stack of the call `call_id`.
+    Stack(usize, StackAddress),
+    /// Storage slot `key` of the account located at `address`.
+    Storage(Address, EvmWord),
+}
+
+/// Rule of the State circuit broken by a [`StateViolation`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ViolationKind {
+    /// A read returns a value different from the last one written into (or
+    /// read from) the same location, which is zero for a fresh memory byte.
+    ReadMismatch {
+        /// Value the read should have returned.
+        expected: EvmWord,
+    },
+    /// A stack slot is read before anything is written into it.
+    UninitializedRead,
+    /// The previous value of a storage operation differs from the last value
+    /// of the slot.
+    PrevValueMismatch {
+        /// Previous value the operation should have recorded.
+        expected: EvmWord,
+    },
+}
+
+/// Operation of an [`ExecutionTrace`](super::ExecutionTrace) that breaks one
+/// of the rules the State circuit enforces over it's target.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StateViolation {
+    op: Operation,
+    address: StateAddress,
+    step: usize,
+    kind: ViolationKind,
+}
+
+impl StateViolation {
+    /// Generate a new `StateViolation` instance from it's fields.
+    pub const fn new(
+        op: Operation,
+        address: StateAddress,
+        step: usize,
+        kind: ViolationKind,
+    ) -> StateViolation {
+        StateViolation {
+            op,
+            address,
+            step,
+            kind,
+        }
+    }
+
+    #[inline]
+    /// Return the [`Operation`] that breaks the rule.
+    pub const fn op(&self) -> &Operation {
+        &self.op
+    }
+
+    #[inline]
+    /// Return the [`StateAddress`] accessed by the operation.
+    pub const fn address(&self) -> &StateAddress {
+        &self.address
+    }
+
+    #[inline]
+    /// Return the [`GlobalCounter`] of the operation.
+    pub const fn gc(&self) -> GlobalCounter {
+        self.op.gc()
+    }
+
+    #[inline]
+    /// Return the index of the [`ExecutionStep`] that generated the
+    /// operation.
+    pub const fn step(&self) -> usize {
+        self.step
+    }
+
+    #[inline]
+    /// Return the [`ViolationKind`] of the rule broken.
+    pub const fn kind(&self) -> &ViolationKind {
+        &self.kind
+    }
+}
+
+/// Walks the sorted memory, stack and storage operations of `container`,
+/// returning the ones that break the rules of the State circuit:
+/// - Memory: a read returns the last value of the byte, and fresh bytes start
+///   at zero.
+/// - Stack: a read returns the last value of the slot, which must have been
+///   written before.
+/// - Storage: a read returns the last value of the slot, and the previous value
+///   of every operation is the last value of the slot. The first operation over
+///   a slot sets it's initial value.
+pub(crate) fn check_state_consistency(
+    container: &OperationContainer,
+    steps: &[ExecutionStep],
+) -> Vec<StateViolation> {
+    let mut violations = Vec::new();
+    let mut violation = |op: Operation, address, gc, kind| {
+        violations.push(StateViolation::new(
+            op,
+            address,
+            step_of(steps, gc),
+            kind,
+        ))
+    };
+
+    let mut last: Option<(&MemoryOp, &EvmWord)> = None;
+    for op in container.sorted_memory() {
+        let fresh = EvmWord::from(0u8);
+        let expected = match last {
+            Some((prev, value))
+                if prev.call_id() == op.call_id()
+                    && prev.address() == op.address() =>
+            {
+                value
+            }
+            _ => &fresh,
+        };
+        if op.rw() == RW::READ && op.value() != expected {
+            violation(
+                op.clone().into(),
+                StateAddress::Memory(op.call_id(), op.address().clone()),
+                op.gc(),
+                ViolationKind::ReadMismatch {
+                    expected: expected.clone(),
+                },
+            );
+        }
+        last = Some((op, op.value()));
+    }
+
+    let mut last: Option<&StackOp> = None;
+    for op in container.sorted_stack() {
+        let prev = last.filter(|prev| {
+            prev.call_id() == op.call_id() && prev.address() == op.address()
+        });
+        if op.rw() == RW::READ {
+            let kind = match prev {
+                None => Some(ViolationKind::UninitializedRead),
+                Some(prev) if prev.value() != op.value() => {
+                    Some(ViolationKind::ReadMismatch {
+                        expected: prev.value().clone(),
+                    })
+                }
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                violation(
+                    op.clone().into(),
+                    StateAddress::Stack(op.call_id(), *op.address()),
+                    op.gc(),
+                    kind,
+                );
+            }
+        }
+        last = Some(op);
+    }
+
+    let mut last: Option<&StorageOp> = None;
+    for op in container.sorted_storage() {
+        let address = || StateAddress::Storage(*op.address(), op.key().clone());
+        let prev = last.filter(|prev| {
+            prev.address() == op.address() && prev.key() == op.key()
+        });
+        if let Some(prev) = prev {
+            if op.value_prev() != prev.value() {
+                violation(
+                    op.clone().into(),
+                    address(),
+                    op.gc(),
+                    ViolationKind::PrevValueMismatch {
+                        expected: prev.value().clone(),
+                    },
+                );
+            }
+        }
+        if op.rw() == RW::READ && op.value() != op.value_prev() {
+            violation(
+                op.clone().into(),
+                address(),
+                op.gc(),
+                ViolationKind::ReadMismatch {
+                    expected: op.value_prev().clone(),
+                },
+            );
+        }
+        last = Some(op);
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod consistency_tests {
+    use super::*;
+    use crate::exec_trace::ExecutionTrace;
+    use crate::test_util;
+
+    #[test]
+    fn consistent_and_broken_traces() {
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["40"], "opcode": "PUSH1 40", "pc": 0 },
+            { "memory": {}, "stack": ["40", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": [], "opcode": "SSTORE", "pc": 4 },
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 5 },
+            { "memory": {}, "stack": ["40"], "opcode": "SLOAD", "pc": 7 }
+        ]
+        "#;
+        let block_ctants = test_util::block_ctants();
+        let trace = ExecutionTrace::from_trace_bytes(
+            input_trace.as_bytes(),
+            block_ctants,
+        )
+        .expect("Error on trace generation");
+        assert_eq!(trace.state_violations(), vec![]);
+
+        // Break the witness: a read of a fresh memory byte that isn't zero,
+        // a stack read with no write before it and a storage read that
+        // doesn't match the slot.
+        let mut container = trace.container().clone();
+        let last_gc = trace.steps()[4].gc().0;
+        container.insert(MemoryOp::new(
+            RW::READ,
+            GlobalCounter(last_gc + 10),
+            1,
+            MemoryAddress::from(0x100),
+            EvmWord::from(1u8),
+        ));
+        container.insert(StackOp::new(
+            RW::READ,
+            GlobalCounter(last_gc + 11),
+            1,
+            StackAddress::from(1000),
+            EvmWord::from(1u8),
+        ));
+        container.insert(StorageOp::new(
+            RW::WRITE,
+            GlobalCounter(3),
+            Address::zero(),
+            EvmWord::from(1u8),
+            EvmWord::from(5u8),
+            EvmWord::from(0u8),
+        ));
+        container.insert(StorageOp::new(
+            RW::READ,
+            GlobalCounter(last_gc + 12),
+            Address::zero(),
+            EvmWord::from(1u8),
+            EvmWord::from(6u8),
+            EvmWord::from(6u8),
+        ));
+
+        let violations = check_state_consistency(&container, trace.steps());
+        let found: Vec<(StateAddress, usize, ViolationKind)> = violations
+            .iter()
+            .map(|v| (v.address().clone(), v.step(), v.kind().clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    StateAddress::Memory(1, MemoryAddress::from(0x100)),
+                    4,
+                    ViolationKind::ReadMismatch {
+                        expected: EvmWord::from(0u8)
+                    }
+                ),
+                (
+                    StateAddress::Stack(1, StackAddress::from(1000)),
+                    4,
+                    ViolationKind::UninitializedRead
+                ),
+                (
+                    StateAddress::Storage(Address::zero(), EvmWord::from(1u8)),
+                    4,
+                    ViolationKind::PrevValueMismatch {
+                        expected: EvmWord::from(5u8)
+                    }
+                ),
+            ]
+        );
+        assert_eq!(violations[0].gc(), GlobalCounter(last_gc + 10));
+    }
+}
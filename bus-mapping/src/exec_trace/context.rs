@@ -0,0 +1,382 @@
+//! Definition of the transaction and call contexts in which the
+//! [`ExecutionStep`](super::ExecutionStep)s of an
+//! [`ExecutionTrace`](super::ExecutionTrace) are executed.
+
+use crate::evm::{create_address, Address, EvmWord, OpcodeId};
+use crate::operation::ContextField;
+use alloc::collections::BTreeMap;
+
+/// Definition of all of the data related to the transaction that generated
+/// an [`ExecutionTrace`](super::ExecutionTrace).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TxContext {
+    from: Address,
+    to: Option<Address>,
+    nonce: u64,
+    value: EvmWord,
+    gas_price: EvmWord,
+    gas: u64,
+    input: Vec<u8>,
+    access_list: Vec<(Address, Vec<EvmWord>)>,
+}
+
+impl Default for TxContext {
+    /// Returns the context assumed for traces that come without transaction
+    /// data: a call from and to the zero address with no value nor input.
+    fn default() -> Self {
+        TxContext::new(
+            Address::zero(),
+            Some(Address::zero()),
+            0,
+            EvmWord::from(0u8),
+            EvmWord::from(0u8),
+            0,
+            vec![],
+        )
+    }
+}
+
+impl TxContext {
+    #[allow(clippy::too_many_arguments)]
+    /// Generates a new `TxContext` instance from it's fields.
+    /// A `to` equal to `None` marks the transaction as a contract creation
+    /// where `input` is the init code to run.
+    pub fn new(
+        from: Address,
+        to: Option<Address>,
+        nonce: u64,
+        value: EvmWord,
+        gas_price: EvmWord,
+        gas: u64,
+        input: Vec<u8>,
+    ) -> TxContext {
+        TxContext {
+            from,
+            to,
+            nonce,
+            value,
+            gas_price,
+            gas,
+            input,
+            access_list: vec![],
+        }
+    }
+
+    /// Returns the `TxContext` with the `access_list` of the transaction
+    /// ([EIP-2930](https://eips.ethereum.org/EIPS/eip-2930)): the accounts,
+    /// each together with some of it's storage keys, that start as accessed.
+    pub fn with_access_list(
+        mut self,
+        access_list: Vec<(Address, Vec<EvmWord>)>,
+    ) -> TxContext {
+        self.access_list = access_list;
+        self
+    }
+
+    #[inline]
+    /// Return the sender of the transaction.
+    pub const fn from(&self) -> &Address {
+        &self.from
+    }
+
+    #[inline]
+    /// Return the receiver of the transaction (`None` for contract
+    /// creations).
+    pub const fn to(&self) -> Option<&Address> {
+        self.to.as_ref()
+    }
+
+    #[inline]
+    /// Return the nonce of the transaction.
+    pub const fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    #[inline]
+    /// Return the value transferred by the transaction.
+    pub const fn value(&self) -> &EvmWord {
+        &self.value
+    }
+
+    #[inline]
+    /// Return the gas price of the transaction.
+    pub const fn gas_price(&self) -> &EvmWord {
+        &self.gas_price
+    }
+
+    #[inline]
+    /// Return the gas limit of the transaction.
+    pub const fn gas(&self) -> u64 {
+        self.gas
+    }
+
+    #[inline]
+    /// Return the input of the transaction (the calldata or the init code in
+    /// case of a contract creation).
+    pub fn input(&self) -> &[u8] {
+        &self.input
+    }
+
+    #[inline]
+    /// Return the access list of the transaction, which is empty for
+    /// transactions without one.
+    pub fn access_list(&self) -> &[(Address, Vec<EvmWord>)] {
+        &self.access_list
+    }
+
+    /// Returns true if the transaction creates a contract.
+    pub const fn is_create(&self) -> bool {
+        self.to.is_none()
+    }
+
+    /// Returns the [`CallContext`] of the root call of the transaction.
+    pub(crate) fn root_call(&self, call_id: usize) -> CallContext {
+        match self.to {
+            Some(to) => CallContext::new(
+                call_id,
+                CallKind::Call,
+                1,
+                self.from,
+                to,
+                self.value.clone(),
+                self.input.clone(),
+                vec![],
+            ),
+            None => CallContext::new(
+                call_id,
+                CallKind::Create,
+                1,
+                self.from,
+                create_address(&self.from, self.nonce),
+                self.value.clone(),
+                vec![],
+                self.input.clone(),
+            ),
+        }
+    }
+}
+
+/// Kind of call that opened a [`CallContext`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CallKind {
+    /// Frame opened by a `CALL` or by a transaction to an account.
+    Call,
+    /// Frame opened by a `CALLCODE`.
+    CallCode,
+    /// Frame opened by a `DELEGATECALL`.
+    DelegateCall,
+    /// Frame opened by a `STATICCALL`.
+    StaticCall,
+    /// Frame opened by a `CREATE` or by a contract creation transaction.
+    Create,
+    /// Frame opened by a `CREATE2`.
+    Create2,
+}
+
+impl CallKind {
+    /// Returns the kind of the frames opened by `opcode`, if it's a call or
+    /// create opcode.
+    pub const fn from_opcode(opcode: OpcodeId) -> Option<CallKind> {
+        match opcode {
+            OpcodeId::CALL => Some(CallKind::Call),
+            OpcodeId::CALLCODE => Some(CallKind::CallCode),
+            OpcodeId::DELEGATECALL => Some(CallKind::DelegateCall),
+            OpcodeId::STATICCALL => Some(CallKind::StaticCall),
+            OpcodeId::CREATE => Some(CallKind::Create),
+            OpcodeId::CREATE2 => Some(CallKind::Create2),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the frame runs init code which deploys a contract.
+    pub const fn is_create(&self) -> bool {
+        matches!(self, CallKind::Create | CallKind::Create2)
+    }
+}
+
+/// Represents a call frame of the EVM: every frame has it's own stack and
+/// memory and executes the code of a single account.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CallContext {
+    call_id: usize,
+    kind: CallKind,
+    depth: usize,
+    caller: Address,
+    address: Address,
+    value: EvmWord,
+    calldata: Vec<u8>,
+    code: Vec<u8>,
+    is_reverted: bool,
+}
+
+impl CallContext {
+    #[allow(clippy::too_many_arguments)]
+    /// Generates a new `CallContext` instance from it's fields.
+    pub fn new(
+        call_id: usize,
+        kind: CallKind,
+        depth: usize,
+        caller: Address,
+        address: Address,
+        value: EvmWord,
+        calldata: Vec<u8>,
+        code: Vec<u8>,
+    ) -> CallContext {
+        CallContext {
+            call_id,
+            kind,
+            depth,
+            caller,
+            address,
+            value,
+            calldata,
+            code,
+            is_reverted: false,
+        }
+    }
+
+    #[inline]
+    /// Return the identifier of the call, which is unique inside of an
+    /// [`ExecutionTrace`](super::ExecutionTrace).
+    pub const fn call_id(&self) -> usize {
+        self.call_id
+    }
+
+    #[inline]
+    /// Return the [`CallKind`] of the call.
+    pub const fn kind(&self) -> CallKind {
+        self.kind
+    }
+
+    #[inline]
+    /// Return the depth of the call (the root call has depth 1).
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+
+    #[inline]
+    /// Return the address of the account that opened the call.
+    pub const fn caller(&self) -> &Address {
+        &self.caller
+    }
+
+    #[inline]
+    /// Return the address of the account whose context the call runs in.
+    pub const fn address(&self) -> &Address {
+        &self.address
+    }
+
+    #[inline]
+    /// Return the value sent along with the call.
+    pub const fn value(&self) -> &EvmWord {
+        &self.value
+    }
+
+    #[inline]
+    /// Return the calldata of the call.
+    pub fn calldata(&self) -> &[u8] {
+        &self.calldata
+    }
+
+    #[inline]
+    /// Return the code run by the call (if it's known). For contract
+    /// creations this is the init code.
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    #[inline]
+    /// Return true if the state changes done by the call were reverted,
+    /// either because the call itself or one of it's callers reverted.
+    pub const fn is_reverted(&self) -> bool {
+        self.is_reverted
+    }
+
+    /// Marks the call as reverted.
+    pub(crate) fn set_reverted(&mut self) {
+        self.is_reverted = true;
+    }
+
+    /// Sets the code run by the call.
+    pub(crate) fn set_code(&mut self, code: Vec<u8>) {
+        self.code = code;
+    }
+}
+
+/// Read-only table holding the entries of the [`TxContext`] of a transaction
+/// and of the [`CallContext`] of each one of it's calls, which are the values
+/// that the environment opcodes (`ADDRESS`, `CALLER`, `CALLDATASIZE`...) look
+/// up through [`ContextOp`](crate::operation::ContextOp)s.
+///
+/// Transaction entries are shared by all of the calls, while the
+/// [dynamic](ContextField::is_dynamic) fields aren't part of the table since
+/// they change from step to step.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ContextTable {
+    tx_entries: BTreeMap<ContextField, EvmWord>,
+    call_entries: BTreeMap<(usize, ContextField), EvmWord>,
+}
+
+impl ContextTable {
+    /// Generates a new `ContextTable` holding the entries of `tx_ctx`.
+    pub fn new(tx_ctx: &TxContext) -> ContextTable {
+        let mut tx_entries = BTreeMap::new();
+        tx_entries.insert(ContextField::Origin, tx_ctx.from().to_word());
+        tx_entries.insert(ContextField::GasPrice, tx_ctx.gas_price().clone());
+        ContextTable {
+            tx_entries,
+            call_entries: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the entries of `call` to the table. The code size is only added
+    /// when the code of the call is known.
+    pub(crate) fn insert_call(&mut self, call: &CallContext) {
+        let call_id = call.call_id();
+        let entries = [
+            (ContextField::Address, call.address().to_word()),
+            (ContextField::Caller, call.caller().to_word()),
+            (ContextField::Value, call.value().clone()),
+            (
+                ContextField::CalldataSize,
+                EvmWord::from(call.calldata().len()),
+            ),
+        ];
+        self.call_entries.extend(
+            entries
+                .iter()
+                .cloned()
+                .map(|(field, value)| ((call_id, field), value)),
+        );
+        if !call.code().is_empty() {
+            self.call_entries.insert(
+                (call_id, ContextField::CodeSize),
+                EvmWord::from(call.code().len()),
+            );
+        }
+    }
+
+    /// Returns the value of the entry `field` of the call `call_id`.
+    pub fn get(&self, call_id: usize, field: ContextField) -> Option<&EvmWord> {
+        if field.is_tx_field() {
+            self.tx_entries.get(&field)
+        } else {
+            self.call_entries.get(&(call_id, field))
+        }
+    }
+
+    /// Returns the entries of the transaction.
+    pub fn tx_entries(&self) -> impl Iterator<Item = (ContextField, &EvmWord)> {
+        self.tx_entries.iter().map(|(field, value)| (*field, value))
+    }
+
+    /// Returns the entries of the calls, ordered by `call_id` and
+    /// [`ContextField`].
+    pub fn call_entries(
+        &self,
+    ) -> impl Iterator<Item = (usize, ContextField, &EvmWord)> {
+        self.call_entries
+            .iter()
+            .map(|((call_id, field), value)| (*call_id, *field, value))
+    }
+}
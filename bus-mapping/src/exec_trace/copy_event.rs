@@ -0,0 +1,125 @@
+//! Definition of the records of the byte ranges copied into memory by the
+//! `CALLDATACOPY`, `CODECOPY`, `EXTCODECOPY` and `RETURNDATACOPY` steps of an
+//! [`ExecutionTrace`](super::ExecutionTrace).
+
+use crate::evm::{EvmWord, GlobalCounter};
+
+/// Source of the bytes copied by a [`CopyEvent`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CopySource {
+    /// Calldata of the call that executes the copy (`CALLDATACOPY`).
+    Calldata,
+    /// Code whose hash is held (`CODECOPY` and `EXTCODECOPY`).
+    Code(EvmWord),
+    /// Data returned by the last sub-call of the call that executes the copy
+    /// (`RETURNDATACOPY`).
+    ReturnData,
+}
+
+/// Single byte copied by a [`CopyEvent`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CopyByte {
+    value: u8,
+    is_pad: bool,
+    gc: GlobalCounter,
+}
+
+impl CopyByte {
+    /// Generate a new `CopyByte` instance from it's fields.
+    pub const fn new(value: u8, is_pad: bool, gc: GlobalCounter) -> CopyByte {
+        CopyByte { value, is_pad, gc }
+    }
+
+    #[inline]
+    /// Return the value of the byte.
+    pub const fn value(&self) -> u8 {
+        self.value
+    }
+
+    #[inline]
+    /// Return true if the byte is past the end of the source, so that it's
+    /// a zero used as padding instead of a byte read from it.
+    pub const fn is_pad(&self) -> bool {
+        self.is_pad
+    }
+
+    #[inline]
+    /// Return the [`GlobalCounter`] of the
+    /// [`MemoryOp`](crate::operation::MemoryOp) that writes the byte into
+    /// memory.
+    pub const fn gc(&self) -> GlobalCounter {
+        self.gc
+    }
+}
+
+/// Record of the bytes copied into memory by a single step, which a copy
+/// circuit can consume to prove that the
+/// [`MemoryOp`](crate::operation::MemoryOp) writes of the step match the
+/// bytes read from the source.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CopyEvent {
+    step_gc: GlobalCounter,
+    call_id: usize,
+    src: CopySource,
+    src_offset: usize,
+    dst_offset: usize,
+    bytes: Vec<CopyByte>,
+}
+
+impl CopyEvent {
+    /// Generate a new `CopyEvent` instance from it's fields.
+    pub fn new(
+        step_gc: GlobalCounter,
+        call_id: usize,
+        src: CopySource,
+        src_offset: usize,
+        dst_offset: usize,
+        bytes: Vec<CopyByte>,
+    ) -> CopyEvent {
+        CopyEvent {
+            step_gc,
+            call_id,
+            src,
+            src_offset,
+            dst_offset,
+            bytes,
+        }
+    }
+
+    #[inline]
+    /// Return the [`GlobalCounter`] of the step that performs the copy.
+    pub const fn step_gc(&self) -> GlobalCounter {
+        self.step_gc
+    }
+
+    #[inline]
+    /// Return the `call_id` of the call that performs the copy, into whose
+    /// memory the bytes are written.
+    pub const fn call_id(&self) -> usize {
+        self.call_id
+    }
+
+    #[inline]
+    /// Return the [`CopySource`] the bytes are read from.
+    pub const fn src(&self) -> &CopySource {
+        &self.src
+    }
+
+    #[inline]
+    /// Return the offset of the first byte read from the source.
+    pub const fn src_offset(&self) -> usize {
+        self.src_offset
+    }
+
+    #[inline]
+    /// Return the memory address the first byte is written into.
+    pub const fn dst_offset(&self) -> usize {
+        self.dst_offset
+    }
+
+    #[inline]
+    /// Return the bytes copied, in order.
+    pub fn bytes(&self) -> &[CopyByte] {
+        &self.bytes
+    }
+}
@@ -1,27 +1,38 @@
 // Doc this
 
+use crate::evm::gas::can_expand_memory;
 use crate::evm::{
-    EvmWord, GlobalCounter, Instruction, MemoryAddress, ProgramCounter,
-    StackAddress, MEM_ADDR_ZERO,
+    EvmWord, Gas, GasCost, GlobalCounter, Instruction, MemoryAddress,
+    ProgramCounter, StackAddress, MEM_ADDR_ZERO,
 };
 use crate::{
-    error::Error, evm::opcodes::Opcode,
-    operation::container::OperationContainer,
+    error::Error,
+    evm::opcodes::{Opcode, OpcodeRegistry},
+    operation::{container::OperationContainer, Operation},
 };
 use alloc::collections::BTreeMap;
-use core::{convert::TryFrom, str::FromStr};
+use core::{convert::TryFrom, fmt, str::FromStr};
 use halo2::arithmetic::FieldExt;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use super::OperationRef;
+use super::{OperationRef, StepSnapshots, TraceState};
 
 /// Represents a single step of an [`ExecutionTrace`](super::ExecutionTrace). It
 /// contains all of the information relative to this step:
-/// - Memory view at current execution step.`
+/// - Memory view at current execution step.
 /// - Stack view at current execution step.
+///
+///   Both views are taken before or after the [`Instruction`] runs as given
+///   by the [`SnapshotConvention`](super::SnapshotConvention) of the trace.
 /// - EVM [`Instruction`] executed in this step.
 /// - [`ProgramCounter`] relative to this step.
+/// - Depth of the call frame this step runs in (the root call has depth 1).
+/// - Size in words of the memory of it's frame before and after the step runs,
+///   tracked from the memory accessed by the steps of the frame.
+/// - [`Gas`] left before executing the step and the [`GasCost`] of it's
+///   [`Instruction`] as reported by the trace.
+/// - Gas refund counter of the transaction before executing the step.
 /// - [`GlobalCounter`] assigned to this step by the program.
 /// - Bus Mapping instances containing references to all of the
 ///   [`Operation`](crate::operation::Operation)s generated by this step.
@@ -31,19 +42,46 @@ pub struct ExecutionStep {
     stack: Vec<EvmWord>,
     instruction: Instruction,
     pc: ProgramCounter,
+    depth: usize,
+    memory_size_before: u64,
+    memory_size_after: u64,
+    gas: Option<Gas>,
+    gas_cost: GasCost,
+    refund: Gas,
     gc: GlobalCounter,
     // Holds refs to the container with the related mem ops.
     bus_mapping_instance: Vec<OperationRef>,
 }
 
+impl fmt::Display for ExecutionStep {
+    /// Formats the step as `<pc> <instruction>` followed by it's depth, the
+    /// gas left (when the trace reports it) and it's gas cost.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} (depth {}, ",
+            self.pc, self.instruction, self.depth
+        )?;
+        if let Some(gas) = self.gas {
+            write!(f, "gas {}, ", gas)?;
+        }
+        write!(f, "cost {})", self.gas_cost)
+    }
+}
+
 impl ExecutionStep {
     /// Generate a new `ExecutionStep` from it's fields but with an empty
     /// bus-mapping instance vec.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         memory: BTreeMap<MemoryAddress, EvmWord>,
         stack: Vec<EvmWord>,
         instruction: Instruction,
         pc: ProgramCounter,
+        depth: usize,
+        gas: Option<Gas>,
+        gas_cost: GasCost,
+        refund: Gas,
         gc: GlobalCounter,
     ) -> Self {
         ExecutionStep {
@@ -51,6 +89,12 @@ impl ExecutionStep {
             stack,
             instruction,
             pc,
+            depth,
+            memory_size_before: 0,
+            memory_size_after: 0,
+            gas,
+            gas_cost,
+            refund,
             gc,
             bus_mapping_instance: Vec::new(),
         }
@@ -67,6 +111,16 @@ impl ExecutionStep {
         &self.stack
     }
 
+    /// Sets the Memory and Stack views of this `ExecutionStep`.
+    pub(crate) fn set_views(
+        &mut self,
+        memory: BTreeMap<MemoryAddress, EvmWord>,
+        stack: Vec<EvmWord>,
+    ) {
+        self.memory = memory;
+        self.stack = stack;
+    }
+
     /// Returns the stack pointer at this execution step height.
     pub fn stack_addr(&self) -> StackAddress {
         // Stack has 1024 slots.
@@ -83,6 +137,36 @@ impl ExecutionStep {
             .unwrap_or_else(|| &MEM_ADDR_ZERO)
     }
 
+    /// Returns `size` bytes of the Memory view of this `ExecutionStep`
+    /// starting at the byte `offset`. Bytes that aren't part of the view are
+    /// zero.
+    ///
+    /// Returns `None` if the step can't pay for expanding the memory up to the
+    /// end of the region with the gas left before it (or the
+    /// [`DEFAULT_GAS_LIMIT`](crate::evm::gas::DEFAULT_GAS_LIMIT) if the trace
+    /// doesn't report it), which is the bound of the memory it can access.
+    pub fn memory_bytes(&self, offset: usize, size: usize) -> Option<Vec<u8>> {
+        let end = offset.checked_add(size)?;
+        if !can_expand_memory(self.gas, self.memory_size_before, end as u64) {
+            return None;
+        }
+        Some(self.read_memory(offset, size))
+    }
+
+    /// Returns `size` bytes of the Memory view of this `ExecutionStep`
+    /// starting at the byte `offset`, a region that the caller checked to be
+    /// accessible.
+    pub(crate) fn read_memory(&self, offset: usize, size: usize) -> Vec<u8> {
+        (offset..offset + size)
+            .map(|byte_addr| {
+                self.memory
+                    .get(&MemoryAddress::from(byte_addr - byte_addr % 32))
+                    .map(|word| word.to_be_bytes()[byte_addr % 32])
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
     /// Returns the [`Instruction`] executed at this step.
     pub const fn instruction(&self) -> &Instruction {
         &self.instruction
@@ -93,6 +177,49 @@ impl ExecutionStep {
         self.pc
     }
 
+    /// Returns the depth of the call frame this step runs in.
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the size in 32-byte words of the memory of this step's frame
+    /// before it's [`Instruction`] runs.
+    pub const fn memory_size_before(&self) -> u64 {
+        self.memory_size_before
+    }
+
+    /// Returns the size in 32-byte words of the memory of this step's frame
+    /// once it's [`Instruction`] has been executed, which is larger than the
+    /// size before it only if the step expands the memory.
+    pub const fn memory_size_after(&self) -> u64 {
+        self.memory_size_after
+    }
+
+    /// Sets the sizes in words of the memory of this step's frame before and
+    /// after it runs.
+    pub(crate) fn set_memory_size(&mut self, before: u64, after: u64) {
+        self.memory_size_before = before;
+        self.memory_size_after = after;
+    }
+
+    /// Returns the [`Gas`] left before executing this step, if the trace
+    /// reports it.
+    pub const fn gas(&self) -> Option<Gas> {
+        self.gas
+    }
+
+    /// Returns the [`GasCost`] of this step's [`Instruction`] reported by the
+    /// trace.
+    pub const fn gas_cost(&self) -> GasCost {
+        self.gas_cost
+    }
+
+    /// Returns the gas refund counter of the transaction before executing
+    /// this step, as reported by the trace.
+    pub const fn refund(&self) -> Gas {
+        self.refund
+    }
+
     /// Returns the [`GlobalCounter`] associated to this step's `Instuction`
     /// execution.
     pub const fn gc(&self) -> GlobalCounter {
@@ -117,21 +244,72 @@ impl ExecutionStep {
         &mut self.bus_mapping_instance
     }
 
+    /// Registers an [`Operation`] into the [`OperationContainer`] and then adds
+    /// a reference to the stored operation ([`OperationRef`]) inside the
+    /// bus-mapping instance of the step.
+    pub fn add_op(
+        &mut self,
+        container: &mut OperationContainer,
+        op: impl Into<Operation>,
+    ) {
+        let op_ref = container.insert(op.into());
+        self.bus_mapping_instance.push(op_ref);
+    }
+
+    /// Returns the [`GlobalCounter`] that the next [`Operation`] generated by
+    /// this step has to be assigned.
+    pub fn next_op_gc(&self) -> GlobalCounter {
+        GlobalCounter::from(self.gc.0 + self.bus_mapping_instance.len() + 1)
+    }
+
     /// Given a mutable reference to an [`OperationContainer`], generate all of
-    /// it's [`Instruction`]-related Memory, Stack and Storage ops, and register
-    /// them in the container. This function will not only add the ops to
-    /// the [`OperationContainer`] but also get it's [`OperationRef`]s and add
+    /// it's [`Instruction`]-related Memory, Stack, Storage and Account ops
+    /// with the [`Opcode`] registered for it in `registry`, and register them
+    /// in the container. This function will not only add the ops to the
+    /// [`OperationContainer`] but also get it's [`OperationRef`]s and add
     /// them to the bus-mapping instance of the step.
     ///
     /// ## Returns the #operations added by the
     /// [`OpcodeId`](crate::evm::OpcodeId) into the container.
+    ///
+    /// Fails with [`Error::OpcodeNotRegistered`] if `registry` has no
+    /// [`Opcode`] registered for the step's
+    /// [`OpcodeId`](crate::evm::OpcodeId).
     pub(crate) fn gen_associated_ops<F: FieldExt>(
         &mut self,
+        registry: &OpcodeRegistry,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
         container: &mut OperationContainer,
-    ) -> usize {
-        self.instruction()
-            .opcode_id()
-            .gen_associated_ops(self, container)
+    ) -> Result<usize, Error> {
+        Ok(self
+            .opcode(registry)?
+            .gen_associated_ops(state, snapshots, self, container))
+    }
+
+    /// Computes the gas cost of this step with the [`Opcode`] registered for
+    /// it's [`Instruction`] in `registry`.
+    ///
+    /// Fails with [`Error::OpcodeNotRegistered`] if `registry` has no
+    /// [`Opcode`] registered for the step's
+    /// [`OpcodeId`](crate::evm::OpcodeId).
+    pub(crate) fn gen_gas_cost(
+        &self,
+        registry: &OpcodeRegistry,
+        state: &mut TraceState,
+        snapshots: StepSnapshots<'_>,
+    ) -> Result<GasCost, Error> {
+        Ok(self.opcode(registry)?.gen_gas_cost(state, snapshots, self))
+    }
+
+    fn opcode<'r>(
+        &self,
+        registry: &'r OpcodeRegistry,
+    ) -> Result<&'r dyn Opcode, Error> {
+        let opcode_id = self.instruction().opcode_id();
+        registry
+            .get(&opcode_id)
+            .ok_or(Error::OpcodeNotRegistered(opcode_id))
     }
 }
 
@@ -141,30 +319,81 @@ impl<'a> TryFrom<&ParsedExecutionStep<'a>> for ExecutionStep {
     fn try_from(
         parsed_step: &ParsedExecutionStep<'a>,
     ) -> Result<Self, Self::Error> {
-        // Memory part
-        let mut mem_map = BTreeMap::new();
-        parsed_step.memory.iter().try_for_each(|(mem_addr, word)| {
-            mem_map.insert(
-                MemoryAddress::from_str(mem_addr)?,
-                EvmWord::from_str(word)?,
-            );
-            Ok(())
-        })?;
-
-        // Stack part
-        let mut stack = vec![];
-        parsed_step.stack.iter().try_for_each(|word| {
-            stack.push(EvmWord::from_str(word)?);
-            Ok(())
-        })?;
-
-        Ok(ExecutionStep::new(
-            mem_map,
-            stack,
-            Instruction::from_str(parsed_step.opcode)?,
-            parsed_step.pc,
+        // Full traces hold both views of every step.
+        match parsed_step.slim_step()? {
+            (step, MissingViews::NONE) => Ok(step),
+            _ => Err(Error::SerdeError),
+        }
+    }
+}
+
+/// Views of an [`ExecutionStep`] that it's trace lacks, like the ones of the
+/// traces generated with the `disableMemory` and `disableStack` options of
+/// the tracer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct MissingViews {
+    pub(crate) memory: bool,
+    pub(crate) stack: bool,
+}
+
+impl MissingViews {
+    /// Views of a step that lacks none of them.
+    pub(crate) const NONE: MissingViews = MissingViews {
+        memory: false,
+        stack: false,
+    };
+}
+
+impl<'a> ParsedExecutionStep<'a> {
+    /// Generates the [`ExecutionStep`] of a trace that may lack it's memory
+    /// or stack, along with the [`MissingViews`] of the step, which are left
+    /// empty.
+    pub(crate) fn slim_step(
+        &self,
+    ) -> Result<(ExecutionStep, MissingViews), Error> {
+        let memory = self
+            .memory
+            .as_ref()
+            .map(|memory| {
+                memory
+                    .iter()
+                    .map(|(mem_addr, word)| {
+                        Ok((
+                            MemoryAddress::from_str(mem_addr)?,
+                            EvmWord::from_str(word)?,
+                        ))
+                    })
+                    .collect::<Result<BTreeMap<MemoryAddress, EvmWord>, Error>>(
+                    )
+            })
+            .transpose()?;
+        let stack = self
+            .stack
+            .as_ref()
+            .map(|words| {
+                words
+                    .iter()
+                    .map(|word| EvmWord::from_str(word))
+                    .collect::<Result<Vec<EvmWord>, Error>>()
+            })
+            .transpose()?;
+        let missing = MissingViews {
+            memory: memory.is_none(),
+            stack: stack.is_none(),
+        };
+
+        let step = ExecutionStep::new(
+            memory.unwrap_or_default(),
+            stack.unwrap_or_default(),
+            Instruction::from_str(self.opcode)?,
+            self.pc,
+            self.depth,
+            self.gas,
+            self.gas_cost,
+            self.refund,
             0.into(),
-        ))
+        );
+        Ok((step, missing))
     }
 }
 
@@ -173,10 +402,26 @@ impl<'a> TryFrom<&ParsedExecutionStep<'a>> for ExecutionStep {
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[doc(hidden)]
 pub(crate) struct ParsedExecutionStep<'a> {
-    memory: HashMap<&'a str, &'a str>,
-    stack: Vec<&'a str>,
+    #[serde(default, borrow)]
+    memory: Option<HashMap<&'a str, &'a str>>,
+    #[serde(default, borrow)]
+    stack: Option<Vec<&'a str>>,
     opcode: &'a str,
     pc: ProgramCounter,
+    #[serde(default = "root_depth")]
+    depth: usize,
+    #[serde(default)]
+    gas: Option<Gas>,
+    #[serde(default, rename = "gasCost")]
+    gas_cost: GasCost,
+    #[serde(default)]
+    refund: Gas,
+}
+
+/// Depth assigned to the steps of traces that don't specify it, which are
+/// considered to run in the root call.
+const fn root_depth() -> usize {
+    1
 }
 
 #[cfg(test)]
@@ -196,7 +441,9 @@ mod tests {
             },
             "stack": [],
             "opcode": "JUMPDEST",
-            "pc": 53
+            "pc": 53,
+            "gas": 82,
+            "gasCost": 1
         }
         "#;
 
@@ -226,6 +473,10 @@ mod tests {
                 vec![],
                 Instruction::new(OpcodeId::JUMPDEST, None),
                 ProgramCounter(53),
+                1,
+                Some(Gas(82)),
+                GasCost::ONE,
+                Gas(0),
                 GlobalCounter(0),
             )
         };
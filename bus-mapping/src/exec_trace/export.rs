@@ -0,0 +1,296 @@
+//! Rendering of the sorted operations and the steps of an
+//! [`ExecutionTrace`](super::ExecutionTrace) as tables of aligned text,
+//! Markdown or CSV, to review witnesses by hand or load them into a
+//! spreadsheet.
+
+use super::{ExecutionStep, OpOrigin};
+use crate::operation::{MemoryOp, StackOp, StorageOp};
+use itertools::Itertools;
+
+/// Format in which an [`ExecutionTrace`](super::ExecutionTrace) exports it's
+/// tables.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportFormat {
+    /// Columns padded with spaces to the width of their widest cell, with a
+    /// dashed line below the headers.
+    Text,
+    /// GitHub flavoured Markdown table.
+    Markdown,
+    /// Comma separated values, quoted when needed as specified by RFC 4180.
+    Csv,
+}
+
+/// Operation that can be exported as a row of a table.
+pub(crate) trait ExportRow {
+    /// Headers of the columns of the table.
+    const HEADERS: &'static [&'static str];
+
+    /// Returns the cells of the row of the operation.
+    fn row(&self) -> Vec<String>;
+}
+
+impl ExportRow for MemoryOp {
+    const HEADERS: &'static [&'static str] =
+        &["call_id", "key", "val", "rw", "gc"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.call_id().to_string(),
+            self.address().to_string(),
+            self.value().to_string(),
+            self.rw().to_string(),
+            self.gc().to_string(),
+        ]
+    }
+}
+
+impl ExportRow for StackOp {
+    const HEADERS: &'static [&'static str] =
+        &["call_id", "key", "val", "rw", "gc"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.call_id().to_string(),
+            self.address().to_string(),
+            self.value().to_string(),
+            self.rw().to_string(),
+            self.gc().to_string(),
+        ]
+    }
+}
+
+impl ExportRow for StorageOp {
+    const HEADERS: &'static [&'static str] =
+        &["address", "key", "val", "val_prev", "rw", "gc"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.address().to_string(),
+            self.key().to_string(),
+            self.value().to_string(),
+            self.value_prev().to_string(),
+            self.rw().to_string(),
+            self.gc().to_string(),
+        ]
+    }
+}
+
+/// Table of cells ready to be rendered in any [`ExportFormat`].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Builds the table of `ops`, adding a `note` column with the `pc` and
+    /// opcode of the step that generated each operation when `annotate` is
+    /// set.
+    pub(crate) fn from_ops<'a, T, I, O>(
+        ops: I,
+        annotate: bool,
+        origin: O,
+    ) -> Table
+    where
+        T: ExportRow + 'a,
+        I: IntoIterator<Item = &'a T>,
+        O: Fn(&T) -> Option<OpOrigin>,
+    {
+        let mut headers: Vec<String> =
+            T::HEADERS.iter().map(|h| h.to_string()).collect();
+        if annotate {
+            headers.push("note".to_string());
+        }
+        let rows = ops
+            .into_iter()
+            .map(|op| {
+                let mut row = op.row();
+                if annotate {
+                    row.push(
+                        origin(op)
+                            .map(|o| format!("{} {}", o.pc(), o.opcode()))
+                            .unwrap_or_default(),
+                    );
+                }
+                row
+            })
+            .collect();
+        Table { headers, rows }
+    }
+
+    /// Builds the table of `steps`, with one row per step holding it's
+    /// counters, instruction, gas and the stack after it.
+    pub(crate) fn from_steps(steps: &[ExecutionStep]) -> Table {
+        let headers = [
+            "gc",
+            "pc",
+            "instruction",
+            "depth",
+            "gas",
+            "gas_cost",
+            "stack",
+        ]
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+        let rows = steps
+            .iter()
+            .map(|step| {
+                vec![
+                    step.gc().to_string(),
+                    step.pc().to_string(),
+                    step.instruction().to_string(),
+                    step.depth().to_string(),
+                    step.gas().map_or_else(String::new, |gas| gas.to_string()),
+                    step.gas_cost().to_string(),
+                    format!("[{}]", step.stack().iter().join(", ")),
+                ]
+            })
+            .collect();
+        Table { headers, rows }
+    }
+
+    /// Renders the table in the given [`ExportFormat`], ending every line
+    /// with a newline.
+    pub(crate) fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Text => {
+                let widths: Vec<usize> = (0..self.headers.len())
+                    .map(|col| {
+                        std::iter::once(&self.headers)
+                            .chain(self.rows.iter())
+                            .map(|row| row[col].len())
+                            .max()
+                            .unwrap_or(0)
+                    })
+                    .collect();
+                let pad = |row: &Vec<String>| {
+                    row.iter()
+                        .zip(widths.iter())
+                        .map(|(cell, width)| format!("{:<1$}", cell, width))
+                        .join("  ")
+                        .trim_end()
+                        .to_string()
+                };
+                let dashes =
+                    widths.iter().map(|width| "-".repeat(*width)).join("  ");
+                std::iter::once(pad(&self.headers))
+                    .chain(std::iter::once(dashes))
+                    .chain(self.rows.iter().map(pad))
+                    .map(|line| line + "\n")
+                    .collect()
+            }
+            ExportFormat::Markdown => {
+                let line = |row: &Vec<String>| {
+                    format!(
+                        "| {} |\n",
+                        row.iter()
+                            .map(|cell| cell.replace('|', "\\|"))
+                            .join(" | ")
+                    )
+                };
+                let separator = format!(
+                    "|{}|\n",
+                    self.headers.iter().map(|_| " --- ").join("|")
+                );
+                std::iter::once(line(&self.headers))
+                    .chain(std::iter::once(separator))
+                    .chain(self.rows.iter().map(line))
+                    .collect()
+            }
+            ExportFormat::Csv => std::iter::once(&self.headers)
+                .chain(self.rows.iter())
+                .map(|row| {
+                    row.iter().map(|cell| csv_field(cell)).join(",") + "\n"
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Quotes `cell` if it holds a comma, a quote or a line break, doubling the
+/// quotes inside of it.
+fn csv_field(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+    use crate::evm::OpcodeId;
+    use crate::exec_trace::ExecutionTrace;
+    use crate::test_util;
+
+    #[test]
+    fn export_formats() {
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["40"], "opcode": "PUSH1 40", "pc": 0 },
+            { "memory": {}, "stack": ["40", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": [], "opcode": "SSTORE", "pc": 4 }
+        ]
+        "#;
+        let block_ctants = test_util::block_ctants();
+        let trace = ExecutionTrace::from_trace_bytes(
+            input_trace.as_bytes(),
+            block_ctants,
+        )
+        .expect("Error on trace generation");
+        let storage_op = trace.sorted_storage_ops().next().unwrap();
+        let stack_gc = trace.sorted_stack_ops().next().unwrap().gc();
+
+        let storage = trace.export_storage_ops(ExportFormat::Markdown, true);
+        let mut lines = storage.lines();
+        assert_eq!(
+            lines.next(),
+            Some("| address | key | val | val_prev | rw | gc | note |")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("| --- | --- | --- | --- | --- | --- | --- |")
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                format!(
+                    "| {} | 0x0 | 0x40 | 0x0 | WRITE | {} | 4 {} |",
+                    storage_op.address(),
+                    storage_op.gc(),
+                    OpcodeId::SSTORE
+                )
+                .as_str()
+            )
+        );
+        assert_eq!(lines.next(), None);
+
+        // Text columns are padded to the widest cell of each of them.
+        let stack = trace.export_stack_ops(ExportFormat::Text, false);
+        let mut lines = stack.lines();
+        assert_eq!(lines.next(), Some("call_id  key   val   rw     gc"));
+        assert_eq!(lines.next(), Some("-------  ----  ----  -----  --"));
+        assert_eq!(
+            lines.next(),
+            Some(format!("1        1022  0x0   WRITE  {}", stack_gc).as_str())
+        );
+
+        // CSV cells holding commas are quoted.
+        let steps = trace.export_steps(ExportFormat::Csv);
+        let mut lines = steps.lines();
+        assert_eq!(
+            lines.next(),
+            Some("gc,pc,instruction,depth,gas,gas_cost,stack")
+        );
+        assert!(lines.nth(1).unwrap().ends_with(",\"[0x40, 0x0]\""));
+        assert!(trace
+            .export_memory_ops(ExportFormat::Csv, true)
+            .lines()
+            .eq(["call_id,key,val,rw,gc,note"].iter().copied()));
+
+        assert_eq!(csv_field("0x40"), "0x40");
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+}
@@ -0,0 +1,219 @@
+//! Tracking of the size of the memory of each call frame along the
+//! [`ExecutionStep`]s of a trace, from the memory regions accessed by their
+//! instructions.
+
+use super::{ExecutionStep, SnapshotConvention};
+use crate::evm::gas::{
+    memory_access, memory_expansion_cost, memory_word_size, memory_words,
+};
+use crate::evm::GasCost;
+
+/// Expansion of the memory of a call frame performed by a single step, which
+/// accesses a region that ends past the size of the memory before it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MemoryExpansion {
+    step: usize,
+    offset: u64,
+    size: u64,
+    words_before: u64,
+    words_after: u64,
+}
+
+impl MemoryExpansion {
+    /// Generate a new `MemoryExpansion` instance from it's fields.
+    pub const fn new(
+        step: usize,
+        offset: u64,
+        size: u64,
+        words_before: u64,
+        words_after: u64,
+    ) -> MemoryExpansion {
+        MemoryExpansion {
+            step,
+            offset,
+            size,
+            words_before,
+            words_after,
+        }
+    }
+
+    #[inline]
+    /// Return the index of the step that expands the memory.
+    pub const fn step(&self) -> usize {
+        self.step
+    }
+
+    #[inline]
+    /// Return the address of the first byte of the region accessed.
+    pub const fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    #[inline]
+    /// Return the size in bytes of the region accessed.
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+
+    #[inline]
+    /// Return the size in words of the memory before the step.
+    pub const fn words_before(&self) -> u64 {
+        self.words_before
+    }
+
+    #[inline]
+    /// Return the size in words of the memory after the step.
+    pub const fn words_after(&self) -> u64 {
+        self.words_after
+    }
+
+    /// Returns the gas charged for the expansion.
+    pub fn cost(&self) -> GasCost {
+        memory_expansion_cost(self.words_before, self.words_after)
+    }
+}
+
+/// Sets the size of the memory before and after each one of the `steps`,
+/// returning the [`MemoryExpansion`]s they perform.
+///
+/// The memory of every frame starts empty and grows when a step accesses a
+/// region past it's end, whose bounds are read from the stack
+/// [before](super::StepSnapshots::before) the step. Memory that shows up in
+/// the view of a step without any step of the frame accessing it, like in the
+/// traces of a fragment of an execution, is taken as already there before the
+/// step.
+pub(crate) fn track_memory(
+    steps: &mut [ExecutionStep],
+    convention: SnapshotConvention,
+) -> Vec<MemoryExpansion> {
+    // Last step executed and memory size of each active frame, indexed by
+    // `depth - 1`.
+    let mut frames: Vec<(Option<usize>, u64)> = vec![];
+    let mut expansions = vec![];
+    for idx in 0..steps.len() {
+        let depth = steps[idx].depth().max(1);
+        frames.truncate(depth);
+        frames.resize(depth, (None, 0));
+        let (last_step, words) = frames[depth - 1];
+
+        let before = match convention {
+            SnapshotConvention::PostState => last_step.map(|last| &steps[last]),
+            SnapshotConvention::PreState => Some(&steps[idx]),
+        };
+        let arg = |idx: usize| {
+            before
+                .and_then(|step| step.stack().iter().rev().nth(idx))
+                .map(|word| word.to_u64().unwrap_or(u64::MAX))
+                .unwrap_or(0)
+        };
+        let step = &steps[idx];
+        let access = memory_access(step.instruction().opcode_id(), arg);
+        let access_words = access
+            .map(|(offset, size)| memory_word_size(offset.saturating_add(size)))
+            .unwrap_or(0);
+        let view_words = memory_words(step);
+        let words_before = match convention {
+            SnapshotConvention::PostState if view_words <= access_words => {
+                words
+            }
+            _ => words.max(view_words),
+        };
+        let words_after = words_before.max(access_words);
+        if let Some((offset, size)) =
+            access.filter(|_| words_after > words_before)
+        {
+            expansions.push(MemoryExpansion::new(
+                idx,
+                offset,
+                size,
+                words_before,
+                words_after,
+            ));
+        }
+
+        steps[idx].set_memory_size(words_before, words_after);
+        frames[depth - 1] = (Some(idx), words_after);
+    }
+    expansions
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::*;
+    use crate::evm::{EvmWord, OpcodeId};
+    use crate::exec_trace::ExecutionTrace;
+    use crate::operation::ContextField;
+    use crate::test_util;
+
+    use pasta_curves::Fp;
+
+    #[test]
+    fn sizes_and_expansions() {
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["20"], "opcode": "PUSH1 20", "pc": 0, "gas": 100, "gasCost": 3 },
+            { "memory": {}, "stack": ["20", "0"], "opcode": "PUSH1 00", "pc": 2, "gas": 97, "gasCost": 3 },
+            { "memory": {}, "stack": ["20", "0", "0"], "opcode": "PUSH1 00", "pc": 4, "gas": 94, "gasCost": 3 },
+            { "memory": { "0": "w" }, "stack": [], "opcode": "CALLDATACOPY", "pc": 6, "gas": 91, "gasCost": 9 },
+            { "memory": { "0": "w" }, "stack": ["1"], "opcode": "PUSH1 01", "pc": 7, "gas": 82, "gasCost": 3 },
+            { "memory": { "0": "w" }, "stack": ["1", "0"], "opcode": "PUSH1 00", "pc": 9, "gas": 79, "gasCost": 3 },
+            { "memory": { "0": "w" }, "stack": ["1", "0", "40"], "opcode": "PUSH1 40", "pc": 11, "gas": 76, "gasCost": 3 },
+            { "memory": { "0": "w", "20": "w", "40": "w" }, "stack": [], "opcode": "CALLDATACOPY", "pc": 13, "gas": 73, "gasCost": 12 },
+            { "memory": { "0": "w", "20": "w", "40": "w" }, "stack": ["60"], "opcode": "MSIZE", "pc": 14, "gas": 61, "gasCost": 2 },
+            { "memory": { "0": "w", "20": "w", "40": "w" }, "stack": ["60"], "opcode": "STOP", "pc": 15, "gas": 59, "gasCost": 0 }
+        ]
+        "#
+        .replace("\"w\"", &format!("\"{}\"", "0".repeat(64)));
+        let block_ctants = test_util::block_ctants();
+        let trace = ExecutionTrace::<Fp>::from_trace_bytes(
+            input_trace.as_bytes(),
+            block_ctants,
+        )
+        .expect("Error on trace generation");
+
+        let sizes: Vec<(u64, u64)> = trace
+            .steps()
+            .iter()
+            .map(|step| (step.memory_size_before(), step.memory_size_after()))
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![
+                (0, 0),
+                (0, 0),
+                (0, 0),
+                (0, 1),
+                (1, 1),
+                (1, 1),
+                (1, 1),
+                (1, 3),
+                (3, 3),
+                (3, 3)
+            ]
+        );
+        assert_eq!(
+            trace.memory_expansions(),
+            &[
+                MemoryExpansion::new(3, 0, 32, 0, 1),
+                MemoryExpansion::new(7, 0x40, 1, 1, 3),
+            ]
+        );
+        assert_eq!(trace.memory_expansions()[1].cost(), GasCost(6));
+
+        // The expansions are charged, and `MSIZE` reads the tracked size.
+        assert_eq!(trace.gas_mismatches(), &[]);
+        assert!(trace.sorted_context_ops().any(|op| {
+            op.field() == ContextField::MemorySize
+                && op.value() == &EvmWord::from(0x60u8)
+        }));
+
+        // Calls access their input and output regions.
+        let call_args = [0, 0, 0, 0x20, 0x10, 0x80, 0x20];
+        assert_eq!(
+            memory_access(OpcodeId::CALL, |idx| call_args[idx]),
+            Some((0, 0xa0))
+        );
+        assert_eq!(memory_access(OpcodeId::SHA3, |_| 0), None);
+        assert_eq!(memory_word_size(u64::MAX), u64::MAX / 32 + 1);
+    }
+}
@@ -0,0 +1,74 @@
+//! Definition of the link between each
+//! [`Operation`](crate::operation::Operation)
+//! of an [`ExecutionTrace`](super::ExecutionTrace) and the [`ExecutionStep`]
+//! that generated it.
+
+use super::ExecutionStep;
+use crate::evm::{GlobalCounter, OpcodeId, ProgramCounter};
+
+/// Step of an [`ExecutionTrace`](super::ExecutionTrace) that generated an
+/// operation, identified by it's index in the trace together with the
+/// instruction it executes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OpOrigin {
+    step: usize,
+    pc: ProgramCounter,
+    opcode: OpcodeId,
+}
+
+impl OpOrigin {
+    /// Generate a new `OpOrigin` instance from it's fields.
+    pub const fn new(
+        step: usize,
+        pc: ProgramCounter,
+        opcode: OpcodeId,
+    ) -> OpOrigin {
+        OpOrigin { step, pc, opcode }
+    }
+
+    #[inline]
+    /// Return the index of the step inside of the trace.
+    pub const fn step(&self) -> usize {
+        self.step
+    }
+
+    #[inline]
+    /// Return the [`ProgramCounter`] of the step.
+    pub const fn pc(&self) -> ProgramCounter {
+        self.pc
+    }
+
+    #[inline]
+    /// Return the [`OpcodeId`] executed by the step.
+    pub const fn opcode(&self) -> OpcodeId {
+        self.opcode
+    }
+}
+
+/// Returns the index of the last of the `steps` whose [`GlobalCounter`] is
+/// smaller than `gc`, which is the step an operation with that counter
+/// belongs to, as the operations of each step get the counters that follow
+/// the one of the step.
+pub(crate) fn step_of(steps: &[ExecutionStep], gc: GlobalCounter) -> usize {
+    steps
+        .partition_point(|step| step.gc() < gc)
+        .saturating_sub(1)
+}
+
+/// Returns the [`OpOrigin`] of the operation with the [`GlobalCounter`] `gc`,
+/// or `None` if none of the `steps` generated it.
+pub(crate) fn find_origin(
+    steps: &[ExecutionStep],
+    gc: GlobalCounter,
+) -> Option<OpOrigin> {
+    let idx = step_of(steps, gc);
+    steps
+        .get(idx)
+        .filter(|step| {
+            step.gc() < gc
+                && gc.0 <= step.gc().0 + step.bus_mapping_instance().len()
+        })
+        .map(|step| {
+            OpOrigin::new(idx, step.pc(), step.instruction().opcode_id())
+        })
+}
@@ -0,0 +1,887 @@
+//! Reconstruction of the stack and memory views of the [`ExecutionStep`]s of
+//! traces generated with the `disableStack` and `disableMemory` options of
+//! the tracer, by replaying the instructions of the steps over the code they
+//! run.
+
+use super::exec_step::MissingViews;
+use super::{
+    field_to_word, root_code, BlockConstants, ExecutionStep,
+    SnapshotConvention, TxContext,
+};
+use crate::error::Error;
+use crate::evm::gas::{
+    can_expand_memory, max_memory_words, memory_access, memory_word_size,
+    DEFAULT_GAS_LIMIT,
+};
+use crate::evm::{keccak256, Address, EvmWord, MemoryAddress, OpcodeId};
+use crate::state_db::StateDB;
+use alloc::collections::BTreeMap;
+use num::{BigUint, CheckedSub, One, ToPrimitive, Zero};
+use pasta_curves::arithmetic::FieldExt;
+
+/// Stack and memory of the root call while it's steps are replayed.
+#[derive(Debug, Default)]
+struct Machine {
+    stack: Vec<EvmWord>,
+    memory: Vec<u8>,
+}
+
+impl Machine {
+    /// Returns the memory as the view of a step: the 32-byte words that
+    /// cover it from address zero.
+    fn memory_view(&self) -> BTreeMap<MemoryAddress, EvmWord> {
+        self.memory
+            .chunks(32)
+            .enumerate()
+            .map(|(word, bytes)| {
+                (
+                    MemoryAddress::from(word * 32),
+                    EvmWord::from_be_bytes(bytes),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns `size` bytes of the memory starting at `offset`, which must
+    /// be inside of it.
+    fn memory_bytes(&self, offset: usize, size: usize) -> &[u8] {
+        &self.memory[offset..offset + size]
+    }
+
+    /// Takes the views that `step` holds as the stack and memory of the
+    /// machine, and sets the ones it lacks to them. Memory views past the
+    /// `max_words` that the gas of the transaction can pay for aren't taken,
+    /// and make it return false.
+    fn sync(
+        &mut self,
+        step: &mut ExecutionStep,
+        missing: MissingViews,
+        max_words: u64,
+    ) -> bool {
+        if !missing.memory {
+            let end =
+                step.memory().keys().last().map_or(Some(0), |address| {
+                    address.0.to_u64()?.checked_add(32)
+                });
+            match end.filter(|end| memory_word_size(*end) <= max_words) {
+                Some(end) => {
+                    self.memory = vec![0; end as usize];
+                    for (address, word) in step.memory() {
+                        let offset = address.0.to_usize().unwrap_or_default();
+                        self.memory[offset..offset + 32]
+                            .copy_from_slice(&word.to_be_bytes());
+                    }
+                }
+                None => return false,
+            }
+        }
+        if !missing.stack {
+            self.stack = step.stack().clone();
+        }
+        let memory = if missing.memory {
+            self.memory_view()
+        } else {
+            step.memory().clone()
+        };
+        let stack = if missing.stack {
+            self.stack.clone()
+        } else {
+            step.stack().clone()
+        };
+        step.set_views(memory, stack);
+        true
+    }
+}
+
+/// Sets the `missing` stack and memory views of each one of the `steps` by
+/// replaying their instructions over the code of the root call of `tx_ctx`,
+/// from the empty stack and memory it starts with. The views that a step
+/// holds replace the replayed ones. The views are taken before or after
+/// each step as given by `convention`.
+///
+/// Storage slots and balances are read from `prestate`, once the sender has
+/// paid for the gas and value of the transaction, and the fields of the
+/// block from `block_ctants`. Instructions whose results depend on state the
+/// replay doesn't hold, like the code of other accounts, can't be replayed,
+/// and neither can the steps of frames other than the root call. A step that
+/// can't pay for expanding the memory with it's gas (or the
+/// [`DEFAULT_GAS_LIMIT`] if the trace lacks it) runs out of gas, leaving the
+/// stack and memory as they were.
+///
+/// Fails with [`Error::StepNotReplayable`] at the first step that can't be
+/// replayed, including the ones whose instruction doesn't match the code at
+/// their pc, that take more words than the stack holds, or that push the gas
+/// left when the trace lacks it.
+pub(crate) fn replay_steps<F: FieldExt>(
+    steps: &mut [ExecutionStep],
+    missing: &[MissingViews],
+    tx_ctx: &TxContext,
+    block_ctants: &BlockConstants<F>,
+    prestate: &StateDB,
+    convention: SnapshotConvention,
+) -> Result<(), Error> {
+    let code = root_code(tx_ctx, prestate).unwrap_or(&[]);
+    let root_call = tx_ctx.root_call(1);
+    let address = *root_call.address();
+    // No step can access more memory than the gas of the transaction pays
+    // for.
+    let max_words = max_memory_words(match tx_ctx.gas() {
+        0 => DEFAULT_GAS_LIMIT,
+        gas => gas,
+    });
+    let mut storage: BTreeMap<EvmWord, EvmWord> = BTreeMap::new();
+    let mut machine = Machine::default();
+    for (idx, (step, missing)) in steps.iter_mut().zip(missing).enumerate() {
+        let opcode = step.instruction().opcode_id();
+        let not_replayable = Error::StepNotReplayable(idx, opcode);
+        let pc = step.pc().0;
+        let code_opcode = match code.get(pc) {
+            Some(byte) => OpcodeId(*byte),
+            None => OpcodeId::STOP,
+        };
+        if step.depth() != 1 || code_opcode != opcode {
+            return Err(not_replayable);
+        }
+
+        if convention == SnapshotConvention::PreState
+            && !machine.sync(step, *missing, max_words)
+        {
+            return Err(not_replayable);
+        }
+
+        let arg = |idx: usize| {
+            machine
+                .stack
+                .iter()
+                .rev()
+                .nth(idx)
+                .map(|word| word.to_u64().unwrap_or(u64::MAX))
+                .unwrap_or(0)
+        };
+        let end = memory_access(opcode, arg)
+            .map(|(offset, size)| offset.saturating_add(size));
+        let words = machine.memory.len() as u64 / 32;
+        if matches!(end, Some(end) if !can_expand_memory(step.gas(), words, end))
+        {
+            // The step runs out of gas, so the views after it are the ones
+            // before it.
+            if convention == SnapshotConvention::PostState
+                && !machine.sync(step, *missing, max_words)
+            {
+                return Err(not_replayable);
+            }
+            continue;
+        }
+        // Expand the memory over the region accessed by the step.
+        if let Some(end) = end {
+            let len = (memory_word_size(end) * 32) as usize;
+            if len > machine.memory.len() {
+                machine.memory.resize(len, 0);
+            }
+        }
+
+        let n_args = stack_inputs(opcode).ok_or(not_replayable)?;
+        if machine.stack.len() < n_args {
+            return Err(not_replayable);
+        }
+        let args: Vec<EvmWord> = (0..n_args)
+            .map(|_| machine.stack.pop().expect("stack holds the args"))
+            .collect();
+        let usize_arg = |idx: usize| args[idx].to_usize().unwrap_or(usize::MAX);
+        let result = match opcode {
+            OpcodeId::ADD => Some(wrap(&args[0].0 + &args[1].0)),
+            OpcodeId::MUL => Some(wrap(&args[0].0 * &args[1].0)),
+            OpcodeId::SUB => Some(wrap(&args[0].0 + modulus() - &args[1].0)),
+            OpcodeId::DIV => {
+                Some(checked_div(&args[0].0, &args[1].0, |a, b| a / b))
+            }
+            OpcodeId::MOD => {
+                Some(checked_div(&args[0].0, &args[1].0, |a, b| a % b))
+            }
+            OpcodeId::SDIV | OpcodeId::SMOD => {
+                let (a_neg, a) = to_signed(&args[0]);
+                let (b_neg, b) = to_signed(&args[1]);
+                Some(if b.is_zero() {
+                    EvmWord::from(0u8)
+                } else if opcode == OpcodeId::SDIV {
+                    from_signed(a_neg != b_neg, a / b)
+                } else {
+                    from_signed(a_neg, a % b)
+                })
+            }
+            OpcodeId::ADDMOD => Some(checked_div(
+                &(&args[0].0 + &args[1].0),
+                &args[2].0,
+                |a, n| a % n,
+            )),
+            OpcodeId::MULMOD => Some(checked_div(
+                &(&args[0].0 * &args[1].0),
+                &args[2].0,
+                |a, n| a % n,
+            )),
+            OpcodeId::EXP => {
+                Some(EvmWord(args[0].0.modpow(&args[1].0, &modulus())))
+            }
+            OpcodeId::SIGNEXTEND => Some(match args[0].to_usize() {
+                Some(byte) if byte < 31 => {
+                    let bit = byte * 8 + 7;
+                    let low = (BigUint::one() << (bit + 1)) - 1u8;
+                    if args[1].0.bit(bit as u64) {
+                        EvmWord(&args[1].0 | (max_word() - &low))
+                    } else {
+                        EvmWord(&args[1].0 & low)
+                    }
+                }
+                _ => args[1].clone(),
+            }),
+            OpcodeId::LT => Some(bool_word(args[0].0 < args[1].0)),
+            OpcodeId::GT => Some(bool_word(args[0].0 > args[1].0)),
+            OpcodeId::SLT | OpcodeId::SGT => {
+                let (a, b) = match opcode {
+                    OpcodeId::SLT => (&args[0], &args[1]),
+                    _ => (&args[1], &args[0]),
+                };
+                let (a_neg, b_neg) = (to_signed(a).0, to_signed(b).0);
+                Some(bool_word(if a_neg == b_neg { a.0 < b.0 } else { a_neg }))
+            }
+            OpcodeId::EQ => Some(bool_word(args[0] == args[1])),
+            OpcodeId::ISZERO => Some(bool_word(args[0].is_zero())),
+            OpcodeId::AND => Some(EvmWord(&args[0].0 & &args[1].0)),
+            OpcodeId::OR => Some(EvmWord(&args[0].0 | &args[1].0)),
+            OpcodeId::XOR => Some(EvmWord(&args[0].0 ^ &args[1].0)),
+            OpcodeId::NOT => Some(EvmWord(max_word() - &args[0].0)),
+            OpcodeId::BYTE => Some(match args[0].to_usize() {
+                Some(byte) if byte < 32 => {
+                    EvmWord::from(args[1].to_be_bytes()[byte])
+                }
+                _ => EvmWord::from(0u8),
+            }),
+            OpcodeId::SHL | OpcodeId::SHR | OpcodeId::SAR => {
+                let shift = args[0].to_usize().filter(|shift| *shift < 256);
+                let negative = opcode == OpcodeId::SAR && to_signed(&args[1]).0;
+                Some(match (shift, negative) {
+                    (Some(shift), _) if opcode == OpcodeId::SHL => {
+                        wrap(&args[1].0 << shift)
+                    }
+                    // Shifting a negative word right fills it with ones.
+                    (Some(shift), true) => EvmWord(
+                        (&args[1].0 >> shift)
+                            | (max_word() - (max_word() >> shift)),
+                    ),
+                    (Some(shift), false) => EvmWord(&args[1].0 >> shift),
+                    (None, true) => EvmWord(max_word()),
+                    (None, false) => EvmWord::from(0u8),
+                })
+            }
+            OpcodeId::SHA3 => {
+                let bytes: &[u8] = if args[1].is_zero() {
+                    &[]
+                } else {
+                    machine.memory_bytes(usize_arg(0), usize_arg(1))
+                };
+                Some(EvmWord::from_be_bytes(&keccak256(bytes)))
+            }
+            OpcodeId::ADDRESS => Some(address.to_word()),
+            OpcodeId::ORIGIN => Some(tx_ctx.from().to_word()),
+            OpcodeId::CALLER => Some(root_call.caller().to_word()),
+            OpcodeId::CALLVALUE => Some(root_call.value().clone()),
+            OpcodeId::CALLDATALOAD => Some(EvmWord::from_be_bytes(
+                &padded_bytes(root_call.calldata(), usize_arg(0), 32),
+            )),
+            OpcodeId::CALLDATASIZE => {
+                Some(EvmWord::from(root_call.calldata().len()))
+            }
+            OpcodeId::CODESIZE => Some(EvmWord::from(code.len())),
+            OpcodeId::GASPRICE => Some(tx_ctx.gas_price().clone()),
+            OpcodeId::BALANCE => Some(
+                balance(tx_ctx, prestate, &Address::from_word(&args[0]))
+                    .ok_or(not_replayable)?,
+            ),
+            OpcodeId::SELFBALANCE => Some(
+                balance(tx_ctx, prestate, &address).ok_or(not_replayable)?,
+            ),
+            OpcodeId::COINBASE => {
+                Some(block_ctants.coinbase_address().to_word())
+            }
+            OpcodeId::TIMESTAMP => {
+                Some(field_to_word(block_ctants.timestamp()))
+            }
+            OpcodeId::NUMBER => Some(field_to_word(block_ctants.number())),
+            OpcodeId::DIFFICULTY => {
+                Some(field_to_word(block_ctants.difficulty()))
+            }
+            OpcodeId::GASLIMIT => Some(field_to_word(block_ctants.gas_limit())),
+            OpcodeId::CHAINID => Some(field_to_word(block_ctants.chain_id())),
+            OpcodeId::BASEFEE => Some(block_ctants.base_fee_word()),
+            // No call has returned data to the root call.
+            OpcodeId::RETURNDATASIZE => Some(EvmWord::from(0u8)),
+            OpcodeId::CALLDATACOPY | OpcodeId::CODECOPY => {
+                let source = match opcode {
+                    OpcodeId::CALLDATACOPY => root_call.calldata(),
+                    _ => code,
+                };
+                if !args[2].is_zero() {
+                    let (dest, size) = (usize_arg(0), usize_arg(2));
+                    let bytes = padded_bytes(source, usize_arg(1), size);
+                    machine.memory[dest..dest + size].copy_from_slice(&bytes);
+                }
+                None
+            }
+            OpcodeId::MLOAD => Some(EvmWord::from_be_bytes(
+                machine.memory_bytes(usize_arg(0), 32),
+            )),
+            OpcodeId::MSTORE => {
+                let offset = usize_arg(0);
+                machine.memory[offset..offset + 32]
+                    .copy_from_slice(&args[1].to_be_bytes());
+                None
+            }
+            OpcodeId::MSTORE8 => {
+                machine.memory[usize_arg(0)] = args[1].to_be_bytes()[31];
+                None
+            }
+            OpcodeId::SLOAD => Some(
+                storage
+                    .get(&args[0])
+                    .or_else(|| prestate.storage(&address, &args[0]))
+                    .cloned()
+                    .unwrap_or_else(|| EvmWord::from(0u8)),
+            ),
+            OpcodeId::SSTORE => {
+                storage.insert(args[0].clone(), args[1].clone());
+                None
+            }
+            OpcodeId::PC => Some(EvmWord::from(pc)),
+            OpcodeId::MSIZE => Some(EvmWord::from(machine.memory.len())),
+            // The gas left once the step is charged.
+            OpcodeId::GAS => Some(EvmWord::from(
+                step.gas()
+                    .map(|gas| gas.0.saturating_sub(step.gas_cost().0))
+                    .ok_or(not_replayable)?,
+            )),
+            _ if opcode.push_size() > 0 => Some(EvmWord::from_be_bytes(
+                &padded_bytes(code, pc + 1, opcode.push_size()),
+            )),
+            _ if (0x80..=0x8f).contains(&opcode.as_u8()) => {
+                // DUPn takes the n top words and pushes them back followed by
+                // the deepest one.
+                let dup = args.last().cloned();
+                machine.stack.extend(args.into_iter().rev());
+                dup
+            }
+            _ if (0x90..=0x9f).contains(&opcode.as_u8()) => {
+                // SWAPn takes the n + 1 top words and pushes them back with
+                // the first and last ones exchanged.
+                let mut args = args;
+                let last = args.len() - 1;
+                args.swap(0, last);
+                machine.stack.extend(args.into_iter().rev());
+                None
+            }
+            _ => None,
+        };
+        machine.stack.extend(result);
+        if machine.stack.len() > super::validation::STACK_LIMIT {
+            return Err(not_replayable);
+        }
+
+        if convention == SnapshotConvention::PostState
+            && !machine.sync(step, *missing, max_words)
+        {
+            return Err(not_replayable);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of words the `opcode` takes from the stack, or `None`
+/// if it can't be replayed.
+fn stack_inputs(opcode: OpcodeId) -> Option<usize> {
+    Some(match opcode {
+        OpcodeId::STOP
+        | OpcodeId::ADDRESS
+        | OpcodeId::ORIGIN
+        | OpcodeId::CALLER
+        | OpcodeId::CALLVALUE
+        | OpcodeId::CALLDATASIZE
+        | OpcodeId::CODESIZE
+        | OpcodeId::GASPRICE
+        | OpcodeId::SELFBALANCE
+        | OpcodeId::COINBASE
+        | OpcodeId::TIMESTAMP
+        | OpcodeId::NUMBER
+        | OpcodeId::DIFFICULTY
+        | OpcodeId::GASLIMIT
+        | OpcodeId::CHAINID
+        | OpcodeId::BASEFEE
+        | OpcodeId::RETURNDATASIZE
+        | OpcodeId::PC
+        | OpcodeId::MSIZE
+        | OpcodeId::GAS
+        | OpcodeId::JUMPDEST
+        | OpcodeId::INVALID => 0,
+        OpcodeId::ISZERO
+        | OpcodeId::NOT
+        | OpcodeId::BALANCE
+        | OpcodeId::CALLDATALOAD
+        | OpcodeId::POP
+        | OpcodeId::MLOAD
+        | OpcodeId::SLOAD
+        | OpcodeId::JUMP => 1,
+        OpcodeId::ADD
+        | OpcodeId::MUL
+        | OpcodeId::SUB
+        | OpcodeId::DIV
+        | OpcodeId::SDIV
+        | OpcodeId::MOD
+        | OpcodeId::SMOD
+        | OpcodeId::EXP
+        | OpcodeId::SIGNEXTEND
+        | OpcodeId::LT
+        | OpcodeId::GT
+        | OpcodeId::SLT
+        | OpcodeId::SGT
+        | OpcodeId::EQ
+        | OpcodeId::AND
+        | OpcodeId::OR
+        | OpcodeId::XOR
+        | OpcodeId::BYTE
+        | OpcodeId::SHL
+        | OpcodeId::SHR
+        | OpcodeId::SAR
+        | OpcodeId::SHA3
+        | OpcodeId::MSTORE
+        | OpcodeId::MSTORE8
+        | OpcodeId::SSTORE
+        | OpcodeId::JUMPI
+        | OpcodeId::RETURN
+        | OpcodeId::REVERT => 2,
+        OpcodeId::ADDMOD
+        | OpcodeId::MULMOD
+        | OpcodeId::CALLDATACOPY
+        | OpcodeId::CODECOPY => 3,
+        _ if opcode.push_size() > 0 => 0,
+        // DUP1..DUP16, SWAP1..SWAP16 and LOG0..LOG4.
+        _ => match opcode.as_u8() {
+            byte @ 0x80..=0x8f => (byte - 0x7f) as usize,
+            byte @ 0x90..=0x9f => (byte - 0x8e) as usize,
+            byte @ 0xa0..=0xa4 => (byte - 0x9e) as usize,
+            _ => return None,
+        },
+    })
+}
+
+/// Returns the balance of the account located at `address` while the root
+/// call of `tx_ctx` runs, once the sender has paid for the gas and value of
+/// the transaction, or `None` if it isn't known from `prestate`.
+///
+/// Like when the trace is built, a sender missing from `prestate` is assumed
+/// to afford the transaction exactly, and a created account to start empty.
+fn balance(
+    tx_ctx: &TxContext,
+    prestate: &StateDB,
+    address: &Address,
+) -> Option<EvmWord> {
+    let (sender, receiver) = (tx_ctx.from(), *tx_ctx.root_call(1).address());
+    let value = &tx_ctx.value().0;
+    let cost = BigUint::from(tx_ctx.gas()) * &tx_ctx.gas_price().0 + value;
+    let mut balance = if prestate.contains_account(address) {
+        prestate.account(address).balance().0.clone()
+    } else if address == sender {
+        cost.clone()
+    } else if tx_ctx.is_create() && *address == receiver {
+        BigUint::zero()
+    } else {
+        return None;
+    };
+    if address == sender {
+        balance = balance.checked_sub(&cost)?;
+    }
+    if *address == receiver {
+        balance += value;
+    }
+    Some(EvmWord(balance))
+}
+
+/// Returns `size` bytes of `bytes` starting at `offset`, where the bytes past
+/// it's end are zero.
+fn padded_bytes(bytes: &[u8], offset: usize, size: usize) -> Vec<u8> {
+    (0..size)
+        .map(|idx| {
+            offset
+                .checked_add(idx)
+                .and_then(|idx| bytes.get(idx))
+                .copied()
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+/// Returns 2^256, the modulus of the arithmetic of the EVM.
+fn modulus() -> BigUint {
+    BigUint::one() << 256
+}
+
+/// Returns the highest value an [`EvmWord`] can hold.
+fn max_word() -> BigUint {
+    modulus() - 1u8
+}
+
+/// Reduces `value` modulo 2^256.
+fn wrap(value: BigUint) -> EvmWord {
+    EvmWord(value % modulus())
+}
+
+/// Returns the result of `op` over `a` and `b`, or zero when `b` is zero.
+fn checked_div(
+    a: &BigUint,
+    b: &BigUint,
+    op: impl Fn(&BigUint, &BigUint) -> BigUint,
+) -> EvmWord {
+    if b.is_zero() {
+        EvmWord::from(0u8)
+    } else {
+        EvmWord(op(a, b))
+    }
+}
+
+/// Returns the sign and absolute value of `word` read as a two's complement
+/// integer.
+fn to_signed(word: &EvmWord) -> (bool, BigUint) {
+    if word.0.bit(255) {
+        (true, modulus() - &word.0)
+    } else {
+        (false, word.0.clone())
+    }
+}
+
+/// Returns the two's complement [`EvmWord`] of the integer with the given
+/// sign and absolute value.
+fn from_signed(negative: bool, abs: BigUint) -> EvmWord {
+    if negative && !abs.is_zero() {
+        wrap(modulus() - abs)
+    } else {
+        wrap(abs)
+    }
+}
+
+/// Returns one if `value` holds, zero otherwise.
+fn bool_word(value: bool) -> EvmWord {
+    EvmWord::from(value as u8)
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use super::*;
+    use crate::exec_trace::{
+        exec_step::ParsedExecutionStep, ExecutionTrace, ExecutionTraceBuilder,
+    };
+    use crate::test_util;
+    use pasta_curves::Fp;
+
+    /// Replays the slim steps of `input_trace` over the init `code` of a
+    /// creation, without prestate.
+    fn replay(
+        input_trace: &str,
+        code: &[u8],
+        convention: SnapshotConvention,
+    ) -> Result<Vec<ExecutionStep>, Error> {
+        let (mut steps, missing): (Vec<ExecutionStep>, Vec<MissingViews>) =
+            serde_json::from_str::<Vec<ParsedExecutionStep>>(input_trace)
+                .expect("Error on parsing")
+                .iter()
+                .map(ParsedExecutionStep::slim_step)
+                .collect::<Result<Vec<(ExecutionStep, MissingViews)>, Error>>()?
+                .into_iter()
+                .unzip();
+        replay_steps(
+            &mut steps,
+            &missing,
+            &test_util::tx_ctx(None, code.to_vec()),
+            &test_util::block_ctants(),
+            &StateDB::new(),
+            convention,
+        )?;
+        Ok(steps)
+    }
+
+    #[test]
+    fn slim_trace_replay() {
+        // PUSH1 05 PUSH1 03 SUB PUSH1 02 SDIV PUSH1 01 SAR DUP1 PUSH1 00
+        // BYTE PUSH1 00 SIGNEXTEND EQ PUSH1 01 MSTORE8 MSIZE STOP
+        let code = bytes_of("600560030360020560011d8060001a60000b1460015359");
+        let input_trace = r#"
+        [
+            { "opcode": "PUSH1", "pc": 0 },
+            { "opcode": "PUSH1", "pc": 2 },
+            { "opcode": "SUB", "pc": 4 },
+            { "opcode": "PUSH1", "pc": 5 },
+            { "opcode": "SDIV", "pc": 7 },
+            { "opcode": "PUSH1", "pc": 8 },
+            { "opcode": "SAR", "pc": 10 },
+            { "opcode": "DUP1", "pc": 11 },
+            { "opcode": "PUSH1", "pc": 12 },
+            { "opcode": "BYTE", "pc": 14 },
+            { "opcode": "PUSH1", "pc": 15 },
+            { "opcode": "SIGNEXTEND", "pc": 17 },
+            { "opcode": "EQ", "pc": 18 },
+            { "opcode": "PUSH1", "pc": 19 },
+            { "opcode": "MSTORE8", "pc": 21 },
+            { "opcode": "MSIZE", "pc": 22 },
+            { "opcode": "STOP", "pc": 23 }
+        ]
+        "#;
+        let steps =
+            replay(input_trace, &code, SnapshotConvention::PostState).unwrap();
+        let minus = |value: u8| EvmWord(modulus() - value);
+        assert_eq!(steps[2].stack(), &vec![minus(2)]);
+        assert_eq!(steps[4].stack(), &vec![minus(1)]);
+        assert_eq!(steps[6].stack(), &vec![minus(1)]);
+        assert_eq!(steps[9].stack(), &vec![minus(1), EvmWord::from(0xffu8)]);
+        assert_eq!(steps[11].stack(), &vec![minus(1), minus(1)]);
+        assert_eq!(steps[12].stack(), &vec![EvmWord::from(1u8)]);
+        assert!(steps[13].memory().is_empty());
+        let mut word = [0u8; 32];
+        word[1] = 1;
+        assert_eq!(
+            steps[14].memory().get(&MemoryAddress::from(0)),
+            Some(&EvmWord::from_be_bytes(&word))
+        );
+        assert_eq!(steps[15].stack(), &vec![EvmWord::from(0x20u8)]);
+        // The pc past the end of the code runs an implicit `STOP`.
+        assert_eq!(steps[16].stack(), steps[15].stack());
+
+        // Views taken before each step.
+        let pre_steps =
+            replay(input_trace, &code, SnapshotConvention::PreState).unwrap();
+        assert!(pre_steps[0].stack().is_empty());
+        for idx in 1..steps.len() {
+            assert_eq!(pre_steps[idx].stack(), steps[idx - 1].stack());
+            assert_eq!(pre_steps[idx].memory(), steps[idx - 1].memory());
+        }
+
+        // Steps that don't match the code, take words the stack doesn't hold,
+        // depend on state the replay lacks or run in other frames.
+        let convention = SnapshotConvention::PostState;
+        assert!(matches!(
+            replay(r#"[{ "opcode": "PUSH1", "pc": 1 }]"#, &code, convention),
+            Err(Error::StepNotReplayable(0, OpcodeId::PUSH1))
+        ));
+        assert!(matches!(
+            replay(r#"[{ "opcode": "ADD", "pc": 0 }]"#, &[0x01], convention),
+            Err(Error::StepNotReplayable(0, OpcodeId::ADD))
+        ));
+        assert!(matches!(
+            replay(
+                r#"[{ "opcode": "PUSH1", "pc": 0 }, { "opcode": "EXTCODESIZE", "pc": 2 }]"#,
+                &[0x60, 0x00, 0x3b],
+                convention
+            ),
+            Err(Error::StepNotReplayable(1, OpcodeId::EXTCODESIZE))
+        ));
+        assert!(matches!(
+            replay(r#"[{ "opcode": "GAS", "pc": 0 }]"#, &[0x5a], convention),
+            Err(Error::StepNotReplayable(0, OpcodeId::GAS))
+        ));
+        assert!(matches!(
+            replay(
+                r#"[{ "opcode": "STOP", "pc": 0, "depth": 2 }]"#,
+                &[0x00],
+                convention
+            ),
+            Err(Error::StepNotReplayable(0, OpcodeId::STOP))
+        ));
+    }
+
+    #[test]
+    fn slim_and_full_traces_match() {
+        let contract = test_util::address("aa");
+        // CALLDATASIZE PUSH1 00 PUSH1 00 CALLDATACOPY MSIZE PUSH1 00 SSTORE
+        // STOP
+        let code = bytes_of("36600060003759600055");
+        let mut prestate = StateDB::new();
+        prestate.set_code(&contract, code);
+        prestate.set_storage(&contract, EvmWord::from(0u8), EvmWord::from(1u8));
+        let tx_ctx = TxContext::new(
+            Address::zero(),
+            Some(contract),
+            0,
+            EvmWord::from(0u8),
+            EvmWord::from(0u8),
+            100_000,
+            vec![0xff; 40],
+        );
+        let build = |input_trace: &str, slim: bool| {
+            let mut builder =
+                ExecutionTraceBuilder::<Fp>::new(test_util::block_ctants())
+                    .with_tx(tx_ctx.clone())
+                    .with_convention(SnapshotConvention::PreState)
+                    .with_prestate(prestate.clone());
+            if slim {
+                builder.push_slim_trace_bytes(input_trace.as_bytes())
+            } else {
+                builder.push_trace_bytes(input_trace.as_bytes())
+            }
+            .expect("Error on parsing");
+            builder.finish().expect("Error on trace generation")
+        };
+
+        let full_trace = r#"
+        [
+            { "memory": {}, "stack": [], "opcode": "CALLDATASIZE", "pc": 0 },
+            { "memory": {}, "stack": ["28"], "opcode": "PUSH1 00", "pc": 1 },
+            { "memory": {}, "stack": ["28", "0"], "opcode": "PUSH1 00", "pc": 3 },
+            { "memory": {}, "stack": ["28", "0", "0"], "opcode": "CALLDATACOPY", "pc": 5 },
+            { "memory": { "0": "w0", "20": "w1" }, "stack": [], "opcode": "MSIZE", "pc": 6 },
+            { "memory": { "0": "w0", "20": "w1" }, "stack": ["40"], "opcode": "PUSH1 00", "pc": 7 },
+            { "memory": { "0": "w0", "20": "w1" }, "stack": ["40", "0"], "opcode": "SSTORE", "pc": 9 },
+            { "memory": { "0": "w0", "20": "w1" }, "stack": [], "opcode": "STOP", "pc": 10 }
+        ]
+        "#
+        .replace("w0", &"ff".repeat(32))
+        .replace("w1", &format!("{}{}", "ff".repeat(8), "00".repeat(24)));
+        // The slim trace lacks the views of the steps, or holds them partly.
+        let slim_trace = r#"
+        [
+            { "opcode": "CALLDATASIZE", "pc": 0 },
+            { "opcode": "PUSH1 00", "pc": 1 },
+            { "opcode": "PUSH1 00", "pc": 3 },
+            { "opcode": "CALLDATACOPY", "pc": 5 },
+            { "opcode": "MSIZE", "pc": 6 },
+            { "stack": ["40"], "opcode": "PUSH1 00", "pc": 7 },
+            { "opcode": "SSTORE", "pc": 9 },
+            { "memory": { "0": "w0", "20": "w1" }, "opcode": "STOP", "pc": 10 }
+        ]
+        "#
+        .replace("w0", &"ff".repeat(32))
+        .replace("w1", &format!("{}{}", "ff".repeat(8), "00".repeat(24)));
+
+        let full = build(&full_trace, false);
+        let slim = build(&slim_trace, true);
+        assert_eq!(slim.steps(), full.steps());
+        assert_eq!(slim.rw_table(), full.rw_table());
+        assert_eq!(slim.memory_expansions(), full.memory_expansions());
+        assert_eq!(
+            slim.state_db().storage(&contract, &EvmWord::from(0u8)),
+            Some(&EvmWord::from(0x40u8))
+        );
+
+        // Full traces still require both views.
+        assert!(matches!(
+            ExecutionTrace::<Fp>::from_trace_bytes(
+                slim_trace.as_bytes(),
+                test_util::block_ctants()
+            ),
+            Err(Error::SerdeError)
+        ));
+    }
+
+    #[test]
+    fn slim_traces_read_the_block_and_balances() {
+        let (sender, contract) =
+            (test_util::address("11"), test_util::address("aa"));
+        // PUSH1 02 PUSH1 03 ADD DUP1 PUSH1 00 MSTORE NUMBER SELFBALANCE ADD
+        // PUSH1 11 BALANCE STOP
+        let code = bytes_of("600260030180600052434701601131");
+        let mut prestate = StateDB::new();
+        prestate.set_code(&contract, code);
+        prestate.set_balance(&contract, EvmWord::from(7u8));
+        prestate.set_balance(&sender, EvmWord::from(0x100000u64));
+        // The sender pays 200000 wei for the gas and sends 5 wei.
+        let tx_ctx = TxContext::new(
+            sender,
+            Some(contract),
+            0,
+            EvmWord::from(5u8),
+            EvmWord::from(2u8),
+            100_000,
+            vec![],
+        );
+        let block_ctants = BlockConstants::new(
+            EvmWord::from(0u8),
+            Fp::zero(),
+            Fp::zero(),
+            Fp::from(0x10),
+            Fp::zero(),
+            Fp::zero(),
+            Fp::zero(),
+            Fp::zero(),
+        );
+        let build = |input_trace: &str, slim: bool| {
+            let mut builder = ExecutionTraceBuilder::new(block_ctants.clone())
+                .with_tx(tx_ctx.clone())
+                .with_prestate(prestate.clone());
+            if slim {
+                builder.push_slim_trace_bytes(input_trace.as_bytes())
+            } else {
+                builder.push_trace_bytes(input_trace.as_bytes())
+            }
+            .expect("Error on parsing");
+            builder.finish().expect("Error on trace generation")
+        };
+
+        let full_trace = r#"
+        [
+            { "memory": {}, "stack": ["2"], "opcode": "PUSH1 02", "pc": 0 },
+            { "memory": {}, "stack": ["2", "3"], "opcode": "PUSH1 03", "pc": 2 },
+            { "memory": {}, "stack": ["5"], "opcode": "ADD", "pc": 4 },
+            { "memory": {}, "stack": ["5", "5"], "opcode": "DUP1", "pc": 5 },
+            { "memory": {}, "stack": ["5", "5", "0"], "opcode": "PUSH1 00", "pc": 6 },
+            { "memory": { "0": "w" }, "stack": ["5"], "opcode": "MSTORE", "pc": 8 },
+            { "memory": { "0": "w" }, "stack": ["5", "10"], "opcode": "NUMBER", "pc": 9 },
+            { "memory": { "0": "w" }, "stack": ["5", "10", "c"], "opcode": "SELFBALANCE", "pc": 10 },
+            { "memory": { "0": "w" }, "stack": ["5", "1c"], "opcode": "ADD", "pc": 11 },
+            { "memory": { "0": "w" }, "stack": ["5", "1c", "11"], "opcode": "PUSH1 11", "pc": 12 },
+            { "memory": { "0": "w" }, "stack": ["5", "1c", "cf2bb"], "opcode": "BALANCE", "pc": 14 },
+            { "memory": { "0": "w" }, "stack": ["5", "1c", "cf2bb"], "opcode": "STOP", "pc": 15 }
+        ]
+        "#
+        .replace("\"w\"", &format!("\"{:0>64}\"", "5"));
+        // The slim trace holds the stack of `DUP1` and the memory of `STOP`.
+        let slim_trace = r#"
+        [
+            { "opcode": "PUSH1 02", "pc": 0 },
+            { "opcode": "PUSH1 03", "pc": 2 },
+            { "opcode": "ADD", "pc": 4 },
+            { "stack": ["5", "5"], "opcode": "DUP1", "pc": 5 },
+            { "opcode": "PUSH1 00", "pc": 6 },
+            { "opcode": "MSTORE", "pc": 8 },
+            { "opcode": "NUMBER", "pc": 9 },
+            { "opcode": "SELFBALANCE", "pc": 10 },
+            { "opcode": "ADD", "pc": 11 },
+            { "opcode": "PUSH1 11", "pc": 12 },
+            { "opcode": "BALANCE", "pc": 14 },
+            { "memory": { "0": "w" }, "opcode": "STOP", "pc": 15 }
+        ]
+        "#
+        .replace("\"w\"", &format!("\"{:0>64}\"", "5"));
+
+        let full = build(&full_trace, false);
+        let slim = build(&slim_trace, true);
+        assert_eq!(slim.steps(), full.steps());
+        assert_eq!(slim.rw_table(), full.rw_table());
+        assert_eq!(slim.state_violations(), vec![]);
+    }
+
+    #[test]
+    fn memory_bounded_by_gas() {
+        // PUSH1 01 PUSH2 0400 MSTORE STOP, where the `MSTORE` can't pay for
+        // expanding the memory to 33 words.
+        let code = bytes_of("6001610400520000");
+        let input_trace = r#"
+        [
+            { "opcode": "PUSH1", "pc": 0, "gas": 100, "gasCost": 3 },
+            { "opcode": "PUSH2", "pc": 2, "gas": 97, "gasCost": 3 },
+            { "opcode": "MSTORE", "pc": 5, "gas": 94, "gasCost": 101 }
+        ]
+        "#;
+        let steps =
+            replay(input_trace, &code, SnapshotConvention::PostState).unwrap();
+        assert!(steps[2].memory().is_empty());
+        assert_eq!(steps[2].stack(), steps[1].stack());
+    }
+
+    fn bytes_of(hex: &str) -> Vec<u8> {
+        crate::evm::bytes_from_hex(hex).expect("Error on hex parsing")
+    }
+}
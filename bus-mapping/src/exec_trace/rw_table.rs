@@ -0,0 +1,416 @@
+//! Flat read/write table holding the operations of every [`Target`] of an
+//! [`ExecutionTrace`](super::ExecutionTrace) as rows of the same shape, which
+//! is the layout the circuits assign and commit to.
+
+use crate::evm::{EvmWord, GlobalCounter};
+use crate::operation::{container::OperationContainer, Target, RW};
+
+/// Target of a [`RwRow`]. It matches [`Target`], except for the access list,
+/// whose accounts and storage slots are kept apart so that the `key` of a row
+/// always identifies a single location.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub enum RwTarget {
+    /// Byte of the memory of a call.
+    Memory = 1,
+    /// Slot of the stack of a call.
+    Stack,
+    /// Storage slot of an account.
+    Storage,
+    /// Field of the state of an account.
+    Account,
+    /// Field of the context of a call or transaction.
+    Context,
+    /// Account inside of the accessed addresses set.
+    AccessListAccount,
+    /// Storage slot inside of the accessed storage keys set.
+    AccessListStorage,
+    /// Field of a log emitted during the transaction.
+    Log,
+}
+
+impl RwTarget {
+    /// Returns the [`Target`] of the operations the rows come from.
+    pub const fn target(&self) -> Target {
+        match self {
+            RwTarget::Memory => Target::Memory,
+            RwTarget::Stack => Target::Stack,
+            RwTarget::Storage => Target::Storage,
+            RwTarget::Account => Target::Account,
+            RwTarget::Context => Target::Context,
+            RwTarget::AccessListAccount | RwTarget::AccessListStorage => {
+                Target::AccessList
+            }
+            RwTarget::Log => Target::Log,
+        }
+    }
+
+    /// Returns the tag assigned to the target column of the table.
+    pub const fn as_u64(&self) -> u64 {
+        *self as u64
+    }
+}
+
+/// Row of the read/write table of an
+/// [`ExecutionTrace`](super::ExecutionTrace), normalised so that every
+/// operation fills the same columns:
+///
+/// | Target              | `call_id` | `address`       | `key`                    | `value`, `value_prev` |
+/// | ------------------- | --------- | --------------- | ------------------------ | --------------------- |
+/// | `Memory`            | call      | memory address  | 0                        | byte                  |
+/// | `Stack`             | call      | stack address   | 0                        | word                  |
+/// | `Storage`           | 0         | account address | storage key              | word                  |
+/// | `Account`           | 0         | account address | field tag                | word                  |
+/// | `Context`           | call      | 0               | field tag                | word                  |
+/// | `AccessListAccount` | 0         | account address | 0                        | 0 or 1                |
+/// | `AccessListStorage` | 0         | account address | storage key              | 0 or 1                |
+/// | `Log`               | 0         | log id          | field tag · 2^32 + index | word                  |
+///
+/// The `value_prev` of memory and stack rows is the value of the location
+/// before the operation (zero for fresh locations), and the one of context
+/// rows is their `value`, as they are only read.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RwRow {
+    gc: GlobalCounter,
+    target: RwTarget,
+    call_id: usize,
+    address: EvmWord,
+    key: EvmWord,
+    rw: RW,
+    value: EvmWord,
+    value_prev: EvmWord,
+}
+
+impl RwRow {
+    /// Generate a new `RwRow` instance from it's fields.
+    #[allow(clippy::too_many_arguments)]
+    pub const fn new(
+        gc: GlobalCounter,
+        target: RwTarget,
+        call_id: usize,
+        address: EvmWord,
+        key: EvmWord,
+        rw: RW,
+        value: EvmWord,
+        value_prev: EvmWord,
+    ) -> RwRow {
+        RwRow {
+            gc,
+            target,
+            call_id,
+            address,
+            key,
+            rw,
+            value,
+            value_prev,
+        }
+    }
+
+    #[inline]
+    /// Return the [`GlobalCounter`] of the operation.
+    pub const fn gc(&self) -> GlobalCounter {
+        self.gc
+    }
+
+    #[inline]
+    /// Return the [`RwTarget`] of the row.
+    pub const fn target(&self) -> RwTarget {
+        self.target
+    }
+
+    #[inline]
+    /// Return the id of the call the location belongs to.
+    pub const fn call_id(&self) -> usize {
+        self.call_id
+    }
+
+    #[inline]
+    /// Return the address of the location.
+    pub const fn address(&self) -> &EvmWord {
+        &self.address
+    }
+
+    #[inline]
+    /// Return the key of the location inside of it's address.
+    pub const fn key(&self) -> &EvmWord {
+        &self.key
+    }
+
+    #[inline]
+    /// Return the [`RW`] of the operation.
+    pub const fn rw(&self) -> RW {
+        self.rw
+    }
+
+    #[inline]
+    /// Return the value of the location after the operation.
+    pub const fn value(&self) -> &EvmWord {
+        &self.value
+    }
+
+    #[inline]
+    /// Return the value of the location before the operation.
+    pub const fn value_prev(&self) -> &EvmWord {
+        &self.value_prev
+    }
+
+    /// Returns the columns of the row as words in the order `gc`, `target`,
+    /// `call_id`, `address`, `key`, `rw` (1 for writes), `value` and
+    /// `value_prev`, which is how they get assigned and hashed.
+    pub fn to_words(&self) -> [EvmWord; 8] {
+        [
+            EvmWord::from(self.gc.0),
+            EvmWord::from(self.target.as_u64()),
+            EvmWord::from(self.call_id),
+            self.address.clone(),
+            self.key.clone(),
+            EvmWord::from(self.rw.is_write() as u8),
+            self.value.clone(),
+            self.value_prev.clone(),
+        ]
+    }
+
+    /// Returns the tuple the table is sorted by.
+    fn sort_key(&self) -> (RwTarget, usize, &EvmWord, &EvmWord, GlobalCounter) {
+        (self.target, self.call_id, &self.address, &self.key, self.gc)
+    }
+}
+
+/// Builds the read/write table of the operations of `container`, sorted by
+/// target, call id, address, key and [`GlobalCounter`].
+pub(crate) fn build_rw_table(container: &OperationContainer) -> Vec<RwRow> {
+    let zero = || EvmWord::from(0u8);
+    let bit = |b: bool| EvmWord::from(b as u8);
+    let mut rows = Vec::with_capacity(
+        container.memory().len()
+            + container.stack().len()
+            + container.storage().len()
+            + container.account().len()
+            + container.context().len()
+            + container.access_list().len()
+            + container.log().len(),
+    );
+
+    // Memory and stack operations don't record the previous value of their
+    // location, so it's taken from the row before them once sorted.
+    rows.extend(container.memory().iter().map(|op| {
+        RwRow::new(
+            op.gc(),
+            RwTarget::Memory,
+            op.call_id(),
+            EvmWord(op.address().0.clone()),
+            zero(),
+            op.rw(),
+            op.value().clone(),
+            zero(),
+        )
+    }));
+    rows.extend(container.stack().iter().map(|op| {
+        RwRow::new(
+            op.gc(),
+            RwTarget::Stack,
+            op.call_id(),
+            EvmWord::from(op.address().0),
+            zero(),
+            op.rw(),
+            op.value().clone(),
+            zero(),
+        )
+    }));
+    rows.extend(container.storage().iter().map(|op| {
+        RwRow::new(
+            op.gc(),
+            RwTarget::Storage,
+            0,
+            op.address().to_word(),
+            op.key().clone(),
+            op.rw(),
+            op.value().clone(),
+            op.value_prev().clone(),
+        )
+    }));
+    rows.extend(container.account().iter().map(|op| {
+        RwRow::new(
+            op.gc(),
+            RwTarget::Account,
+            0,
+            op.address().to_word(),
+            EvmWord::from(op.field() as u8),
+            op.rw(),
+            op.value().clone(),
+            op.value_prev().clone(),
+        )
+    }));
+    rows.extend(container.context().iter().map(|op| {
+        RwRow::new(
+            op.gc(),
+            RwTarget::Context,
+            op.call_id(),
+            zero(),
+            EvmWord::from(op.field() as u8),
+            op.rw(),
+            op.value().clone(),
+            op.value().clone(),
+        )
+    }));
+    rows.extend(container.access_list().iter().map(|op| {
+        let (target, key) = match op.key() {
+            Some(key) => (RwTarget::AccessListStorage, key.clone()),
+            None => (RwTarget::AccessListAccount, zero()),
+        };
+        RwRow::new(
+            op.gc(),
+            target,
+            0,
+            op.address().to_word(),
+            key,
+            op.rw(),
+            bit(op.value()),
+            bit(op.value_prev()),
+        )
+    }));
+
+    rows.extend(container.log().iter().map(|op| {
+        RwRow::new(
+            op.gc(),
+            RwTarget::Log,
+            0,
+            EvmWord::from(op.log_id()),
+            EvmWord::from(((op.field() as u64) << 32) + op.index() as u64),
+            op.rw(),
+            op.value().clone(),
+            op.value_prev().clone(),
+        )
+    }));
+
+    rows.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+
+    for idx in 1..rows.len() {
+        let (before, after) = rows.split_at_mut(idx);
+        let (prev, row) = (&before[idx - 1], &mut after[0]);
+        if matches!(row.target, RwTarget::Memory | RwTarget::Stack)
+            && prev.target == row.target
+            && prev.call_id == row.call_id
+            && prev.address == row.address
+        {
+            row.value_prev = prev.value.clone();
+        }
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod rw_table_tests {
+    use super::*;
+    use crate::evm::{Address, MemoryAddress, StackAddress};
+    use crate::operation::{
+        AccessListOp, AccountField, AccountOp, ContextField, ContextOp,
+        MemoryOp, StackOp, StorageOp,
+    };
+
+    #[test]
+    fn rows_are_normalised_and_sorted() {
+        let address = Address::from([0xaa; 20]);
+        let mut container = OperationContainer::new();
+        container.insert(AccessListOp::new(
+            RW::WRITE,
+            GlobalCounter(1),
+            address,
+            Some(EvmWord::from(0u8)),
+            true,
+            false,
+        ));
+        container.insert(AccessListOp::new(
+            RW::WRITE,
+            GlobalCounter(2),
+            address,
+            None,
+            true,
+            false,
+        ));
+        container.insert(StackOp::new(
+            RW::WRITE,
+            GlobalCounter(3),
+            1,
+            StackAddress::from(1023),
+            EvmWord::from(0x40u8),
+        ));
+        container.insert(StackOp::new(
+            RW::READ,
+            GlobalCounter(5),
+            1,
+            StackAddress::from(1023),
+            EvmWord::from(0x40u8),
+        ));
+        container.insert(MemoryOp::new(
+            RW::WRITE,
+            GlobalCounter(6),
+            1,
+            MemoryAddress::from(0x40),
+            EvmWord::from(0x80u8),
+        ));
+        container.insert(StorageOp::new(
+            RW::WRITE,
+            GlobalCounter(7),
+            address,
+            EvmWord::from(1u8),
+            EvmWord::from(2u8),
+            EvmWord::from(1u8),
+        ));
+        container.insert(AccountOp::new(
+            RW::WRITE,
+            GlobalCounter(8),
+            address,
+            AccountField::Balance,
+            EvmWord::from(9u8),
+            EvmWord::from(10u8),
+        ));
+        container.insert(ContextOp::new(
+            GlobalCounter(9),
+            1,
+            ContextField::Caller,
+            address.to_word(),
+        ));
+
+        let rows = build_rw_table(&container);
+        let targets: Vec<(RwTarget, GlobalCounter)> =
+            rows.iter().map(|row| (row.target(), row.gc())).collect();
+        assert_eq!(
+            targets,
+            vec![
+                (RwTarget::Memory, GlobalCounter(6)),
+                (RwTarget::Stack, GlobalCounter(3)),
+                (RwTarget::Stack, GlobalCounter(5)),
+                (RwTarget::Storage, GlobalCounter(7)),
+                (RwTarget::Account, GlobalCounter(8)),
+                (RwTarget::Context, GlobalCounter(9)),
+                (RwTarget::AccessListAccount, GlobalCounter(2)),
+                (RwTarget::AccessListStorage, GlobalCounter(1)),
+            ]
+        );
+
+        // Memory and stack rows get the previous value of their location.
+        assert_eq!(rows[0].value_prev(), &EvmWord::from(0u8));
+        assert_eq!(rows[1].value_prev(), &EvmWord::from(0u8));
+        assert_eq!(rows[2].value_prev(), &EvmWord::from(0x40u8));
+        assert_eq!(rows[2].address(), &EvmWord::from(1023u16));
+
+        assert_eq!(
+            rows[4].to_words(),
+            [
+                EvmWord::from(8u8),
+                EvmWord::from(RwTarget::Account.as_u64()),
+                EvmWord::from(0u8),
+                address.to_word(),
+                EvmWord::from(AccountField::Balance as u8),
+                EvmWord::from(1u8),
+                EvmWord::from(9u8),
+                EvmWord::from(10u8),
+            ]
+        );
+        assert_eq!(rows[5].value_prev(), &address.to_word());
+        assert_eq!(rows[7].key(), &EvmWord::from(0u8));
+        assert_eq!(rows[7].value(), &EvmWord::from(1u8));
+        assert_eq!(rows[7].target().target(), Target::AccessList);
+    }
+}
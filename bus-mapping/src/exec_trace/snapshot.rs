@@ -0,0 +1,302 @@
+//! Conventions followed by the stack and memory views held by the
+//! [`ExecutionStep`]s of a trace, and the snapshots around each step that the
+//! [`Opcode`](crate::evm::opcodes::Opcode)s read their operands and results
+//! from.
+
+use super::ExecutionStep;
+use crate::evm::{Gas, GasCost, GlobalCounter, Instruction, OpcodeId};
+use alloc::collections::BTreeMap;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// Step holding the empty stack and memory that every call frame starts
+    /// with.
+    static ref FRAME_START: ExecutionStep = ExecutionStep::new(
+        BTreeMap::new(),
+        vec![],
+        Instruction::new(OpcodeId::STOP, None),
+        0.into(),
+        0,
+        None,
+        GasCost::default(),
+        Gas::default(),
+        GlobalCounter::from(0),
+    );
+}
+
+/// Point of the execution of it's instruction at which the stack and memory
+/// views of the [`ExecutionStep`]s of a trace are taken.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SnapshotConvention {
+    /// The views are taken once the instruction has been executed, so the
+    /// operands of a step are found in the previous step of it's call frame.
+    /// This is the convention of the zkevm test vectors.
+    PostState,
+    /// The views are taken right before the instruction runs, so the results
+    /// of a step are found in the next step of it's call frame. This is the
+    /// convention of the struct logs returned by geth's
+    /// `debug_traceTransaction`.
+    PreState,
+}
+
+impl Default for SnapshotConvention {
+    fn default() -> Self {
+        SnapshotConvention::PostState
+    }
+}
+
+/// Steps executed right before and right after an [`ExecutionStep`] inside of
+/// it's call frame, which together with the step itself hold the views of the
+/// stack and memory before and after it's instruction runs.
+///
+/// The [`SnapshotConvention`] of the trace decides which of them holds each
+/// view:
+///
+/// | Convention  | [`before`](StepSnapshots::before) | [`after`](StepSnapshots::after) |
+/// | ----------- | --------------------------------- | ------------------------------- |
+/// | `PostState` | previous step                     | the step itself                 |
+/// | `PreState`  | the step itself                   | next step                       |
+///
+/// The next step of a call opcode is the one it's frame resumes at once the
+/// callee returns.
+#[derive(Clone, Copy, Debug)]
+pub struct StepSnapshots<'a> {
+    convention: SnapshotConvention,
+    prev: Option<&'a ExecutionStep>,
+    next: Option<&'a ExecutionStep>,
+}
+
+impl<'a> StepSnapshots<'a> {
+    /// Generate a new `StepSnapshots` instance from it's fields.
+    pub const fn new(
+        convention: SnapshotConvention,
+        prev: Option<&'a ExecutionStep>,
+        next: Option<&'a ExecutionStep>,
+    ) -> StepSnapshots<'a> {
+        StepSnapshots {
+            convention,
+            prev,
+            next,
+        }
+    }
+
+    #[inline]
+    /// Return the [`SnapshotConvention`] of the trace.
+    pub const fn convention(&self) -> SnapshotConvention {
+        self.convention
+    }
+
+    #[inline]
+    /// Return the previous step of the call frame, if any.
+    pub const fn prev(&self) -> Option<&'a ExecutionStep> {
+        self.prev
+    }
+
+    #[inline]
+    /// Return the next step of the call frame, if any.
+    pub const fn next(&self) -> Option<&'a ExecutionStep> {
+        self.next
+    }
+
+    /// Returns the step holding the stack and memory of the frame of `step`
+    /// right before it's instruction runs, which is where it's operands are
+    /// read from. The first step of a frame, which has no previous step,
+    /// gets an empty stack and memory under the `PostState` convention.
+    pub fn before<'s>(&self, step: &'s ExecutionStep) -> &'s ExecutionStep
+    where
+        'a: 's,
+    {
+        match self.convention {
+            SnapshotConvention::PostState => self.prev.unwrap_or(&FRAME_START),
+            SnapshotConvention::PreState => step,
+        }
+    }
+
+    /// Returns the step holding the stack and memory of the frame of `step`
+    /// once it's instruction has been executed, which is where it's results
+    /// are read from.
+    pub fn after<'s>(
+        &self,
+        step: &'s ExecutionStep,
+    ) -> Option<&'s ExecutionStep>
+    where
+        'a: 's,
+    {
+        match self.convention {
+            SnapshotConvention::PostState => Some(step),
+            SnapshotConvention::PreState => self.next,
+        }
+    }
+}
+
+/// Returns the index of the step the frame of `steps[idx]` resumes at once it
+/// has been executed: the first of the following steps at the same depth,
+/// provided the frame doesn't end before it.
+pub(crate) fn next_in_frame(
+    steps: &[ExecutionStep],
+    idx: usize,
+) -> Option<usize> {
+    let depth = steps[idx].depth();
+    steps[idx + 1..]
+        .iter()
+        .take_while(|step| step.depth() >= depth)
+        .position(|step| step.depth() == depth)
+        .map(|offset| idx + 1 + offset)
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::*;
+    use crate::evm::opcodes::OpcodeRegistry;
+    use crate::evm::{Hardfork, ProgramCounter};
+    use crate::exec_trace::{ExecutionTrace, TxContext};
+    use crate::operation::RW;
+    use crate::test_util;
+    use pasta_curves::Fp;
+
+    fn trace(
+        input_trace: &str,
+        convention: SnapshotConvention,
+    ) -> ExecutionTrace<Fp> {
+        let block_ctants = test_util::block_ctants();
+        ExecutionTrace::from_trace_bytes_with_convention(
+            input_trace.as_bytes(),
+            block_ctants,
+            TxContext::default(),
+            Hardfork::default(),
+            &OpcodeRegistry::default(),
+            convention,
+        )
+        .expect("Error on trace generation")
+    }
+
+    #[test]
+    fn pre_and_post_state_traces_match() {
+        let post_state = trace(
+            r#"
+            [
+                { "memory": {}, "stack": ["40"], "opcode": "PUSH1 40", "pc": 0 },
+                { "memory": {}, "stack": ["40", "0"], "opcode": "PUSH1 00", "pc": 2 },
+                { "memory": {}, "stack": [], "opcode": "SSTORE", "pc": 4 },
+                { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 5 },
+                { "memory": {}, "stack": ["40"], "opcode": "SLOAD", "pc": 7 },
+                { "memory": {}, "stack": ["40"], "opcode": "STOP", "pc": 8 }
+            ]
+            "#,
+            SnapshotConvention::PostState,
+        );
+        // The same execution, with the views taken before each step.
+        let pre_state = trace(
+            r#"
+            [
+                { "memory": {}, "stack": [], "opcode": "PUSH1 40", "pc": 0 },
+                { "memory": {}, "stack": ["40"], "opcode": "PUSH1 00", "pc": 2 },
+                { "memory": {}, "stack": ["40", "0"], "opcode": "SSTORE", "pc": 4 },
+                { "memory": {}, "stack": [], "opcode": "PUSH1 00", "pc": 5 },
+                { "memory": {}, "stack": ["0"], "opcode": "SLOAD", "pc": 7 },
+                { "memory": {}, "stack": ["40"], "opcode": "STOP", "pc": 8 }
+            ]
+            "#,
+            SnapshotConvention::PreState,
+        );
+
+        assert_eq!(
+            pre_state.snapshot_convention(),
+            SnapshotConvention::PreState
+        );
+        assert_eq!(pre_state.container(), post_state.container());
+        assert_eq!(pre_state.state_violations(), vec![]);
+        assert_eq!(pre_state.sorted_stack_ops().len(), 7);
+    }
+
+    #[test]
+    fn results_without_a_next_step_are_skipped() {
+        // The last step of a `PreState` trace has no step after it holding
+        // the sum it pushes, so only it's operands are read.
+        let pre_state = trace(
+            r#"
+            [
+                { "memory": {}, "stack": [], "opcode": "PUSH1 40", "pc": 0 },
+                { "memory": {}, "stack": ["40"], "opcode": "DUP1", "pc": 2 },
+                { "memory": {}, "stack": ["40", "40"], "opcode": "ADD", "pc": 3 }
+            ]
+            "#,
+            SnapshotConvention::PreState,
+        );
+
+        let add_ops: Vec<RW> = pre_state
+            .sorted_stack_ops()
+            .filter(|op| {
+                pre_state.origin_of(*op).map(|origin| origin.opcode())
+                    == Some(OpcodeId::ADD)
+            })
+            .map(|op| op.rw())
+            .collect();
+        assert_eq!(add_ops, vec![RW::READ, RW::READ]);
+    }
+
+    #[test]
+    fn first_steps_read_an_empty_stack() {
+        // ADD has no operands to read as the first step of a `PostState`
+        // trace, and halts exceptionally.
+        let post_state = trace(
+            r#"
+            [
+                { "memory": {}, "stack": [], "opcode": "ADD", "pc": 0 }
+            ]
+            "#,
+            SnapshotConvention::PostState,
+        );
+
+        assert_eq!(post_state.sorted_stack_ops().len(), 0);
+    }
+
+    #[test]
+    fn snapshots_around_steps() {
+        let step = |opcode: OpcodeId, depth: usize| {
+            ExecutionStep::new(
+                BTreeMap::new(),
+                vec![],
+                Instruction::new(opcode, None),
+                ProgramCounter::from(0),
+                depth,
+                None,
+                GasCost::default(),
+                Gas::default(),
+                GlobalCounter::from(0),
+            )
+        };
+        let steps = vec![
+            step(OpcodeId::CALL, 1),
+            step(OpcodeId::STOP, 2),
+            step(OpcodeId::CALLER, 1),
+            step(OpcodeId::STOP, 1),
+        ];
+
+        // Calls resume once the callee returns, and frames end on their last
+        // step.
+        assert_eq!(next_in_frame(&steps, 0), Some(2));
+        assert_eq!(next_in_frame(&steps, 1), None);
+        assert_eq!(next_in_frame(&steps, 3), None);
+
+        let post_state = StepSnapshots::new(
+            SnapshotConvention::PostState,
+            Some(&steps[0]),
+            Some(&steps[3]),
+        );
+        assert_eq!(post_state.before(&steps[2]), &steps[0]);
+        // The first step of a frame reads from an empty stack and memory.
+        let first =
+            StepSnapshots::new(SnapshotConvention::PostState, None, None);
+        assert!(first.before(&steps[0]).stack().is_empty());
+        assert!(first.before(&steps[0]).memory().is_empty());
+        assert_eq!(post_state.after(&steps[2]), Some(&steps[2]));
+        let pre_state = StepSnapshots::new(
+            SnapshotConvention::PreState,
+            Some(&steps[0]),
+            Some(&steps[3]),
+        );
+        assert_eq!(pre_state.before(&steps[2]), &steps[2]);
+        assert_eq!(pre_state.after(&steps[2]), Some(&steps[3]));
+    }
+}
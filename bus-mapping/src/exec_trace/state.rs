@@ -0,0 +1,1251 @@
+//! State shared across all of the [`ExecutionStep`](super::ExecutionStep)s of
+//! an [`ExecutionTrace`](super::ExecutionTrace) while their
+//! [`Operation`](crate::operation::Operation)s are being generated.
+
+use super::context::{CallContext, CallKind, ContextTable, TxContext};
+use super::copy_event::CopyEvent;
+use super::{validation, ExecutionStep, StepSnapshots};
+use crate::evm::{
+    gas::{
+        all_but_one_64th, capped_refund, intrinsic_gas, memory_access,
+        memory_word_size,
+    },
+    precompiles::{Precompile, PrecompileCall},
+    Address, EvmWord, GasCost, Hardfork, EMPTY_CODE_HASH,
+};
+use crate::operation::{
+    container::OperationContainer, AccessListOp, AccountField, AccountOp,
+    ContextField, ContextOp, LogField, LogOp, StorageOp, RW,
+};
+use crate::state_db::{Account, StateDB};
+use crate::TraceError;
+use alloc::collections::{BTreeMap, BTreeSet};
+use num::{BigUint, CheckedSub, Zero};
+
+/// Write into the state performed by an
+/// [`Operation`](crate::operation::Operation) that has to be undone if the call
+/// frame that performed it (or any of it's ancestors) ends up reverting.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum JournalEntry {
+    Account {
+        address: Address,
+        field: AccountField,
+        value: EvmWord,
+        value_prev: EvmWord,
+    },
+    Storage {
+        address: Address,
+        key: EvmWord,
+        value: EvmWord,
+        value_prev: EvmWord,
+    },
+    AccessedAddress {
+        address: Address,
+    },
+    AccessedStorage {
+        address: Address,
+        key: EvmWord,
+    },
+    Log {
+        log_id: usize,
+        field: LogField,
+        index: usize,
+        value: EvmWord,
+    },
+}
+
+/// Call frame that is currently active.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Frame {
+    // Index into `TraceState::calls`.
+    call_idx: usize,
+    // Length of the journal when the frame was opened.
+    journal_len: usize,
+    // Whether any step has been executed inside of the frame.
+    executed: bool,
+    // Whether the frame ended on `STOP`, `RETURN` or `SELFDESTRUCT`.
+    halted: bool,
+    // Whether the gas left is returned to the caller once the frame is
+    // exited, which exceptional halts don't do.
+    returns_gas: bool,
+    // Gas left in the frame, unknown when the gas of the transaction is.
+    gas: Option<u64>,
+    // Size in words of the memory of the frame.
+    memory_words: u64,
+    // Index of the last step executed inside of the frame.
+    last_step: Option<usize>,
+    // Address of the memory of the caller the calldata was read from.
+    calldata_offset: usize,
+    // Data returned by the last sub-call of the frame.
+    return_data: Vec<u8>,
+    // `call_id` of the last sub-call of the frame and address of it's memory
+    // the data it returned was read from.
+    return_data_source: (usize, usize),
+}
+
+impl Frame {
+    const fn new(
+        call_idx: usize,
+        journal_len: usize,
+        gas: Option<u64>,
+        calldata_offset: usize,
+    ) -> Frame {
+        Frame {
+            call_idx,
+            journal_len,
+            executed: false,
+            halted: false,
+            returns_gas: false,
+            gas,
+            memory_words: 0,
+            last_step: None,
+            calldata_offset,
+            return_data: vec![],
+            return_data_source: (0, 0),
+        }
+    }
+}
+
+/// Holds the [`TxContext`], the [`StateDB`] and the stack of active
+/// [`CallContext`]s that the [`Opcode`](crate::evm::Opcode) implementations
+/// read and update while generating the operations of each step.
+///
+/// It also keeps a journal of all of the writes into the state and the logs
+/// emitted by the active frames, so that the ones done inside of a frame that
+/// reverts can be undone by compensating writes, together with the accounts and
+/// storage slots accessed during the transaction
+/// ([EIP-2929](https://eips.ethereum.org/EIPS/eip-2929)). Since Berlin, every
+/// access to them is witnessed by an [`AccessListOp`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceState {
+    tx_ctx: TxContext,
+    hardfork: Hardfork,
+    state_db: StateDB,
+    // Every call opened during the trace, indexed by `call_id - 1`.
+    calls: Vec<CallContext>,
+    context_table: ContextTable,
+    frames: Vec<Frame>,
+    journal: Vec<JournalEntry>,
+    accessed_addresses: BTreeSet<Address>,
+    accessed_storage: BTreeSet<(Address, EvmWord)>,
+    // Accesses done while computing the gas cost of the current step, as
+    // (address, key, was warm), whose ops are yet to be generated.
+    pending_accesses: Vec<(Address, Option<EvmWord>, bool)>,
+    // Values of the storage slots written during the transaction as they
+    // were before it.
+    committed_storage: BTreeMap<(Address, EvmWord), EvmWord>,
+    precompile_calls: Vec<PrecompileCall>,
+    copy_events: Vec<CopyEvent>,
+    // Number of logs emitted during the transaction and not reverted.
+    logs: usize,
+    // Gas forwarded to the callee by the current step, charged while
+    // computing it's gas cost.
+    call_gas: Option<u64>,
+    // Whether the step that follows the current one in the trace runs in a
+    // deeper frame.
+    next_step_deeper: bool,
+    // Index of the current step.
+    step_idx: usize,
+    // Account fields whose values are known from the initial state or from
+    // the values read by previous steps, rather than assumed.
+    known_fields: BTreeSet<(Address, AccountField)>,
+    // First flaw found while generating the operations of the steps.
+    error: Option<TraceError>,
+}
+
+impl TraceState {
+    /// Generates a new `TraceState` with the root call of the transaction as
+    /// the only active frame.
+    pub fn new(
+        tx_ctx: TxContext,
+        state_db: StateDB,
+        hardfork: Hardfork,
+    ) -> TraceState {
+        let mut root_call = tx_ctx.root_call(1);
+        if !root_call.kind().is_create() {
+            let code_hash =
+                state_db.account(root_call.address()).code_hash().clone();
+            if let Some(code) = state_db.code(&code_hash) {
+                root_call.set_code(code.to_vec());
+            }
+        }
+        let mut context_table = ContextTable::new(&tx_ctx);
+        context_table.insert_call(&root_call);
+        let known_fields = state_db
+            .addresses()
+            .flat_map(|address| {
+                [
+                    AccountField::Nonce,
+                    AccountField::Balance,
+                    AccountField::CodeHash,
+                ]
+                .map(|field| (*address, field))
+            })
+            .collect();
+        // The root call gets the gas limit of the transaction once it's
+        // intrinsic gas has been charged.
+        let gas = Some(tx_ctx.gas())
+            .filter(|gas| *gas != 0)
+            .and_then(|gas| gas.checked_sub(intrinsic_gas(&tx_ctx, hardfork)));
+        TraceState {
+            tx_ctx,
+            hardfork,
+            state_db,
+            calls: vec![root_call],
+            context_table,
+            frames: vec![Frame::new(0, 0, gas, 0)],
+            journal: vec![],
+            accessed_addresses: BTreeSet::new(),
+            accessed_storage: BTreeSet::new(),
+            pending_accesses: vec![],
+            committed_storage: BTreeMap::new(),
+            precompile_calls: vec![],
+            copy_events: vec![],
+            logs: 0,
+            call_gas: None,
+            next_step_deeper: false,
+            step_idx: 0,
+            known_fields,
+            error: None,
+        }
+    }
+
+    /// Returns the [`TxContext`] of the trace.
+    pub const fn tx_ctx(&self) -> &TxContext {
+        &self.tx_ctx
+    }
+
+    /// Returns the [`Hardfork`] the trace is executed at.
+    pub const fn hardfork(&self) -> Hardfork {
+        self.hardfork
+    }
+
+    /// Returns the [`StateDB`] as it is at the current step.
+    pub const fn state_db(&self) -> &StateDB {
+        &self.state_db
+    }
+
+    /// Returns a mutable reference to the [`StateDB`].
+    ///
+    /// Writes done through it are not journaled, so they are not undone on
+    /// revert. Use [`TraceState::gen_account_write`] and
+    /// [`TraceState::gen_storage_write`] to witness state changes.
+    pub fn state_db_mut(&mut self) -> &mut StateDB {
+        &mut self.state_db
+    }
+
+    /// Returns the [`CallContext`] of the frame the current step runs in.
+    pub fn call(&self) -> &CallContext {
+        &self.calls[self.frame().call_idx]
+    }
+
+    /// Returns all of the [`CallContext`]s opened so far, ordered by
+    /// `call_id`.
+    pub fn calls(&self) -> &[CallContext] {
+        &self.calls
+    }
+
+    /// Returns the [`ContextTable`] of the transaction and of the calls
+    /// opened so far.
+    pub fn context_table(&self) -> &ContextTable {
+        &self.context_table
+    }
+
+    /// Returns true if the step that follows the current one in the trace
+    /// runs in a deeper frame, which means that the current step opened a
+    /// frame whose steps were executed.
+    pub fn enters_frame(&self) -> bool {
+        self.next_step_deeper
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("the root call is never exited")
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("the root call is never exited")
+    }
+
+    /// Opens a new frame on top of the current one, which becomes the frame
+    /// of the following steps, and returns it's `call_id`. The `calldata` of
+    /// the new call is read from the memory of the current one at
+    /// `calldata_offset`.
+    ///
+    /// The callee address is accessed by the current frame, so it stays warm
+    /// even if the new one reverts. The return data of the current frame is
+    /// cleared, and the entries of the new call are added to the
+    /// [`ContextTable`].
+    ///
+    /// Calls run with the gas forwarded to them by the gas cost of the
+    /// current step, while creations take all of the gas left in the current
+    /// frame but one 64th of it (all of it before
+    /// [EIP-150](https://eips.ethereum.org/EIPS/eip-150)).
+    pub fn push_call(
+        &mut self,
+        kind: CallKind,
+        address: Address,
+        value: EvmWord,
+        calldata: Vec<u8>,
+        calldata_offset: usize,
+        code: Vec<u8>,
+    ) -> usize {
+        let call_id = self.calls.len() + 1;
+        // `DELEGATECALL` keeps the caller of the current call.
+        let caller = match kind {
+            CallKind::DelegateCall => *self.call().caller(),
+            _ => *self.call().address(),
+        };
+        let depth = self.call().depth() + 1;
+        // Calls already access the callee when charged for it.
+        if !self.is_address_warm(&address) {
+            self.access_address(address);
+        }
+        let gas = if kind.is_create() {
+            let hardfork = self.hardfork;
+            let frame = self.frame_mut();
+            let gas = frame.gas.map(|gas| {
+                if hardfork >= Hardfork::TangerineWhistle {
+                    all_but_one_64th(gas)
+                } else {
+                    gas
+                }
+            });
+            frame.gas = frame.gas.zip(gas).map(|(left, gas)| left - gas);
+            gas
+        } else {
+            self.call_gas.take()
+        };
+        self.frame_mut().return_data.clear();
+        let call = CallContext::new(
+            call_id, kind, depth, caller, address, value, calldata, code,
+        );
+        self.context_table.insert_call(&call);
+        self.calls.push(call);
+        self.frames.push(Frame::new(
+            call_id - 1,
+            self.journal.len(),
+            gas,
+            calldata_offset,
+        ));
+        call_id
+    }
+
+    /// Returns the gas left in the current frame, if it's known.
+    pub fn gas_left(&self) -> Option<u64> {
+        self.frame().gas
+    }
+
+    /// Charges `cost` to the gas left in the current frame.
+    pub fn charge_gas(&mut self, cost: GasCost) {
+        let frame = self.frame_mut();
+        frame.gas = frame.gas.map(|gas| gas.saturating_sub(cost.0));
+    }
+
+    /// Sets the gas forwarded to the callee by the current step, including
+    /// the stipend of the calls that transfer value.
+    pub(crate) fn set_call_gas(&mut self, gas: Option<u64>) {
+        self.call_gas = gas;
+    }
+
+    /// Returns the gas forwarded to the callee by the current step, if it's
+    /// known.
+    pub(crate) fn call_gas(&self) -> Option<u64> {
+        self.call_gas
+    }
+
+    /// Returns the gas forwarded by the current step to the current frame,
+    /// as calls that fail without opening a frame do.
+    pub fn return_call_gas(&mut self) {
+        if let Some(call_gas) = self.call_gas.take() {
+            let frame = self.frame_mut();
+            frame.gas = frame.gas.map(|gas| gas.saturating_add(call_gas));
+        }
+    }
+
+    /// Expands the memory of the current frame over the region accessed by
+    /// `exec_step`, whose bounds are read from the stack
+    /// [before](StepSnapshots::before) it.
+    pub(crate) fn expand_memory(
+        &mut self,
+        snapshots: StepSnapshots<'_>,
+        exec_step: &ExecutionStep,
+    ) {
+        let before = snapshots.before(exec_step);
+        let arg = |idx: usize| {
+            before
+                .stack()
+                .iter()
+                .rev()
+                .nth(idx)
+                .map(|word| word.to_u64().unwrap_or(u64::MAX))
+                .unwrap_or(0)
+        };
+        if let Some((offset, size)) =
+            memory_access(exec_step.instruction().opcode_id(), arg)
+        {
+            let frame = self.frame_mut();
+            frame.memory_words = frame
+                .memory_words
+                .max(memory_word_size(offset.saturating_add(size)));
+        }
+    }
+
+    /// Writes `value` into the `field` of the account located at `address`,
+    /// generating the corresponding [`AccountOp`] for `exec_step` and
+    /// journaling the write so that it's undone if the frame reverts.
+    pub fn gen_account_write(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        address: Address,
+        field: AccountField,
+        value: EvmWord,
+    ) {
+        let value_prev = self.state_db.account_field(&address, field);
+        self.journal.push(JournalEntry::Account {
+            address,
+            field,
+            value: value.clone(),
+            value_prev: value_prev.clone(),
+        });
+        self.apply_account_write(
+            exec_step, container, address, field, value, value_prev,
+        );
+    }
+
+    /// Reads the `field` of the account located at `address`, generating the
+    /// corresponding [`AccountOp`] for `exec_step`, and returns it's value.
+    pub fn gen_account_read(
+        &self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        address: Address,
+        field: AccountField,
+    ) -> EvmWord {
+        let value = self.state_db.account_field(&address, field);
+        exec_step.add_op(
+            container,
+            AccountOp::new(
+                RW::READ,
+                exec_step.next_op_gc(),
+                address,
+                field,
+                value.clone(),
+                value.clone(),
+            ),
+        );
+        value
+    }
+
+    /// Returns true if the balance of `sender` covers `value`, which is the
+    /// check the EVM performs before any value transfer of a call or a
+    /// contract creation.
+    pub fn can_transfer(&self, sender: &Address, value: &EvmWord) -> bool {
+        self.state_db.account(sender).balance().0 >= value.0
+    }
+
+    /// Moves `value` from the balance of `sender` to the balance of
+    /// `receiver`, generating the corresponding [`AccountOp`]s for
+    /// `exec_step` and journaling them so that they are undone if the frame
+    /// reverts. Transfers of zero value don't generate any write, and the
+    /// ones that `sender` can't afford invalidate the trace with
+    /// [`TraceError::StateMismatch`].
+    pub fn transfer(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        sender: Address,
+        receiver: Address,
+        value: &EvmWord,
+    ) {
+        if value.is_zero() {
+            return;
+        }
+        let sender_balance = match self
+            .state_db
+            .account(&sender)
+            .balance()
+            .0
+            .checked_sub(&value.0)
+        {
+            Some(balance) => balance,
+            None => {
+                let step = self.step_idx;
+                self.invalidate(TraceError::StateMismatch { step });
+                return;
+            }
+        };
+        self.gen_account_write(
+            exec_step,
+            container,
+            sender,
+            AccountField::Balance,
+            EvmWord(sender_balance),
+        );
+        let receiver_balance =
+            &self.state_db.account(&receiver).balance().0 + &value.0;
+        self.gen_account_write(
+            exec_step,
+            container,
+            receiver,
+            AccountField::Balance,
+            EvmWord(receiver_balance),
+        );
+    }
+
+    /// Applies the effects of the transaction that take place before it's
+    /// first step runs, generating their [`AccountOp`]s for `exec_step`:
+    /// 1. The gas limit of the transaction is bought with the balance of the
+    /// sender.
+    /// 2. The nonce of the sender is incremented.
+    /// 3. For contract creations, the nonce of the new account is set to 1
+    /// ([EIP-161](https://eips.ethereum.org/EIPS/eip-161)).
+    /// 4. The value of the transaction is transferred to the receiver.
+    /// 5. The sender, the receiver, the precompiled contracts deployed at the
+    /// hardfork and the entries of the access list of the transaction are
+    /// added to the accessed sets, generating their [`AccessListOp`]s since
+    /// Berlin.
+    ///
+    /// The first two writes and the accesses are kept even if the transaction
+    /// reverts, so they aren't journaled. A sender unknown by the [`StateDB`]
+    /// is seeded with the nonce of the transaction and the balance it needs
+    /// to pay for it, while a known one that can't afford it invalidates the
+    /// trace with [`TraceError::StateMismatch`].
+    pub(crate) fn begin_tx(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) {
+        let tx_ctx = self.tx_ctx.clone();
+        let sender = *tx_ctx.from();
+        let gas_fee = BigUint::from(tx_ctx.gas()) * &tx_ctx.gas_price().0;
+        if !self.state_db.contains_account(&sender) {
+            self.state_db.insert_account(
+                sender,
+                Account::new(
+                    tx_ctx.nonce(),
+                    EvmWord(&gas_fee + &tx_ctx.value().0),
+                    EMPTY_CODE_HASH.clone(),
+                ),
+            );
+        }
+
+        let sender_account = self.state_db.account(&sender);
+        if sender_account.balance().0 < &gas_fee + &tx_ctx.value().0 {
+            let step = self.step_idx;
+            self.invalidate(TraceError::StateMismatch { step });
+            return;
+        }
+        if !gas_fee.is_zero() {
+            let balance = &sender_account.balance().0 - &gas_fee;
+            self.apply_account_write(
+                exec_step,
+                container,
+                sender,
+                AccountField::Balance,
+                EvmWord(balance),
+                sender_account.balance().clone(),
+            );
+        }
+        self.apply_account_write(
+            exec_step,
+            container,
+            sender,
+            AccountField::Nonce,
+            EvmWord::from(sender_account.nonce() + 1),
+            EvmWord::from(sender_account.nonce()),
+        );
+
+        let receiver = *self.calls[0].address();
+        if tx_ctx.is_create() && self.hardfork >= Hardfork::SpuriousDragon {
+            self.gen_account_write(
+                exec_step,
+                container,
+                receiver,
+                AccountField::Nonce,
+                EvmWord::from(1u8),
+            );
+        }
+        self.transfer(exec_step, container, sender, receiver, tx_ctx.value());
+
+        let precompiles = (1..=9u8).filter_map(|precompile| {
+            let mut address = [0u8; 20];
+            address[19] = precompile;
+            let address = Address::from(address);
+            Precompile::from_address(&address)
+                .filter(|precompile| self.hardfork.has_precompile(*precompile))
+                .map(|_| address)
+        });
+        let addresses: Vec<Address> = [sender, receiver]
+            .iter()
+            .copied()
+            .chain(precompiles)
+            .chain(tx_ctx.access_list().iter().map(|(address, _)| *address))
+            .collect();
+        for address in addresses {
+            let was_warm = !self.accessed_addresses.insert(address);
+            self.apply_access(
+                exec_step, container, address, None, true, was_warm,
+            );
+        }
+        for (address, keys) in tx_ctx.access_list() {
+            for key in keys {
+                let was_warm =
+                    !self.accessed_storage.insert((*address, key.clone()));
+                self.apply_access(
+                    exec_step,
+                    container,
+                    *address,
+                    Some(key.clone()),
+                    true,
+                    was_warm,
+                );
+            }
+        }
+    }
+
+    /// Applies the effects of the transaction that take place once it's last
+    /// step has run, generating their [`AccountOp`]s for `exec_step`:
+    /// 1. The `gas_left` plus the `refund` counter (capped as
+    /// [`capped_refund`] does and discarded if the transaction reverted) are
+    /// given back to the sender.
+    /// 2. The `coinbase` receives the priority fee of the gas used, which is
+    /// the whole gas price before London
+    /// ([EIP-1559](https://eips.ethereum.org/EIPS/eip-1559)).
+    pub(crate) fn end_tx(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        gas_left: u64,
+        refund: u64,
+        coinbase: Address,
+        base_fee: &EvmWord,
+    ) {
+        let tx_ctx = self.tx_ctx.clone();
+        let gas_used = tx_ctx.gas().saturating_sub(gas_left);
+        let refund = if self.calls[0].is_reverted() {
+            0
+        } else {
+            capped_refund(gas_used, refund, self.hardfork)
+        };
+        let gas_used = gas_used - refund;
+
+        let sender = *tx_ctx.from();
+        let gas_price = &tx_ctx.gas_price().0;
+        self.add_balance(
+            exec_step,
+            container,
+            sender,
+            BigUint::from(tx_ctx.gas() - gas_used) * gas_price,
+        );
+
+        let priority_fee = if self.hardfork >= Hardfork::London {
+            gas_price.checked_sub(&base_fee.0).unwrap_or_default()
+        } else {
+            gas_price.clone()
+        };
+        self.add_balance(
+            exec_step,
+            container,
+            coinbase,
+            BigUint::from(gas_used) * priority_fee,
+        );
+    }
+
+    /// Adds `amount` to the balance of the account located at `address`
+    /// without journaling the write.
+    fn add_balance(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        address: Address,
+        amount: BigUint,
+    ) {
+        if amount.is_zero() {
+            return;
+        }
+        let balance = self.state_db.account(&address).balance().clone();
+        self.apply_account_write(
+            exec_step,
+            container,
+            address,
+            AccountField::Balance,
+            EvmWord(&balance.0 + amount),
+            balance,
+        );
+    }
+
+    /// Writes `value` under `key` into the storage of the account located at
+    /// `address`, generating the corresponding [`StorageOp`] for `exec_step`
+    /// and journaling the write so that it's undone if the frame reverts.
+    pub fn gen_storage_write(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        address: Address,
+        key: EvmWord,
+        value: EvmWord,
+    ) {
+        let value_prev = self
+            .state_db
+            .storage(&address, &key)
+            .cloned()
+            .unwrap_or_else(|| EvmWord::from(0u8));
+        self.committed_storage
+            .entry((address, key.clone()))
+            .or_insert_with(|| value_prev.clone());
+        self.journal.push(JournalEntry::Storage {
+            address,
+            key: key.clone(),
+            value: value.clone(),
+            value_prev: value_prev.clone(),
+        });
+        self.apply_storage_write(
+            exec_step, container, address, key, value, value_prev,
+        );
+    }
+
+    /// Emits a log of the current call with the given `topics` and `data`,
+    /// generating the [`LogOp`]s that write it's address, topics and data
+    /// bytes for `exec_step` and journaling them so that the log is removed
+    /// if the frame reverts. Returns the id of the log.
+    pub fn gen_log(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        topics: &[EvmWord],
+        data: &[u8],
+    ) -> usize {
+        self.logs += 1;
+        let log_id = self.logs;
+        let address = self.call().address().to_word();
+        let fields = core::iter::once((LogField::Address, 0, address))
+            .chain(
+                topics
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, topic)| (LogField::Topic, idx, topic.clone())),
+            )
+            .chain(data.iter().enumerate().map(|(idx, byte)| {
+                (LogField::Data, idx, EvmWord::from(*byte))
+            }));
+        for (field, index, value) in fields {
+            self.journal.push(JournalEntry::Log {
+                log_id,
+                field,
+                index,
+                value: value.clone(),
+            });
+            self.apply_log_write(
+                exec_step,
+                container,
+                log_id,
+                field,
+                index,
+                value,
+                EvmWord::from(0u8),
+            );
+        }
+        log_id
+    }
+
+    /// Returns the value stored under `key` in the storage of the account
+    /// located at `address` before the transaction started.
+    pub fn committed_storage(
+        &self,
+        address: &Address,
+        key: &EvmWord,
+    ) -> EvmWord {
+        self.committed_storage
+            .get(&(*address, key.clone()))
+            .or_else(|| self.state_db.storage(address, key))
+            .cloned()
+            .unwrap_or_else(|| EvmWord::from(0u8))
+    }
+
+    /// Returns true if the account located at `address` has already been
+    /// accessed during the transaction.
+    pub fn is_address_warm(&self, address: &Address) -> bool {
+        self.accessed_addresses.contains(address)
+    }
+
+    /// Returns true if the storage slot `key` of the account located at
+    /// `address` has already been accessed during the transaction.
+    pub fn is_storage_warm(&self, address: &Address, key: &EvmWord) -> bool {
+        self.accessed_storage.contains(&(*address, key.clone()))
+    }
+
+    /// Marks the account located at `address` as accessed, returning true if
+    /// it wasn't (the access is cold). The access is undone if the current
+    /// frame reverts, and it's [`AccessListOp`] is generated by
+    /// [`TraceState::gen_access_ops`].
+    pub(crate) fn access_address(&mut self, address: Address) -> bool {
+        let is_cold = self.accessed_addresses.insert(address);
+        if is_cold {
+            self.journal.push(JournalEntry::AccessedAddress { address });
+        }
+        self.pending_accesses.push((address, None, !is_cold));
+        is_cold
+    }
+
+    /// Marks the storage slot `key` of the account located at `address` as
+    /// accessed, returning true if it wasn't (the access is cold). The access
+    /// is undone if the current frame reverts, and it's [`AccessListOp`] is
+    /// generated by [`TraceState::gen_access_ops`].
+    pub(crate) fn access_storage(
+        &mut self,
+        address: Address,
+        key: EvmWord,
+    ) -> bool {
+        let is_cold = self.accessed_storage.insert((address, key.clone()));
+        if is_cold {
+            self.journal.push(JournalEntry::AccessedStorage {
+                address,
+                key: key.clone(),
+            });
+        }
+        self.pending_accesses.push((address, Some(key), !is_cold));
+        is_cold
+    }
+
+    /// Generates the [`AccessListOp`]s of the accesses done since the last
+    /// call for `exec_step`, which are the ones done while computing it's gas
+    /// cost or while generating it's operations.
+    pub(crate) fn gen_access_ops(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) {
+        for (address, key, was_warm) in
+            core::mem::take(&mut self.pending_accesses)
+        {
+            self.apply_access(
+                exec_step, container, address, key, true, was_warm,
+            );
+        }
+    }
+
+    /// Generates the [`AccessListOp`] for `exec_step` that sets whether the
+    /// account located at `address` (or it's storage slot `key`) is in the
+    /// accessed sets. Access sets only exist since Berlin, so no operation is
+    /// generated before it.
+    fn apply_access(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        address: Address,
+        key: Option<EvmWord>,
+        value: bool,
+        value_prev: bool,
+    ) {
+        if self.hardfork < Hardfork::Berlin {
+            return;
+        }
+        exec_step.add_op(
+            container,
+            AccessListOp::new(
+                RW::WRITE,
+                exec_step.next_op_gc(),
+                address,
+                key,
+                value,
+                value_prev,
+            ),
+        );
+    }
+
+    fn apply_account_write(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        address: Address,
+        field: AccountField,
+        value: EvmWord,
+        value_prev: EvmWord,
+    ) {
+        exec_step.add_op(
+            container,
+            AccountOp::new(
+                RW::WRITE,
+                exec_step.next_op_gc(),
+                address,
+                field,
+                value.clone(),
+                value_prev,
+            ),
+        );
+        self.state_db.set_account_field(&address, field, value);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn apply_log_write(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        log_id: usize,
+        field: LogField,
+        index: usize,
+        value: EvmWord,
+        value_prev: EvmWord,
+    ) {
+        exec_step.add_op(
+            container,
+            LogOp::new(
+                RW::WRITE,
+                exec_step.next_op_gc(),
+                log_id,
+                field,
+                index,
+                value,
+                value_prev,
+            ),
+        );
+    }
+
+    fn apply_storage_write(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        address: Address,
+        key: EvmWord,
+        value: EvmWord,
+        value_prev: EvmWord,
+    ) {
+        exec_step.add_op(
+            container,
+            StorageOp::new(
+                RW::WRITE,
+                exec_step.next_op_gc(),
+                address,
+                key.clone(),
+                value.clone(),
+                value_prev,
+            ),
+        );
+        self.state_db.set_storage(&address, key, value);
+    }
+
+    /// Returns the data returned to the current frame by it's last sub-call.
+    pub fn return_data(&self) -> &[u8] {
+        &self.frame().return_data
+    }
+
+    /// Returns the `call_id` of the last sub-call of the current frame and
+    /// the address of it's memory that the data it returned was read from.
+    pub fn return_data_source(&self) -> (usize, usize) {
+        self.frame().return_data_source
+    }
+
+    /// Returns the `call_id` of the caller of the current call and the
+    /// address of it's memory that the calldata was read from, or `None`
+    /// for the root call, whose calldata is the input of the transaction.
+    pub fn calldata_source(&self) -> Option<(usize, usize)> {
+        let caller_idx = self.frames.len().checked_sub(2)?;
+        Some((
+            self.frames[caller_idx].call_idx + 1,
+            self.frame().calldata_offset,
+        ))
+    }
+
+    /// Clears the data returned to the current frame, as calls that fail
+    /// without opening a frame do.
+    pub fn clear_return_data(&mut self) {
+        self.frame_mut().return_data.clear();
+    }
+
+    /// Sets `data`, read from the memory of the current frame at `offset`,
+    /// as the data returned by the current frame to it's caller.
+    pub fn set_return_data(&mut self, data: Vec<u8>, offset: usize) {
+        let source = (self.call().call_id(), offset);
+        if let Some(caller_idx) = self.frames.len().checked_sub(2) {
+            let caller = &mut self.frames[caller_idx];
+            caller.return_data = data;
+            caller.return_data_source = source;
+        }
+    }
+
+    /// Reads the entry `field` of the context of the current call, generating
+    /// the corresponding [`ContextOp`] for `exec_step`, and returns it's
+    /// value.
+    ///
+    /// The [dynamic](ContextField::is_dynamic) fields are taken from the
+    /// current frame, with the gas left once the step has been charged, and
+    /// the rest from the [`ContextTable`]. Entries that aren't known, like the
+    /// code size of a call whose code is missing from the state, invalidate
+    /// the trace with [`TraceError::UnknownContext`] and read as zero.
+    pub fn gen_context_read(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+        field: ContextField,
+    ) -> EvmWord {
+        let call_id = self.call().call_id();
+        let value = match field {
+            ContextField::ReturnDataSize => {
+                Some(EvmWord::from(self.frame().return_data.len()))
+            }
+            ContextField::MemorySize => {
+                Some(EvmWord::from(self.frame().memory_words * 32))
+            }
+            ContextField::Gas => self.frame().gas.map(EvmWord::from),
+            _ => self.context_table.get(call_id, field).cloned(),
+        };
+        let value = value.unwrap_or_else(|| {
+            self.invalidate(TraceError::UnknownContext {
+                step: self.step_idx,
+                field,
+            });
+            EvmWord::from(0u8)
+        });
+        exec_step.add_op(
+            container,
+            ContextOp::new(
+                exec_step.next_op_gc(),
+                call_id,
+                field,
+                value.clone(),
+            ),
+        );
+        value
+    }
+
+    /// Marks the current frame as successfully halted (by `STOP`, `RETURN`
+    /// or `SELFDESTRUCT`), so that it's writes are kept once it's exited.
+    pub fn halt_call(&mut self) {
+        let frame = self.frame_mut();
+        frame.halted = true;
+        frame.returns_gas = true;
+    }
+
+    /// Makes the current frame return the gas it has left to it's caller once
+    /// exited, which the frames reverted by `REVERT` do on top of the ones
+    /// that halt successfully.
+    pub fn return_gas_left(&mut self) {
+        self.frame_mut().returns_gas = true;
+    }
+
+    /// Reverts the current frame (as `REVERT` or `INVALID` do): every write
+    /// journaled since the frame was opened (including the ones done by it's
+    /// sub-calls) is undone, in reverse order, by a compensating write added
+    /// to the bus-mapping instance of `exec_step`, including the removal of
+    /// the logs they emitted and of the accounts and storage slots first
+    /// accessed by them from the accessed sets. The frame and all of it's
+    /// sub-calls are marked as reverted.
+    pub fn revert_call(
+        &mut self,
+        exec_step: &mut ExecutionStep,
+        container: &mut OperationContainer,
+    ) {
+        let Frame {
+            call_idx,
+            journal_len,
+            ..
+        } = *self.frame();
+        // Frames opened after the current one are all sub-calls of it.
+        self.calls[call_idx..]
+            .iter_mut()
+            .for_each(|call| call.set_reverted());
+
+        self.gen_access_ops(exec_step, container);
+        let entries = self.journal.split_off(journal_len);
+        for entry in entries.into_iter().rev() {
+            match entry {
+                JournalEntry::Account {
+                    address,
+                    field,
+                    value,
+                    value_prev,
+                } => self.apply_account_write(
+                    exec_step, container, address, field, value_prev, value,
+                ),
+                JournalEntry::Storage {
+                    address,
+                    key,
+                    value,
+                    value_prev,
+                } => self.apply_storage_write(
+                    exec_step, container, address, key, value_prev, value,
+                ),
+                // The fields of a log are cleared in reverse order, so it's
+                // address goes last and frees the id for the following logs.
+                JournalEntry::Log {
+                    log_id,
+                    field,
+                    index,
+                    value,
+                } => {
+                    self.apply_log_write(
+                        exec_step,
+                        container,
+                        log_id,
+                        field,
+                        index,
+                        EvmWord::from(0u8),
+                        value,
+                    );
+                    self.logs = log_id - 1;
+                }
+                JournalEntry::AccessedAddress { address } => {
+                    self.accessed_addresses.remove(&address);
+                    self.apply_access(
+                        exec_step, container, address, None, false, true,
+                    );
+                }
+                JournalEntry::AccessedStorage { address, key } => {
+                    self.accessed_storage.remove(&(address, key.clone()));
+                    self.apply_access(
+                        exec_step,
+                        container,
+                        address,
+                        Some(key),
+                        false,
+                        true,
+                    );
+                }
+            }
+        }
+        self.frame_mut().halted = true;
+    }
+
+    /// Exits every frame deeper than the depth of `exec_step`, so that the
+    /// current frame is the one the step (located at `step_idx` in the trace)
+    /// runs in, and returns the index of the previous step of that frame.
+    /// `next_depth` is the depth of the step that follows it, if any.
+    ///
+    /// Frames that executed some step but didn't halt ended on an exceptional
+    /// halt (i.e. out of gas or stack underflow), so they are reverted
+    /// generating the compensating writes for `exec_step`. A step running
+    /// past the known code of a sub-call invalidates the trace with
+    /// [`TraceError::PcOutOfCode`].
+    pub(crate) fn enter_step(
+        &mut self,
+        step_idx: usize,
+        exec_step: &mut ExecutionStep,
+        next_depth: Option<usize>,
+        container: &mut OperationContainer,
+    ) -> Option<usize> {
+        self.step_idx = step_idx;
+        self.call_gas = None;
+        self.next_step_deeper =
+            matches!(next_depth, Some(depth) if depth > exec_step.depth());
+        while self.frames.len() > exec_step.depth().max(1) {
+            if self.frame().executed && !self.frame().halted {
+                self.revert_call(exec_step, container);
+            }
+            let frame = self.frames.pop().expect("the frame was just read");
+            if frame.returns_gas {
+                let caller = self.frame_mut();
+                caller.gas = caller
+                    .gas
+                    .zip(frame.gas)
+                    .map(|(gas, returned)| gas.saturating_add(returned));
+            }
+        }
+        // The code of the root call is checked along with the structure of
+        // the trace, while the one of the sub-calls is only known once the
+        // step that opens them runs.
+        let code = self.call().code();
+        let (pc, opcode) =
+            (exec_step.pc(), exec_step.instruction().opcode_id());
+        if self.call().depth() > 1
+            && !code.is_empty()
+            && !validation::runs_in_code(usize::from(pc), opcode, code)
+        {
+            let code_size = code.len();
+            self.invalidate(TraceError::PcOutOfCode {
+                step: step_idx,
+                pc,
+                code_size,
+            });
+        }
+        let frame = self.frame_mut();
+        frame.executed = true;
+        frame.last_step.replace(step_idx)
+    }
+
+    /// Returns the index of the step whose operations are being generated.
+    pub const fn step_idx(&self) -> usize {
+        self.step_idx
+    }
+
+    /// Records `error` as a flaw of the trace, which makes it fail with
+    /// [`Error::InvalidTrace`](crate::Error::InvalidTrace) once the
+    /// operations of the current step are generated. Only the first one
+    /// recorded is kept.
+    pub fn invalidate(&mut self, error: TraceError) {
+        self.error.get_or_insert(error);
+    }
+
+    /// Returns the flaw of the trace recorded by
+    /// [`TraceState::invalidate`], if any.
+    pub(crate) fn take_error(&mut self) -> Option<TraceError> {
+        self.error.take()
+    }
+
+    /// Returns true if the value of the `field` of the account located at
+    /// `address` is known, from the initial state or from a previous
+    /// [`TraceState::check_account_field`], rather than assumed.
+    pub fn is_known(&self, address: &Address, field: AccountField) -> bool {
+        self.known_fields.contains(&(*address, field))
+    }
+
+    /// Checks the `value` of the `field` of the account located at `address`
+    /// that the current step pushes against the state.
+    ///
+    /// If the field is known, from the initial state or from a previous
+    /// check, a mismatch invalidates the trace with
+    /// [`TraceError::StateMismatch`]. Otherwise the state takes `value`,
+    /// replacing the one assumed for the field (if any), and the field
+    /// becomes known.
+    pub fn check_account_field(
+        &mut self,
+        address: Address,
+        field: AccountField,
+        value: &EvmWord,
+    ) {
+        if self.is_known(&address, field) {
+            if &self.state_db.account_field(&address, field) != value {
+                self.invalidate(TraceError::StateMismatch {
+                    step: self.step_idx,
+                });
+            }
+        } else {
+            self.state_db
+                .set_account_field(&address, field, value.clone());
+            self.known_fields.insert((address, field));
+        }
+    }
+
+    /// Records the witness of a call to a precompiled contract.
+    pub(crate) fn add_precompile_call(&mut self, call: PrecompileCall) {
+        self.precompile_calls.push(call);
+    }
+
+    /// Records the bytes copied into memory by a step.
+    pub(crate) fn add_copy_event(&mut self, event: CopyEvent) {
+        self.copy_events.push(event);
+    }
+
+    /// Consumes the `TraceState` returning the calls opened during the trace,
+    /// their [`ContextTable`], the final [`StateDB`], the witnesses of the
+    /// precompile calls and the [`CopyEvent`]s of the steps.
+    pub(crate) fn finish(
+        self,
+    ) -> (
+        Vec<CallContext>,
+        ContextTable,
+        StateDB,
+        Vec<PrecompileCall>,
+        Vec<CopyEvent>,
+    ) {
+        (
+            self.calls,
+            self.context_table,
+            self.state_db,
+            self.precompile_calls,
+            self.copy_events,
+        )
+    }
+}
@@ -0,0 +1,373 @@
+//! Structural validation of the [`ExecutionStep`]s of a trace, which rejects
+//! traces that can't come from an execution of the EVM before any of their
+//! operations is generated.
+
+use super::{snapshot::next_in_frame, ExecutionStep, SnapshotConvention};
+use crate::error::TraceError;
+use crate::evm::{OpcodeId, ProgramCounter};
+use num::ToPrimitive;
+
+/// Maximum number of words the stack of the EVM can hold.
+pub(crate) const STACK_LIMIT: usize = 1024;
+
+/// Checks that the `steps` of a trace are structurally sound:
+/// - The stack of each step holds at most [`STACK_LIMIT`] words.
+/// - The memory of each step is made of 32-byte aligned words that cover it
+///   from address zero with no gaps, all of them inside of the 2^64 addressable
+///   bytes.
+/// - The steps of the root call run inside of `root_code` (when it's known).
+///   The code of the sub-calls is only known while generating the operations of
+///   the steps, which checks it then.
+/// - Each step continues the execution of it's frame where the previous step of
+///   the frame left it, frames are only opened by the call and create opcodes,
+///   and no step runs in a frame after it halted.
+/// - The last step of each frame halts it, or can halt it exceptionally given
+///   the stack and gas around it, which are read following `convention`.
+pub(crate) fn validate_steps(
+    steps: &[ExecutionStep],
+    root_code: Option<&[u8]>,
+    convention: SnapshotConvention,
+) -> Result<(), TraceError> {
+    for (idx, step) in steps.iter().enumerate() {
+        if step.stack().len() > STACK_LIMIT {
+            return Err(TraceError::StackOverflow {
+                step: idx,
+                size: step.stack().len(),
+            });
+        }
+
+        if let Some(address) = step.memory().keys().last() {
+            if address.0.to_u64().is_none() {
+                return Err(TraceError::MemoryOutOfBounds { step: idx });
+            }
+        }
+        for (word, address) in step.memory().keys().enumerate() {
+            let address = address.0.to_usize().unwrap_or(usize::MAX);
+            if address % 32 != 0 {
+                return Err(TraceError::UnalignedMemory { step: idx, address });
+            }
+            if address != word * 32 {
+                return Err(TraceError::NonContiguousMemory {
+                    step: idx,
+                    address: word * 32,
+                });
+            }
+        }
+
+        let opcode = step.instruction().opcode_id();
+        let pc = usize::from(step.pc());
+        if let Some(code) = root_code.filter(|_| step.depth() <= 1) {
+            if !runs_in_code(pc, opcode, code) {
+                return Err(TraceError::PcOutOfCode {
+                    step: idx,
+                    pc: step.pc(),
+                    code_size: code.len(),
+                });
+            }
+        }
+
+        if let Some(prev) = idx.checked_sub(1).map(|prev| &steps[prev]) {
+            if step.depth() > prev.depth() {
+                if step.depth() != prev.depth() + 1
+                    || !prev.instruction().opcode_id().is_call_or_create()
+                {
+                    return Err(TraceError::UnexpectedDepth {
+                        step: idx,
+                        depth: step.depth(),
+                    });
+                }
+                if pc != 0 {
+                    return Err(TraceError::MissingStep {
+                        step: idx,
+                        expected: ProgramCounter::from(0),
+                        found: step.pc(),
+                    });
+                }
+            }
+        }
+
+        let next_idx = match next_in_frame(steps, idx) {
+            Some(next_idx) => next_idx,
+            None if opcode.is_halt()
+                || can_halt_exceptionally(steps, idx, convention) =>
+            {
+                continue
+            }
+            None => return Err(TraceError::UnexplainedHalt { step: idx }),
+        };
+        let next = &steps[next_idx];
+        let fallthrough = ProgramCounter::from(pc + 1 + opcode.push_size());
+        let jumped = next.instruction().opcode_id() == OpcodeId::JUMPDEST;
+        let continues = match opcode {
+            _ if opcode.is_halt() => {
+                return Err(TraceError::StepAfterHalt { step: next_idx })
+            }
+            OpcodeId::JUMP => jumped,
+            OpcodeId::JUMPI => jumped || next.pc() == fallthrough,
+            _ => next.pc() == fallthrough,
+        };
+        if !continues {
+            return Err(TraceError::MissingStep {
+                step: next_idx,
+                expected: fallthrough,
+                found: next.pc(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if a step executing `opcode` at `pc` runs inside of `code`,
+/// where the pc right past it's end executes an implicit `STOP`.
+pub(crate) fn runs_in_code(pc: usize, opcode: OpcodeId, code: &[u8]) -> bool {
+    pc < code.len() || (pc == code.len() && opcode == OpcodeId::STOP)
+}
+
+/// Returns true if the step `steps[idx]`, the last one of it's frame, can
+/// halt the frame exceptionally: it's opcode isn't defined, it underflows
+/// or overflows the stack, it can't pay for it's gas cost (which can't be
+/// told when the trace doesn't report the gas of the step), or it can fail
+/// depending on the values of it's operands or the context of it's call.
+fn can_halt_exceptionally(
+    steps: &[ExecutionStep],
+    idx: usize,
+    convention: SnapshotConvention,
+) -> bool {
+    let step = &steps[idx];
+    let opcode = step.instruction().opcode_id();
+    // The first step of a frame runs with an empty stack.
+    let stack = match convention {
+        SnapshotConvention::PostState => steps[..idx]
+            .iter()
+            .rev()
+            .find(|prev| prev.depth() <= step.depth())
+            .filter(|prev| prev.depth() == step.depth())
+            .map_or(0, |prev| prev.stack().len()),
+        SnapshotConvention::PreState => step.stack().len(),
+    };
+    opcode.name().is_none()
+        || stack < opcode.stack_inputs()
+        || stack - opcode.stack_inputs() + opcode.stack_outputs() > STACK_LIMIT
+        || !matches!(step.gas(), Some(gas) if gas.0 >= step.gas_cost().0)
+        || opcode.is_call_or_create()
+        || matches!(
+            opcode,
+            OpcodeId::JUMP
+                | OpcodeId::JUMPI
+                | OpcodeId::RETURNDATACOPY
+                | OpcodeId::SSTORE
+                | OpcodeId(0xa0..=0xa4)
+        )
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use super::*;
+    use crate::{
+        exec_trace::{ExecutionTrace, ExecutionTraceBuilder, TxContext},
+        state_db::StateDB,
+        test_util, Error,
+    };
+    use pasta_curves::Fp;
+
+    fn validate(input_trace: &str, tx_ctx: TxContext) -> Result<(), Error> {
+        let block_ctants = test_util::block_ctants();
+        ExecutionTrace::<Fp>::from_trace_bytes_with_tx(
+            input_trace.as_bytes(),
+            block_ctants,
+            tx_ctx,
+        )
+        .map(|_| ())
+    }
+
+    fn trace_error(input_trace: &str) -> Option<TraceError> {
+        match validate(input_trace, TxContext::default()) {
+            Err(Error::InvalidTrace(err)) => Some(err),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn structural_errors() {
+        let stack = format!(
+            r#"[{{ "memory": {{}}, "stack": [{}], "opcode": "PUSH1 01", "pc": 0 }}]"#,
+            vec!["\"1\""; 1025].join(",")
+        );
+        assert_eq!(
+            trace_error(&stack),
+            Some(TraceError::StackOverflow {
+                step: 0,
+                size: 1025
+            })
+        );
+
+        assert_eq!(
+            trace_error(
+                r#"[{ "memory": { "10": "1" }, "stack": [], "opcode": "STOP", "pc": 0 }]"#
+            ),
+            Some(TraceError::UnalignedMemory {
+                step: 0,
+                address: 0x10
+            })
+        );
+        assert_eq!(
+            trace_error(
+                r#"[{ "memory": { "0": "1", "40": "1" }, "stack": [], "opcode": "STOP", "pc": 0 }]"#
+            ),
+            Some(TraceError::NonContiguousMemory {
+                step: 0,
+                address: 0x20
+            })
+        );
+
+        assert_eq!(
+            trace_error(
+                r#"[{ "memory": { "10000000000000000": "1" }, "stack": [], "opcode": "STOP", "pc": 0 }]"#
+            ),
+            Some(TraceError::MemoryOutOfBounds { step: 0 })
+        );
+
+        // Frames continue right after the immediate of a push, and are only
+        // opened by calls.
+        assert_eq!(
+            trace_error(
+                r#"
+                [
+                    { "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0 },
+                    { "memory": {}, "stack": ["1"], "opcode": "STOP", "pc": 3 }
+                ]
+                "#
+            ),
+            Some(TraceError::MissingStep {
+                step: 1,
+                expected: ProgramCounter::from(2),
+                found: ProgramCounter::from(3)
+            })
+        );
+        assert_eq!(
+            trace_error(
+                r#"
+                [
+                    { "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0 },
+                    { "memory": {}, "stack": [], "opcode": "STOP", "pc": 0, "depth": 2 }
+                ]
+                "#
+            ),
+            Some(TraceError::UnexpectedDepth { step: 1, depth: 2 })
+        );
+        assert_eq!(
+            trace_error(
+                r#"
+                [
+                    { "memory": {}, "stack": [], "opcode": "STOP", "pc": 0 },
+                    { "memory": {}, "stack": [], "opcode": "STOP", "pc": 1 }
+                ]
+                "#
+            ),
+            Some(TraceError::StepAfterHalt { step: 1 })
+        );
+
+        // The init code of a creation bounds the pc of the root call, whose
+        // end runs an implicit `STOP`.
+        let create = |code: Vec<u8>| test_util::tx_ctx(None, code);
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0 },
+            { "memory": {}, "stack": ["1"], "opcode": "STOP", "pc": 2 }
+        ]
+        "#;
+        assert!(validate(input_trace, create(vec![0x60, 0x01])).is_ok());
+        assert!(matches!(
+            validate(input_trace, create(vec![0x60])),
+            Err(Error::InvalidTrace(TraceError::PcOutOfCode {
+                step: 1,
+                code_size: 1,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn frames_end_on_halts() {
+        // Without the gas of the steps, the last step of a frame may have run
+        // out of it.
+        let push = r#"[{ "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0 }]"#;
+        assert!(validate(push, TxContext::default()).is_ok());
+        let push = r#"[{ "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0, "gas": 10, "gasCost": 3 }]"#;
+        assert_eq!(
+            trace_error(push),
+            Some(TraceError::UnexplainedHalt { step: 0 })
+        );
+        let out_of_gas = r#"[{ "memory": {}, "stack": [], "opcode": "PUSH1 01", "pc": 0, "gas": 2, "gasCost": 3 }]"#;
+        assert!(validate(out_of_gas, TxContext::default()).is_ok());
+        let underflow = r#"
+        [
+            { "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0, "gas": 10, "gasCost": 3 },
+            { "memory": {}, "stack": [], "opcode": "ADD", "pc": 2, "gas": 7, "gasCost": 3 }
+        ]
+        "#;
+        assert!(validate(underflow, TxContext::default()).is_ok());
+    }
+
+    #[test]
+    fn underflowing_steps_read_no_operands() {
+        for opcode in &["RETURN", "CREATE", "CALL", "CALLDATACOPY"] {
+            let underflow = format!(
+                r#"
+                [
+                    {{ "memory": {{}}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 }},
+                    {{ "memory": {{}}, "stack": [], "opcode": "{}", "pc": 2 }}
+                ]
+                "#,
+                opcode
+            );
+            assert!(validate(&underflow, TxContext::default()).is_ok());
+        }
+    }
+
+    #[test]
+    fn sub_calls_run_inside_of_their_code() {
+        // The root call calls 0xbb, whose code is a single `PUSH1 01` that
+        // ends with an implicit `STOP`.
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["0", "0"], "opcode": "PUSH1 00", "pc": 2 },
+            { "memory": {}, "stack": ["0", "0", "0"], "opcode": "PUSH1 00", "pc": 4 },
+            { "memory": {}, "stack": ["0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 6 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0"], "opcode": "PUSH1 00", "pc": 8 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0", "bb"], "opcode": "PUSH1 bb", "pc": 10 },
+            { "memory": {}, "stack": ["0", "0", "0", "0", "0", "bb", "0"], "opcode": "PUSH1 00", "pc": 12 },
+            { "memory": {}, "stack": ["1"], "opcode": "CALL", "pc": 14 },
+            { "memory": {}, "stack": ["1"], "opcode": "PUSH1 01", "pc": 0, "depth": 2 },
+            { "memory": {}, "stack": ["1"], "opcode": "STOP", "pc": 2, "depth": 2 },
+            { "memory": {}, "stack": ["1"], "opcode": "STOP", "pc": 15 }
+        ]
+        "#;
+        let validate = |code: Vec<u8>| {
+            let mut prestate = StateDB::new();
+            prestate.set_code(&test_util::address("bb"), code);
+            let mut builder =
+                ExecutionTraceBuilder::<Fp>::new(test_util::block_ctants())
+                    .with_tx(test_util::tx_ctx(
+                        Some(test_util::address("aa")),
+                        vec![],
+                    ))
+                    .with_prestate(prestate);
+            builder
+                .push_trace_bytes(input_trace.as_bytes())
+                .expect("Error on parsing");
+            builder.finish().map(|_| ())
+        };
+
+        assert!(validate(vec![0x60, 0x01]).is_ok());
+        assert!(matches!(
+            validate(vec![0x60]),
+            Err(Error::InvalidTrace(TraceError::PcOutOfCode {
+                step: 9,
+                code_size: 1,
+                ..
+            }))
+        ));
+    }
+}
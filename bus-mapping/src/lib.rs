@@ -74,7 +74,7 @@
 //!             "80"
 //!         ],
 //!         "opcode": "PUSH1 80",
-//!         "pc": 1
+//!         "pc": 2
 //!     }
 //! ]
 //! "#;
@@ -96,7 +96,7 @@
 //!     block_ctants,
 //! ).expect("Error on trace generation");
 //!
-//! // Get an ordered vector with all of the Stack operations of this trace.
+//! // Iterate in order over all of the Stack operations of this trace.
 //! let stack_ops = obtained_exec_trace.sorted_stack_ops();
 //!
 //! // You can also iterate over the steps of the trace and witness the EVM Proof.
@@ -152,7 +152,10 @@
 //! ```
 //!
 //! Where as you see, we group by `memory_address` and then order by
-//! `global_counter`.
+//! `global_counter`. Tables like this one can be produced with
+//! [`ExecutionTrace::export_memory_ops`](crate::exec_trace::ExecutionTrace::export_memory_ops)
+//! (and the analogous exporters for the other targets and for the steps) as
+//! aligned text, Markdown or CSV.
 //!
 //! Aside from that, we also can iterate over the `ExecutionTrace` itself over
 //! each Evm Instruction in order to add constrains for each Opcode is executed.
@@ -170,8 +173,7 @@
 // Catch documentation errors caused by code changes.
 #![deny(broken_intra_doc_links)]
 #![deny(missing_docs)]
-//#![deny(unsafe_code)] Allowed now until we find a
-// better way to handle downcasting from Operation into it's variants.
+#![deny(unsafe_code)]
 #![allow(clippy::upper_case_acronyms)] // Too pedantic
 
 extern crate alloc;
@@ -180,6 +182,11 @@ mod error;
 pub mod evm;
 pub mod exec_trace;
 pub mod operation;
+pub mod state_db;
+#[cfg(test)]
+pub(crate) mod test_util;
 
-pub use error::Error;
-pub use exec_trace::{BlockConstants, ExecutionStep, ExecutionTrace};
+pub use error::{Error, TraceError};
+pub use exec_trace::{
+    BlockConstants, ExecutionStep, ExecutionTrace, ExecutionTraceBuilder,
+};
@@ -1,16 +1,19 @@
 //! Collection of structs and functions used to:
-//! - Define the internals of a [`MemoryOp`], [`StackOp`] and [`StorageOp`].
+//! - Define the internals of a [`MemoryOp`], [`StackOp`], [`StorageOp`],
+//!   [`AccountOp`], [`ContextOp`], [`AccessListOp`] and [`LogOp`].
 //! - Define the actual operation types and a wrapper over them (the
 //!   [`Operation`] enum).
 //! - Define structures that interact with operations such as
 //!   [`OperationContainer`].
 pub(crate) mod container;
 
-use super::evm::{EvmWord, GlobalCounter, MemoryAddress, StackAddress};
+use super::evm::{
+    Address, EvmWord, GlobalCounter, MemoryAddress, StackAddress,
+};
 use crate::error::Error;
-pub use container::OperationContainer;
+pub use container::{OperationContainer, SortedOps};
 use core::cmp::Ordering;
-use core::fmt::Debug;
+use core::fmt::{self, Debug};
 use std::convert::TryFrom;
 
 /// Marker that defines whether an Operation performs a `READ` or a `WRITE`.
@@ -40,7 +43,17 @@ impl RW {
     }
 }
 
-/// Enum used to differenciate between EVM Stack, Memory and Storage operations.
+impl fmt::Display for RW {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RW::READ => "READ",
+            RW::WRITE => "WRITE",
+        })
+    }
+}
+
+/// Enum used to differenciate between EVM Stack, Memory, Storage, Account,
+/// Context, AccessList and Log operations.
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
 pub enum Target {
     /// Means the target of the operation is the Memory.
@@ -49,6 +62,17 @@ pub enum Target {
     Stack,
     /// Means the target of the operation is the Storage.
     Storage,
+    /// Means the target of the operation is the state of an Account.
+    Account,
+    /// Means the target of the operation is the context of a transaction or
+    /// of a call.
+    Context,
+    /// Means the target of the operation is the set of accounts or storage
+    /// slots accessed during the transaction.
+    AccessList,
+    /// Means the target of the operation is a log emitted during the
+    /// transaction.
+    Log,
 }
 
 /// Represents a [`READ`](RW::READ)/[`WRITE`](RW::WRITE) into the memory implied
@@ -58,6 +82,7 @@ pub enum Target {
 pub struct MemoryOp {
     rw: RW,
     gc: GlobalCounter,
+    call_id: usize,
     addr: MemoryAddress,
     value: EvmWord,
 }
@@ -67,12 +92,14 @@ impl MemoryOp {
     pub const fn new(
         rw: RW,
         gc: GlobalCounter,
+        call_id: usize,
         addr: MemoryAddress,
         value: EvmWord,
     ) -> MemoryOp {
         MemoryOp {
             rw,
             gc,
+            call_id,
             addr,
             value,
         }
@@ -94,6 +121,12 @@ impl MemoryOp {
         self.gc
     }
 
+    /// Returns the identifier of the call whose context this Operation
+    /// belongs to.
+    pub const fn call_id(&self) -> usize {
+        self.call_id
+    }
+
     /// Returns the [`MemoryAddress`] associated to this Operation.
     pub const fn address(&self) -> &MemoryAddress {
         &self.addr
@@ -107,23 +140,29 @@ impl MemoryOp {
 
 impl PartialOrd for MemoryOp {
     fn partial_cmp(&self, other: &MemoryOp) -> Option<Ordering> {
-        match self.address().partial_cmp(other.address()) {
-            None => None,
-            Some(ord) => match ord {
-                std::cmp::Ordering::Equal => self.gc().partial_cmp(&other.gc()),
-                _ => Some(ord),
-            },
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for MemoryOp {
     fn cmp(&self, other: &MemoryOp) -> Ordering {
-        match self.address().cmp(other.address()) {
-            Ordering::Equal => self.gc().cmp(&other.gc()),
-            Ordering::Less => Ordering::Less,
-            Ordering::Greater => Ordering::Greater,
-        }
+        (self.call_id(), self.address(), self.gc()).cmp(&(
+            other.call_id(),
+            other.address(),
+            other.gc(),
+        ))
+    }
+}
+
+impl fmt::Display for MemoryOp {
+    /// Formats the operation as `#<gc> <rw> memory[<address>] = <value>`
+    /// followed by the call it belongs to.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} memory[{}] = {} (call {})",
+            self.gc, self.rw, self.addr, self.value, self.call_id
+        )
     }
 }
 
@@ -145,6 +184,7 @@ impl TryFrom<Operation> for MemoryOp {
 pub struct StackOp {
     rw: RW,
     gc: GlobalCounter,
+    call_id: usize,
     addr: StackAddress,
     value: EvmWord,
 }
@@ -154,12 +194,14 @@ impl StackOp {
     pub const fn new(
         rw: RW,
         gc: GlobalCounter,
+        call_id: usize,
         addr: StackAddress,
         value: EvmWord,
     ) -> StackOp {
         StackOp {
             rw,
             gc,
+            call_id,
             addr,
             value,
         }
@@ -181,6 +223,12 @@ impl StackOp {
         self.gc
     }
 
+    /// Returns the identifier of the call whose context this Operation
+    /// belongs to.
+    pub const fn call_id(&self) -> usize {
+        self.call_id
+    }
+
     /// Returns the [`StackAddress`] associated to this Operation.
     pub const fn address(&self) -> &StackAddress {
         &self.addr
@@ -194,23 +242,29 @@ impl StackOp {
 
 impl PartialOrd for StackOp {
     fn partial_cmp(&self, other: &StackOp) -> Option<Ordering> {
-        match self.address().partial_cmp(other.address()) {
-            None => None,
-            Some(ord) => match ord {
-                std::cmp::Ordering::Equal => self.gc().partial_cmp(&other.gc()),
-                _ => Some(ord),
-            },
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for StackOp {
     fn cmp(&self, other: &StackOp) -> Ordering {
-        match self.address().cmp(other.address()) {
-            Ordering::Equal => self.gc().cmp(&other.gc()),
-            Ordering::Less => Ordering::Less,
-            Ordering::Greater => Ordering::Greater,
-        }
+        (self.call_id(), self.address(), self.gc()).cmp(&(
+            other.call_id(),
+            other.address(),
+            other.gc(),
+        ))
+    }
+}
+
+impl fmt::Display for StackOp {
+    /// Formats the operation as `#<gc> <rw> stack[<address>] = <value>`
+    /// followed by the call it belongs to.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} stack[{}] = {} (call {})",
+            self.gc, self.rw, self.addr, self.value, self.call_id
+        )
     }
 }
 
@@ -228,8 +282,108 @@ impl TryFrom<Operation> for StackOp {
 /// Represents a [`READ`](RW::READ)/[`WRITE`](RW::WRITE) into the storage
 /// implied by an specific [`OpcodeId`](crate::evm::opcodes::ids::OpcodeId) of
 /// the [`ExecutionTrace`](crate::exec_trace::ExecutionTrace).
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct StorageOp; // Update with https://hackmd.io/kON1GVL6QOC6t5tf_OTuKA with Han's review
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageOp {
+    rw: RW,
+    gc: GlobalCounter,
+    address: Address,
+    key: EvmWord,
+    value: EvmWord,
+    value_prev: EvmWord,
+}
+
+impl StorageOp {
+    /// Create a new instance of a `StorageOp` from it's components.
+    pub const fn new(
+        rw: RW,
+        gc: GlobalCounter,
+        address: Address,
+        key: EvmWord,
+        value: EvmWord,
+        value_prev: EvmWord,
+    ) -> StorageOp {
+        StorageOp {
+            rw,
+            gc,
+            address,
+            key,
+            value,
+            value_prev,
+        }
+    }
+
+    /// Returns the internal [`RW`] which says whether the operation corresponds
+    /// to a Read or a Write into storage.
+    pub const fn rw(&self) -> RW {
+        self.rw
+    }
+
+    /// Returns the [`Target`] (operation type) of this operation.
+    pub const fn target(&self) -> Target {
+        Target::Storage
+    }
+
+    /// Returns the [`GlobalCounter`] associated to this Operation.
+    pub const fn gc(&self) -> GlobalCounter {
+        self.gc
+    }
+
+    /// Returns the [`Address`] of the account whose storage is accessed by
+    /// this Operation.
+    pub const fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns the storage key associated to this Operation.
+    pub const fn key(&self) -> &EvmWord {
+        &self.key
+    }
+
+    /// Returns the [`EvmWord`] read or written by this operation.
+    pub const fn value(&self) -> &EvmWord {
+        &self.value
+    }
+
+    /// Returns the [`EvmWord`] that the storage slot held before this
+    /// operation.
+    pub const fn value_prev(&self) -> &EvmWord {
+        &self.value_prev
+    }
+}
+
+impl PartialOrd for StorageOp {
+    fn partial_cmp(&self, other: &StorageOp) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StorageOp {
+    fn cmp(&self, other: &StorageOp) -> Ordering {
+        (self.address(), self.key(), self.gc()).cmp(&(
+            other.address(),
+            other.key(),
+            other.gc(),
+        ))
+    }
+}
+
+impl fmt::Display for StorageOp {
+    /// Formats the operation as
+    /// `#<gc> <rw> storage[<address>][<key>] = <value>` followed by the
+    /// previous value of the slot.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} storage[{}][{}] = {} (prev {})",
+            self.gc,
+            self.rw,
+            self.address,
+            self.key,
+            self.value,
+            self.value_prev
+        )
+    }
+}
 
 impl TryFrom<Operation> for StorageOp {
     type Error = Error;
@@ -242,8 +396,596 @@ impl TryFrom<Operation> for StorageOp {
     }
 }
 
+/// Enum used to differenciate between the fields of an account that an
+/// [`AccountOp`] can read or write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AccountField {
+    /// Means the target of the operation is the nonce of the account.
+    Nonce,
+    /// Means the target of the operation is the balance of the account.
+    Balance,
+    /// Means the target of the operation is the code hash of the account.
+    CodeHash,
+}
+
+/// Represents a [`READ`](RW::READ)/[`WRITE`](RW::WRITE) into a field of the
+/// state of an account implied by an specific
+/// [`OpcodeId`](crate::evm::opcodes::ids::OpcodeId) of the
+/// [`ExecutionTrace`](crate::exec_trace::ExecutionTrace).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountOp {
+    rw: RW,
+    gc: GlobalCounter,
+    address: Address,
+    field: AccountField,
+    value: EvmWord,
+    value_prev: EvmWord,
+}
+
+impl AccountOp {
+    /// Create a new instance of an `AccountOp` from it's components.
+    pub const fn new(
+        rw: RW,
+        gc: GlobalCounter,
+        address: Address,
+        field: AccountField,
+        value: EvmWord,
+        value_prev: EvmWord,
+    ) -> AccountOp {
+        AccountOp {
+            rw,
+            gc,
+            address,
+            field,
+            value,
+            value_prev,
+        }
+    }
+
+    /// Returns the internal [`RW`] which says whether the operation corresponds
+    /// to a Read or a Write into the account.
+    pub const fn rw(&self) -> RW {
+        self.rw
+    }
+
+    /// Returns the [`Target`] (operation type) of this operation.
+    pub const fn target(&self) -> Target {
+        Target::Account
+    }
+
+    /// Returns the [`GlobalCounter`] associated to this Operation.
+    pub const fn gc(&self) -> GlobalCounter {
+        self.gc
+    }
+
+    /// Returns the [`Address`] of the account associated to this Operation.
+    pub const fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns the [`AccountField`] read or written by this operation.
+    pub const fn field(&self) -> AccountField {
+        self.field
+    }
+
+    /// Returns the [`EvmWord`] read or written by this operation.
+    pub const fn value(&self) -> &EvmWord {
+        &self.value
+    }
+
+    /// Returns the [`EvmWord`] that the field held before this operation.
+    pub const fn value_prev(&self) -> &EvmWord {
+        &self.value_prev
+    }
+}
+
+impl PartialOrd for AccountOp {
+    fn partial_cmp(&self, other: &AccountOp) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AccountOp {
+    fn cmp(&self, other: &AccountOp) -> Ordering {
+        (self.address(), self.field(), self.gc()).cmp(&(
+            other.address(),
+            other.field(),
+            other.gc(),
+        ))
+    }
+}
+
+impl fmt::Display for AccountOp {
+    /// Formats the operation as
+    /// `#<gc> <rw> account[<address>][<field>] = <value>` followed by the
+    /// previous value of the field.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} account[{}][{:?}] = {} (prev {})",
+            self.gc,
+            self.rw,
+            self.address,
+            self.field,
+            self.value,
+            self.value_prev
+        )
+    }
+}
+
+impl TryFrom<Operation> for AccountOp {
+    type Error = Error;
+
+    fn try_from(op: Operation) -> Result<Self, Self::Error> {
+        match op {
+            Operation::Account(account_op) => Ok(account_op),
+            _ => Err(Error::InvalidOpConversion),
+        }
+    }
+}
+
+/// Enum used to differenciate between the entries of the context of a
+/// transaction or of a call that a [`ContextOp`] can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ContextField {
+    /// Sender of the transaction (`ORIGIN`).
+    Origin,
+    /// Gas price of the transaction (`GASPRICE`).
+    GasPrice,
+    /// Address of the account the call runs in (`ADDRESS`).
+    Address,
+    /// Address of the account that opened the call (`CALLER`).
+    Caller,
+    /// Value sent along with the call (`CALLVALUE`).
+    Value,
+    /// Size of the calldata of the call (`CALLDATASIZE`).
+    CalldataSize,
+    /// Size of the code run by the call (`CODESIZE`).
+    CodeSize,
+    /// Size of the data returned by the last sub-call (`RETURNDATASIZE`).
+    ReturnDataSize,
+    /// Size in bytes of the memory of the call (`MSIZE`).
+    MemorySize,
+    /// Gas left in the call (`GAS`).
+    Gas,
+}
+
+impl ContextField {
+    /// Returns true if the field belongs to the transaction and so it's
+    /// shared by all of it's calls.
+    pub const fn is_tx_field(&self) -> bool {
+        matches!(self, ContextField::Origin | ContextField::GasPrice)
+    }
+
+    /// Returns true if the value of the field changes while the call runs, so
+    /// that it's read from the frame of the call instead of from the
+    /// [`ContextTable`](crate::exec_trace::ContextTable).
+    pub const fn is_dynamic(&self) -> bool {
+        matches!(
+            self,
+            ContextField::ReturnDataSize
+                | ContextField::MemorySize
+                | ContextField::Gas
+        )
+    }
+}
+
+/// Represents a [`READ`](RW::READ) of an entry of the context of the
+/// transaction or of the call that executes a specific
+/// [`OpcodeId`](crate::evm::opcodes::ids::OpcodeId) of the
+/// [`ExecutionTrace`](crate::exec_trace::ExecutionTrace).
+///
+/// The context is read-only, so these operations are always reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextOp {
+    gc: GlobalCounter,
+    call_id: usize,
+    field: ContextField,
+    value: EvmWord,
+}
+
+impl ContextOp {
+    /// Create a new instance of a `ContextOp` from it's components.
+    pub const fn new(
+        gc: GlobalCounter,
+        call_id: usize,
+        field: ContextField,
+        value: EvmWord,
+    ) -> ContextOp {
+        ContextOp {
+            gc,
+            call_id,
+            field,
+            value,
+        }
+    }
+
+    /// Returns the internal [`RW`] of the operation, which is always a
+    /// [`READ`](RW::READ).
+    pub const fn rw(&self) -> RW {
+        RW::READ
+    }
+
+    /// Returns the [`Target`] (operation type) of this operation.
+    pub const fn target(&self) -> Target {
+        Target::Context
+    }
+
+    /// Returns the [`GlobalCounter`] associated to this Operation.
+    pub const fn gc(&self) -> GlobalCounter {
+        self.gc
+    }
+
+    /// Returns the identifier of the call whose context this Operation
+    /// reads.
+    pub const fn call_id(&self) -> usize {
+        self.call_id
+    }
+
+    /// Returns the [`ContextField`] read by this Operation.
+    pub const fn field(&self) -> ContextField {
+        self.field
+    }
+
+    /// Returns the [`EvmWord`] read by this operation.
+    pub const fn value(&self) -> &EvmWord {
+        &self.value
+    }
+}
+
+impl PartialOrd for ContextOp {
+    fn partial_cmp(&self, other: &ContextOp) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ContextOp {
+    fn cmp(&self, other: &ContextOp) -> Ordering {
+        (self.call_id(), self.field(), self.gc()).cmp(&(
+            other.call_id(),
+            other.field(),
+            other.gc(),
+        ))
+    }
+}
+
+impl fmt::Display for ContextOp {
+    /// Formats the operation as `#<gc> READ context[<field>] = <value>`
+    /// followed by the call it belongs to.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} context[{:?}] = {} (call {})",
+            self.gc,
+            self.rw(),
+            self.field,
+            self.value,
+            self.call_id
+        )
+    }
+}
+
+impl TryFrom<Operation> for ContextOp {
+    type Error = Error;
+
+    fn try_from(op: Operation) -> Result<Self, Self::Error> {
+        match op {
+            Operation::Context(context_op) => Ok(context_op),
+            _ => Err(Error::InvalidOpConversion),
+        }
+    }
+}
+
+/// Represents a [`WRITE`](RW::WRITE) that adds an account, or a storage slot
+/// of it, to the sets of accessed accounts and storage slots of the
+/// transaction ([EIP-2929](https://eips.ethereum.org/EIPS/eip-2929)), which
+/// tell whether an access is warm or cold.
+///
+/// Accesses to members of the sets are written too, keeping them in the set,
+/// so that the previous value proves the access was warm. Additions to the
+/// sets done inside of a call that reverts are undone by writes that remove
+/// the member again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessListOp {
+    rw: RW,
+    gc: GlobalCounter,
+    address: Address,
+    key: Option<EvmWord>,
+    value: bool,
+    value_prev: bool,
+}
+
+impl AccessListOp {
+    /// Create a new instance of an `AccessListOp` from it's components.
+    /// A `key` equal to `None` marks an operation over the set of accounts
+    /// instead of the set of storage slots.
+    pub const fn new(
+        rw: RW,
+        gc: GlobalCounter,
+        address: Address,
+        key: Option<EvmWord>,
+        value: bool,
+        value_prev: bool,
+    ) -> AccessListOp {
+        AccessListOp {
+            rw,
+            gc,
+            address,
+            key,
+            value,
+            value_prev,
+        }
+    }
+
+    /// Returns the internal [`RW`] which says whether the operation corresponds
+    /// to a Read or a Write into the access sets.
+    pub const fn rw(&self) -> RW {
+        self.rw
+    }
+
+    /// Returns the [`Target`] (operation type) of this operation.
+    pub const fn target(&self) -> Target {
+        Target::AccessList
+    }
+
+    /// Returns the [`GlobalCounter`] associated to this Operation.
+    pub const fn gc(&self) -> GlobalCounter {
+        self.gc
+    }
+
+    /// Returns the [`Address`] of the account accessed by this Operation.
+    pub const fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns the storage key accessed by this Operation, or `None` if it
+    /// accesses the account itself.
+    pub const fn key(&self) -> Option<&EvmWord> {
+        self.key.as_ref()
+    }
+
+    /// Returns true if the account or storage slot is in the set after this
+    /// operation.
+    pub const fn value(&self) -> bool {
+        self.value
+    }
+
+    /// Returns true if the account or storage slot was in the set before this
+    /// operation, which means the access is warm.
+    pub const fn value_prev(&self) -> bool {
+        self.value_prev
+    }
+}
+
+impl PartialOrd for AccessListOp {
+    fn partial_cmp(&self, other: &AccessListOp) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AccessListOp {
+    fn cmp(&self, other: &AccessListOp) -> Ordering {
+        (self.address(), self.key(), self.gc()).cmp(&(
+            other.address(),
+            other.key(),
+            other.gc(),
+        ))
+    }
+}
+
+impl fmt::Display for AccessListOp {
+    /// Formats the operation as
+    /// `#<gc> <rw> access_list[<address>](<key>) = <value>` followed by the
+    /// previous value, where the key is only present for storage slots.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {} access_list[{}]", self.gc, self.rw, self.address)?;
+        if let Some(key) = &self.key {
+            write!(f, "[{}]", key)?;
+        }
+        write!(f, " = {} (prev {})", self.value, self.value_prev)
+    }
+}
+
+impl TryFrom<Operation> for AccessListOp {
+    type Error = Error;
+
+    fn try_from(op: Operation) -> Result<Self, Self::Error> {
+        match op {
+            Operation::AccessList(access_list_op) => Ok(access_list_op),
+            _ => Err(Error::InvalidOpConversion),
+        }
+    }
+}
+
+/// Enum used to differenciate between the fields of a log that a [`LogOp`]
+/// can write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogField {
+    /// Address of the account that emitted the log.
+    Address,
+    /// Topic of the log placed at the index of the operation.
+    Topic,
+    /// Byte of the data of the log placed at the index of the operation.
+    Data,
+}
+
+/// Represents a [`WRITE`](RW::WRITE) into a field of a log emitted by one of
+/// the `LOG` [`OpcodeId`](crate::evm::opcodes::ids::OpcodeId)s of the
+/// [`ExecutionTrace`](crate::exec_trace::ExecutionTrace).
+///
+/// Logs are identified by the order in which they are emitted during the
+/// transaction, starting at 1. The logs emitted inside of a call that reverts
+/// are undone by writes that restore the previous value (zero) of each one
+/// of their fields, so their ids are taken again by the logs that follow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogOp {
+    rw: RW,
+    gc: GlobalCounter,
+    log_id: usize,
+    field: LogField,
+    index: usize,
+    value: EvmWord,
+    value_prev: EvmWord,
+}
+
+impl LogOp {
+    /// Create a new instance of a `LogOp` from it's components.
+    pub const fn new(
+        rw: RW,
+        gc: GlobalCounter,
+        log_id: usize,
+        field: LogField,
+        index: usize,
+        value: EvmWord,
+        value_prev: EvmWord,
+    ) -> LogOp {
+        LogOp {
+            rw,
+            gc,
+            log_id,
+            field,
+            index,
+            value,
+            value_prev,
+        }
+    }
+
+    /// Returns the internal [`RW`] which says whether the operation corresponds
+    /// to a Read or a Write into the log.
+    pub const fn rw(&self) -> RW {
+        self.rw
+    }
+
+    /// Returns the [`Target`] (operation type) of this operation.
+    pub const fn target(&self) -> Target {
+        Target::Log
+    }
+
+    /// Returns the [`GlobalCounter`] associated to this Operation.
+    pub const fn gc(&self) -> GlobalCounter {
+        self.gc
+    }
+
+    /// Returns the identifier of the log written by this Operation.
+    pub const fn log_id(&self) -> usize {
+        self.log_id
+    }
+
+    /// Returns the [`LogField`] written by this Operation.
+    pub const fn field(&self) -> LogField {
+        self.field
+    }
+
+    /// Returns the index of the topic or of the data byte written by this
+    /// Operation, which is zero for the address.
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the [`EvmWord`] written by this operation.
+    pub const fn value(&self) -> &EvmWord {
+        &self.value
+    }
+
+    /// Returns the [`EvmWord`] that the field held before this operation.
+    pub const fn value_prev(&self) -> &EvmWord {
+        &self.value_prev
+    }
+}
+
+impl PartialOrd for LogOp {
+    fn partial_cmp(&self, other: &LogOp) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LogOp {
+    fn cmp(&self, other: &LogOp) -> Ordering {
+        (self.log_id(), self.field(), self.index(), self.gc()).cmp(&(
+            other.log_id(),
+            other.field(),
+            other.index(),
+            other.gc(),
+        ))
+    }
+}
+
+impl fmt::Display for LogOp {
+    /// Formats the operation as
+    /// `#<gc> <rw> log[<log_id>][<field>][<index>] = <value>` followed by the
+    /// previous value of the field.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} log[{}][{:?}][{}] = {} (prev {})",
+            self.gc,
+            self.rw,
+            self.log_id,
+            self.field,
+            self.index,
+            self.value,
+            self.value_prev
+        )
+    }
+}
+
+impl TryFrom<Operation> for LogOp {
+    type Error = Error;
+
+    fn try_from(op: Operation) -> Result<Self, Self::Error> {
+        match op {
+            Operation::Log(log_op) => Ok(log_op),
+            _ => Err(Error::InvalidOpConversion),
+        }
+    }
+}
+
+/// Kind of operation an [`OperationRef`](crate::exec_trace::OperationRef) can
+/// be typed by, which is any of the operation types wrapped by [`Operation`].
+pub trait OpKind: Into<Operation> {
+    /// Returns true if the operations of this kind have the [`Target`]
+    /// `target`.
+    fn has_target(target: Target) -> bool;
+
+    /// Returns the [`GlobalCounter`] associated to the operation.
+    fn gc(&self) -> GlobalCounter;
+
+    /// Returns all of the operations of this kind stored inside of
+    /// `container`, in insertion order.
+    fn ops(container: &OperationContainer) -> &[Self];
+}
+
+macro_rules! impl_op_kind {
+    ($($op:ident => $target:ident: $method:ident),*) => {
+        $(impl OpKind for $op {
+            fn has_target(target: Target) -> bool {
+                target == Target::$target
+            }
+
+            fn gc(&self) -> GlobalCounter {
+                $op::gc(self)
+            }
+
+            fn ops(container: &OperationContainer) -> &[Self] {
+                container.$method()
+            }
+        })*
+    };
+}
+
+impl_op_kind!(
+    StackOp => Stack: stack,
+    MemoryOp => Memory: memory,
+    StorageOp => Storage: storage,
+    AccountOp => Account: account,
+    ContextOp => Context: context,
+    AccessListOp => AccessList: access_list,
+    LogOp => Log: log
+);
+
 /// Generic enum that wraps over all the operation types possible.
-/// In particular [`StackOp`], [`MemoryOp`] and [`StorageOp`].
+/// In particular [`StackOp`], [`MemoryOp`], [`StorageOp`], [`AccountOp`],
+/// [`ContextOp`], [`AccessListOp`] and [`LogOp`].
 #[derive(Debug, Clone)]
 pub enum Operation {
     /// Doc
@@ -252,6 +994,14 @@ pub enum Operation {
     Memory(MemoryOp),
     /// Doc
     Storage(StorageOp),
+    /// Doc
+    Account(AccountOp),
+    /// Doc
+    Context(ContextOp),
+    /// Doc
+    AccessList(AccessListOp),
+    /// Doc
+    Log(LogOp),
 }
 
 impl From<&StackOp> for Operation {
@@ -290,6 +1040,68 @@ impl From<StorageOp> for Operation {
     }
 }
 
+impl From<&AccountOp> for Operation {
+    fn from(op: &AccountOp) -> Self {
+        Operation::Account(op.clone())
+    }
+}
+
+impl From<AccountOp> for Operation {
+    fn from(op: AccountOp) -> Self {
+        Operation::Account(op)
+    }
+}
+
+impl From<&ContextOp> for Operation {
+    fn from(op: &ContextOp) -> Self {
+        Operation::Context(op.clone())
+    }
+}
+
+impl From<ContextOp> for Operation {
+    fn from(op: ContextOp) -> Self {
+        Operation::Context(op)
+    }
+}
+
+impl From<&AccessListOp> for Operation {
+    fn from(op: &AccessListOp) -> Self {
+        Operation::AccessList(op.clone())
+    }
+}
+
+impl From<AccessListOp> for Operation {
+    fn from(op: AccessListOp) -> Self {
+        Operation::AccessList(op)
+    }
+}
+
+impl From<&LogOp> for Operation {
+    fn from(op: &LogOp) -> Self {
+        Operation::Log(op.clone())
+    }
+}
+
+impl From<LogOp> for Operation {
+    fn from(op: LogOp) -> Self {
+        Operation::Log(op)
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Stack(op) => fmt::Display::fmt(op, f),
+            Operation::Memory(op) => fmt::Display::fmt(op, f),
+            Operation::Storage(op) => fmt::Display::fmt(op, f),
+            Operation::Account(op) => fmt::Display::fmt(op, f),
+            Operation::Context(op) => fmt::Display::fmt(op, f),
+            Operation::AccessList(op) => fmt::Display::fmt(op, f),
+            Operation::Log(op) => fmt::Display::fmt(op, f),
+        }
+    }
+}
+
 impl PartialEq for Operation {
     fn eq(&self, other: &Operation) -> bool {
         match (self, other) {
@@ -304,6 +1116,21 @@ impl PartialEq for Operation {
                 Operation::Storage(storage_op_1),
                 Operation::Storage(storage_op_2),
             ) => storage_op_1.eq(storage_op_2),
+            (
+                Operation::Account(account_op_1),
+                Operation::Account(account_op_2),
+            ) => account_op_1.eq(account_op_2),
+            (
+                Operation::Context(context_op_1),
+                Operation::Context(context_op_2),
+            ) => context_op_1.eq(context_op_2),
+            (
+                Operation::AccessList(access_list_op_1),
+                Operation::AccessList(access_list_op_2),
+            ) => access_list_op_1.eq(access_list_op_2),
+            (Operation::Log(log_op_1), Operation::Log(log_op_2)) => {
+                log_op_1.eq(log_op_2)
+            }
             _ => false,
         }
     }
@@ -325,6 +1152,21 @@ impl PartialOrd for Operation {
                 Operation::Storage(storage_op_1),
                 Operation::Storage(storage_op_2),
             ) => storage_op_1.partial_cmp(storage_op_2),
+            (
+                Operation::Account(account_op_1),
+                Operation::Account(account_op_2),
+            ) => account_op_1.partial_cmp(account_op_2),
+            (
+                Operation::Context(context_op_1),
+                Operation::Context(context_op_2),
+            ) => context_op_1.partial_cmp(context_op_2),
+            (
+                Operation::AccessList(access_list_op_1),
+                Operation::AccessList(access_list_op_2),
+            ) => access_list_op_1.partial_cmp(access_list_op_2),
+            (Operation::Log(log_op_1), Operation::Log(log_op_2)) => {
+                log_op_1.partial_cmp(log_op_2)
+            }
             _ => None,
         }
     }
@@ -338,6 +1180,23 @@ impl Operation {
             Operation::Memory(_) => Target::Memory,
             Operation::Stack(_) => Target::Stack,
             Operation::Storage(_) => Target::Storage,
+            Operation::Account(_) => Target::Account,
+            Operation::Context(_) => Target::Context,
+            Operation::AccessList(_) => Target::AccessList,
+            Operation::Log(_) => Target::Log,
+        }
+    }
+
+    /// Returns the [`GlobalCounter`] of the internal op it stores inside.
+    pub const fn gc(&self) -> GlobalCounter {
+        match self {
+            Operation::Memory(op) => op.gc(),
+            Operation::Stack(op) => op.gc(),
+            Operation::Storage(op) => op.gc(),
+            Operation::Account(op) => op.gc(),
+            Operation::Context(op) => op.gc(),
+            Operation::AccessList(op) => op.gc(),
+            Operation::Log(op) => op.gc(),
         }
     }
 
@@ -356,38 +1215,207 @@ impl Operation {
         matches!(*self, Operation::Storage(_))
     }
 
-    /// Transmutes the internal (unlabeled) repr of the operation contained
-    /// inside of the enum into a [`StackOp`].
+    /// Returns true if the Operation hold internally is an [`AccountOp`].
+    pub const fn is_account(&self) -> bool {
+        matches!(*self, Operation::Account(_))
+    }
+
+    /// Returns true if the Operation hold internally is a [`ContextOp`].
+    pub const fn is_context(&self) -> bool {
+        matches!(*self, Operation::Context(_))
+    }
+
+    /// Returns true if the Operation hold internally is an [`AccessListOp`].
+    pub const fn is_access_list(&self) -> bool {
+        matches!(*self, Operation::AccessList(_))
+    }
+
+    /// Returns true if the Operation hold internally is a [`LogOp`].
+    pub const fn is_log(&self) -> bool {
+        matches!(*self, Operation::Log(_))
+    }
+
+    /// Returns a reference to the [`StackOp`] contained inside of the enum, or
+    /// `None` if it holds another kind of operation.
+    pub const fn as_stack(&self) -> Option<&StackOp> {
+        match self {
+            Operation::Stack(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`StackOp`] contained inside of the enum, or `None` if it
+    /// holds another kind of operation.
+    pub fn into_stack(self) -> Option<StackOp> {
+        match self {
+            Operation::Stack(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`StackOp`] contained inside of the enum.
+    ///
+    /// # Panics
+    /// If the enum holds another kind of operation.
     pub fn into_stack_unchecked(self) -> StackOp {
+        self.into_stack().expect("Broken Invariant")
+    }
+
+    /// Returns a reference to the [`MemoryOp`] contained inside of the enum, or
+    /// `None` if it holds another kind of operation.
+    pub const fn as_memory(&self) -> Option<&MemoryOp> {
+        match self {
+            Operation::Memory(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`MemoryOp`] contained inside of the enum, or `None` if it
+    /// holds another kind of operation.
+    pub fn into_memory(self) -> Option<MemoryOp> {
         match self {
-            Operation::Stack(stack_op) => unsafe {
-                std::mem::transmute(stack_op)
-            },
-            _ => panic!("Broken Invariant"),
+            Operation::Memory(op) => Some(op),
+            _ => None,
         }
     }
 
-    /// Transmutes the internal (unlabeled) repr of the operation contained
-    /// inside of the enum into a [`MemoryOp`].
+    /// Returns the [`MemoryOp`] contained inside of the enum.
+    ///
+    /// # Panics
+    /// If the enum holds another kind of operation.
     pub fn into_memory_unchecked(self) -> MemoryOp {
+        self.into_memory().expect("Broken Invariant")
+    }
+
+    /// Returns a reference to the [`StorageOp`] contained inside of the enum,
+    /// or `None` if it holds another kind of operation.
+    pub const fn as_storage(&self) -> Option<&StorageOp> {
+        match self {
+            Operation::Storage(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`StorageOp`] contained inside of the enum, or `None` if it
+    /// holds another kind of operation.
+    pub fn into_storage(self) -> Option<StorageOp> {
         match self {
-            Operation::Memory(memory_op) => unsafe {
-                std::mem::transmute(memory_op)
-            },
-            _ => panic!("Broken Invariant"),
+            Operation::Storage(op) => Some(op),
+            _ => None,
         }
     }
 
-    /// Transmutes the internal (unlabeled) repr of the operation contained
-    /// inside of the enum into a [`StorageOp`].
+    /// Returns the [`StorageOp`] contained inside of the enum.
+    ///
+    /// # Panics
+    /// If the enum holds another kind of operation.
     pub fn into_storage_unchecked(self) -> StorageOp {
+        self.into_storage().expect("Broken Invariant")
+    }
+
+    /// Returns a reference to the [`AccountOp`] contained inside of the enum,
+    /// or `None` if it holds another kind of operation.
+    pub const fn as_account(&self) -> Option<&AccountOp> {
+        match self {
+            Operation::Account(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`AccountOp`] contained inside of the enum, or `None` if it
+    /// holds another kind of operation.
+    pub fn into_account(self) -> Option<AccountOp> {
+        match self {
+            Operation::Account(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`AccountOp`] contained inside of the enum.
+    ///
+    /// # Panics
+    /// If the enum holds another kind of operation.
+    pub fn into_account_unchecked(self) -> AccountOp {
+        self.into_account().expect("Broken Invariant")
+    }
+
+    /// Returns a reference to the [`ContextOp`] contained inside of the enum,
+    /// or `None` if it holds another kind of operation.
+    pub const fn as_context(&self) -> Option<&ContextOp> {
+        match self {
+            Operation::Context(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`ContextOp`] contained inside of the enum, or `None` if it
+    /// holds another kind of operation.
+    pub fn into_context(self) -> Option<ContextOp> {
         match self {
-            Operation::Storage(storage_op) => unsafe {
-                std::mem::transmute(storage_op)
-            },
-            _ => panic!("Broken Invariant"),
+            Operation::Context(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`ContextOp`] contained inside of the enum.
+    ///
+    /// # Panics
+    /// If the enum holds another kind of operation.
+    pub fn into_context_unchecked(self) -> ContextOp {
+        self.into_context().expect("Broken Invariant")
+    }
+
+    /// Returns a reference to the [`AccessListOp`] contained inside of the
+    /// enum, or `None` if it holds another kind of operation.
+    pub const fn as_access_list(&self) -> Option<&AccessListOp> {
+        match self {
+            Operation::AccessList(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`AccessListOp`] contained inside of the enum, or `None`
+    /// if it holds another kind of operation.
+    pub fn into_access_list(self) -> Option<AccessListOp> {
+        match self {
+            Operation::AccessList(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`AccessListOp`] contained inside of the enum.
+    ///
+    /// # Panics
+    /// If the enum holds another kind of operation.
+    pub fn into_access_list_unchecked(self) -> AccessListOp {
+        self.into_access_list().expect("Broken Invariant")
+    }
+
+    /// Returns a reference to the [`LogOp`] contained inside of the enum, or
+    /// `None` if it holds another kind of operation.
+    pub const fn as_log(&self) -> Option<&LogOp> {
+        match self {
+            Operation::Log(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`LogOp`] contained inside of the enum, or `None` if it
+    /// holds another kind of operation.
+    pub fn into_log(self) -> Option<LogOp> {
+        match self {
+            Operation::Log(op) => Some(op),
+            _ => None,
         }
     }
+
+    /// Returns the [`LogOp`] contained inside of the enum.
+    ///
+    /// # Panics
+    /// If the enum holds another kind of operation.
+    pub fn into_log_unchecked(self) -> LogOp {
+        self.into_log().expect("Broken Invariant")
+    }
 }
 
 #[cfg(test)]
@@ -396,10 +1424,11 @@ mod operation_tests {
     use num::BigUint;
 
     #[test]
-    fn unchecked_op_transmutations_are_safe() {
+    fn op_downcasts() {
         let stack_op = StackOp::new(
             RW::WRITE,
             GlobalCounter(1usize),
+            1,
             StackAddress::from(1024),
             EvmWord::from(0x40u8),
         );
@@ -409,12 +1438,18 @@ mod operation_tests {
         let memory_op = MemoryOp::new(
             RW::WRITE,
             GlobalCounter(1usize),
+            1,
             MemoryAddress(BigUint::from(0x40u8)),
             EvmWord::from(0x40u8),
         );
 
         let memory_op_as_operation = Operation::from(memory_op.clone());
 
+        assert_eq!(stack_op_as_operation.as_stack(), Some(&stack_op));
+        assert_eq!(stack_op_as_operation.as_memory(), None);
+        assert_eq!(memory_op_as_operation.as_memory(), Some(&memory_op));
+        assert_eq!(memory_op_as_operation.clone().into_storage(), None);
+
         assert_eq!(stack_op, stack_op_as_operation.into_stack_unchecked());
         assert_eq!(memory_op, memory_op_as_operation.into_memory_unchecked())
     }
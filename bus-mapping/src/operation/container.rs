@@ -1,86 +1,373 @@
-use super::{MemoryOp, Operation, StackOp, StorageOp};
+use super::{
+    AccessListOp, AccountOp, ContextOp, LogOp, MemoryOp, OpKind, Operation,
+    StackOp, StorageOp, Target,
+};
 use crate::exec_trace::OperationRef;
-use itertools::Itertools;
-use std::convert::TryInto;
+use core::ops::Index;
+use std::borrow::Cow;
 
 /// The `OperationContainer` is meant to store all of the [`Operation`]s that an
 /// [`ExecutionTrace`](crate::exec_trace::ExecutionTrace) performs during it's
 /// execution.
 ///
-/// Once an operation is inserted into the container, it returns an
-/// [`OperationRef`] which holds an index to the operation just inserted.
+/// The operations of each [`Target`] are stored in their own vector, in the
+/// order they were inserted. Once an operation is inserted into the
+/// container, it returns an [`OperationRef`] which holds the index of the
+/// operation just inserted inside of the vector of it's target.
 /// These references are stored inside of the bus-mapping instances of each
-/// [`ExecutionStep`](crate::exec_trace::ExecutionStep).
+/// [`ExecutionStep`](crate::exec_trace::ExecutionStep), and indexing the
+/// container with them returns the operation they point to.
 ///
-/// Finally, the container also provides the capability of retrieving all of the
-/// `Stack`, `Memory` or `Storage` operations ordered according to the criterias
-/// they have specified.
+/// Finally, the container also provides the capability of iterating over all
+/// of the `Stack`, `Memory`, `Storage`, `Account`, `Context`, `AccessList` or
+/// `Log` operations
+/// ordered according to the criterias they have specified, without cloning
+/// them. The order is kept in an index per target which
+/// [`OperationContainer::sort`] brings up to date with the operations inserted
+/// since it was last called.
 /// That serves as a way to get an input with which is easy to work with in
 /// order to construct the State proof.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct OperationContainer(pub(crate) Vec<Operation>);
+#[derive(Debug, Clone, Default)]
+pub struct OperationContainer {
+    stack: OpList<StackOp>,
+    memory: OpList<MemoryOp>,
+    storage: OpList<StorageOp>,
+    account: OpList<AccountOp>,
+    context: OpList<ContextOp>,
+    access_list: OpList<AccessListOp>,
+    log: OpList<LogOp>,
+}
+
+/// Operations of a single [`Target`] together with the indexes that sort
+/// them.
+#[derive(Debug, Clone)]
+struct OpList<T> {
+    ops: Vec<T>,
+    /// Indexes of the operations in `ops` in sorted order. It's up to date
+    /// only when it holds as many indexes as there are operations, as it's
+    /// only extended by [`OpList::sort`].
+    sorted: Vec<usize>,
+}
 
-impl Default for OperationContainer {
+impl<T> Default for OpList<T> {
     fn default() -> Self {
-        Self::new()
+        OpList {
+            ops: Vec::new(),
+            sorted: Vec::new(),
+        }
+    }
+}
+
+impl<T: Ord> OpList<T> {
+    fn push(&mut self, op: T) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+
+    /// Adds the operations inserted since the last call to the sorted
+    /// indexes. As the indexes already sorted form a single run, sorting them
+    /// again together with the new ones is close to a merge.
+    fn sort(&mut self) {
+        if self.sorted.len() == self.ops.len() {
+            return;
+        }
+        let ops = &self.ops;
+        self.sorted.extend(self.sorted.len()..ops.len());
+        self.sorted.sort_by(|a, b| ops[*a].cmp(&ops[*b]));
+    }
+
+    /// Returns an iterator over the operations in sorted order, which only
+    /// has to sort a fresh set of indexes if [`OpList::sort`] wasn't called
+    /// after the last insertion.
+    fn sorted(&self) -> SortedOps<'_, T> {
+        let idx = if self.sorted.len() == self.ops.len() {
+            Cow::Borrowed(self.sorted.as_slice())
+        } else {
+            let mut idx: Vec<usize> = (0..self.ops.len()).collect();
+            idx.sort_by(|a, b| self.ops[*a].cmp(&self.ops[*b]));
+            Cow::Owned(idx)
+        };
+        SortedOps {
+            ops: &self.ops,
+            end: idx.len(),
+            idx,
+            start: 0,
+        }
+    }
+}
+
+/// Iterator over the operations of a single [`Target`] stored in an
+/// [`OperationContainer`], which yields references to them in sorted order.
+#[derive(Debug, Clone)]
+pub struct SortedOps<'a, T> {
+    ops: &'a [T],
+    idx: Cow<'a, [usize]>,
+    start: usize,
+    end: usize,
+}
+
+impl<'a, T> Iterator for SortedOps<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start == self.end {
+            return None;
+        }
+        self.start += 1;
+        Some(&self.ops[self.idx[self.start - 1]])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.start;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for SortedOps<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.start == self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(&self.ops[self.idx[self.end]])
+    }
+}
+
+impl<'a, T> ExactSizeIterator for SortedOps<'a, T> {}
+
+impl PartialEq for OperationContainer {
+    /// Two containers are equal when they hold the same operations inserted
+    /// in the same order, regardless of whether they have been sorted.
+    fn eq(&self, other: &Self) -> bool {
+        self.stack.ops == other.stack.ops
+            && self.memory.ops == other.memory.ops
+            && self.storage.ops == other.storage.ops
+            && self.account.ops == other.account.ops
+            && self.context.ops == other.context.ops
+            && self.access_list.ops == other.access_list.ops
+            && self.log.ops == other.log.ops
+    }
+}
+
+impl Eq for OperationContainer {}
+
+impl<T: OpKind> Index<OperationRef<T>> for OperationContainer {
+    type Output = T;
+
+    fn index(&self, reference: OperationRef<T>) -> &Self::Output {
+        T::ops(self)
+            .get(reference.as_usize())
+            .expect("it should not be possible to have a ref to a non-existent operation")
     }
 }
 
-// TODO: impl Index for OperationContainer
 impl OperationContainer {
     /// Generates a enw instance of an `OperationContainer`.
     pub fn new() -> Self {
-        Self(Vec::new())
-    }
-
-    /// Inserts an [`Operation`] into the  container returning a lightwheight
-    /// reference to it in the form of an [`OperationRef`] which points to the
-    /// location of the inserted operation inside the container.
-    pub fn insert(&mut self, op: impl Into<Operation>) -> OperationRef {
-        let op = op.into();
-        self.0.push(op.clone());
-        OperationRef::from((op.target(), self.0.len()))
-    }
-
-    /// Given a [`OperationRef`] return the actual [`Operation`] it is refering
-    /// to.
-    pub(crate) fn fetch_op(&self, reference: OperationRef) -> &Operation {
-        self.0.get(reference.as_usize()).expect("it should not be possible to have a ref to a non-existent operation")
-    }
-
-    /// Returns a sorted vector of all of the [`MemoryOp`]s contained inside of
-    /// the container.
-    pub fn sorted_memory(&self) -> Vec<MemoryOp> {
-        self.0
-            .iter()
-            .map(|op| op.clone().try_into())
-            .filter(|result| result.is_ok())
-            .map(|result| result.unwrap())
-            .sorted()
-            .collect()
-    }
-
-    /// Returns a sorted vector of all of the [`StackOp`]s contained inside of
-    /// the container.
-    pub fn sorted_stack(&self) -> Vec<StackOp> {
-        self.0
-            .iter()
-            .map(|op| op.clone().try_into())
-            .filter(|result| result.is_ok())
-            .map(|result| result.unwrap())
-            .sorted()
-            .collect()
-    }
-
-    /// Returns a sorted vector of all of the [`StorageOp`]s contained inside of
-    /// the container.
-    pub fn sorted_storage(&self) -> Vec<StorageOp> {
-        self.0
-            .iter()
-            .map(|op| op.clone().try_into())
-            .filter(|result| result.is_ok())
-            .map(|result| result.unwrap())
-            .sorted()
-            .collect()
+        Self::default()
+    }
+
+    /// Inserts an operation into the container returning a lightwheight
+    /// reference to it in the form of an [`OperationRef`], typed by the kind
+    /// of operation inserted, which points to the location of the inserted
+    /// operation inside the vector of it's [`Target`].
+    pub fn insert<T: Into<Operation>>(&mut self, op: T) -> OperationRef<T> {
+        let (target, idx) = match op.into() {
+            Operation::Stack(op) => (Target::Stack, self.stack.push(op)),
+            Operation::Memory(op) => (Target::Memory, self.memory.push(op)),
+            Operation::Storage(op) => (Target::Storage, self.storage.push(op)),
+            Operation::Account(op) => (Target::Account, self.account.push(op)),
+            Operation::Context(op) => (Target::Context, self.context.push(op)),
+            Operation::AccessList(op) => {
+                (Target::AccessList, self.access_list.push(op))
+            }
+            Operation::Log(op) => (Target::Log, self.log.push(op)),
+        };
+        OperationRef::new(target, idx)
+    }
+
+    /// Brings the sorted indexes of every [`Target`] up to date with the
+    /// operations inserted since the last call, so that the sorted iterators
+    /// of the container don't need to sort anything.
+    pub fn sort(&mut self) {
+        self.stack.sort();
+        self.memory.sort();
+        self.storage.sort();
+        self.account.sort();
+        self.context.sort();
+        self.access_list.sort();
+        self.log.sort();
+    }
+
+    /// Returns all of the [`StackOp`]s contained inside of the container, in
+    /// insertion order.
+    pub fn stack(&self) -> &[StackOp] {
+        &self.stack.ops
+    }
+
+    /// Returns all of the [`MemoryOp`]s contained inside of the container, in
+    /// insertion order.
+    pub fn memory(&self) -> &[MemoryOp] {
+        &self.memory.ops
+    }
+
+    /// Returns all of the [`StorageOp`]s contained inside of the container, in
+    /// insertion order.
+    pub fn storage(&self) -> &[StorageOp] {
+        &self.storage.ops
+    }
+
+    /// Returns all of the [`AccountOp`]s contained inside of the container, in
+    /// insertion order.
+    pub fn account(&self) -> &[AccountOp] {
+        &self.account.ops
+    }
+
+    /// Returns all of the [`ContextOp`]s contained inside of the container, in
+    /// insertion order.
+    pub fn context(&self) -> &[ContextOp] {
+        &self.context.ops
+    }
+
+    /// Returns all of the [`AccessListOp`]s contained inside of the container,
+    /// in insertion order.
+    pub fn access_list(&self) -> &[AccessListOp] {
+        &self.access_list.ops
+    }
+
+    /// Returns all of the [`LogOp`]s contained inside of the container, in
+    /// insertion order.
+    pub fn log(&self) -> &[LogOp] {
+        &self.log.ops
+    }
+
+    /// Returns an iterator over all of the [`MemoryOp`]s contained inside of
+    /// the container in sorted order.
+    pub fn sorted_memory(&self) -> SortedOps<'_, MemoryOp> {
+        self.memory.sorted()
+    }
+
+    /// Returns an iterator over all of the [`StackOp`]s contained inside of
+    /// the container in sorted order.
+    pub fn sorted_stack(&self) -> SortedOps<'_, StackOp> {
+        self.stack.sorted()
+    }
+
+    /// Returns an iterator over all of the [`StorageOp`]s contained inside of
+    /// the container in sorted order.
+    pub fn sorted_storage(&self) -> SortedOps<'_, StorageOp> {
+        self.storage.sorted()
+    }
+
+    /// Returns an iterator over all of the [`AccountOp`]s contained inside of
+    /// the container in sorted order.
+    pub fn sorted_account(&self) -> SortedOps<'_, AccountOp> {
+        self.account.sorted()
+    }
+
+    /// Returns an iterator over all of the [`ContextOp`]s contained inside of
+    /// the container in sorted order.
+    pub fn sorted_context(&self) -> SortedOps<'_, ContextOp> {
+        self.context.sorted()
+    }
+
+    /// Returns an iterator over all of the [`AccessListOp`]s contained inside
+    /// of the container in sorted order.
+    pub fn sorted_access_list(&self) -> SortedOps<'_, AccessListOp> {
+        self.access_list.sorted()
+    }
+
+    /// Returns an iterator over all of the [`LogOp`]s contained inside of the
+    /// container in sorted order.
+    pub fn sorted_log(&self) -> SortedOps<'_, LogOp> {
+        self.log.sorted()
+    }
+}
+
+#[cfg(test)]
+mod container_tests {
+    use super::*;
+    use crate::evm::{EvmWord, GlobalCounter, MemoryAddress, StackAddress};
+    use crate::operation::{Target, RW};
+
+    #[test]
+    fn typed_references() {
+        let stack_op = StackOp::new(
+            RW::WRITE,
+            GlobalCounter(1usize),
+            1,
+            StackAddress::from(1023),
+            EvmWord::from(0x40u8),
+        );
+        let memory_op = MemoryOp::new(
+            RW::READ,
+            GlobalCounter(2usize),
+            1,
+            MemoryAddress::from(0),
+            EvmWord::from(0x40u8),
+        );
+
+        let mut container = OperationContainer::new();
+        let stack_ref = container.insert(stack_op.clone());
+        let memory_ref = container.insert(memory_op.clone());
+
+        // References point to the operation just inserted, inside of the
+        // operations of it's target.
+        assert_eq!(stack_ref.as_usize(), 0);
+        assert_eq!(memory_ref.as_usize(), 0);
+        assert_eq!(memory_ref.target(), Target::Memory);
+        assert_eq!(container[stack_ref], stack_op);
+        assert_eq!(container[memory_ref], memory_op);
+
+        // Untyped references can only be typed by the kind of operation they
+        // point to.
+        let untyped = memory_ref.untyped();
+        assert_eq!(untyped.typed::<MemoryOp>(), Some(memory_ref));
+        assert_eq!(untyped.typed::<StackOp>(), None);
+        assert_eq!(container[untyped.typed::<MemoryOp>().unwrap()], memory_op);
+    }
+
+    #[test]
+    fn sorted_iterators() {
+        let stack_op = |gc: usize, address: usize| {
+            StackOp::new(
+                RW::WRITE,
+                GlobalCounter(gc),
+                1,
+                StackAddress::from(address),
+                EvmWord::from(gc),
+            )
+        };
+
+        let mut container = OperationContainer::new();
+        container.insert(stack_op(1, 1023));
+        container.insert(stack_op(2, 1022));
+        container.insert(stack_op(3, 1023));
+
+        // Iterating before sorting the container sorts on the fly.
+        let expected =
+            [stack_op(2, 1022), stack_op(1, 1023), stack_op(3, 1023)];
+        assert!(container.sorted_stack().eq(expected.iter()));
+
+        // Once sorted, the iterators borrow the sorted indexes.
+        container.sort();
+        assert!(container.sorted_stack().eq(expected.iter()));
+        assert!(container.sorted_stack().rev().eq(expected.iter().rev()));
+
+        // Operations inserted afterwards are merged on the next sort, while
+        // their references still point to their insertion position.
+        let op_ref = container.insert(stack_op(4, 1022));
+        assert_eq!(op_ref.as_usize(), 3);
+        container.sort();
+        let expected = [
+            stack_op(2, 1022),
+            stack_op(4, 1022),
+            stack_op(1, 1023),
+            stack_op(3, 1023),
+        ];
+        assert_eq!(container.sorted_stack().len(), 4);
+        assert!(container.sorted_stack().eq(expected.iter()));
+        assert_eq!(container.stack()[3], container[op_ref]);
+        assert_eq!(container.sorted_memory().next(), None);
     }
 }
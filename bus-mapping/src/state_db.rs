@@ -0,0 +1,379 @@
+//! Implementation of an in-memory view of the accounts state that the
+//! [`ExecutionTrace`](crate::exec_trace::ExecutionTrace) works on while
+//! generating the [`Operation`](crate::operation::Operation)s of each step.
+
+use crate::evm::{
+    bytes_from_hex, keccak256, Address, EvmWord, EMPTY_CODE_HASH,
+};
+use crate::operation::AccountField;
+use crate::Error;
+use alloc::collections::BTreeMap;
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Represents the state of a single account of the EVM.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Account {
+    nonce: u64,
+    balance: EvmWord,
+    code_hash: EvmWord,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Account {
+            nonce: 0,
+            balance: EvmWord::from(0u8),
+            code_hash: EMPTY_CODE_HASH.clone(),
+        }
+    }
+}
+
+impl Account {
+    /// Generates a new `Account` from it's fields.
+    pub const fn new(
+        nonce: u64,
+        balance: EvmWord,
+        code_hash: EvmWord,
+    ) -> Account {
+        Account {
+            nonce,
+            balance,
+            code_hash,
+        }
+    }
+
+    /// Returns the nonce of the account.
+    pub const fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// Returns the balance of the account.
+    pub const fn balance(&self) -> &EvmWord {
+        &self.balance
+    }
+
+    /// Returns the Keccak256 hash of the code of the account.
+    pub const fn code_hash(&self) -> &EvmWord {
+        &self.code_hash
+    }
+
+    /// Returns true if the account is empty as defined by
+    /// [EIP-161](https://eips.ethereum.org/EIPS/eip-161): zero nonce, zero
+    /// balance and no code.
+    pub fn is_empty(&self) -> bool {
+        self.nonce == 0
+            && self.balance.is_zero()
+            && self.code_hash == *EMPTY_CODE_HASH
+    }
+}
+
+/// Keeps track of the [`Account`]s touched by an execution trace, of their
+/// storage and of the bytecode each one of them holds (indexed by code hash).
+///
+/// Accounts that were never inserted are considered empty: zero nonce, zero
+/// balance and no code.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct StateDB {
+    accounts: BTreeMap<Address, Account>,
+    storage: BTreeMap<(Address, EvmWord), EvmWord>,
+    codes: BTreeMap<EvmWord, Vec<u8>>,
+}
+
+impl StateDB {
+    /// Generates a new empty `StateDB`.
+    pub fn new() -> StateDB {
+        StateDB::default()
+    }
+
+    /// Given the output of geth's `prestateTracer` in JSON format, generate
+    /// a `StateDB` holding the balance, nonce, code and storage that each
+    /// account touched by a transaction had before it ran.
+    ///
+    /// Used as the initial state of an
+    /// [`ExecutionTrace`](crate::exec_trace::ExecutionTrace) through
+    /// [`ExecutionTraceBuilder::with_prestate`](crate::exec_trace::ExecutionTraceBuilder::with_prestate),
+    /// it sets the previous values of the first
+    /// [`AccountOp`](crate::operation::AccountOp) and
+    /// [`StorageOp`](crate::operation::StorageOp) over each account and slot,
+    /// and the code the steps of the root call are checked against.
+    pub fn from_prestate_bytes<T: AsRef<[u8]>>(
+        bytes: T,
+    ) -> Result<StateDB, Error> {
+        let parsed = serde_json::from_slice::<
+            BTreeMap<&str, ParsedPrestateAccount>,
+        >(bytes.as_ref())
+        .map_err(|_| Error::SerdeError)?;
+        let word = |hex: &str| EvmWord::from_str(hex.trim_start_matches("0x"));
+
+        let mut state_db = StateDB::new();
+        for (address, account) in parsed {
+            let address = Address::from_str(address)?;
+            state_db.set_nonce(&address, account.nonce);
+            state_db.set_balance(&address, word(account.balance)?);
+            if let Some(code) = account.code {
+                state_db.set_code(&address, bytes_from_hex(code)?);
+            }
+            for (key, value) in account.storage {
+                state_db.set_storage(&address, word(key)?, word(value)?);
+            }
+        }
+        Ok(state_db)
+    }
+
+    /// Sets the state of the account located at `address`.
+    pub fn insert_account(&mut self, address: Address, account: Account) {
+        self.accounts.insert(address, account);
+    }
+
+    /// Returns the [`Account`] located at `address`.
+    pub fn account(&self, address: &Address) -> Account {
+        self.accounts.get(address).cloned().unwrap_or_default()
+    }
+
+    /// Returns true if the account located at `address` has been inserted or
+    /// written.
+    pub fn contains_account(&self, address: &Address) -> bool {
+        self.accounts.contains_key(address)
+    }
+
+    /// Returns an iterator over the addresses of the accounts that have been
+    /// inserted or written.
+    pub(crate) fn addresses(&self) -> impl Iterator<Item = &Address> {
+        self.accounts.keys()
+    }
+
+    /// Returns a mutable reference to the [`Account`] located at `address`,
+    /// creating an empty one if it didn't exist.
+    pub(crate) fn account_mut(&mut self, address: &Address) -> &mut Account {
+        self.accounts.entry(*address).or_default()
+    }
+
+    /// Sets the nonce of the account located at `address`.
+    pub fn set_nonce(&mut self, address: &Address, nonce: u64) {
+        self.account_mut(address).nonce = nonce;
+    }
+
+    /// Sets the balance of the account located at `address`.
+    pub fn set_balance(&mut self, address: &Address, balance: EvmWord) {
+        self.account_mut(address).balance = balance;
+    }
+
+    /// Returns the value of the `field` of the account located at `address`.
+    pub fn account_field(
+        &self,
+        address: &Address,
+        field: AccountField,
+    ) -> EvmWord {
+        let account = self.account(address);
+        match field {
+            AccountField::Nonce => EvmWord::from(account.nonce),
+            AccountField::Balance => account.balance,
+            AccountField::CodeHash => account.code_hash,
+        }
+    }
+
+    /// Sets the value of the `field` of the account located at `address`.
+    pub(crate) fn set_account_field(
+        &mut self,
+        address: &Address,
+        field: AccountField,
+        value: EvmWord,
+    ) {
+        let account = self.account_mut(address);
+        match field {
+            AccountField::Nonce => {
+                account.nonce = value.to_u64().expect("nonces fit in a u64")
+            }
+            AccountField::Balance => account.balance = value,
+            AccountField::CodeHash => account.code_hash = value,
+        }
+    }
+
+    /// Stores `code` as the code of the account located at `address` and
+    /// returns it's code hash.
+    pub fn set_code(&mut self, address: &Address, code: Vec<u8>) -> EvmWord {
+        let code_hash = self.insert_code(code);
+        self.account_mut(address).code_hash = code_hash.clone();
+        code_hash
+    }
+
+    /// Stores `code` without assigning it to any account and returns it's
+    /// code hash.
+    pub fn insert_code(&mut self, code: Vec<u8>) -> EvmWord {
+        let code_hash = EvmWord::from_be_bytes(&keccak256(&code));
+        self.codes.insert(code_hash.clone(), code);
+        code_hash
+    }
+
+    /// Returns the value stored under `key` in the storage of the account
+    /// located at `address` (if it is known).
+    pub fn storage(
+        &self,
+        address: &Address,
+        key: &EvmWord,
+    ) -> Option<&EvmWord> {
+        self.storage.get(&(*address, key.clone()))
+    }
+
+    /// Sets the value stored under `key` in the storage of the account
+    /// located at `address`.
+    pub fn set_storage(
+        &mut self,
+        address: &Address,
+        key: EvmWord,
+        value: EvmWord,
+    ) {
+        self.storage.insert((*address, key), value);
+    }
+
+    /// Returns the bytecode whose Keccak256 hash is `code_hash` (if it is
+    /// known).
+    pub fn code(&self, code_hash: &EvmWord) -> Option<&[u8]> {
+        if code_hash == &*EMPTY_CODE_HASH {
+            return Some(&[]);
+        }
+        self.codes.get(code_hash).map(|code| code.as_slice())
+    }
+}
+
+/// Helper structure whose only purpose is to serve as a De/Serialization
+/// derivation guide for the serde Derive macro.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[doc(hidden)]
+struct ParsedPrestateAccount<'a> {
+    balance: &'a str,
+    #[serde(default)]
+    nonce: u64,
+    #[serde(default, borrow)]
+    code: Option<&'a str>,
+    #[serde(default, borrow)]
+    storage: BTreeMap<&'a str, &'a str>,
+}
+
+#[cfg(test)]
+mod state_db_tests {
+    use super::*;
+    use crate::exec_trace::{ExecutionTraceBuilder, TxContext};
+    use crate::operation::RW;
+    use crate::{test_util, TraceError};
+    use pasta_curves::Fp;
+
+    #[test]
+    fn prestate_parsing_and_seeding() {
+        let sender =
+            Address::from_str("0x00000000000000000000000000000000000000f0")
+                .unwrap();
+        let contract =
+            Address::from_str("0x00000000000000000000000000000000000000aa")
+                .unwrap();
+        // PUSH1 00 SLOAD PUSH1 2a PUSH1 01 SSTORE STOP
+        let code = "0x600054602a60015500";
+        let prestate_json = |code: &str| {
+            format!(
+                r#"
+                {{
+                    "0x00000000000000000000000000000000000000f0": {{
+                        "balance": "0x0",
+                        "nonce": 3
+                    }},
+                    "0x00000000000000000000000000000000000000aa": {{
+                        "balance": "0x64",
+                        "nonce": 1,
+                        "code": "{}",
+                        "storage": {{
+                            "0x0000000000000000000000000000000000000000000000000000000000000000": "0x0000000000000000000000000000000000000000000000000000000000000005",
+                            "0x0000000000000000000000000000000000000000000000000000000000000001": "0x0000000000000000000000000000000000000000000000000000000000000007"
+                        }}
+                    }}
+                }}
+                "#,
+                code
+            )
+        };
+        let prestate = StateDB::from_prestate_bytes(prestate_json(code))
+            .expect("Error on prestate parsing");
+        assert_eq!(prestate.account(&sender).nonce(), 3);
+        assert_eq!(
+            prestate.account(&contract).balance(),
+            &EvmWord::from(100u8)
+        );
+        assert_eq!(
+            prestate.code(prestate.account(&contract).code_hash()),
+            Some(&[0x60, 0x00, 0x54, 0x60, 0x2a, 0x60, 0x01, 0x55, 0x00][..])
+        );
+        assert_eq!(
+            prestate.storage(&contract, &EvmWord::from(1u8)),
+            Some(&EvmWord::from(7u8))
+        );
+        assert!(matches!(
+            StateDB::from_prestate_bytes(r#"{ "0xaa": { "nonce": 1 } }"#),
+            Err(Error::SerdeError)
+        ));
+
+        let input_trace = r#"
+        [
+            { "memory": {}, "stack": ["0"], "opcode": "PUSH1 00", "pc": 0 },
+            { "memory": {}, "stack": ["5"], "opcode": "SLOAD", "pc": 2 },
+            { "memory": {}, "stack": ["5", "2a"], "opcode": "PUSH1 2a", "pc": 3 },
+            { "memory": {}, "stack": ["5", "2a", "1"], "opcode": "PUSH1 01", "pc": 5 },
+            { "memory": {}, "stack": ["5"], "opcode": "SSTORE", "pc": 7 },
+            { "memory": {}, "stack": ["5"], "opcode": "STOP", "pc": 8 }
+        ]
+        "#;
+        let build = |prestate: StateDB| {
+            let mut builder =
+                ExecutionTraceBuilder::<Fp>::new(test_util::block_ctants())
+                    .with_tx(TxContext::new(
+                        sender,
+                        Some(contract),
+                        3,
+                        EvmWord::from(0u8),
+                        EvmWord::from(0u8),
+                        100_000,
+                        vec![],
+                    ))
+                    .with_prestate(prestate);
+            builder
+                .push_trace_bytes(input_trace.as_bytes())
+                .expect("Error on parsing");
+            builder.finish()
+        };
+        let trace = build(prestate).expect("Error on trace generation");
+
+        // The storage ops start from the values of the prestate.
+        let storage: Vec<(RW, EvmWord, EvmWord)> = trace
+            .sorted_storage_ops()
+            .map(|op| (op.rw(), op.value().clone(), op.value_prev().clone()))
+            .collect();
+        assert_eq!(
+            storage,
+            vec![
+                (RW::READ, EvmWord::from(5u8), EvmWord::from(5u8)),
+                (RW::WRITE, EvmWord::from(0x2au8), EvmWord::from(7u8)),
+            ]
+        );
+        assert!(trace.sorted_account_ops().any(|op| {
+            op.address() == &sender
+                && op.value_prev() == &EvmWord::from(3u8)
+                && op.value() == &EvmWord::from(4u8)
+        }));
+        assert_eq!(trace.state_violations(), vec![]);
+        assert_eq!(
+            trace.state_db().storage(&contract, &EvmWord::from(1u8)),
+            Some(&EvmWord::from(0x2au8))
+        );
+
+        // The steps of the root call run the code of the prestate.
+        let truncated = StateDB::from_prestate_bytes(prestate_json("0x6000"))
+            .expect("Error on prestate parsing");
+        assert!(matches!(
+            build(truncated),
+            Err(Error::InvalidTrace(TraceError::PcOutOfCode {
+                step: 1,
+                code_size: 2,
+                ..
+            }))
+        ));
+    }
+}
@@ -0,0 +1,42 @@
+//! Fixtures shared by the tests of the crate.
+
+use crate::evm::{Address, EvmWord};
+use crate::exec_trace::TxContext;
+use crate::BlockConstants;
+use core::str::FromStr;
+use pasta_curves::Fp;
+
+/// Returns the address whose hex representation, left-padded with zeros, is
+/// `hex`.
+pub(crate) fn address(hex: &str) -> Address {
+    Address::from_str(&format!("{:0>40}", hex)).unwrap()
+}
+
+/// Returns the [`BlockConstants`] of a block whose fields are all zero.
+pub(crate) fn block_ctants() -> BlockConstants<Fp> {
+    BlockConstants::new(
+        EvmWord::from(0u8),
+        Fp::zero(),
+        Fp::zero(),
+        Fp::zero(),
+        Fp::zero(),
+        Fp::zero(),
+        Fp::zero(),
+        Fp::zero(),
+    )
+}
+
+/// Returns a transaction from the zero address to `to` (a contract creation
+/// if it's `None`) with `input` as it's data, 100000 gas, and no value nor
+/// gas price.
+pub(crate) fn tx_ctx(to: Option<Address>, input: Vec<u8>) -> TxContext {
+    TxContext::new(
+        Address::zero(),
+        to,
+        0,
+        EvmWord::from(0u8),
+        EvmWord::from(0u8),
+        100_000,
+        input,
+    )
+}